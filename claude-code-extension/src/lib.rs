@@ -1,20 +1,59 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
 use zed_extension_api::{
-    current_platform, download_file, latest_github_release, lsp::*, make_file_executable,
-    Architecture, DownloadedFileType, GithubReleaseOptions, Os, *,
+    current_platform, download_file, github_release_by_tag_name, latest_github_release, lsp::*,
+    make_file_executable, process::Command as ProcessCommand, Architecture, DownloadedFileType,
+    GithubReleaseOptions, Os, *,
 };
 
-// Development configuration
-// Set this to true to always use local development binaries instead of GitHub releases
-// This allows using local fixes without waiting for official releases
-// DEFAULT: false (production behavior - downloads from GitHub)
-const FORCE_DEVELOPMENT_MODE: bool = false;
+mod settings;
+mod signature;
+mod startup;
 
-struct ClaudeCodeExtension;
+use settings::{Channel, ExtensionSettings};
+use signature::Verification;
+use startup::{InstallStage, StartupReport};
+
+/// Environment variable that enables development mode when set to "1" or "true" in the
+/// shell Zed resolves for the worktree, unless the `dev_mode` setting overrides it
+const DEV_MODE_ENV: &str = "CLAUDE_CODE_ZED_DEV";
+
+/// Editor name reported to the server and, through the lock file, to Claude
+const IDE_NAME: &str = "Zed";
+
+/// How many times to query GitHub for a release before falling back to a cached binary
+const RELEASE_LOOKUP_ATTEMPTS: u32 = 3;
+
+/// Delay before the first release lookup retry, doubled after each further failure
+const RELEASE_RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// What `--version` printed for each binary path that was run with it, so a binary is only
+/// started once to check both that it loads and its version
+static VERSION_PROBES: Mutex<BTreeMap<String, Option<VersionProbe>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone)]
+struct VersionProbe {
+    succeeded: bool,
+    stdout: String,
+    stderr: String,
+}
+
+struct ClaudeCodeExtension {
+    /// Degraded-mode warnings from the last server start, keyed by worktree ID.
+    /// Forwarded to the server through initialization options so it can show them.
+    startup_warnings: HashMap<u64, Vec<String>>,
+    /// Binary each language server was started with, keyed by worktree ID and channel.
+    /// The slash commands run it instead of resolving, and maybe downloading, it again.
+    server_binaries: HashMap<(u64, Channel), String>,
+}
 
 impl Extension for ClaudeCodeExtension {
     fn new() -> Self {
         eprintln!("🎉 [INIT] Claude Code Extension: Extension loaded!");
-        Self
+        Self {
+            startup_warnings: HashMap::new(),
+            server_binaries: HashMap::new(),
+        }
     }
 
     fn language_server_command(
@@ -22,29 +61,56 @@ impl Extension for ClaudeCodeExtension {
         language_server_id: &LanguageServerId,
         worktree: &Worktree,
     ) -> Result<Command, String> {
-        match language_server_id.as_ref() {
-            "claude-code-server" => {
+        match Channel::for_language_server(language_server_id.as_ref()) {
+            Some(channel) => {
                 eprintln!(
-                    "🚀 [INFO] Claude Code Extension: Starting claude-code-server for worktree: {}",
+                    "🚀 [INFO] Claude Code Extension: Starting {} for worktree: {}",
+                    language_server_id,
                     worktree.root_path()
                 );
 
                 // In development, we'll try to find the binary in the workspace
                 // In production, this would be a distributed binary
-                let server_path = find_server_binary(worktree)?;
+                let settings = ExtensionSettings::for_worktree(worktree, channel);
+                if !settings.is_enabled() {
+                    return Err(format!(
+                        "{} is disabled for this worktree; set lsp.{}.settings.enabled to run it",
+                        language_server_id, language_server_id
+                    ));
+                }
+                let mut report = StartupReport::for_language_server(language_server_id);
+                let server_path = match find_server_binary(worktree, &settings, &mut report)
+                    .and_then(|path| {
+                        // An explicitly configured binary is the user's responsibility
+                        if settings.binary_path.is_none() {
+                            check_server_version(&path)?;
+                        }
+                        Ok(path)
+                    }) {
+                    Ok(path) => {
+                        report.set_stage(InstallStage::Ready, &path);
+                        path
+                    }
+                    Err(e) => {
+                        set_language_server_installation_status(
+                            language_server_id,
+                            &LanguageServerInstallationStatus::Failed(e.clone()),
+                        );
+                        return Err(e);
+                    }
+                };
+                self.startup_warnings
+                    .insert(worktree.id(), report.warnings().to_vec());
+                self.server_binaries
+                    .insert((worktree.id(), channel), server_path.clone());
 
                 Ok(Command {
                     command: server_path,
-                    args: vec![
-                        "--debug".to_string(),
-                        "--worktree".to_string(),
-                        worktree.root_path().to_string(),
-                        "hybrid".to_string(),
-                    ],
-                    env: Default::default(),
+                    args: settings.server_args(&worktree.root_path()),
+                    env: settings.server_env(&worktree.shell_env()),
                 })
             }
-            _ => Err(format!("Unknown language server: {}", language_server_id)),
+            None => Err(format!("Unknown language server: {}", language_server_id)),
         }
     }
 
@@ -53,61 +119,72 @@ impl Extension for ClaudeCodeExtension {
         language_server_id: &LanguageServerId,
         worktree: &Worktree,
     ) -> Result<Option<serde_json::Value>, String> {
-        match language_server_id.as_ref() {
-            "claude-code-server" => {
-                eprintln!("🔧 [DEBUG] Setting up initialization options for claude-code-server");
+        match Channel::for_language_server(language_server_id.as_ref()) {
+            Some(channel) => {
+                eprintln!(
+                    "🔧 [DEBUG] Setting up initialization options for {}",
+                    language_server_id
+                );
 
-                let options = serde_json::json!({
+                let settings = ExtensionSettings::for_worktree(worktree, channel);
+                let root_path = worktree.root_path();
+                // The extension API only exposes this worktree; the server adds the project's
+                // other worktrees from the workspace folders Zed sends in `initialize`
+                let mut options = serde_json::json!({
                     "workspaceFolders": [{
-                        "uri": format!("file://{}", worktree.root_path()),
-                        "name": worktree.root_path().split('/').last().unwrap_or("workspace")
+                        "uri": file_uri(&root_path),
+                        "name": worktree_name(&root_path)
                     }],
                     "claudeCode": {
                         "enabled": true,
-                        "extensionVersion": "0.1.0",
-                        "ideName": "Zed"
+                        "extensionVersion": env!("CARGO_PKG_VERSION"),
+                        "ideName": IDE_NAME,
+                        // Zed can't push `$/claudeCode/selectionDidChange` yet, so the server
+                        // infers selections until a client does
+                        "selectionSource": "inferred",
+                        "startupWarnings": self
+                            .startup_warnings
+                            .get(&worktree.id())
+                            .cloned()
+                            .unwrap_or_default()
                     }
                 });
+                merge_json(&mut options["claudeCode"], settings.claude_code_options());
+                // Options the user set explicitly take precedence over everything above
+                if let Some(user_options) = settings.initialization_options {
+                    merge_json(&mut options, user_options);
+                }
 
                 Ok(Some(options))
             }
-            _ => Ok(None),
+            None => Ok(None),
         }
     }
 
     fn language_server_workspace_configuration(
         &mut self,
         language_server_id: &LanguageServerId,
-        _worktree: &Worktree,
+        worktree: &Worktree,
     ) -> Result<Option<serde_json::Value>, String> {
-        match language_server_id.as_ref() {
-            "claude-code-server" => {
-                let config = serde_json::json!({
-                    "claudeCode": {
-                        "enabled": true,
-                        "debug": true,
-                        "websocket": {
-                            "host": "127.0.0.1",
-                            "portRange": [10000, 65535]
-                        },
-                        "auth": {
-                            "generateTokens": true
-                        }
-                    }
-                });
-
-                Ok(Some(config))
-            }
-            _ => Ok(None),
+        match Channel::for_language_server(language_server_id.as_ref()) {
+            Some(channel) => Ok(Some(workspace_configuration(
+                &ExtensionSettings::for_worktree(worktree, channel),
+            ))),
+            None => Ok(None),
         }
     }
 
     fn label_for_completion(
         &self,
-        _language_server_id: &LanguageServerId,
-        _completion: Completion,
+        language_server_id: &LanguageServerId,
+        completion: Completion,
     ) -> Option<CodeLabel> {
-        None
+        Channel::for_language_server(language_server_id.as_ref())?;
+        let action = completion.label.strip_prefix("@claude ")?;
+        Some(claude_completion_label(
+            action,
+            completion.detail.as_deref(),
+        ))
     }
 
     fn label_for_symbol(
@@ -117,108 +194,466 @@ impl Extension for ClaudeCodeExtension {
     ) -> Option<CodeLabel> {
         None
     }
+
+    fn run_slash_command(
+        &self,
+        command: SlashCommand,
+        args: Vec<String>,
+        worktree: Option<&Worktree>,
+    ) -> Result<SlashCommandOutput, String> {
+        let worktree = worktree.ok_or("Claude Code needs an open worktree")?;
+        let mention_args = match command.name.as_str() {
+            "claude-mention" => {
+                let target = args.join(" ");
+                let (path, lines) = split_line_range(target.trim());
+                if path.is_empty() {
+                    return Err("Usage: /claude-mention <path>[:<start>-<end>]".to_string());
+                }
+                let mut mention_args = vec![path.to_string()];
+                if let Some(lines) = lines {
+                    mention_args.extend(["--lines".to_string(), lines.to_string()]);
+                }
+                mention_args
+            }
+            "claude-selection" => vec!["--selection".to_string()],
+            name => return Err(format!("Unknown slash command: {}", name)),
+        };
+
+        // Worktrees that enable the experimental server mention through it
+        let channel = [Channel::Experimental, Channel::Stable]
+            .into_iter()
+            .find(|channel| ExtensionSettings::for_worktree(worktree, *channel).is_enabled())
+            .ok_or("Claude Code is disabled for this worktree")?;
+        let server_path = self
+            .server_binaries
+            .get(&(worktree.id(), channel))
+            .ok_or("The Claude Code server is not running for this worktree")?;
+        let mentioned = run_mention(server_path, worktree, mention_args)?;
+        let text = format!("Shared {} with Claude Code", mentioned);
+        Ok(SlashCommandOutput {
+            sections: vec![SlashCommandOutputSection {
+                range: (0..text.len() as u32).into(),
+                label: format!("Claude: {}", mentioned),
+            }],
+            text,
+        })
+    }
+}
+
+/// Label for an `@claude <action>` completion: an icon, the prefix and action highlighted
+/// like code, and the item's description dimmed, so it stands out from ordinary completions
+fn claude_completion_label(action: &str, detail: Option<&str>) -> CodeLabel {
+    let icon = match action {
+        "explain" => "💬",
+        "improve" => "✨",
+        "fix" => "🔧",
+        _ => "✳",
+    };
+    let prefix = format!("{} ", icon);
+    let filter_start = prefix.len();
+    let mut spans = vec![
+        CodeLabelSpan::literal(prefix, None),
+        CodeLabelSpan::literal("@claude", Some("keyword".to_string())),
+        CodeLabelSpan::literal(" ", None),
+        CodeLabelSpan::literal(action, Some("function".to_string())),
+    ];
+    let filter_end = filter_start + "@claude ".len() + action.len();
+    if let Some(detail) = detail {
+        spans.push(CodeLabelSpan::literal(
+            format!("  {}", detail),
+            Some("comment".to_string()),
+        ));
+    }
+
+    CodeLabel {
+        code: String::new(),
+        spans,
+        filter_range: (filter_start..filter_end).into(),
+    }
+}
+
+/// Last component of a worktree root, with either separator and ignoring a trailing one
+fn worktree_name(root_path: &str) -> &str {
+    root_path
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("workspace")
+}
+
+/// `file://` URI of a local path, including Windows drive paths, with the characters that
+/// would break URI parsing percent-encoded
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for c in path.chars() {
+        match c {
+            ' ' => uri.push_str("%20"),
+            '%' => uri.push_str("%25"),
+            '#' => uri.push_str("%23"),
+            '?' => uri.push_str("%3F"),
+            _ => uri.push(c),
+        }
+    }
+    uri
+}
+
+/// Settings the server pulls with `workspace/configuration`: the same `claudeCode` options
+/// the settings contribute to the initialization options, so pulling them changes nothing
+/// the user didn't change
+fn workspace_configuration(settings: &ExtensionSettings) -> serde_json::Value {
+    let mut config = serde_json::json!({ "claudeCode": settings.claude_code_options() });
+    let user_options = settings
+        .initialization_options
+        .as_ref()
+        .and_then(|options| options.get("claudeCode"));
+    if let Some(user_options) = user_options {
+        merge_json(&mut config["claudeCode"], user_options.clone());
+    }
+    config
+}
+
+/// Recursively merge `overrides` into `base`: objects are merged key by key, anything else
+/// replaces the value in `base`
+fn merge_json(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_json(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Split a `path:START-END` (or `path:LINE`) slash command argument into the path and range
+fn split_line_range(target: &str) -> (&str, Option<&str>) {
+    match target.rsplit_once(':') {
+        Some((path, lines))
+            if !lines.is_empty() && lines.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+        {
+            (path, Some(lines))
+        }
+        _ => (target, None),
+    }
+}
+
+/// Have the server running for the worktree send an at-mention to Claude, returning the
+/// `path:start-end` it reported
+fn run_mention(
+    server_path: &str,
+    worktree: &Worktree,
+    mention_args: Vec<String>,
+) -> Result<String, String> {
+    let output = ProcessCommand::new(absolute_binary_path(server_path))
+        .args([
+            "--worktree".to_string(),
+            worktree.root_path(),
+            "mention".to_string(),
+        ])
+        .args(mention_args)
+        .env("RUST_LOG", "error")
+        .output()?;
+    if output.status != Some(0) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let error = stderr
+            .lines()
+            .find_map(|line| line.strip_prefix("Error: "))
+            .or_else(|| stderr.lines().rev().find(|line| !line.trim().is_empty()))
+            .unwrap_or("the server did not answer");
+        return Err(format!("Claude Code mention failed: {}", error));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
 /// Find the claude-code-server binary - downloads from GitHub releases if needed
-fn find_server_binary(worktree: &Worktree) -> Result<String, String> {
+fn find_server_binary(
+    worktree: &Worktree,
+    settings: &ExtensionSettings,
+    report: &mut StartupReport,
+) -> Result<String, String> {
     let worktree_root = worktree.root_path();
 
     eprintln!(
         "🔍 [DEBUG] find_server_binary called with worktree_root: {}",
         worktree_root
     );
-    eprintln!(
-        "🔍 [DEBUG] FORCE_DEVELOPMENT_MODE: {}",
-        FORCE_DEVELOPMENT_MODE
-    );
-    eprintln!(
-        "🔍 [DEBUG] Checking if '{}' contains 'claude-code-zed'",
-        worktree_root
-    );
 
-    // For development: look for manually copied binary in extension work directory
-    // Check both the directory name AND the development flag
-    if worktree_root.contains("claude-code-zed") || FORCE_DEVELOPMENT_MODE {
-        if FORCE_DEVELOPMENT_MODE {
-            eprintln!("✅ [DEBUG] Development mode FORCED via FORCE_DEVELOPMENT_MODE flag");
-        } else {
-            eprintln!("✅ [DEBUG] Detected development environment (claude-code-zed in path)");
+    // An explicit binary path wins over development detection and downloads
+    if let Some(path) = &settings.binary_path {
+        eprintln!(
+            "✅ [INFO] Using server binary from binary.path setting: {}",
+            path
+        );
+        return Ok(path.clone());
+    }
+
+    // A server installed with a package manager is preferred over downloads when asked for
+    if settings.use_system_binary {
+        if let Some(path) = probe_system_binary(worktree, report) {
+            return Ok(path);
         }
+    }
 
-        // Check for manually copied development binary in extension work directory
-        // This allows developers to use their local build with fixes
-        let dev_binary_name =
-            get_platform_binary_prefix().unwrap_or("claude-code-server".to_string());
+    // For development: use a binary copied into the extension work directory
+    if is_development_mode(worktree, settings) {
+        let dev_binary_name = format!(
+            "{}{}",
+            get_platform_binary_prefix().unwrap_or("claude-code-server".to_string()),
+            executable_suffix()
+        );
         eprintln!(
-            "🔍 [DEBUG] Looking for development binary: {}",
+            "✅ [DEBUG] Development mode enabled, looking for development binary: {}",
             dev_binary_name
         );
 
-        // The binary should be manually copied to the extension work directory
-        // We'll return the expected path and let the download logic handle it
-        eprintln!("💡 [INFO] Development mode detected!");
+        if std::path::Path::new(&dev_binary_name).exists() {
+            return Ok(dev_binary_name);
+        }
+
         eprintln!("📋 [INFO] To use your local development build:");
         eprintln!("   1. Build the server: cd claude-code-server && cargo build");
         eprintln!(
             "   2. Copy binary to: ~/.../Zed/extensions/work/claude-code-zed/{}",
             dev_binary_name
         );
-        eprintln!("   3. Or let the extension download the GitHub release");
+        report.record_degraded(format!(
+            "Development mode is on but {} was not found; using a release binary",
+            dev_binary_name
+        ));
+    }
 
-        // Return the expected path - download_server_binary will handle checking if it exists
-        return Ok(dev_binary_name);
-    } else {
-        eprintln!("ℹ️ [INFO] Not in development environment, downloading from GitHub releases");
+    // For production: download binary from GitHub releases
+    if settings.prefer_musl && musl_binary_prefix().is_some() {
+        return download_server_binary(worktree, settings, report, true);
+    }
+    let binary = download_server_binary(worktree, settings, report, false)?;
+
+    // A glibc-linked build fails to load on distributions with an older glibc; the static
+    // musl build runs anywhere
+    if musl_binary_prefix().is_some() {
+        if let Some(error) = glibc_load_error(&binary) {
+            report.record_degraded(format!(
+                "{} cannot run on this system ({}); using the static musl build",
+                binary, error
+            ));
+            return download_server_binary(worktree, settings, report, true);
+        }
+    }
+    Ok(binary)
+}
+
+/// Refuse a server whose major version differs from the extension's, since the two would
+/// disagree on the protocol in ways that only show up as subtle failures. A binary that
+/// can't report its version is let through.
+fn check_server_version(binary: &str) -> Result<(), String> {
+    let Some(version) = binary_version(binary) else {
         eprintln!(
-            "🔍 [DEBUG] Worktree path '{}' does not contain 'claude-code-zed'",
-            worktree_root
+            "⚠️ [WARNING] Could not read the version of {}; starting it unchecked",
+            binary
         );
+        return Ok(());
+    };
+    let expected = env!("CARGO_PKG_VERSION");
+    let major =
+        |version: &str| major_minor(version).and_then(|(major, _)| major.parse::<u64>().ok());
+    let (Some(server_major), Some(extension_major)) = (major(&version), major(expected)) else {
+        return Ok(());
+    };
+    if server_major == extension_major {
+        return Ok(());
     }
 
-    // For production: download binary from GitHub releases
-    download_server_binary()
+    let upgrade = if server_major > extension_major {
+        "Update the Claude Code extension from Zed's Extensions panel".to_string()
+    } else {
+        format!(
+            "Remove any server_version or rollback_version pin, or set binary.path to a claude-code-server {}.x",
+            extension_major
+        )
+    };
+    Err(format!(
+        "claude-code-server {} is incompatible with the Claude Code extension {} (different major version). {}.",
+        version, expected, upgrade
+    ))
+}
+
+/// Path of `binary` usable by a process. Downloaded binaries live in the extension work
+/// directory, while processes run elsewhere.
+fn absolute_binary_path(binary: &str) -> String {
+    match std::env::current_dir() {
+        Ok(dir) if !std::path::Path::new(binary).is_absolute() => {
+            dir.join(binary).to_string_lossy().to_string()
+        }
+        _ => binary.to_string(),
+    }
+}
+
+/// Run `binary --version`, or recall what it printed when it already ran. `None` when it
+/// couldn't be started at all.
+fn probe_version(binary: &str) -> Option<VersionProbe> {
+    let mut probes = VERSION_PROBES.lock().unwrap();
+    probes
+        .entry(binary.to_string())
+        .or_insert_with(|| {
+            let output = ProcessCommand::new(absolute_binary_path(binary))
+                .arg("--version")
+                .output()
+                .ok()?;
+            Some(VersionProbe {
+                succeeded: output.status == Some(0),
+                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            })
+        })
+        .clone()
+}
+
+/// The version `binary --version` reports, if it runs
+fn binary_version(binary: &str) -> Option<String> {
+    let probe = probe_version(binary).filter(|probe| probe.succeeded)?;
+    // clap prints "claude-code-server <version>"
+    probe.stdout.split_whitespace().last().map(str::to_string)
+}
+
+/// The dynamic loader's error if `binary` can't run because it needs a newer glibc
+fn glibc_load_error(binary: &str) -> Option<String> {
+    let probe = probe_version(binary).filter(|probe| !probe.succeeded)?;
+    probe
+        .stderr
+        .lines()
+        .find(|line| line.contains("GLIBC_") || line.contains("libc.so"))
+        .map(|line| line.trim().to_string())
+}
+
+/// Whether to run a locally built server copied into the extension work directory, decided
+/// at runtime per worktree so contributors can switch without rebuilding the extension
+fn is_development_mode(worktree: &Worktree, settings: &ExtensionSettings) -> bool {
+    if let Some(dev_mode) = settings.dev_mode {
+        return dev_mode;
+    }
+    worktree
+        .shell_env()
+        .iter()
+        .any(|(key, value)| key == DEV_MODE_ENV && matches!(value.as_str(), "1" | "true"))
 }
 
 /// Download claude-code-server binary from GitHub releases
-/// Binary naming format: claude-code-server-<platform>-<version>
+/// Binary naming format: claude-code-server-<platform>[-experimental]-<version>
 /// e.g., claude-code-server-macos-aarch64-v0.1.0
-fn download_server_binary() -> Result<String, String> {
-    const GITHUB_REPO: &str = "celve/claude-code-zed";
-
-    // Determine platform-specific binary prefix (without version)
-    let binary_prefix = match get_platform_binary_prefix() {
+fn download_server_binary(
+    worktree: &Worktree,
+    settings: &ExtensionSettings,
+    report: &mut StartupReport,
+    musl: bool,
+) -> Result<String, String> {
+    // Determine platform-specific asset prefix (without version)
+    let platform_prefix = if musl {
+        musl_binary_prefix().ok_or_else(|| "No musl build for this platform".to_string())
+    } else {
+        get_platform_binary_prefix()
+    };
+    let asset_prefix = match platform_prefix {
         Ok(name) => {
             eprintln!("🔍 [DEBUG] Platform binary prefix: {}", name);
             name
         }
         Err(e) => {
-            eprintln!("❌ [ERROR] Failed to determine platform binary prefix: {}", e);
-            return Err(e);
+            report.record_failure("Platform detection", &e);
+            return use_system_binary(worktree, report, startup::HINT_UNSUPPORTED_PLATFORM);
         }
     };
+    let binary_prefix = settings.channel.binary_prefix(&asset_prefix);
 
-    // Try to get the latest release from GitHub
-    eprintln!("🔍 [DEBUG] Fetching latest release from GitHub repo: {}", GITHUB_REPO);
-    let release = match latest_github_release(
-        GITHUB_REPO,
-        GithubReleaseOptions {
-            require_assets: true,
-            pre_release: false,
-        },
-    ) {
+    // Zed performs the requests below with its own proxy setting; note the shell's proxy
+    // so a blocked request can explain the mismatch
+    let shell_proxy = startup::shell_proxy(&worktree.shell_env());
+    if let Some(proxy) = &shell_proxy {
+        eprintln!("🔍 [DEBUG] Shell proxy configured: {}", proxy);
+    }
+    report.set_shell_proxy(shell_proxy);
+
+    // A rollback pin skips the update check entirely and uses a retained older version
+    if let Some(version) = &settings.rollback_version {
+        return use_rollback_binary(&binary_prefix, version, report);
+    }
+
+    // A version pin fetches that exact tagged release and skips upgrades
+    if let Some(version) = &settings.server_version {
+        let pinned_binary = versioned_binary_name(&binary_prefix, version);
+        if std::path::Path::new(&pinned_binary).exists() {
+            eprintln!("✅ [INFO] Using pinned server binary {}", pinned_binary);
+            if let Err(e) = ensure_executable(&pinned_binary) {
+                eprintln!("⚠️ [WARNING] Failed to make binary executable: {}", e);
+            }
+            prune_old_binaries(&binary_prefix, &pinned_binary, settings);
+            return Ok(pinned_binary);
+        }
+    }
+
+    // A mirror serves assets directly, without GitHub's release API
+    if let Some(template) = &settings.release_url {
+        return download_from_mirror(
+            template,
+            &asset_prefix,
+            &binary_prefix,
+            worktree,
+            settings,
+            report,
+        );
+    }
+
+    let repo = settings.release_repo();
+
+    // Try to get the pinned or latest release from GitHub, retrying transient failures
+    report.set_stage(InstallStage::CheckingRelease, repo);
+    let release = with_retry(RELEASE_LOOKUP_ATTEMPTS, || match &settings.server_version {
+        Some(version) => {
+            eprintln!(
+                "🔍 [DEBUG] Fetching pinned release {} from GitHub repo: {}",
+                version, repo
+            );
+            github_release_by_tag_name(repo, version)
+        }
+        None => {
+            eprintln!(
+                "🔍 [DEBUG] Fetching latest {:?} release from GitHub repo: {}",
+                settings.channel, repo
+            );
+            // The experimental channel follows pre-releases
+            latest_github_release(
+                repo,
+                GithubReleaseOptions {
+                    require_assets: true,
+                    pre_release: settings.channel == Channel::Experimental,
+                },
+            )
+        }
+    });
+    let release = match release {
         Ok(r) => r,
         Err(e) => {
-            eprintln!("⚠️ [WARNING] Failed to fetch GitHub release: {}", e);
-            // Offline fallback: try to find any existing versioned binary
-            let existing = find_existing_binaries(&binary_prefix);
-            if let Some(binary) = existing.into_iter().find(|b| b.contains("-v")) {
-                eprintln!("🔄 [FALLBACK] Using cached binary: {}", binary);
-                if let Err(e) = make_file_executable(&binary) {
+            report.record_failure(&format!("GitHub release lookup ({})", repo), &e);
+            // Offline fallback: use the newest versioned binary downloaded earlier
+            let newest = versioned_binaries_newest_first(&binary_prefix)
+                .into_iter()
+                .next();
+            if let Some(binary) = newest {
+                report.record_degraded(format!(
+                    "Could not check for server updates ({}); using cached binary {}",
+                    e, binary
+                ));
+                if let Err(e) = ensure_executable(&binary) {
                     eprintln!("⚠️ [WARNING] Failed to make binary executable: {}", e);
                 }
                 return Ok(binary);
             }
-            return Err(format!("Failed to get latest release and no cached binary: {}", e));
+            report.record_failure("Cached binaries", "none found");
+            return use_system_binary(worktree, report, startup::HINT_OFFLINE);
         }
     };
 
@@ -229,15 +664,20 @@ fn download_server_binary() -> Result<String, String> {
     );
 
     // Expected binary name with version included
-    let versioned_binary_name = format!("{}-{}", binary_prefix, release.version);
-    eprintln!("🔍 [DEBUG] Expected versioned binary: {}", versioned_binary_name);
+    let versioned_binary_name = versioned_binary_name(&binary_prefix, &release.version);
+    eprintln!(
+        "🔍 [DEBUG] Expected versioned binary: {}",
+        versioned_binary_name
+    );
 
     // Check if we already have this exact version
     if std::path::Path::new(&versioned_binary_name).exists() {
         eprintln!("✅ [INFO] Binary {} is up to date", versioned_binary_name);
-        if let Err(e) = make_file_executable(&versioned_binary_name) {
+        if let Err(e) = ensure_executable(&versioned_binary_name) {
             eprintln!("⚠️ [WARNING] Failed to make binary executable: {}", e);
         }
+        // Collect garbage left by earlier runs, e.g. when a previous cleanup was interrupted
+        prune_old_binaries(&binary_prefix, &versioned_binary_name, settings);
         return Ok(versioned_binary_name);
     }
 
@@ -248,83 +688,242 @@ fn download_server_binary() -> Result<String, String> {
     }
 
     // Find the asset that matches our platform (GitHub releases use non-versioned names)
-    let asset = release
-        .assets
-        .iter()
-        .find(|asset| asset.name == binary_prefix)
-        .ok_or_else(|| {
-            eprintln!("❌ [ERROR] Asset {} not found in release", binary_prefix);
-            eprintln!("🔍 [DEBUG] Looking for asset matching: {}", binary_prefix);
-            format!("Asset {} not found in release", binary_prefix)
-        })?;
+    let asset_name = format!("{}{}", asset_prefix, executable_suffix());
+    let Some(asset) = release.assets.iter().find(|asset| asset.name == asset_name) else {
+        report.record_failure(
+            &format!("GitHub release {}", release.version),
+            format!("asset {} not found", asset_name),
+        );
+        return use_existing_binary(&binary_prefix, worktree, report);
+    };
 
     eprintln!("✅ [SUCCESS] Found matching asset: {}", asset.name);
-    eprintln!("🔍 [DEBUG] Download URL: {}", asset.download_url);
+
+    // A published signature must match; releases from before signing have none and are
+    // only installed when unsigned binaries are explicitly allowed
+    let signature_name = format!("{}{}", asset.name, signature::SIGNATURE_EXTENSION);
+    let signature_asset = release.assets.iter().find(|a| a.name == signature_name);
+    install_binary(
+        &asset.download_url,
+        signature_asset.map(|signature_asset| signature_asset.download_url.as_str()),
+        &binary_prefix,
+        &versioned_binary_name,
+        worktree,
+        settings,
+        report,
+    )
+}
+
+/// Download the server from a mirror, given a URL template for release assets
+fn download_from_mirror(
+    template: &str,
+    asset_prefix: &str,
+    binary_prefix: &str,
+    worktree: &Worktree,
+    settings: &ExtensionSettings,
+    report: &mut StartupReport,
+) -> Result<String, String> {
+    // Mirrors have no release API to find the latest version, so one must be pinned
+    let Some(version) = &settings.server_version else {
+        report.record_failure(
+            &format!("Release mirror {}", template),
+            "server_version must be set to download from a mirror",
+        );
+        return use_existing_binary(binary_prefix, worktree, report);
+    };
+
+    let asset_name = format!("{}{}", asset_prefix, executable_suffix());
+    let download_url = template
+        .replace("{version}", version)
+        .replace("{asset}", &asset_name);
+    let signature_url = format!("{}{}", download_url, signature::SIGNATURE_EXTENSION);
+    let versioned_binary_name = versioned_binary_name(binary_prefix, version);
+
+    install_binary(
+        &download_url,
+        Some(&signature_url),
+        binary_prefix,
+        &versioned_binary_name,
+        worktree,
+        settings,
+        report,
+    )
+}
+
+/// Download a release asset, verify its signature and install it as `versioned_binary_name`,
+/// falling back to an existing binary on any failure. An asset without a signature is
+/// rejected unless `allow_unsigned_binaries` is set.
+fn install_binary(
+    download_url: &str,
+    signature_url: Option<&str>,
+    binary_prefix: &str,
+    versioned_binary_name: &str,
+    worktree: &Worktree,
+    settings: &ExtensionSettings,
+    report: &mut StartupReport,
+) -> Result<String, String> {
+    eprintln!("🔍 [DEBUG] Download URL: {}", download_url);
 
     // Download to temp file first to preserve existing binary until success
+    report.set_stage(InstallStage::Downloading, download_url);
     let temp_binary_name = format!("{}.downloading", versioned_binary_name);
     eprintln!("🔍 [DEBUG] Downloading to temp file: {}", temp_binary_name);
 
-    match download_file(
-        &asset.download_url,
+    if let Err(e) = download_file(
+        download_url,
         &temp_binary_name,
         DownloadedFileType::Uncompressed,
     ) {
-        Ok(_) => {
-            eprintln!("✅ [SUCCESS] Binary downloaded to temp file: {}", temp_binary_name);
-
-            // Make the binary executable
-            eprintln!("🔍 [DEBUG] Making binary executable: {}", temp_binary_name);
-            if let Err(e) = make_file_executable(&temp_binary_name) {
-                eprintln!("❌ [ERROR] Failed to make binary executable: {}", e);
-                let _ = std::fs::remove_file(&temp_binary_name);
-                // Fallback to existing binary
-                let existing = find_existing_binaries(&binary_prefix);
-                if let Some(binary) = existing.into_iter().next() {
-                    eprintln!("🔄 [FALLBACK] Using existing binary: {}", binary);
-                    return Ok(binary);
-                }
-                return Err(format!("Failed to make binary executable: {}", e));
-            }
+        report.record_failure(&format!("Download of {}", download_url), &e);
+        // Clean up partial download if any
+        let _ = std::fs::remove_file(&temp_binary_name);
+        return use_existing_binary(binary_prefix, worktree, report);
+    }
+    eprintln!(
+        "✅ [SUCCESS] Binary downloaded to temp file: {}",
+        temp_binary_name
+    );
 
-            // Rename temp file to final name (atomic on most filesystems)
-            if let Err(e) = std::fs::rename(&temp_binary_name, &versioned_binary_name) {
-                eprintln!("❌ [ERROR] Failed to rename binary: {}", e);
-                let _ = std::fs::remove_file(&temp_binary_name);
-                // Fallback to existing binary
-                let existing = find_existing_binaries(&binary_prefix);
-                if let Some(binary) = existing.into_iter().next() {
-                    eprintln!("🔄 [FALLBACK] Using existing binary: {}", binary);
-                    return Ok(binary);
-                }
-                return Err(format!("Failed to rename binary: {}", e));
-            }
+    report.set_stage(
+        InstallStage::Verifying,
+        signature_url.unwrap_or(download_url),
+    );
+    let verified = signature::verify_release_asset(
+        signature_url,
+        &temp_binary_name,
+        settings.allow_unsigned_binaries,
+    );
+    match verified {
+        Ok(Verification::Verified) => {
+            eprintln!("✅ [SUCCESS] Signature verified for {}", download_url)
+        }
+        Ok(Verification::Unsigned) => report.record_degraded(format!(
+            "{} has no published signature, so it was installed unverified",
+            download_url
+        )),
+        Err(e) => {
+            report.record_failure(&format!("Signature verification of {}", download_url), &e);
+            let _ = std::fs::remove_file(&temp_binary_name);
+            return use_existing_binary(binary_prefix, worktree, report);
+        }
+    }
 
-            // Clean up old binaries only AFTER successful download and rename
-            for old_binary in find_existing_binaries(&binary_prefix) {
-                if old_binary != versioned_binary_name {
-                    eprintln!("🗑️ [INFO] Removing old binary: {}", old_binary);
-                    let _ = std::fs::remove_file(&old_binary);
-                }
-            }
+    // Make the binary executable
+    eprintln!("🔍 [DEBUG] Making binary executable: {}", temp_binary_name);
+    if let Err(e) = ensure_executable(&temp_binary_name) {
+        report.record_failure("Marking downloaded binary executable", &e);
+        let _ = std::fs::remove_file(&temp_binary_name);
+        return use_existing_binary(binary_prefix, worktree, report);
+    }
+
+    // Rename temp file to final name (atomic on most filesystems)
+    if let Err(e) = std::fs::rename(&temp_binary_name, versioned_binary_name) {
+        report.record_failure("Installing downloaded binary", &e);
+        let _ = std::fs::remove_file(&temp_binary_name);
+        return use_existing_binary(binary_prefix, worktree, report);
+    }
+
+    // Clean up old binaries only AFTER successful download and rename
+    prune_old_binaries(binary_prefix, versioned_binary_name, settings);
 
-            eprintln!("✅ [SUCCESS] Binary {} is ready", versioned_binary_name);
-            Ok(versioned_binary_name)
+    eprintln!("✅ [SUCCESS] Binary {} is ready", versioned_binary_name);
+    Ok(versioned_binary_name.to_string())
+}
+
+/// Fall back to a previously downloaded binary, then to the system PATH
+fn use_existing_binary(
+    prefix: &str,
+    worktree: &Worktree,
+    report: &mut StartupReport,
+) -> Result<String, String> {
+    if let Some(binary) = find_existing_binaries(prefix).into_iter().next() {
+        report.record_degraded(format!(
+            "Could not install the latest server release; using existing binary {}",
+            binary
+        ));
+        return Ok(binary);
+    }
+    report.record_failure("Existing binaries", "none found");
+    use_system_binary(worktree, report, startup::HINT_OFFLINE)
+}
+
+/// Find claude-code-server on the user's PATH and check that its version is compatible with
+/// this extension (same major and minor version), for the `use_system_binary` setting
+fn probe_system_binary(worktree: &Worktree, report: &mut StartupReport) -> Option<String> {
+    let Some(path) = worktree.which("claude-code-server") else {
+        report.record_failure("System PATH", "claude-code-server not found");
+        return None;
+    };
+
+    // Run by name in the worktree's environment, which finds the same binary, so the
+    // capability allowing it can name the command
+    let output = match ProcessCommand::new("claude-code-server")
+        .arg("--version")
+        .envs(worktree.shell_env())
+        .output()
+    {
+        Ok(output) if output.status == Some(0) => output,
+        Ok(output) => {
+            report.record_failure(
+                &format!("System binary {}", path),
+                format!(
+                    "--version failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            );
+            return None;
         }
         Err(e) => {
-            eprintln!("❌ [ERROR] Failed to download binary: {}", e);
-            eprintln!("🔍 [DEBUG] Download error details: {}", e);
-            // Clean up partial download if any
-            let _ = std::fs::remove_file(&temp_binary_name);
+            report.record_failure(&format!("System binary {}", path), e);
+            return None;
+        }
+    };
 
-            // Fallback: try existing binary first, then system PATH
-            let existing = find_existing_binaries(&binary_prefix);
-            if let Some(binary) = existing.into_iter().next() {
-                eprintln!("🔄 [FALLBACK] Using existing binary: {}", binary);
-                return Ok(binary);
-            }
-            eprintln!("🔄 [FALLBACK] Using system binary: claude-code-server");
-            Ok("claude-code-server".to_string())
+    // clap prints "claude-code-server <version>"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.split_whitespace().last().unwrap_or_default();
+    let expected = env!("CARGO_PKG_VERSION");
+    if major_minor(version).is_none() || major_minor(version) != major_minor(expected) {
+        report.record_failure(
+            &format!("System binary {}", path),
+            format!(
+                "version {} is incompatible with the extension ({})",
+                version, expected
+            ),
+        );
+        return None;
+    }
+
+    eprintln!(
+        "✅ [INFO] Using claude-code-server {} from PATH: {}",
+        version, path
+    );
+    Some(path)
+}
+
+/// The major and minor components of a version such as "0.1.3" or "v0.1.3"
+fn major_minor(version: &str) -> Option<(&str, &str)> {
+    let mut parts = version.trim_start_matches('v').split('.');
+    Some((parts.next()?, parts.next()?))
+}
+
+/// Last-resort fallback: a claude-code-server binary on the user's PATH
+fn use_system_binary(
+    worktree: &Worktree,
+    report: &mut StartupReport,
+    hint: &str,
+) -> Result<String, String> {
+    match worktree.which("claude-code-server") {
+        Some(path) => {
+            report.record_degraded(format!(
+                "Using claude-code-server from PATH ({}); its version may not match the extension",
+                path
+            ));
+            Ok(path)
+        }
+        None => {
+            report.record_failure("System PATH", "claude-code-server not found");
+            Err(report.error("no usable server binary", hint))
         }
     }
 }
@@ -339,11 +938,48 @@ fn get_platform_binary_prefix() -> Result<String, String> {
         (Os::Mac, Architecture::Aarch64) => Ok("claude-code-server-macos-aarch64".to_string()),
         (Os::Mac, Architecture::X8664) => Ok("claude-code-server-macos-x86_64".to_string()),
         (Os::Linux, Architecture::X8664) => Ok("claude-code-server-linux-x86_64".to_string()),
-        (Os::Windows, _) => Err("Windows is not currently supported".to_string()),
+        (Os::Linux, Architecture::Aarch64) => Ok("claude-code-server-linux-aarch64".to_string()),
+        (Os::Windows, Architecture::X8664) => Ok("claude-code-server-windows-x86_64".to_string()),
+        (Os::Windows, Architecture::Aarch64) => {
+            Ok("claude-code-server-windows-aarch64".to_string())
+        }
         (os, arch) => Err(format!("Unsupported platform: {:?}-{:?}", os, arch)),
     }
 }
 
+/// Asset prefix of the statically linked musl build, on the platforms that have one
+/// e.g., "claude-code-server-linux-x86_64-musl"
+fn musl_binary_prefix() -> Option<String> {
+    match current_platform() {
+        (Os::Linux, Architecture::X8664) => {
+            Some("claude-code-server-linux-x86_64-musl".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// File extension of executables on the current platform ("" or ".exe")
+fn executable_suffix() -> &'static str {
+    match current_platform().0 {
+        Os::Windows => ".exe",
+        _ => "",
+    }
+}
+
+/// Binary name for a release version
+/// e.g., "claude-code-server-macos-aarch64-v0.1.0" or "claude-code-server-windows-x86_64-v0.1.0.exe"
+fn versioned_binary_name(prefix: &str, version: &str) -> String {
+    format!("{}-{}{}", prefix, version, executable_suffix())
+}
+
+/// Mark a binary as executable. Windows has no executable bit, so nothing needs to change there.
+fn ensure_executable(binary: &str) -> Result<(), String> {
+    match current_platform().0 {
+        Os::Windows => Ok(()),
+        _ => make_file_executable(binary),
+    }
+}
+
 /// Find all existing binaries that match the prefix pattern
 /// Returns filenames for both versioned (e.g., "claude-code-server-macos-aarch64-v0.1.0")
 /// and legacy non-versioned (e.g., "claude-code-server-macos-aarch64") binaries
@@ -351,17 +987,23 @@ fn find_existing_binaries(prefix: &str) -> Vec<String> {
     let mut binaries = Vec::new();
 
     // Check for legacy non-versioned binary (exact match)
-    if std::path::Path::new(prefix).exists() {
-        eprintln!("🔍 [DEBUG] Found legacy binary: {}", prefix);
-        binaries.push(prefix.to_string());
+    let legacy_binary = format!("{}{}", prefix, executable_suffix());
+    if std::path::Path::new(&legacy_binary).exists() {
+        eprintln!("🔍 [DEBUG] Found legacy binary: {}", legacy_binary);
+        binaries.push(legacy_binary);
     }
 
     // Check for versioned binaries
     if let Ok(entries) = std::fs::read_dir(".") {
         for entry in entries.flatten() {
             let filename = entry.file_name().to_string_lossy().to_string();
-            // Match files that start with prefix and have a version suffix (e.g., "-v0.1.0")
-            if filename.starts_with(prefix) && filename.len() > prefix.len() {
+            // Match files that start with prefix and have a version suffix (e.g., "-v0.1.0"),
+            // skipping unfinished downloads
+            if filename.starts_with(prefix)
+                && filename.len() > prefix.len()
+                && filename.ends_with(executable_suffix())
+                && !filename.ends_with(".downloading")
+            {
                 let suffix = &filename[prefix.len()..];
                 if suffix.starts_with("-v") {
                     eprintln!("🔍 [DEBUG] Found versioned binary: {}", filename);
@@ -374,4 +1016,293 @@ fn find_existing_binaries(prefix: &str) -> Vec<String> {
     binaries
 }
 
+/// Use a previously downloaded binary pinned via the `rollback_version` setting
+fn use_rollback_binary(
+    prefix: &str,
+    version: &str,
+    report: &mut StartupReport,
+) -> Result<String, String> {
+    let pinned_binary = versioned_binary_name(prefix, version);
+    eprintln!("📌 [INFO] Rollback pinned to version {}", version);
+
+    if !std::path::Path::new(&pinned_binary).exists() {
+        let available: Vec<String> = versioned_binaries_newest_first(prefix)
+            .into_iter()
+            .map(|binary| {
+                binary[prefix.len() + 1..binary.len() - executable_suffix().len()].to_string()
+            })
+            .collect();
+        report.record_failure(
+            &format!("Rollback binary {}", pinned_binary),
+            format!(
+                "not found (available versions: {})",
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            ),
+        );
+        return Err(report.error(
+            format!("rollback version {} is not available locally", version),
+            "remove the `rollback_version` setting or pin one of the available versions",
+        ));
+    }
+
+    if let Err(e) = ensure_executable(&pinned_binary) {
+        eprintln!("⚠️ [WARNING] Failed to make binary executable: {}", e);
+    }
+    Ok(pinned_binary)
+}
+
+/// Remove old binaries, keeping the current one plus the configured number of previous versions.
+/// Only `<prefix>-<version>` binaries count as versions; the unversioned binary `make dev-build`
+/// installs is never touched. Binaries for other platforms and interrupted downloads are always
+/// removed; a rollback-pinned version is never removed.
+fn prune_old_binaries(prefix: &str, current: &str, settings: &ExtensionSettings) {
+    let pinned = settings
+        .rollback_version
+        .as_ref()
+        .map(|version| versioned_binary_name(prefix, version));
+
+    let previous_versions = versioned_binaries_newest_first(prefix)
+        .into_iter()
+        .filter(|binary| binary != current);
+
+    for (index, old_binary) in previous_versions.enumerate() {
+        if index < settings.keep_previous_versions || pinned.as_deref() == Some(&old_binary) {
+            eprintln!("📦 [INFO] Keeping previous binary: {}", old_binary);
+            continue;
+        }
+        eprintln!("🗑️ [INFO] Removing old binary: {}", old_binary);
+        let _ = std::fs::remove_file(&old_binary);
+    }
+
+    // Leftovers of earlier runs: partial downloads and binaries for another platform
+    // (e.g. after moving from an Intel to an Apple Silicon build of Zed). Other channels'
+    // binaries share the platform prefix and are kept.
+    let platform_prefix = get_platform_binary_prefix().unwrap_or_else(|_| prefix.to_string());
+    let Ok(entries) = std::fs::read_dir(".") else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        let stale = filename.starts_with("claude-code-server-")
+            && (filename.ends_with(".downloading") || !filename.starts_with(&platform_prefix));
+        if stale && entry.path().is_file() {
+            eprintln!("🗑️ [INFO] Removing stale file: {}", filename);
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Run `operation` up to `attempts` times, sleeping with exponential backoff between
+/// failures (e.g. while offline or rate limited), and return the last error if all fail
+fn with_retry<T>(
+    attempts: u32,
+    mut operation: impl FnMut() -> Result<T, String>,
+) -> Result<T, String> {
+    let mut delay = RELEASE_RETRY_BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < attempts => {
+                eprintln!(
+                    "⚠️ [WARNING] Attempt {}/{} failed: {}; retrying in {:?}",
+                    attempt, attempts, e, delay
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Versioned binaries for the prefix, sorted from newest to oldest version
+fn versioned_binaries_newest_first(prefix: &str) -> Vec<String> {
+    let mut binaries: Vec<(semver::Version, String)> = find_existing_binaries(prefix)
+        .into_iter()
+        .filter_map(|binary| {
+            parse_binary_version(prefix, &binary, executable_suffix()).map(|v| (v, binary))
+        })
+        .collect();
+    binaries.sort_by(|a, b| b.0.cmp(&a.0));
+    binaries.into_iter().map(|(_, binary)| binary).collect()
+}
+
+/// Parse the version from a versioned binary name ending in the platform's executable
+/// `suffix`, e.g. "claude-code-server-macos-aarch64-v0.1.10" -> 0.1.10. Versions compare by
+/// semver precedence, so a pre-release sorts before its release.
+fn parse_binary_version(prefix: &str, binary: &str, suffix: &str) -> Option<semver::Version> {
+    let version = binary
+        .strip_prefix(prefix)?
+        .strip_prefix("-v")?
+        .strip_suffix(suffix)?;
+    semver::Version::parse(version).ok()
+}
+
 zed_extension_api::register_extension!(ClaudeCodeExtension);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings in a table of the extension manifest, without comments or the entry name
+    fn manifest_table(header: &str) -> Vec<&'static str> {
+        include_str!("../extension.toml")
+            .lines()
+            .skip_while(|line| *line != header)
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .filter(|line| !line.trim_start().starts_with('#') && !line.starts_with("name = "))
+            .collect()
+    }
+
+    #[test]
+    fn experimental_server_has_the_same_languages() {
+        for table in ["", ".language_ids"] {
+            let stable = manifest_table(&format!("[language_servers.claude-code-server{}]", table));
+            let experimental = manifest_table(&format!(
+                "[language_servers.claude-code-server-experimental{}]",
+                table
+            ));
+            assert!(!stable.is_empty());
+            assert_eq!(stable, experimental, "run `make sync-languages`");
+        }
+    }
+
+    #[test]
+    fn merge_json_merges_objects_key_by_key() {
+        let mut base = serde_json::json!({
+            "claudeCode": { "debug": true, "websocket": { "autoStart": true } },
+            "workspaceFolders": []
+        });
+        merge_json(
+            &mut base,
+            serde_json::json!({
+                "claudeCode": { "debug": false, "websocket": { "portRange": [1, 2] } },
+                "extra": 1
+            }),
+        );
+        assert_eq!(
+            base,
+            serde_json::json!({
+                "claudeCode": {
+                    "debug": false,
+                    "websocket": { "autoStart": true, "portRange": [1, 2] }
+                },
+                "workspaceFolders": [],
+                "extra": 1
+            })
+        );
+    }
+
+    #[test]
+    fn merge_json_replaces_everything_but_objects() {
+        let mut base = serde_json::json!({ "linters": [1, 2], "locale": "en", "tap": {} });
+        merge_json(
+            &mut base,
+            serde_json::json!({ "linters": [3], "locale": null, "tap": "off" }),
+        );
+        assert_eq!(
+            base,
+            serde_json::json!({ "linters": [3], "locale": null, "tap": "off" })
+        );
+
+        let mut base = serde_json::json!("value");
+        merge_json(&mut base, serde_json::json!({ "key": 1 }));
+        assert_eq!(base, serde_json::json!({ "key": 1 }));
+    }
+
+    #[test]
+    fn workspace_configuration_follows_the_settings() {
+        let settings = ExtensionSettings {
+            debug: false,
+            port_range: Some([40000, 41000]),
+            initialization_options: Some(serde_json::json!({
+                "claudeCode": { "websocket": { "strictSubprotocol": true } }
+            })),
+            ..ExtensionSettings::default()
+        };
+        assert_eq!(
+            workspace_configuration(&settings),
+            serde_json::json!({
+                "claudeCode": {
+                    "debug": false,
+                    "websocket": {
+                        "autoStart": true,
+                        "portRange": [40000, 41000],
+                        "strictSubprotocol": true
+                    }
+                }
+            })
+        );
+    }
+
+    const PREFIX: &str = "claude-code-server-linux-x86_64";
+
+    fn version(binary: &str) -> Option<semver::Version> {
+        parse_binary_version(PREFIX, binary, "")
+    }
+
+    #[test]
+    fn parse_binary_version_reads_release_components() {
+        assert_eq!(
+            version("claude-code-server-linux-x86_64-v0.1.10"),
+            Some(semver::Version::new(0, 1, 10))
+        );
+        assert_eq!(
+            parse_binary_version(
+                "claude-code-server-windows-x86_64",
+                "claude-code-server-windows-x86_64-v1.2.3.exe",
+                ".exe"
+            ),
+            Some(semver::Version::new(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn parse_binary_version_orders_by_semver_precedence() {
+        let experimental = "claude-code-server-linux-x86_64-experimental";
+        assert_eq!(
+            parse_binary_version(
+                experimental,
+                "claude-code-server-linux-x86_64-experimental-v0.2.0-beta.3",
+                ""
+            )
+            .map(|version| version.pre.to_string()),
+            Some("beta.3".to_string())
+        );
+        // A pre-release sorts before its release, and pre-releases by their labels
+        assert!(
+            version("claude-code-server-linux-x86_64-v1.2.0-rc.1")
+                < version("claude-code-server-linux-x86_64-v1.2.0")
+        );
+        assert!(
+            version("claude-code-server-linux-x86_64-v1.2.0-beta.3")
+                < version("claude-code-server-linux-x86_64-v1.2.0-rc.1")
+        );
+        assert!(
+            version("claude-code-server-linux-x86_64-v1.2.0")
+                < version("claude-code-server-linux-x86_64-v1.10.0-alpha.1")
+        );
+    }
+
+    #[test]
+    fn parse_binary_version_rejects_other_binaries() {
+        // The legacy unversioned binary, another channel's, another platform's and leftovers
+        for binary in [
+            "claude-code-server-linux-x86_64",
+            "claude-code-server-linux-x86_64-experimental-v0.2.0",
+            "claude-code-server-linux-aarch64-v0.1.0",
+            "claude-code-server-linux-x86_64-v0.1.0.exe",
+            "claude-code-server-linux-x86_64-v0.1.x",
+            "claude-code-server-linux-x86_64-v",
+        ] {
+            assert_eq!(parse_binary_version(PREFIX, binary, ""), None, "{}", binary);
+        }
+    }
+}
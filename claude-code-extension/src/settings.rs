@@ -0,0 +1,294 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use zed_extension_api::{settings::LspSettings, Worktree};
+
+/// Language server ID of the stable server, also used for settings lookups outside a
+/// language server (e.g. slash commands)
+pub const LANGUAGE_SERVER_ID: &str = "claude-code-server";
+
+/// Language server ID of the experimental server, for trialling pre-releases side by side
+/// with the stable configuration
+pub const EXPERIMENTAL_LANGUAGE_SERVER_ID: &str = "claude-code-server-experimental";
+
+/// Which server releases a language server entry runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// The latest release; runs unless disabled
+    #[default]
+    Stable,
+    /// The latest pre-release; only runs in worktrees that enable it
+    Experimental,
+}
+
+impl Channel {
+    /// The channel a language server ID belongs to, if it is one of ours
+    pub fn for_language_server(id: &str) -> Option<Self> {
+        match id {
+            LANGUAGE_SERVER_ID => Some(Channel::Stable),
+            EXPERIMENTAL_LANGUAGE_SERVER_ID => Some(Channel::Experimental),
+            _ => None,
+        }
+    }
+
+    pub fn language_server_id(self) -> &'static str {
+        match self {
+            Channel::Stable => LANGUAGE_SERVER_ID,
+            Channel::Experimental => EXPERIMENTAL_LANGUAGE_SERVER_ID,
+        }
+    }
+
+    /// Local name prefix of this channel's downloaded binaries, given the platform's asset
+    /// prefix; channels keep separate binaries so one never prunes the other's
+    pub fn binary_prefix(self, asset_prefix: &str) -> String {
+        match self {
+            Channel::Stable => asset_prefix.to_string(),
+            Channel::Experimental => format!("{}-experimental", asset_prefix),
+        }
+    }
+}
+
+/// GitHub repository the server is released from
+const DEFAULT_RELEASE_REPO: &str = "celve/claude-code-zed";
+
+/// Number of previous server versions kept on disk by default
+const DEFAULT_KEEP_PREVIOUS_VERSIONS: usize = 1;
+
+/// Environment variables passed from the worktree's shell to the server by default; a
+/// trailing `*` matches any suffix
+const DEFAULT_ENV_PASSTHROUGH: &[&str] = &["ANTHROPIC_*", "CLAUDE_CONFIG_DIR"];
+
+/// Server modes that keep the LSP on stdio, which Zed needs to talk to the server
+const SERVER_MODES: &[&str] = &["hybrid", "lsp"];
+
+/// User-configurable extension settings, read from Zed's `lsp` settings:
+///
+/// ```json
+/// "lsp": {
+///   "claude-code-server": {
+///     "settings": { "keep_previous_versions": 2, "rollback_version": "v0.1.2", "debug": false }
+///   }
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ExtensionSettings {
+    /// The language server entry these settings were read for
+    #[serde(skip)]
+    pub channel: Channel,
+    /// Whether the server runs in this worktree; defaults to on for the stable channel and
+    /// off for the experimental one
+    pub enabled: Option<bool>,
+    /// How many previously downloaded versions to retain after an update
+    pub keep_previous_versions: usize,
+    /// Pin a previously downloaded version (e.g. "v0.1.2") instead of the latest release
+    pub rollback_version: Option<String>,
+    /// Release tag (e.g. "v0.1.3") to download and keep using instead of upgrading
+    pub server_version: Option<String>,
+    /// GitHub repository ("owner/name") to download releases from, e.g. a fork
+    pub release_repo: Option<String>,
+    /// URL template for release assets on a mirror or artifact server, with `{version}`
+    /// and `{asset}` placeholders; bypasses GitHub entirely and requires `server_version`
+    pub release_url: Option<String>,
+    /// Install release binaries that have no signature, e.g. from a mirror that doesn't
+    /// publish one; binaries whose signature doesn't match are still rejected
+    pub allow_unsigned_binaries: bool,
+    /// Server binary to run instead of a downloaded one, from the standard `binary.path`
+    /// LSP setting
+    #[serde(skip)]
+    pub binary_path: Option<String>,
+    /// Extra server arguments, from the standard `binary.arguments` LSP setting
+    #[serde(skip)]
+    pub binary_arguments: Vec<String>,
+    /// Environment variables for the server, from the standard `binary.env` LSP setting
+    #[serde(skip)]
+    pub binary_env: HashMap<String, String>,
+    /// Options from the standard `initialization_options` LSP setting, merged over the ones
+    /// the extension sends
+    #[serde(skip)]
+    pub initialization_options: Option<serde_json::Value>,
+    /// Names of shell environment variables passed through to the server, such as
+    /// credentials for Claude API features; a trailing `*` matches any suffix
+    pub env_passthrough: Option<Vec<String>>,
+    /// Run a development binary copied into the extension work directory; overrides the
+    /// `CLAUDE_CODE_ZED_DEV` environment variable
+    pub dev_mode: Option<bool>,
+    /// Prefer a claude-code-server found on PATH (e.g. from Homebrew or cargo) over downloads,
+    /// as long as its version is compatible
+    pub use_system_binary: bool,
+    /// Download the statically linked musl build on Linux x86_64, for distributions whose
+    /// glibc is too old for the default build
+    pub prefer_musl: bool,
+    /// Run the server with debug logging
+    pub debug: bool,
+    /// Server log level ("error", "warn", "info", "debug", "trace"); overrides `debug`
+    pub log_level: Option<String>,
+    /// Server mode: "hybrid" (LSP plus WebSocket) or "lsp"
+    pub mode: Option<String>,
+    /// Preferred WebSocket port
+    pub port: Option<u16>,
+    /// Inclusive range to allocate the WebSocket port from
+    pub port_range: Option<[u16; 2]>,
+    /// Start the WebSocket server with the language server; when off, it waits for the
+    /// "Start Claude Code" code action
+    pub auto_start: bool,
+}
+
+impl Default for ExtensionSettings {
+    fn default() -> Self {
+        Self {
+            channel: Channel::default(),
+            enabled: None,
+            keep_previous_versions: DEFAULT_KEEP_PREVIOUS_VERSIONS,
+            rollback_version: None,
+            server_version: None,
+            release_repo: None,
+            release_url: None,
+            allow_unsigned_binaries: false,
+            binary_path: None,
+            binary_arguments: Vec::new(),
+            binary_env: HashMap::new(),
+            initialization_options: None,
+            env_passthrough: None,
+            dev_mode: None,
+            use_system_binary: false,
+            prefer_musl: false,
+            debug: true,
+            log_level: None,
+            mode: None,
+            port: None,
+            port_range: None,
+            auto_start: true,
+        }
+    }
+}
+
+impl ExtensionSettings {
+    /// Load the settings of `channel`'s language server for the given worktree, falling back
+    /// to defaults on any error
+    pub fn for_worktree(worktree: &Worktree, channel: Channel) -> Self {
+        let defaults = Self {
+            channel,
+            ..Self::default()
+        };
+        let id = channel.language_server_id();
+        let lsp_settings = match LspSettings::for_worktree(id, worktree) {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("⚠️ [WARNING] Failed to read LSP settings: {}", e);
+                return defaults;
+            }
+        };
+
+        let mut settings = match lsp_settings.settings {
+            Some(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+                eprintln!("⚠️ [WARNING] Invalid {} settings: {}", id, e);
+                Self::default()
+            }),
+            None => Self::default(),
+        };
+        settings.channel = channel;
+        settings.initialization_options = lsp_settings.initialization_options;
+        if let Some(binary) = lsp_settings.binary {
+            settings.binary_path = binary.path;
+            settings.binary_arguments = binary.arguments.unwrap_or_default();
+            settings.binary_env = binary.env.unwrap_or_default();
+        }
+        settings
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(self.channel == Channel::Stable)
+    }
+
+    /// GitHub repository to look up releases in
+    pub fn release_repo(&self) -> &str {
+        self.release_repo.as_deref().unwrap_or(DEFAULT_RELEASE_REPO)
+    }
+
+    /// Command-line arguments for the server, translated from these settings
+    pub fn server_args(&self, worktree_root: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.debug && self.log_level.is_none() {
+            args.push("--debug".to_string());
+        }
+        args.extend(["--worktree".to_string(), worktree_root.to_string()]);
+
+        let mode = match self.mode.as_deref() {
+            Some(mode) if SERVER_MODES.contains(&mode) => mode,
+            Some(mode) => {
+                eprintln!(
+                    "⚠️ [WARNING] Unsupported server mode '{}', using hybrid",
+                    mode
+                );
+                "hybrid"
+            }
+            None => "hybrid",
+        };
+        args.push(mode.to_string());
+
+        // Only the hybrid mode serves WebSocket clients
+        if mode == "hybrid" {
+            if let Some(port) = self.port {
+                args.extend(["--port".to_string(), port.to_string()]);
+            }
+            if let Some([start, end]) = self.port_range {
+                args.extend([
+                    "--port-range".to_string(),
+                    start.to_string(),
+                    end.to_string(),
+                ]);
+            }
+        }
+        args.extend(self.binary_arguments.iter().cloned());
+        args
+    }
+
+    /// Environment variables for the server: the allowed ones from the worktree's shell,
+    /// then `binary.env`, then those translated from these settings
+    pub fn server_env(&self, shell_env: &[(String, String)]) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = shell_env
+            .iter()
+            .filter(|(name, _)| self.passes_through(name))
+            .cloned()
+            .collect();
+        env.extend(
+            self.binary_env
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        env.extend(
+            self.log_level
+                .iter()
+                .map(|level| ("RUST_LOG".to_string(), level.clone())),
+        );
+        env
+    }
+
+    /// The `claudeCode` initialization options these settings translate to
+    pub fn claude_code_options(&self) -> serde_json::Value {
+        let mut options = serde_json::json!({});
+        // An explicit log level wins over `debug` on the server's command line too
+        if self.log_level.is_none() {
+            options["debug"] = self.debug.into();
+        }
+        options["websocket"] = serde_json::json!({ "autoStart": self.auto_start });
+        if let Some(range) = self.port_range {
+            options["websocket"]["portRange"] = serde_json::json!(range);
+        }
+        options
+    }
+
+    /// Whether a shell environment variable is passed through to the server
+    fn passes_through(&self, name: &str) -> bool {
+        let matches = |pattern: &str| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        };
+        match &self.env_passthrough {
+            Some(patterns) => patterns.iter().any(|pattern| matches(pattern)),
+            None => DEFAULT_ENV_PASSTHROUGH
+                .iter()
+                .any(|pattern| matches(pattern)),
+        }
+    }
+}
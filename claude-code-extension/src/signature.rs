@@ -0,0 +1,100 @@
+use minisign_verify::{PublicKey, Signature};
+use zed_extension_api::{download_file, DownloadedFileType};
+
+/// Minisign public key release binaries are signed with. The matching secret key is the
+/// `MINISIGN_SECRET_KEY` secret of the release workflow; rotating it means updating this key
+/// and shipping a new extension version.
+const RELEASE_PUBLIC_KEY: &str = "RWR9/MBMdLDRVvKBNo+70JKDANxMy/hSg8AOSNdMyGPR3AmI0p1Bff/q";
+
+/// Extension of the detached signature published next to each release asset
+pub const SIGNATURE_EXTENSION: &str = ".minisig";
+
+/// Outcome of a successful signature check
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The signature matched the embedded release key
+    Verified,
+    /// No signature was available and unsigned installs were explicitly allowed
+    Unsigned,
+}
+
+/// Download the detached signature for a release asset and verify `path` against it,
+/// so a binary from a compromised release pipeline is never executed. A missing or
+/// unreadable signature is an error unless `allow_unsigned` is set.
+pub fn verify_release_asset(
+    signature_url: Option<&str>,
+    path: &str,
+    allow_unsigned: bool,
+) -> Result<Verification, String> {
+    let signature = match signature_url {
+        Some(signature_url) => download_signature(signature_url, path)
+            .map_err(|e| format!("no signature at {}: {}", signature_url, e)),
+        None => Err("the release publishes no signature".to_string()),
+    };
+    check_signature(signature, path, allow_unsigned)
+}
+
+fn download_signature(signature_url: &str, path: &str) -> Result<String, String> {
+    let signature_path = format!("{}{}.downloading", path, SIGNATURE_EXTENSION);
+    let signature = download_file(
+        signature_url,
+        &signature_path,
+        DownloadedFileType::Uncompressed,
+    )
+    .and_then(|_| std::fs::read_to_string(&signature_path).map_err(|e| e.to_string()));
+    let _ = std::fs::remove_file(&signature_path);
+    signature
+}
+
+/// Check `path` against a downloaded signature; a signature that fails to verify is always
+/// an error, even when unsigned installs are allowed
+fn check_signature(
+    signature: Result<String, String>,
+    path: &str,
+    allow_unsigned: bool,
+) -> Result<Verification, String> {
+    match signature {
+        Ok(signature) => verify_file(path, &signature).map(|_| Verification::Verified),
+        Err(e) if allow_unsigned => {
+            eprintln!("⚠️ [WARNING] Installing unsigned binary: {}", e);
+            Ok(Verification::Unsigned)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn verify_file(path: &str, signature: &str) -> Result<(), String> {
+    let public_key = PublicKey::from_base64(RELEASE_PUBLIC_KEY)
+        .map_err(|e| format!("invalid embedded public key: {}", e))?;
+    let signature =
+        Signature::decode(signature).map_err(|e| format!("malformed signature: {}", e))?;
+    let data = std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+
+    // Only prehashed signatures, the minisign default; legacy ones are rejected
+    public_key
+        .verify(&data, &signature, false)
+        .map_err(|e| format!("signature does not match: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_signature_is_an_error_by_default() {
+        let result = check_signature(Err("404 Not Found".to_string()), "unused", false);
+        assert_eq!(result, Err("404 Not Found".to_string()));
+    }
+
+    #[test]
+    fn missing_signature_is_allowed_when_opted_in() {
+        let result = check_signature(Err("404 Not Found".to_string()), "unused", true);
+        assert_eq!(result, Ok(Verification::Unsigned));
+    }
+
+    #[test]
+    fn malformed_signature_is_an_error_even_when_opted_in() {
+        let result = check_signature(Ok("not a signature".to_string()), "unused", true);
+        assert!(result.unwrap_err().starts_with("malformed signature"));
+    }
+}
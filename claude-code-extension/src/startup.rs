@@ -0,0 +1,159 @@
+use std::fmt::Display;
+use zed_extension_api::{
+    set_language_server_installation_status, LanguageServerId, LanguageServerInstallationStatus,
+};
+
+/// Stages of installing the server, shown in Zed's language server status while a release
+/// is checked for and downloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallStage {
+    CheckingRelease,
+    Downloading,
+    Verifying,
+    Ready,
+}
+
+impl InstallStage {
+    fn description(self) -> &'static str {
+        match self {
+            InstallStage::CheckingRelease => "Checking for a server release",
+            InstallStage::Downloading => "Downloading the server",
+            InstallStage::Verifying => "Verifying the server signature",
+            InstallStage::Ready => "Server ready",
+        }
+    }
+
+    /// Zed has no verifying status, so verification shows as part of the download
+    fn status(self) -> LanguageServerInstallationStatus {
+        match self {
+            InstallStage::CheckingRelease => LanguageServerInstallationStatus::CheckingForUpdate,
+            InstallStage::Downloading | InstallStage::Verifying => {
+                LanguageServerInstallationStatus::Downloading
+            }
+            InstallStage::Ready => LanguageServerInstallationStatus::None,
+        }
+    }
+}
+
+/// Record of every source tried while resolving the server binary.
+///
+/// Used to build an actionable error when startup fails, and to collect warnings
+/// when the server starts in a degraded state (e.g. an outdated cached binary).
+#[derive(Debug, Default)]
+pub struct StartupReport {
+    attempts: Vec<String>,
+    warnings: Vec<String>,
+    /// Whether a network failure came from a proxy rejecting the request
+    proxy_blocked: bool,
+    /// Proxy configured in the user's shell, which Zed only uses when launched from it
+    shell_proxy: Option<String>,
+    /// Language server whose status shows the install stages, if any
+    language_server_id: Option<LanguageServerId>,
+}
+
+impl StartupReport {
+    /// A report that also shows install stages in the status of `language_server_id`
+    pub fn for_language_server(language_server_id: &LanguageServerId) -> Self {
+        Self {
+            language_server_id: Some(language_server_id.clone()),
+            ..Self::default()
+        }
+    }
+
+    /// Log an install stage and show it in Zed, so a slow first download doesn't look
+    /// like a dead server
+    pub fn set_stage(&self, stage: InstallStage, detail: impl Display) {
+        eprintln!("📦 [STATUS] {}: {}", stage.description(), detail);
+        if let Some(id) = &self.language_server_id {
+            set_language_server_installation_status(id, &stage.status());
+        }
+    }
+
+    /// Record a source that was tried and did not yield a usable binary
+    pub fn record_failure(&mut self, source: &str, error: impl Display) {
+        eprintln!("❌ [ERROR] {}: {}", source, error);
+        self.proxy_blocked |= is_proxy_error(&error.to_string());
+        self.attempts.push(format!("{}: {}", source, error));
+    }
+
+    /// Record that the server will run, but not in the intended configuration
+    pub fn record_degraded(&mut self, warning: impl Into<String>) {
+        let warning = warning.into();
+        eprintln!("🔄 [FALLBACK] {}", warning);
+        self.warnings.push(warning);
+    }
+
+    /// Remember the proxy from the user's shell environment, to explain proxy failures
+    pub fn set_shell_proxy(&mut self, proxy: Option<String>) {
+        self.shell_proxy = proxy;
+    }
+
+    /// Warnings to surface to the user once the server is running
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Build a user-facing error listing every attempted source and a remediation hint
+    pub fn error(&self, summary: impl Display, hint: &str) -> String {
+        let mut message = format!(
+            "Claude Code: could not start claude-code-server: {}",
+            summary
+        );
+        if !self.attempts.is_empty() {
+            message.push_str("\nAttempted:");
+            for attempt in &self.attempts {
+                message.push_str("\n  - ");
+                message.push_str(attempt);
+            }
+        }
+        message.push_str("\nHint: ");
+        if self.proxy_blocked {
+            message.push_str(HINT_PROXY);
+            if let Some(proxy) = &self.shell_proxy {
+                message.push_str(&format!(
+                    " (your shell uses {}, but Zed only picks it up when launched from that shell)",
+                    proxy
+                ));
+            }
+        } else {
+            message.push_str(hint);
+        }
+        message
+    }
+}
+
+/// Remediation hint for network failures when no fallback binary exists
+pub const HINT_OFFLINE: &str = "check your network connection, or install claude-code-server on your PATH (cargo install --path claude-code-server)";
+
+/// Remediation hint when a proxy rejected the GitHub API or download requests
+pub const HINT_PROXY: &str = "a network proxy blocked the request; downloads go through Zed's HTTP client, so set Zed's \"proxy\" setting (e.g. \"proxy\": \"http://proxy.example.com:8080\", with credentials if required), or install claude-code-server on your PATH";
+
+/// Remediation hint for platforms without a published release asset
+pub const HINT_UNSUPPORTED_PLATFORM: &str =
+    "build claude-code-server from source and place it on your PATH";
+
+/// Whether a network error looks like it came from a proxy rather than from GitHub
+fn is_proxy_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("proxy")
+        || error.contains("407")
+        || error.contains("tunnel")
+        || error.contains("connect method")
+}
+
+/// The proxy URL set in a shell environment, with any credentials removed for display
+pub fn shell_proxy(env: &[(String, String)]) -> Option<String> {
+    const PROXY_VARIABLES: &[&str] = &["https_proxy", "all_proxy", "http_proxy"];
+    let proxy = PROXY_VARIABLES.iter().find_map(|name| {
+        env.iter()
+            .find(|(key, value)| key.eq_ignore_ascii_case(name) && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+    })?;
+    Some(match proxy.split_once('@') {
+        Some((credentials, host)) => match credentials.split_once("://") {
+            Some((scheme, _)) => format!("{}://{}", scheme, host),
+            None => host.to_string(),
+        },
+        None => proxy.to_string(),
+    })
+}
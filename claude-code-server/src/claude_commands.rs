@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::Path;
+
+/// Where a project keeps its custom Claude slash commands, relative to the worktree
+pub const COMMANDS_DIR: &str = ".claude/commands";
+
+/// Longest description shown for a command, in characters
+const MAX_DESCRIPTION_CHARS: usize = 100;
+
+/// A custom slash command defined by a Markdown file in `.claude/commands`
+#[derive(Debug, Clone)]
+pub struct CustomCommand {
+    /// Name Claude knows the command by: the file name without `.md`
+    pub name: String,
+    /// The `description` from the file's front matter, else its first line of text
+    pub description: Option<String>,
+    /// Path of the file relative to the commands directory, e.g. `frontend/component.md`
+    pub path: String,
+}
+
+/// The custom commands defined in `worktree`, sorted by name. Subdirectories only organize
+/// commands, so they are searched too.
+pub fn custom_commands(worktree: &Path) -> Vec<CustomCommand> {
+    let root = worktree.join(COMMANDS_DIR);
+    let mut commands = Vec::new();
+    let mut pending = vec![root.clone()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            if path.extension().and_then(|extension| extension.to_str()) != Some("md") {
+                continue;
+            }
+            let Some(name) = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+            let Ok(relative) = path.strip_prefix(&root) else {
+                continue;
+            };
+            commands.push(CustomCommand {
+                name,
+                description: fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|content| description(&content)),
+                path: relative.to_string_lossy().into_owned(),
+            });
+        }
+    }
+    commands.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.path.cmp(&b.path)));
+    commands
+}
+
+/// The description of a command file: its front matter's `description`, else the first
+/// line of its prompt without Markdown heading marks
+fn description(content: &str) -> Option<String> {
+    let mut lines = content.lines().peekable();
+    if lines.next_if(|line| line.trim() == "---").is_some() {
+        for line in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("description:") {
+                let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                if !value.is_empty() {
+                    return Some(truncate(value));
+                }
+            }
+        }
+    }
+    lines
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .map(truncate)
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_DESCRIPTION_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn description_prefers_the_front_matter() {
+        let content = "---\nallowed-tools: Bash\ndescription: \"Review the diff\"\n---\n# Review\n";
+        assert_eq!(description(content), Some("Review the diff".to_string()));
+        let content = "---\ndescription: 'Quoted'\n---\nPrompt\n";
+        assert_eq!(description(content), Some("Quoted".to_string()));
+    }
+
+    #[test]
+    fn description_falls_back_to_the_first_prompt_line() {
+        assert_eq!(
+            description("\n\n## Fix the build\n\nRun cargo build"),
+            Some("Fix the build".to_string())
+        );
+        // Front matter without a description is skipped, not taken for the prompt
+        assert_eq!(
+            description("---\nmodel: opus\ndescription:\n---\n\nExplain $ARGUMENTS\n"),
+            Some("Explain $ARGUMENTS".to_string())
+        );
+    }
+
+    #[test]
+    fn description_is_none_without_text() {
+        assert_eq!(description(""), None);
+        assert_eq!(description("\n#\n  \n"), None);
+        assert_eq!(description("---\nmodel: opus\n---\n"), None);
+        // Unterminated front matter has no prompt after it
+        assert_eq!(description("---\nmodel: opus\n"), None);
+    }
+
+    #[test]
+    fn description_is_truncated() {
+        let long = "é".repeat(MAX_DESCRIPTION_CHARS + 10);
+        let truncated = description(&long).unwrap();
+        assert_eq!(truncated.chars().count(), MAX_DESCRIPTION_CHARS + 1);
+        assert!(truncated.ends_with('…'));
+
+        let exact = "a".repeat(MAX_DESCRIPTION_CHARS);
+        assert_eq!(description(&exact), Some(exact));
+    }
+}
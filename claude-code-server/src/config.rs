@@ -0,0 +1,446 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::logging;
+
+/// Generated or vendored directories ignored unless the options list others
+pub const DEFAULT_IGNORED_DIRS: &[&str] =
+    &["target", "node_modules", "dist", "build", "vendor", ".git"];
+
+/// Settings sent by the extension in the `claudeCode` block of `initializationOptions`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClaudeCodeOptions {
+    /// Enable debug logging
+    pub debug: Option<bool>,
+    /// Log level ("trace", "debug", "info", "warn" or "error"), taking precedence over
+    /// `debug`
+    pub log_level: Option<String>,
+    pub websocket: WebSocketOptions,
+    pub auth: AuthOptions,
+    /// Degraded-mode warnings reported by the extension while resolving the binary
+    pub startup_warnings: Vec<String>,
+    /// Version of the extension that started the server
+    pub extension_version: Option<String>,
+    /// Emit a `file_saved` notification to Claude when a document is saved
+    pub notify_on_save: bool,
+    /// Linters re-run for a file whenever it is saved
+    pub linters: Vec<LinterConfig>,
+    pub os_notifications: OsNotificationOptions,
+    /// Start in focus mode, with Claude observing but not interacting
+    pub focus_mode: bool,
+    /// Commands run before and after MCP tool calls
+    pub hooks: Vec<HookConfig>,
+    pub tool_cache: ToolCacheOptions,
+    pub selection_source: SelectionSource,
+    /// Generated directories (matched by name) skipped by workspace scans and whose
+    /// files never produce selection or at-mention notifications
+    pub ignored_dirs: Vec<String>,
+    /// Locale for user-facing strings (e.g. "de-DE"), overriding the one Zed sends in
+    /// `initialize`
+    pub locale: Option<String>,
+    /// Path of a FIFO or Unix socket mirroring every IDE notification as JSON lines
+    pub event_tap: Option<String>,
+    /// Minimum milliseconds between two notifications of a method (e.g.
+    /// `{"selection_changed": 250}`); notifications arriving sooner are dropped. Methods
+    /// not listed are unlimited.
+    pub rate_limits: HashMap<String, u64>,
+    /// Give file paths in tool responses relative to the worktree, with the absolute path
+    /// in `absolutePath`, so Claude's references don't depend on the machine
+    pub relative_paths: bool,
+    /// Show Explain, Improve and Write tests lenses above functions and types. Off by
+    /// default, since the lenses take a line above every definition.
+    pub code_lens: bool,
+    /// Milliseconds a selection must stay unchanged before `selection_changed` is sent, so
+    /// bursts of selection changes produce one notification. 0 sends every change.
+    pub selection_debounce_ms: u64,
+    /// The only MCP tools offered to Claude; all of them when unset
+    pub enabled_tools: Option<Vec<String>>,
+    /// Milliseconds between checks that Zed, the parent process, is still alive. The
+    /// server shuts down once it is gone. 0 disables the watchdog.
+    pub watchdog_interval_ms: u64,
+    /// At-mention the file an `@path` typed in a buffer names, once a space or new line
+    /// completes the mention
+    pub auto_mention: bool,
+}
+
+impl Default for ClaudeCodeOptions {
+    fn default() -> Self {
+        Self {
+            debug: None,
+            log_level: None,
+            websocket: WebSocketOptions::default(),
+            auth: AuthOptions::default(),
+            startup_warnings: Vec::new(),
+            extension_version: None,
+            notify_on_save: true,
+            linters: Vec::new(),
+            os_notifications: OsNotificationOptions::default(),
+            focus_mode: false,
+            hooks: Vec::new(),
+            tool_cache: ToolCacheOptions::default(),
+            selection_source: SelectionSource::default(),
+            ignored_dirs: DEFAULT_IGNORED_DIRS.iter().map(|d| d.to_string()).collect(),
+            locale: None,
+            event_tap: None,
+            rate_limits: HashMap::new(),
+            relative_paths: false,
+            code_lens: false,
+            selection_debounce_ms: 100,
+            enabled_tools: None,
+            watchdog_interval_ms: 5000,
+            auto_mention: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebSocketOptions {
+    /// Inclusive port range to allocate the WebSocket server from
+    pub port_range: Option<[u16; 2]>,
+    /// Reject clients that don't request the `mcp` subprotocol
+    pub strict_subprotocol: bool,
+    /// Start the WebSocket server with the LSP; when off, it waits for the
+    /// `claude-code.start` command
+    pub auto_start: bool,
+}
+
+impl Default for WebSocketOptions {
+    fn default() -> Self {
+        Self {
+            port_range: None,
+            strict_subprotocol: false,
+            auto_start: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthOptions {
+    /// Generate a fresh auth token per server start
+    pub generate_tokens: bool,
+    /// Fixed auth token, used when token generation is disabled
+    pub token: Option<String>,
+    /// Token for read-only sessions, e.g. a pairing teammate's Claude, which may observe but
+    /// not change anything. Once set, every session must present one of the two tokens.
+    pub read_only_token: Option<String>,
+}
+
+impl Default for AuthOptions {
+    fn default() -> Self {
+        Self {
+            generate_tokens: true,
+            token: None,
+            read_only_token: None,
+        }
+    }
+}
+
+/// Events worth interrupting the user for when they are not looking at Zed or the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OsNotificationEvent {
+    /// Claude asked for a write tool the untrusted workspace withholds
+    PermissionRequested,
+    /// Claude proposed changes to a file
+    DiffAwaitingReview,
+    /// Claude's session disconnected
+    JobFinished,
+}
+
+/// Where the server learns about selection changes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SelectionSource {
+    /// Inferred from code action, selection range, hover and document highlight requests,
+    /// until the client pushes `$/claudeCode/selectionDidChange`
+    #[default]
+    Inferred,
+    /// Only `$/claudeCode/selectionDidChange` notifications pushed by the client
+    Notification,
+}
+
+/// Native OS notifications for important events, off by default
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OsNotificationOptions {
+    pub enabled: bool,
+    /// Events to notify about
+    pub events: Vec<OsNotificationEvent>,
+}
+
+impl Default for OsNotificationOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            events: vec![
+                OsNotificationEvent::PermissionRequested,
+                OsNotificationEvent::DiffAwaitingReview,
+                OsNotificationEvent::JobFinished,
+            ],
+        }
+    }
+}
+
+impl OsNotificationOptions {
+    pub fn wants(&self, event: OsNotificationEvent) -> bool {
+        self.enabled && self.events.contains(&event)
+    }
+}
+
+/// Placeholder in linter arguments replaced with the saved file's path
+pub const FILE_PLACEHOLDER: &str = "${file}";
+
+/// A linter command configured to run when matching files are saved
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinterConfig {
+    /// File extensions (without the dot) this linter applies to
+    pub extensions: Vec<String>,
+    pub command: String,
+    /// Arguments; `${file}` is replaced with the file path, which is appended if absent
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Caching of idempotent tool results, off by default
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ToolCacheOptions {
+    pub enabled: bool,
+    /// How long a cached result stays fresh, in milliseconds
+    pub ttl_ms: u64,
+}
+
+impl Default for ToolCacheOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_ms: 5000,
+        }
+    }
+}
+
+impl ToolCacheOptions {
+    pub fn ttl(&self) -> Option<Duration> {
+        (self.enabled && self.ttl_ms > 0).then(|| Duration::from_millis(self.ttl_ms))
+    }
+}
+
+/// When a hook runs relative to a tool call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HookEvent {
+    /// Before the tool runs; a non-zero exit blocks the call
+    PreToolCall,
+    /// After the tool has run, with its result
+    PostToolCall,
+}
+
+impl HookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::PreToolCall => "preToolCall",
+            HookEvent::PostToolCall => "postToolCall",
+        }
+    }
+}
+
+/// A command run around tool calls, receiving the event as JSON on stdin
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookConfig {
+    pub event: HookEvent,
+    /// Tools this hook applies to; every tool when empty
+    #[serde(default)]
+    pub tools: Vec<String>,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl ClaudeCodeOptions {
+    /// Extract the `claudeCode` block from LSP initialization options.
+    /// Invalid or missing options fall back to defaults.
+    pub fn from_initialization_options(options: Option<&Value>) -> Self {
+        let Some(block) = options.and_then(|o| o.get("claudeCode")) else {
+            return Self::default();
+        };
+
+        match serde_json::from_value(block.clone()) {
+            Ok(options) => options,
+            Err(e) => {
+                warn!("Invalid claudeCode initialization options: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse settings pulled with `workspace/configuration` or pushed with
+    /// `workspace/didChangeConfiguration`: a `claudeCode` section, or the section itself.
+    /// They are layered key by key, into nested objects too, over the `claudeCode`
+    /// initialization options, so options the settings don't mention are kept. `None` when
+    /// there are no settings or they are invalid.
+    pub fn from_settings(initialization_options: Option<&Value>, settings: &Value) -> Option<Self> {
+        let settings = settings.get("claudeCode").unwrap_or(settings);
+        if !settings.is_object() {
+            return None;
+        }
+        let mut block = initialization_options
+            .and_then(|o| o.get("claudeCode"))
+            .filter(|block| block.is_object())
+            .cloned()
+            .unwrap_or_else(|| Value::Object(Default::default()));
+        merge_json(&mut block, settings.clone());
+        serde_json::from_value(block)
+            .map_err(|e| warn!("Invalid claudeCode settings: {}", e))
+            .ok()
+    }
+
+    /// A warning if the extension's major version differs from this server's, in which
+    /// case they may disagree on the protocol
+    pub fn version_mismatch(&self) -> Option<String> {
+        let extension_version = self.extension_version.as_deref()?;
+        let server_version = env!("CARGO_PKG_VERSION");
+        let major = |version: &str| {
+            version
+                .trim_start_matches('v')
+                .split('.')
+                .next()
+                .map(str::to_string)
+        };
+        (major(extension_version) != major(server_version)).then(|| {
+            format!(
+                "the extension ({}) and claude-code-server ({}) have different major versions; update both to the same release",
+                extension_version, server_version
+            )
+        })
+    }
+
+    /// Log level from `logLevel`, else from `debug`; `None` keeps the current level
+    pub fn log_level(&self) -> Option<tracing::Level> {
+        if let Some(name) = &self.log_level {
+            match logging::parse_level(name) {
+                Some(level) => return Some(level),
+                None => warn!("Ignoring unknown log level {}", name),
+            }
+        }
+        self.debug.map(|debug| {
+            if debug {
+                tracing::Level::DEBUG
+            } else {
+                tracing::Level::INFO
+            }
+        })
+    }
+
+    /// Interval of the parent process watchdog, `None` when it is disabled
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        (self.watchdog_interval_ms > 0).then(|| Duration::from_millis(self.watchdog_interval_ms))
+    }
+
+    /// Port range to use for the WebSocket server, if a valid one was configured
+    pub fn port_range(&self) -> Option<(u16, u16)> {
+        match self.websocket.port_range {
+            Some([start, end]) if start <= end => Some((start, end)),
+            Some([start, end]) => {
+                warn!("Ignoring invalid port range {}-{}", start, end);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Auth token to write to the lock file, when not generating one per start
+    pub fn fixed_auth_token(&self) -> Option<String> {
+        if self.auth.generate_tokens {
+            return None;
+        }
+        if self.auth.token.is_none() {
+            warn!("Token generation disabled but no auth token configured, generating one");
+        }
+        self.auth.token.clone()
+    }
+
+    /// Configured rate limits as intervals per notification method
+    pub fn rate_limit_intervals(&self) -> HashMap<String, Duration> {
+        self.rate_limits
+            .iter()
+            .map(|(method, ms)| (method.clone(), Duration::from_millis(*ms)))
+            .collect()
+    }
+}
+
+/// Merge `overrides` into `base`: objects are merged key by key, recursively, and any
+/// other value replaces the one in `base`
+fn merge_json(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge_json(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn from_settings_keeps_nested_options_the_change_does_not_mention() {
+        let initialization_options = json!({
+            "claudeCode": {
+                "websocket": { "autoStart": false, "strictSubprotocol": true },
+                "auth": { "generateTokens": false, "token": "secret" }
+            }
+        });
+        let options = ClaudeCodeOptions::from_settings(
+            Some(&initialization_options),
+            &json!({ "claudeCode": { "websocket": { "portRange": [40000, 41000] } } }),
+        )
+        .unwrap();
+
+        assert_eq!(options.websocket.port_range, Some([40000, 41000]));
+        assert!(!options.websocket.auto_start);
+        assert!(options.websocket.strict_subprotocol);
+        assert_eq!(options.fixed_auth_token().as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn pulled_configuration_leaves_unrelated_nested_options_alone() {
+        let initialization_options = json!({
+            "claudeCode": {
+                "debug": true,
+                "websocket": { "autoStart": false, "strictSubprotocol": true },
+                "auth": { "generateTokens": false, "token": "secret" },
+                "osNotifications": { "enabled": true }
+            }
+        });
+        // What the extension answers `workspace/configuration` with
+        let pulled = json!({
+            "claudeCode": { "debug": false, "websocket": { "autoStart": false } }
+        });
+        let options =
+            ClaudeCodeOptions::from_settings(Some(&initialization_options), &pulled).unwrap();
+
+        assert_eq!(options.debug, Some(false));
+        assert!(options.websocket.strict_subprotocol);
+        assert_eq!(options.fixed_auth_token().as_deref(), Some("secret"));
+        assert!(options.os_notifications.enabled);
+    }
+
+    #[test]
+    fn from_settings_rejects_missing_or_invalid_settings() {
+        assert!(ClaudeCodeOptions::from_settings(None, &json!(null)).is_none());
+        assert!(ClaudeCodeOptions::from_settings(None, &json!({ "claudeCode": 1 })).is_none());
+        assert!(ClaudeCodeOptions::from_settings(None, &json!({ "debug": "loud" })).is_none());
+    }
+}
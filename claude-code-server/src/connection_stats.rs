@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Why a WebSocket connection ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DisconnectReason {
+    /// The client closed the connection
+    ClientClose,
+    /// The WebSocket handshake failed, so no session started
+    HandshakeFailed,
+    /// Reading from the client failed, e.g. the connection was reset
+    ReadError,
+    /// Sending a response or notification to the client failed
+    WriteError,
+    /// The server closed the session while handing over to a new instance
+    Takeover,
+    /// The server closed the session because it was shutting down
+    Shutdown,
+}
+
+impl DisconnectReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisconnectReason::ClientClose => "clientClose",
+            DisconnectReason::HandshakeFailed => "handshakeFailed",
+            DisconnectReason::ReadError => "readError",
+            DisconnectReason::WriteError => "writeError",
+            DisconnectReason::Takeover => "takeover",
+            DisconnectReason::Shutdown => "shutdown",
+        }
+    }
+}
+
+/// Counters of how connections ended and of notifications sessions missed, for reconnect
+/// diagnostics in the status output
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    disconnects: Arc<Mutex<BTreeMap<DisconnectReason, u64>>>,
+    /// Notifications a session fell too far behind to receive
+    dropped_notifications: Arc<AtomicU64>,
+}
+
+impl ConnectionStats {
+    pub fn record_disconnect(&self, reason: DisconnectReason) {
+        *self.disconnects.lock().unwrap().entry(reason).or_insert(0) += 1;
+    }
+
+    pub fn record_dropped(&self, count: u64) {
+        self.dropped_notifications
+            .fetch_add(count, Ordering::SeqCst);
+    }
+
+    /// Disconnects per reason (every reason listed, zero if it never happened) and the
+    /// dropped notification count
+    pub fn to_json(&self) -> serde_json::Value {
+        let disconnects = self.disconnects.lock().unwrap();
+        let by_reason: serde_json::Map<String, serde_json::Value> = [
+            DisconnectReason::ClientClose,
+            DisconnectReason::HandshakeFailed,
+            DisconnectReason::ReadError,
+            DisconnectReason::WriteError,
+            DisconnectReason::Takeover,
+            DisconnectReason::Shutdown,
+        ]
+        .into_iter()
+        .map(|reason| {
+            (
+                reason.as_str().to_string(),
+                disconnects.get(&reason).copied().unwrap_or(0).into(),
+            )
+        })
+        .collect();
+        serde_json::json!({
+            "disconnects": by_reason,
+            "droppedNotifications": self.dropped_notifications.load(Ordering::SeqCst)
+        })
+    }
+}
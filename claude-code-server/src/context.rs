@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Notify, RwLock};
+use tracing::warn;
+
+use crate::file_locks::write_atomic;
+
+/// Most entries kept; past this the least recently shared are dropped
+const MAX_CONTEXT_ENTRIES: usize = 500;
+
+/// How long the context has to stay unchanged before it is saved, so a burst of mentions
+/// is written once
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// How a file or range ended up in Claude's context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContextSource {
+    /// Sent with the at-mention command
+    AtMention,
+    /// Read by Claude through a selection tool
+    Selection,
+}
+
+/// A file or line range that has been shared with Claude
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextEntry {
+    pub id: u64,
+    pub file_path: String,
+    pub line_start: Option<u32>,
+    pub line_end: Option<u32>,
+    pub source: ContextSource,
+    /// Milliseconds since the Unix epoch
+    pub first_seen_at: u64,
+    pub last_seen_at: u64,
+    /// How many times this exact range was shared
+    pub count: u32,
+}
+
+/// Files and ranges at-mentioned or read during the Claude session, persisted per worktree
+/// so they survive server restarts
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionContext {
+    next_id: u64,
+    entries: Vec<ContextEntry>,
+    /// Backing file; in-memory only when unset
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Changed since the last save
+    #[serde(skip)]
+    dirty: bool,
+    /// Wakes the saver task after a change
+    #[serde(skip)]
+    changed: Arc<Notify>,
+}
+
+pub type SharedContext = Arc<RwLock<SessionContext>>;
+
+impl SessionContext {
+    /// Load the persisted context for a worktree (the current directory when unset)
+    pub fn load(worktree: Option<&Path>) -> Self {
+        let Some(path) = store_path(worktree) else {
+            return Self::default();
+        };
+        let mut context = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring invalid session context {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        context.path = Some(path);
+        context
+    }
+
+    /// Whether the context is saved for the worktree, which needs a data directory
+    pub fn is_persisted(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Record that a file or range was shared, merging repeats of the same range
+    pub fn record(
+        &mut self,
+        file_path: &str,
+        line_start: Option<u32>,
+        line_end: Option<u32>,
+        source: ContextSource,
+    ) {
+        let now = unix_millis();
+        let existing = self.entries.iter_mut().find(|entry| {
+            entry.file_path == file_path
+                && entry.line_start == line_start
+                && entry.line_end == line_end
+                && entry.source == source
+        });
+
+        match existing {
+            Some(entry) => {
+                entry.last_seen_at = now;
+                entry.count += 1;
+            }
+            None => {
+                self.next_id += 1;
+                self.entries.push(ContextEntry {
+                    id: self.next_id,
+                    file_path: file_path.to_string(),
+                    line_start,
+                    line_end,
+                    source,
+                    first_seen_at: now,
+                    last_seen_at: now,
+                    count: 1,
+                });
+                self.evict();
+            }
+        }
+        self.mark_changed();
+    }
+
+    pub fn entries(&self) -> &[ContextEntry] {
+        &self.entries
+    }
+
+    /// Remove entries by ID, returning how many were removed
+    pub fn prune(&mut self, ids: &[u64]) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|entry| !ids.contains(&entry.id));
+        let removed = before - self.entries.len();
+        if removed > 0 {
+            self.mark_changed();
+        }
+        removed
+    }
+
+    /// Merge entries from another session, combining repeats of the same range.
+    /// Returns how many entries were imported.
+    pub fn import(&mut self, entries: Vec<ContextEntry>) -> usize {
+        let imported = entries.len();
+        for entry in entries {
+            let existing = self.entries.iter_mut().find(|e| {
+                e.file_path == entry.file_path
+                    && e.line_start == entry.line_start
+                    && e.line_end == entry.line_end
+                    && e.source == entry.source
+            });
+            match existing {
+                Some(e) => {
+                    e.first_seen_at = e.first_seen_at.min(entry.first_seen_at);
+                    e.last_seen_at = e.last_seen_at.max(entry.last_seen_at);
+                    e.count += entry.count;
+                }
+                None => {
+                    self.next_id += 1;
+                    self.entries.push(ContextEntry {
+                        id: self.next_id,
+                        ..entry
+                    });
+                }
+            }
+        }
+        if imported > 0 {
+            self.evict();
+            self.mark_changed();
+        }
+        imported
+    }
+
+    /// Remove every entry, returning how many were removed
+    pub fn clear(&mut self) -> usize {
+        let removed = self.entries.len();
+        self.entries.clear();
+        if removed > 0 {
+            self.mark_changed();
+        }
+        removed
+    }
+
+    /// Save now if anything changed, for callers without a saver task
+    pub fn flush(&mut self) {
+        if let Some((path, contents)) = self.take_changes() {
+            save(&path, contents);
+        }
+    }
+
+    /// Drop the least recently shared entries beyond `MAX_CONTEXT_ENTRIES`
+    fn evict(&mut self) {
+        let excess = self.entries.len().saturating_sub(MAX_CONTEXT_ENTRIES);
+        if excess == 0 {
+            return;
+        }
+        let mut last_seen: Vec<u64> = self.entries.iter().map(|e| e.last_seen_at).collect();
+        last_seen.sort_unstable();
+        let cutoff = last_seen[excess - 1];
+        let mut dropped = 0;
+        self.entries.retain(|entry| {
+            let drop = dropped < excess && entry.last_seen_at <= cutoff;
+            dropped += usize::from(drop);
+            !drop
+        });
+    }
+
+    fn mark_changed(&mut self) {
+        if self.path.is_some() {
+            self.dirty = true;
+            self.changed.notify_one();
+        }
+    }
+
+    /// The backing file and its new contents when there are unsaved changes
+    fn take_changes(&mut self) -> Option<(PathBuf, String)> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        let path = self.path.clone()?;
+        Some((path, serde_json::to_string_pretty(self).unwrap_or_default()))
+    }
+}
+
+/// Save `context` in the background once changes settle, and right away when `stop`
+/// resolves, so the lock is never held across a write
+pub fn spawn_saver(context: SharedContext, stop: impl Future<Output = ()> + Send + 'static) {
+    tokio::spawn(async move {
+        let changed = context.read().await.changed.clone();
+        tokio::pin!(stop);
+        loop {
+            let stopping = tokio::select! {
+                _ = changed.notified() => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(SAVE_DELAY) => false,
+                        _ = &mut stop => true,
+                    }
+                }
+                _ = &mut stop => true,
+            };
+            if let Some((path, contents)) = context.write().await.take_changes() {
+                let _ = tokio::task::spawn_blocking(move || save(&path, contents)).await;
+            }
+            if stopping {
+                break;
+            }
+        }
+    });
+}
+
+fn save(path: &Path, contents: String) {
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| write_atomic(path, contents));
+    if let Err(e) = result {
+        warn!("Failed to save session context {}: {}", path.display(), e);
+    }
+}
+
+/// Context file for a worktree, named after its path
+fn store_path(worktree: Option<&Path>) -> Option<PathBuf> {
+    let worktree = match worktree {
+        Some(path) => path.to_path_buf(),
+        None => std::env::current_dir().ok()?,
+    };
+    let name: String = worktree
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    dirs::data_dir().map(|dir| {
+        dir.join("claude-code-zed")
+            .join("context")
+            .join(format!("{}.json", name))
+    })
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio_tungstenite::{
+    connect_async, tungstenite::client::IntoClientRequest, tungstenite::Message,
+};
+
+use crate::mention;
+use crate::state::SharedState;
+use crate::takeover;
+
+/// WebSocket path for one-shot control requests, which are not MCP sessions
+pub const CONTROL_PATH: &str = "/control";
+
+/// Header carrying the lock file's auth token
+pub const AUTH_HEADER: &str = "x-claude-code-ide-authorization";
+
+/// Control request method sharing a file or the current selection with Claude
+pub const AT_MENTION_METHOD: &str = "at_mention";
+
+/// Control request method sharing several files or line ranges with Claude at once
+pub const AT_MENTION_RANGES_METHOD: &str = "at_mention_ranges";
+
+/// Control request method asking the server to hand its port and lock file over to a new
+/// instance for the same worktree
+pub const TAKEOVER_METHOD: &str = "takeover";
+
+/// Response to a control request, and whether the server should hand over once it is sent
+pub struct ControlResponse {
+    pub text: String,
+    pub hand_over: bool,
+}
+
+/// Answer a control request received on [`CONTROL_PATH`]
+pub async fn handle_control_request(
+    state: &SharedState,
+    request: &serde_json::Value,
+) -> ControlResponse {
+    let id = request
+        .get("id")
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    let method = request.get("method").and_then(|m| m.as_str()).unwrap_or("");
+    let params = request.get("params").cloned().unwrap_or_default();
+
+    let result = match method {
+        AT_MENTION_METHOD => mention::handle_mention_request(state, &params).await,
+        AT_MENTION_RANGES_METHOD => mention::handle_mention_ranges_request(state, &params).await,
+        TAKEOVER_METHOD => takeover::handle_takeover_request(state, &params),
+        _ => Err(anyhow::anyhow!("Unknown control method: {}", method)),
+    };
+    let hand_over = method == TAKEOVER_METHOD && result.is_ok();
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": -32602, "message": e.to_string() }
+        }),
+    };
+    ControlResponse {
+        text: response.to_string(),
+        hand_over,
+    }
+}
+
+/// Send a control request to the server listening on `port` and return its result
+pub async fn send_request(
+    port: u16,
+    auth_token: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let mut request = format!("ws://127.0.0.1:{}{}", port, CONTROL_PATH).into_client_request()?;
+    request
+        .headers_mut()
+        .insert(AUTH_HEADER, auth_token.parse()?);
+    let (mut ws_stream, _) = connect_async(request).await?;
+
+    let control_request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params
+    });
+    ws_stream
+        .send(Message::Text(control_request.to_string()))
+        .await?;
+
+    let response = loop {
+        match ws_stream.next().await {
+            Some(Ok(Message::Text(text))) => {
+                break serde_json::from_str::<serde_json::Value>(&text)?
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => bail!("Server closed the connection without answering"),
+        }
+    };
+    let _ = ws_stream.close(None).await;
+
+    if let Some(error) = response.get("error") {
+        bail!(
+            "{}",
+            error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("Control request failed")
+        );
+    }
+    Ok(response.get("result").cloned().unwrap_or_default())
+}
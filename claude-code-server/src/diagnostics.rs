@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tower_lsp::lsp_types::{Diagnostic, Url};
+
+/// Where a file's diagnostics came from
+pub const LINTER_SOURCE: &str = "linters";
+pub const CLIENT_SOURCE: &str = "zed";
+pub const CLAUDE_SOURCE: &str = "claude";
+
+/// Latest diagnostics per file and source, from the on-save linters, from diagnostics the
+/// client forwards and from Claude's findings, so `getDiagnostics` can report them to Claude
+#[derive(Debug, Default)]
+pub struct DiagnosticsStore {
+    diagnostics: HashMap<Url, BTreeMap<&'static str, Vec<Diagnostic>>>,
+}
+
+pub type SharedDiagnostics = Arc<RwLock<DiagnosticsStore>>;
+
+impl DiagnosticsStore {
+    /// Replace the diagnostics `source` reported for a file; an empty list clears them
+    pub fn set(&mut self, uri: Url, source: &'static str, diagnostics: Vec<Diagnostic>) {
+        if diagnostics.is_empty() {
+            if let Some(sources) = self.diagnostics.get_mut(&uri) {
+                sources.remove(source);
+                if sources.is_empty() {
+                    self.diagnostics.remove(&uri);
+                }
+            }
+        } else {
+            self.diagnostics
+                .entry(uri)
+                .or_default()
+                .insert(source, diagnostics);
+        }
+    }
+
+    /// Diagnostics of a file from every source
+    pub fn get(&self, uri: &Url) -> Vec<&Diagnostic> {
+        self.diagnostics
+            .get(uri)
+            .into_iter()
+            .flat_map(|sources| sources.values().flatten())
+            .collect()
+    }
+
+    /// Diagnostics of a file from every source but `excluded`
+    pub fn get_excluding(&self, uri: &Url, excluded: &str) -> Vec<&Diagnostic> {
+        self.diagnostics
+            .get(uri)
+            .into_iter()
+            .flat_map(|sources| sources.iter())
+            .filter(|(source, _)| **source != excluded)
+            .flat_map(|(_, diagnostics)| diagnostics)
+            .collect()
+    }
+
+    /// Files with diagnostics, ordered by path
+    pub fn files(&self) -> Vec<&Url> {
+        let mut files: Vec<&Url> = self.diagnostics.keys().collect();
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+        files
+    }
+}
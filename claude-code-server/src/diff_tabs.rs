@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::file_locks::write_atomic;
+
+/// A diff opened on Claude's behalf, backed by a temporary document holding the proposed content
+#[derive(Debug, Clone)]
+pub struct DiffTab {
+    pub tab_name: String,
+    /// The file the diff proposes to change
+    pub original_path: PathBuf,
+    /// Temporary document containing the proposed content
+    pub temp_path: PathBuf,
+}
+
+/// Diff tabs currently managed by the server, keyed by tab name
+#[derive(Debug, Default)]
+pub struct DiffTabs {
+    tabs: HashMap<String, DiffTab>,
+}
+
+pub type SharedDiffTabs = Arc<RwLock<DiffTabs>>;
+
+impl DiffTabs {
+    /// Write the proposed content to a temp document and track it under `tab_name`.
+    /// Opening a tab with an existing name replaces the previous one.
+    pub fn open(
+        &mut self,
+        tab_name: &str,
+        original_path: &Path,
+        contents: &str,
+    ) -> std::io::Result<DiffTab> {
+        if let Some(previous) = self.tabs.remove(tab_name) {
+            remove_temp_document(&previous);
+        }
+
+        let dir = temp_dir();
+        fs::create_dir_all(&dir)?;
+        let temp_path = dir.join(temp_file_name(tab_name, original_path));
+        write_atomic(&temp_path, contents)?;
+
+        let tab = DiffTab {
+            tab_name: tab_name.to_string(),
+            original_path: original_path.to_path_buf(),
+            temp_path,
+        };
+        info!(
+            "Opened diff tab '{}' at {}",
+            tab.tab_name,
+            tab.temp_path.display()
+        );
+        self.tabs.insert(tab_name.to_string(), tab.clone());
+        Ok(tab)
+    }
+
+    /// Close a single tab by name, returning it if it was tracked
+    pub fn close(&mut self, tab_name: &str) -> Option<DiffTab> {
+        let tab = self.tabs.remove(tab_name)?;
+        remove_temp_document(&tab);
+        Some(tab)
+    }
+
+    /// Close every tracked tab, returning the closed tabs
+    pub fn close_all(&mut self) -> Vec<DiffTab> {
+        let tabs: Vec<DiffTab> = self.tabs.drain().map(|(_, tab)| tab).collect();
+        for tab in &tabs {
+            remove_temp_document(tab);
+        }
+        tabs
+    }
+}
+
+/// Per-process directory for temporary diff documents
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir()
+        .join("claude-code-zed")
+        .join(std::process::id().to_string())
+}
+
+/// Build a filesystem-safe name that keeps the original extension for syntax highlighting
+fn temp_file_name(tab_name: &str, original_path: &Path) -> String {
+    let safe_name: String = tab_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match original_path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}.{}", safe_name, extension),
+        None => safe_name,
+    }
+}
+
+fn remove_temp_document(tab: &DiffTab) {
+    if let Err(e) = fs::remove_file(&tab.temp_path) {
+        warn!(
+            "Failed to remove diff document {}: {}",
+            tab.temp_path.display(),
+            e
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use ropey::Rope;
+use std::collections::{HashMap, VecDeque};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent, Url};
+
+use crate::encoding::{DecodedText, TextEncoding};
+use crate::lsp::PositionEncoding;
+use crate::state;
+
+/// A document currently open in the editor, as reported over LSP
+#[derive(Debug, Clone)]
+pub struct OpenDocument {
+    pub uri: Url,
+    pub language_id: String,
+    pub version: i32,
+    /// Contents of the editor buffer, including unsaved edits. A rope keeps edits and
+    /// range lookups logarithmic in the size of the file.
+    pub text: Rope,
+    /// Encoding the file is stored in on disk
+    pub encoding: TextEncoding,
+    /// Whether the buffer was edited since it was opened or last saved
+    pub dirty: bool,
+}
+
+impl OpenDocument {
+    /// The document's path on disk, or its URI when it isn't a file (e.g. an untitled
+    /// buffer)
+    pub fn file_path(&self) -> String {
+        match self.uri.to_file_path() {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(()) => self.uri.to_string(),
+        }
+    }
+
+    /// The whitespace-delimited token around `position`, e.g. an `@path` mention
+    pub fn token_at(&self, position: Position) -> Option<String> {
+        let index = char_index(&self.text, position, state::position_encoding());
+        let line = self.text.char_to_line(index);
+        let line_start = self.text.line_to_char(line);
+        let chars: Vec<char> = self.text.line(line).chars().collect();
+        let offset = index - line_start;
+        let start = chars[..offset.min(chars.len())]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1);
+        let end = chars[offset.min(chars.len())..]
+            .iter()
+            .position(|c| c.is_whitespace())
+            .map_or(chars.len(), |i| offset + i);
+        (start < end).then(|| chars[start..end].iter().collect())
+    }
+
+    /// The 0-based index of the last line. A trailing newline ends the last line rather than
+    /// starting another.
+    pub fn last_line(&self) -> u32 {
+        let lines = self.text.len_lines();
+        let trailing_empty = self.text.line(lines - 1).len_chars() == 0;
+        lines.saturating_sub(if trailing_empty { 2 } else { 1 }) as u32
+    }
+
+    /// An LSP position with its `character` in the UTF-16 code units Claude counts, whatever
+    /// the negotiated encoding
+    pub fn utf16_position(&self, position: Position) -> Position {
+        let index = char_index(&self.text, position, state::position_encoding());
+        let line = self.text.char_to_line(index);
+        let line_start = self.text.line_to_char(line);
+        Position {
+            line: line as u32,
+            character: (self.text.char_to_utf16_cu(index) - self.text.char_to_utf16_cu(line_start))
+                as u32,
+        }
+    }
+
+    /// Byte offset of an LSP position in the text
+    pub fn offset_at(&self, position: Position) -> usize {
+        self.text
+            .char_to_byte(char_index(&self.text, position, state::position_encoding()))
+    }
+
+    /// The text covered by an LSP range
+    pub fn text_in_range(&self, range: Range) -> String {
+        let encoding = state::position_encoding();
+        let start = char_index(&self.text, range.start, encoding);
+        let end = char_index(&self.text, range.end, encoding).max(start);
+        self.text.slice(start..end).to_string()
+    }
+}
+
+/// How many recently requested lines of the active document approximate its viewport
+const RECENT_LINES: usize = 16;
+
+/// Tracks documents opened in Zed so MCP tools can describe the editor state
+#[derive(Debug, Default)]
+pub struct DocumentStore {
+    documents: HashMap<Url, OpenDocument>,
+    active: Option<Url>,
+    /// Lines of the active document that recent LSP requests (hovers, highlights, code
+    /// actions) were made for, oldest first
+    recent_lines: VecDeque<u32>,
+}
+
+pub type SharedDocuments = Arc<RwLock<DocumentStore>>;
+
+impl DocumentStore {
+    pub fn open(
+        &mut self,
+        uri: Url,
+        language_id: String,
+        version: i32,
+        text: &str,
+        encoding: TextEncoding,
+        dirty: bool,
+    ) {
+        self.switch_active(&uri);
+        self.documents.insert(
+            uri.clone(),
+            OpenDocument {
+                uri,
+                language_id,
+                version,
+                text: Rope::from_str(text),
+                encoding,
+                dirty,
+            },
+        );
+    }
+
+    /// Apply the edits of a `didChange` notification, in order, to the buffer
+    pub fn apply_changes(
+        &mut self,
+        uri: &Url,
+        version: i32,
+        changes: Vec<TextDocumentContentChangeEvent>,
+    ) {
+        let encoding = state::position_encoding();
+        let mut edited_lines = Vec::new();
+        if let Some(document) = self.documents.get_mut(uri) {
+            document.version = version;
+            document.dirty |= !changes.is_empty();
+            for change in changes {
+                match change.range {
+                    Some(range) => {
+                        edited_lines.push(range.start.line);
+                        let start = char_index(&document.text, range.start, encoding);
+                        let end = char_index(&document.text, range.end, encoding).max(start);
+                        document.text.remove(start..end);
+                        document.text.insert(start, &change.text);
+                    }
+                    None => document.text = Rope::from_str(&change.text),
+                }
+            }
+        }
+        self.touch(uri, edited_lines);
+    }
+
+    /// Record that the buffer was written to disk
+    pub fn mark_saved(&mut self, uri: &Url) {
+        if let Some(document) = self.documents.get_mut(uri) {
+            document.dirty = false;
+        }
+    }
+
+    /// Record that the file of an open document changed on disk outside Zed, given its new
+    /// contents or `None` if it was deleted. The buffer is dirty while it differs from them.
+    pub fn disk_changed(&mut self, uri: &Url, on_disk: Option<DecodedText>) {
+        if let Some(document) = self.documents.get_mut(uri) {
+            match on_disk {
+                Some(file) => {
+                    document.dirty = document.text != file.text.as_str();
+                    document.encoding = file.encoding;
+                }
+                None => document.dirty = true,
+            }
+        }
+    }
+
+    pub fn close(&mut self, uri: &Url) {
+        self.documents.remove(uri);
+        if self.active.as_ref() == Some(uri) {
+            self.active = None;
+            self.recent_lines.clear();
+        }
+    }
+
+    /// Mark a document as active and record that Zed made a request for `lines` of it,
+    /// which are likely on screen
+    pub fn touch(&mut self, uri: &Url, lines: impl IntoIterator<Item = u32>) {
+        if !self.documents.contains_key(uri) {
+            return;
+        }
+        self.switch_active(uri);
+        for line in lines {
+            if self.recent_lines.len() == RECENT_LINES {
+                self.recent_lines.pop_front();
+            }
+            self.recent_lines.push_back(line);
+        }
+    }
+
+    fn switch_active(&mut self, uri: &Url) {
+        if self.active.as_ref() != Some(uri) {
+            self.active = Some(uri.clone());
+            self.recent_lines.clear();
+        }
+    }
+
+    /// The lines spanned by recent requests in the active document, a rough stand-in for
+    /// the visible viewport, which Zed doesn't report to language servers
+    pub fn recent_line_span(&self) -> Option<RangeInclusive<u32>> {
+        let first = *self.recent_lines.iter().min()?;
+        let last = *self.recent_lines.iter().max()?;
+        Some(first..=last)
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<&OpenDocument> {
+        self.documents.get(uri)
+    }
+
+    pub fn active(&self) -> Option<&OpenDocument> {
+        self.active.as_ref().and_then(|uri| self.documents.get(uri))
+    }
+
+    pub fn is_active(&self, uri: &Url) -> bool {
+        self.active.as_ref() == Some(uri)
+    }
+
+    /// Open documents, ordered by path
+    pub fn documents(&self) -> Vec<&OpenDocument> {
+        let mut documents: Vec<&OpenDocument> = self.documents.values().collect();
+        documents.sort_by(|a, b| a.uri.path().cmp(b.uri.path()));
+        documents
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Number of open documents per language ID
+    pub fn count_by_language(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for document in self.documents.values() {
+            *counts.entry(document.language_id.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Char index of an LSP position (a line and an offset within it in `encoding`'s units) in
+/// `text`, clamped to the end of its line and of the text
+fn char_index(text: &Rope, position: Position, encoding: PositionEncoding) -> usize {
+    let line = position.line as usize;
+    if line >= text.len_lines() {
+        return text.len_chars();
+    }
+    let line_start = text.line_to_char(line);
+    let line_text = text.line(line);
+    let line_breaks = line_text
+        .chars_at(line_text.len_chars())
+        .reversed()
+        .take_while(|c| matches!(c, '\n' | '\r'))
+        .count();
+    let line_end = line_start + line_text.len_chars() - line_breaks;
+
+    let character = position.character as usize;
+    match encoding {
+        PositionEncoding::Utf8 => {
+            let target = text.char_to_byte(line_start) + character;
+            text.byte_to_char(target.min(text.char_to_byte(line_end)))
+        }
+        PositionEncoding::Utf16 => {
+            let target = text.char_to_utf16_cu(line_start) + character;
+            text.utf16_cu_to_char(target.min(text.char_to_utf16_cu(line_end)))
+        }
+        PositionEncoding::Utf32 => (line_start + character).min(line_end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn char_index_counts_in_the_negotiated_encoding() {
+        // "é" takes 2 bytes and 1 UTF-16 unit; "😀" takes 4 bytes and 2 UTF-16 units
+        let text = Rope::from_str("héllo 😀 x\nsecond\n");
+        assert_eq!(
+            char_index(&text, position(0, 0), PositionEncoding::Utf16),
+            0
+        );
+        assert_eq!(
+            char_index(&text, position(0, 6), PositionEncoding::Utf16),
+            6
+        );
+        assert_eq!(
+            char_index(&text, position(0, 8), PositionEncoding::Utf16),
+            7
+        );
+        assert_eq!(char_index(&text, position(0, 3), PositionEncoding::Utf8), 2);
+        assert_eq!(
+            char_index(&text, position(0, 11), PositionEncoding::Utf8),
+            7
+        );
+        assert_eq!(
+            char_index(&text, position(0, 7), PositionEncoding::Utf32),
+            7
+        );
+        assert_eq!(
+            char_index(&text, position(1, 2), PositionEncoding::Utf16),
+            12
+        );
+    }
+
+    #[test]
+    fn char_index_clamps_to_the_end_of_the_line() {
+        let text = Rope::from_str("héllo 😀 x\r\nsecond\n");
+        for encoding in [
+            PositionEncoding::Utf8,
+            PositionEncoding::Utf16,
+            PositionEncoding::Utf32,
+        ] {
+            // Before the line break, whether it is `\r\n` or `\n`
+            assert_eq!(char_index(&text, position(0, 100), encoding), 9);
+            assert_eq!(char_index(&text, position(1, 100), encoding), 17);
+        }
+    }
+
+    fn document(text: &str) -> OpenDocument {
+        OpenDocument {
+            uri: Url::parse("file:///src/lib.rs").unwrap(),
+            language_id: "rust".to_string(),
+            version: 1,
+            text: Rope::from_str(text),
+            encoding: TextEncoding::default(),
+            dirty: false,
+        }
+    }
+
+    #[test]
+    fn offset_at_gives_byte_offsets() {
+        // Positions count UTF-16 units unless the client negotiated another encoding
+        let document = document("let é = \"😀\";\nok\n");
+        assert_eq!(document.offset_at(position(0, 0)), 0);
+        assert_eq!(document.offset_at(position(0, 5)), 6);
+        assert_eq!(document.offset_at(position(0, 12)), 15);
+        assert_eq!(document.offset_at(position(1, 1)), 18);
+        assert_eq!(document.offset_at(position(1, 9)), 19);
+        assert_eq!(document.offset_at(position(5, 0)), 20);
+    }
+
+    #[test]
+    fn char_index_clamps_to_the_end_of_the_text() {
+        let text = Rope::from_str("one\ntwo\n");
+        let end = text.len_chars();
+        assert_eq!(
+            char_index(&text, position(2, 0), PositionEncoding::Utf16),
+            end
+        );
+        assert_eq!(
+            char_index(&text, position(2, 5), PositionEncoding::Utf16),
+            end
+        );
+        assert_eq!(
+            char_index(&text, position(9, 0), PositionEncoding::Utf16),
+            end
+        );
+        assert_eq!(
+            char_index(&Rope::new(), position(0, 3), PositionEncoding::Utf16),
+            0
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_path_decodes_the_uri() {
+        let mut document = document("");
+        document.uri = Url::parse("file:///src/my%20crate/lib%C3%A9.rs").unwrap();
+        assert_eq!(document.file_path(), "/src/my crate/libé.rs");
+
+        document.uri = Url::parse("untitled:Untitled-1").unwrap();
+        assert_eq!(document.file_path(), "untitled:Untitled-1");
+    }
+}
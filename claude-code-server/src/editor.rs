@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Result};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tower_lsp::lsp_types::{Diagnostic, ShowDocumentParams, Url};
+
+use crate::lsp::ClaudeStatusParams;
+
+/// Something MCP sessions ask of the editor, which only the attached LSP session can do
+#[derive(Debug)]
+pub enum EditorRequest {
+    /// Show `title` as progress in Zed until `done` fires or is dropped
+    Progress {
+        title: String,
+        done: oneshot::Receiver<()>,
+    },
+    /// Open a document in Zed with `window/showDocument`, replying whether Zed showed it
+    ShowDocument {
+        params: ShowDocumentParams,
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    /// Ask the user with `window/showMessageRequest`, replying with the action they chose,
+    /// or `None` if they dismissed the message
+    ShowMessageRequest {
+        message: String,
+        actions: Vec<String>,
+        reply: oneshot::Sender<Result<Option<String>>>,
+    },
+    /// Report the WebSocket server's state to Zed with `claude/status`
+    Status(ClaudeStatusParams),
+    /// Show a file's diagnostics in Zed with `textDocument/publishDiagnostics`
+    PublishDiagnostics {
+        uri: Url,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+pub type EditorRequestReceiver = mpsc::UnboundedReceiver<EditorRequest>;
+
+/// Channel from MCP sessions into the attached LSP session. Requests made while no LSP
+/// session is attached are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct EditorBridge {
+    sender: Arc<Mutex<Option<mpsc::UnboundedSender<EditorRequest>>>>,
+}
+
+/// Keeps a progress indicator shown until dropped
+#[must_use]
+pub struct ProgressGuard {
+    _done: Option<oneshot::Sender<()>>,
+}
+
+impl EditorBridge {
+    /// Route requests to a newly attached LSP session, replacing any previous one
+    pub fn attach(&self) -> EditorRequestReceiver {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.sender.lock().unwrap() = Some(sender);
+        receiver
+    }
+
+    pub fn detach(&self) {
+        *self.sender.lock().unwrap() = None;
+    }
+
+    /// Pass a request to the LSP session, returning false if none is attached
+    pub fn send(&self, request: EditorRequest) -> bool {
+        match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender.send(request).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Open a document in Zed, returning whether Zed showed it
+    pub async fn show_document(&self, params: ShowDocumentParams) -> Result<bool> {
+        let (reply, response) = oneshot::channel();
+        if !self.send(EditorRequest::ShowDocument { params, reply }) {
+            return Err(anyhow!("No Zed language server session is attached"));
+        }
+        response
+            .await
+            .map_err(|_| anyhow!("The Zed language server session detached"))?
+    }
+
+    /// Ask the user in Zed to choose one of `actions`, returning the one chosen, or `None`
+    /// if they dismissed the message
+    pub async fn ask(&self, message: String, actions: Vec<String>) -> Result<Option<String>> {
+        let (reply, response) = oneshot::channel();
+        let request = EditorRequest::ShowMessageRequest {
+            message,
+            actions,
+            reply,
+        };
+        if !self.send(request) {
+            return Err(anyhow!("No Zed language server session is attached"));
+        }
+        response
+            .await
+            .map_err(|_| anyhow!("The Zed language server session detached"))?
+    }
+
+    /// Report the WebSocket server's state to Zed, if attached
+    pub fn status(&self, params: ClaudeStatusParams) {
+        self.send(EditorRequest::Status(params));
+    }
+
+    /// Show a file's diagnostics in Zed, returning false if no LSP session is attached
+    pub fn publish_diagnostics(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> bool {
+        self.send(EditorRequest::PublishDiagnostics { uri, diagnostics })
+    }
+
+    /// Show `title` as progress in Zed while the returned guard lives
+    pub fn progress(&self, title: impl Into<String>) -> ProgressGuard {
+        let (done_sender, done) = oneshot::channel();
+        let request = EditorRequest::Progress {
+            title: title.into(),
+            done,
+        };
+        ProgressGuard {
+            _done: self.send(request).then_some(done_sender),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Encoding a text file was stored in before being transcoded to UTF-8
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// UTF-8 with a byte order mark
+    #[serde(rename = "utf-8-bom")]
+    Utf8Bom,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    /// Fallback for bytes that are not valid UTF-8
+    #[serde(rename = "latin-1")]
+    Latin1,
+}
+
+impl TextEncoding {
+    /// Whether the text was read as-is, without transcoding
+    pub fn is_utf8(&self) -> bool {
+        *self == TextEncoding::Utf8
+    }
+}
+
+/// Text decoded from a file, along with the encoding it was stored in
+#[derive(Debug, Clone, Default)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: TextEncoding,
+}
+
+/// Read a text file in any supported encoding, transcoding it to UTF-8
+pub fn read_text_file(path: impl AsRef<Path>) -> io::Result<DecodedText> {
+    fs::read(path).map(decode)
+}
+
+/// Detect the encoding of `bytes` from its byte order mark, falling back to Latin-1 when
+/// the content is not valid UTF-8
+pub fn decode(bytes: Vec<u8>) -> DecodedText {
+    let (text, encoding) = match bytes.as_slice() {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => (
+            String::from_utf8_lossy(rest).into_owned(),
+            TextEncoding::Utf8Bom,
+        ),
+        [0xFF, 0xFE, rest @ ..] => (
+            decode_utf16(rest, u16::from_le_bytes),
+            TextEncoding::Utf16Le,
+        ),
+        [0xFE, 0xFF, rest @ ..] => (
+            decode_utf16(rest, u16::from_be_bytes),
+            TextEncoding::Utf16Be,
+        ),
+        _ => match String::from_utf8(bytes) {
+            Ok(text) => (text, TextEncoding::Utf8),
+            Err(e) => (
+                e.into_bytes().into_iter().map(char::from).collect(),
+                TextEncoding::Latin1,
+            ),
+        },
+    };
+    DecodedText { text, encoding }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use std::path::Path;
+use tokio::sync::broadcast;
+#[cfg(unix)]
+use tracing::{debug, info, warn};
+
+use crate::lsp::JsonRpcNotification;
+
+/// Lines buffered per reader before a slow reader starts missing them
+const TAP_CHANNEL_CAPACITY: usize = 256;
+
+/// Opt-in mirror of every IDE notification as JSON lines, for dashboards and statusline
+/// widgets showing what context Claude has. An existing FIFO at the path is written to while
+/// a reader has it open; otherwise a Unix socket is bound there and every client connecting
+/// to it receives the lines.
+#[derive(Debug, Clone)]
+pub struct EventTap {
+    sender: broadcast::Sender<String>,
+}
+
+impl EventTap {
+    #[cfg(unix)]
+    pub fn start(path: &Path) -> Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let (sender, _) = broadcast::channel(TAP_CHANNEL_CAPACITY);
+        let is_fifo = std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo());
+        if is_fifo {
+            info!("Mirroring notifications to FIFO {}", path.display());
+            tokio::spawn(write_fifo(path.to_path_buf(), sender.subscribe()));
+        } else {
+            match std::fs::symlink_metadata(path) {
+                // A socket left behind by an earlier server would fail the bind
+                Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+                Ok(_) => anyhow::bail!(
+                    "{} exists and is neither a FIFO nor a socket, not replacing it",
+                    path.display()
+                ),
+                Err(_) => {}
+            }
+            let listener = tokio::net::UnixListener::bind(path)?;
+            info!("Mirroring notifications to socket {}", path.display());
+            tokio::spawn(serve_socket(listener, sender.clone()));
+        }
+        Ok(Self { sender })
+    }
+
+    #[cfg(not(unix))]
+    pub fn start(_path: &Path) -> Result<Self> {
+        anyhow::bail!("The event tap needs Unix sockets or FIFOs, which this platform lacks")
+    }
+
+    /// Mirror a notification to the connected readers, if any
+    pub fn mirror(&self, notification: &JsonRpcNotification) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        if let Ok(line) = serde_json::to_string(notification) {
+            let _ = self.sender.send(line);
+        }
+    }
+}
+
+/// Write lines to the FIFO while it has a reader, dropping them while it has none
+#[cfg(unix)]
+async fn write_fifo(path: std::path::PathBuf, mut lines: broadcast::Receiver<String>) {
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::unix::pipe;
+
+    let mut fifo: Option<pipe::Sender> = None;
+    loop {
+        let line = match lines.recv().await {
+            Ok(line) => line,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                debug!("Event tap FIFO missed {} notifications", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if fifo.is_none() {
+            // Opening fails without a reader, in which case the line is dropped
+            fifo = pipe::OpenOptions::new().open_sender(&path).ok();
+        }
+        let Some(writer) = fifo.as_mut() else {
+            continue;
+        };
+        if let Err(e) = writer.write_all(format!("{}\n", line).as_bytes()).await {
+            debug!("Event tap FIFO reader went away: {}", e);
+            fifo = None;
+        }
+    }
+}
+
+/// Accept socket clients, each receiving lines until it disconnects
+#[cfg(unix)]
+async fn serve_socket(listener: tokio::net::UnixListener, sender: broadcast::Sender<String>) {
+    use tokio::io::AsyncWriteExt;
+
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Event tap stopped accepting readers: {}", e);
+                return;
+            }
+        };
+        debug!("Event tap reader connected");
+        let mut lines = sender.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match lines.recv().await {
+                    Ok(line) => {
+                        if stream
+                            .write_all(format!("{}\n", line).as_bytes())
+                            .await
+                            .is_err()
+                        {
+                            debug!("Event tap reader disconnected");
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        debug!("Event tap reader missed {} notifications", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
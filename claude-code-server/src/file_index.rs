@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Upper bound on indexed files, so huge workspaces don't stall completions
+const MAX_INDEXED_FILES: usize = 20_000;
+
+/// How long an index is used before the workspace is walked again
+const INDEX_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Paths of the workspace's files, relative to its root, for `@` file completions
+#[derive(Debug)]
+pub struct FileIndex {
+    root: PathBuf,
+    files: Vec<String>,
+    built_at: Instant,
+}
+
+pub type SharedFileIndex = Arc<RwLock<Option<FileIndex>>>;
+
+impl FileIndex {
+    /// Walk `root`, skipping hidden and ignored directories
+    pub fn build(root: &Path, ignored_dirs: &[String]) -> Self {
+        let mut files = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+
+        'walk: while let Some(dir) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if name.starts_with('.') {
+                    continue;
+                }
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let path = entry.path();
+                if file_type.is_dir() {
+                    if !ignored_dirs.iter().any(|dir| *dir == name) {
+                        pending.push(path);
+                    }
+                } else if file_type.is_file() {
+                    if let Ok(relative) = path.strip_prefix(root) {
+                        files.push(relative.to_string_lossy().into_owned());
+                    }
+                    if files.len() >= MAX_INDEXED_FILES {
+                        break 'walk;
+                    }
+                }
+            }
+        }
+        files.sort();
+
+        Self {
+            root: root.to_path_buf(),
+            files,
+            built_at: Instant::now(),
+        }
+    }
+
+    /// Whether the index is too old to trust, or of another root
+    pub fn is_stale(&self, root: &Path) -> bool {
+        self.root != root || self.built_at.elapsed() > INDEX_MAX_AGE
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Indexed paths, relative to the root
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Up to `limit` indexed paths matching `query` fuzzily, best first
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&str> {
+        let mut matches: Vec<(i64, &str)> = self
+            .files
+            .iter()
+            .filter_map(|file| fuzzy_score(file, query).map(|score| (score, file.as_str())))
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.len().cmp(&b.1.len())));
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, file)| file)
+            .collect()
+    }
+}
+
+/// Score `candidate` against `query` when the query's characters appear in it in order,
+/// ignoring case. Consecutive characters, characters starting a path component or word,
+/// and matches in the file name score higher.
+pub(crate) fn fuzzy_score(candidate: &str, query: &str) -> Option<i64> {
+    let chars: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    let name_start = chars.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        let found = next + chars[next..].iter().position(|c| *c == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(chars[found - 1], '/' | '_' | '-' | '.' | ':') {
+            score += 3;
+        }
+        if found >= name_start {
+            score += 2;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score_needs_the_query_in_order() {
+        assert!(fuzzy_score("src/main.rs", "mrs").is_some());
+        assert_eq!(fuzzy_score("src/main.rs", "rsm"), None);
+        assert_eq!(fuzzy_score("src/main.rs", "mainx"), None);
+        assert_eq!(fuzzy_score("", "a"), None);
+        assert_eq!(fuzzy_score("src/main.rs", ""), Some(0));
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case() {
+        assert_eq!(
+            fuzzy_score("src/Main.RS", "main.rs"),
+            fuzzy_score("src/main.rs", "MAIN.RS")
+        );
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_consecutive_word_start_and_file_name_matches() {
+        let score = |candidate| fuzzy_score(candidate, "main").unwrap();
+        assert!(score("src/main.rs") > score("src/mxaxixn.rs"));
+        assert!(score("lib/main.rs") > score("main/lib.rs"));
+        assert!(score("src/get_main.rs") > score("src/getmain.rs"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Per-path locks serializing operations that mutate a file, so concurrent tool calls
+/// touching the same path run one after another instead of interleaving
+#[derive(Debug, Clone, Default)]
+pub struct PathLocks {
+    locks: Arc<Mutex<HashMap<PathBuf, Arc<AsyncMutex<()>>>>>,
+}
+
+impl PathLocks {
+    /// Wait for exclusive access to `path`; the lock is held until the guard is dropped
+    pub async fn lock(&self, path: &Path) -> OwnedMutexGuard<()> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // Forget locks nobody holds or waits on, so the map doesn't grow with every path
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(key).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+/// Replace `path` with `contents` atomically: the data goes to a temp file in the same
+/// directory which is then renamed over the target, so readers (including the editor)
+/// never observe a partially written file. A symlink is followed so its target is replaced
+/// rather than the link, and the file keeps its permissions.
+pub fn write_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    // A file that doesn't exist yet has nothing to resolve
+    let path = fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+    let permissions = fs::metadata(&path)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let path = path.as_path();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
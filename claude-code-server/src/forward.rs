@@ -0,0 +1,60 @@
+use anyhow::Result;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use tokio::net::TcpStream;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::websocket::{
+    advertise_server, find_available_port, AdvertiseSettings, DEFAULT_PORT_END, DEFAULT_PORT_START,
+};
+
+/// Run a lightweight local forwarder for a server running on a remote host (e.g. a Zed SSH
+/// remote project). The forwarder writes the lock file on this machine and relays every
+/// connection to `remote`, which is usually the local end of an SSH tunnel.
+pub async fn run_forwarder(
+    remote: String,
+    port: Option<u16>,
+    workspace: Option<PathBuf>,
+    auth_token: Option<String>,
+) -> Result<()> {
+    let (listener, local_port) = find_available_port(
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        port,
+        DEFAULT_PORT_START,
+        DEFAULT_PORT_END,
+    )
+    .await?;
+    info!("Forwarding 127.0.0.1:{} to {}", local_port, remote);
+
+    let auth_token = auth_token.unwrap_or_else(|| Uuid::new_v4().to_string());
+    advertise_server(
+        AdvertiseSettings::default(),
+        local_port,
+        workspace,
+        &auth_token,
+    )?;
+
+    while let Ok((mut inbound, peer_addr)) = listener.accept().await {
+        info!("Forwarding connection from {}", peer_addr);
+        let remote = remote.clone();
+        tokio::spawn(async move {
+            let mut outbound = match TcpStream::connect(&remote).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("Failed to connect to remote server {}: {}", remote, e);
+                    return;
+                }
+            };
+            match tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await {
+                Ok((sent, received)) => info!(
+                    "Connection from {} closed ({} bytes sent, {} received)",
+                    peer_addr, sent, received
+                ),
+                Err(e) => error!("Forwarding for {} failed: {}", peer_addr, e),
+            }
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::config::{HookConfig, HookEvent};
+
+/// Maximum time a single hook may take before it is abandoned
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl HookConfig {
+    pub fn applies_to(&self, event: HookEvent, tool_name: &str) -> bool {
+        self.event == event && (self.tools.is_empty() || self.tools.iter().any(|t| t == tool_name))
+    }
+}
+
+/// Run the hooks configured for `event` on `tool_name` in the worktree directory, passing
+/// `payload` as JSON on stdin.
+///
+/// Returns the reason the call was blocked if a pre-tool-call hook failed; post-tool-call
+/// failures are only logged.
+pub async fn run_hooks(
+    hooks: &[HookConfig],
+    event: HookEvent,
+    tool_name: &str,
+    payload: &serde_json::Value,
+    worktree: Option<&Path>,
+) -> Result<(), String> {
+    let input = payload.to_string();
+
+    for hook in hooks.iter().filter(|h| h.applies_to(event, tool_name)) {
+        info!(
+            "Running {} hook for {}: {} {:?}",
+            event.as_str(),
+            tool_name,
+            hook.command,
+            hook.args
+        );
+
+        let failure = match run_hook(hook, &input, worktree).await {
+            Ok(output) if output.status.success() => {
+                debug!(
+                    "Hook {} output:\n{}",
+                    hook.command,
+                    String::from_utf8_lossy(&output.stdout)
+                );
+                continue;
+            }
+            Ok(output) => format!(
+                "hook {} exited with {}: {}",
+                hook.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => format!("hook {} failed: {}", hook.command, e),
+        };
+
+        warn!("{}", failure);
+        if event == HookEvent::PreToolCall {
+            return Err(failure);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_hook(
+    hook: &HookConfig,
+    input: &str,
+    worktree: Option<&Path>,
+) -> std::io::Result<std::process::Output> {
+    let mut command = Command::new(&hook.command);
+    command
+        .args(&hook.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(dir) = worktree {
+        command.current_dir(dir);
+    }
+
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Hooks that don't read their input close stdin early; that's not an error
+        if let Err(e) = stdin.write_all(input.as_bytes()).await {
+            debug!("Hook {} did not read its input: {}", hook.command, e);
+        }
+    }
+
+    tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {:?}", HOOK_TIMEOUT),
+            )
+        })?
+}
@@ -0,0 +1,266 @@
+use std::fmt::Display;
+
+/// Languages user-facing strings are translated into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+    Ja,
+    Zh,
+}
+
+/// Strings shown to the user in prompts, messages and OS notifications. Tool results and
+/// other machine-readable output are never translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    /// Workspace trust prompt; `{}` is the workspace path
+    TrustPrompt,
+    TrustAction,
+    RestrictAction,
+    /// `{}` is the warning reported by the extension
+    StartupWarning,
+    FocusModeOn,
+    FocusModeOff,
+    /// `{}` are the file path, first line and last line
+    AtMentionSent,
+    /// `{}` is the command name
+    UnknownCommand,
+    /// `{}` is the tool name
+    PermissionRequested,
+    /// `{}` is the file path
+    DiffAwaitingReview,
+    /// Asks whether to save a diff Claude proposed; `{}` is the file path
+    DiffReviewPrompt,
+    DiffAcceptAction,
+    DiffRejectAction,
+    SessionEnded,
+    /// Hover and `claude/status` text while Claude is connected; `{}` is the WebSocket port
+    HoverConnected,
+    /// Hover and `claude/status` text while no Claude is connected; `{}` is the WebSocket
+    /// port
+    HoverWaiting,
+    /// Hover status without a WebSocket server, i.e. in `lsp` mode
+    HoverNoServer,
+    /// `{}` is the file and line range last at-mentioned
+    HoverLastMention,
+    /// `{}` are the number of diagnostics and the file path
+    FixRequestSent,
+    /// `{}` is the file and line range sent
+    ActionRequestSent,
+    /// An explain, improve or fix command had no code to act on
+    NoActionTarget,
+    /// `claude/status` text after a connection presented a bad auth token; `{}` is the
+    /// WebSocket port
+    StatusAuthFailed,
+    /// `{}` are the number of diagnostics and of files they are in
+    DiagnosticsSent,
+    /// `claude-code.sendDiagnostics` found no diagnostics
+    NoDiagnostics,
+    /// `claude-code.start` started the WebSocket server held back by `autoStart`
+    WebSocketStarting,
+    /// `claude-code.start` found the WebSocket server already started
+    WebSocketAlreadyStarted,
+}
+
+impl Locale {
+    /// Match a BCP 47 tag such as "de-CH" or "zh_CN" by its primary language; unsupported
+    /// languages fall back to English
+    pub fn from_tag(tag: &str) -> Self {
+        let language = tag
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "de" => Locale::De,
+            "es" => Locale::Es,
+            "fr" => Locale::Fr,
+            "ja" => Locale::Ja,
+            "zh" => Locale::Zh,
+            _ => Locale::En,
+        }
+    }
+
+    /// `text` in this locale, with each `{}` replaced by the next of `args`
+    pub fn format(self, text: Text, args: &[&(dyn Display + Sync)]) -> String {
+        let mut args = args.iter();
+        let mut parts = self.template(text).split("{}");
+        let mut formatted = parts.next().unwrap_or_default().to_string();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                formatted.push_str(&arg.to_string());
+            }
+            formatted.push_str(part);
+        }
+        formatted
+    }
+
+    fn template(self, text: Text) -> &'static str {
+        match (self, text) {
+            (Locale::En, Text::TrustPrompt) => "Claude Code: do you trust {}? Until you do, Claude only gets read-only tools and linters do not run.",
+            (Locale::En, Text::TrustAction) => "Trust Workspace",
+            (Locale::En, Text::RestrictAction) => "Keep Restricted",
+            (Locale::En, Text::StartupWarning) => "Claude Code: {}",
+            (Locale::En, Text::FocusModeOn) => "Claude Code: focus mode on",
+            (Locale::En, Text::FocusModeOff) => "Claude Code: focus mode off",
+            (Locale::En, Text::AtMentionSent) => "At-mention sent for {}:{}-{}",
+            (Locale::En, Text::UnknownCommand) => "Unknown command: {}",
+            (Locale::En, Text::PermissionRequested) => "Claude wants to use {}; trust this workspace to allow it",
+            (Locale::En, Text::DiffAwaitingReview) => "Claude proposed changes to {}",
+            (Locale::En, Text::DiffReviewPrompt) => "Claude Code: save Claude's changes to {}?",
+            (Locale::En, Text::DiffAcceptAction) => "Accept",
+            (Locale::En, Text::DiffRejectAction) => "Reject",
+            (Locale::En, Text::SessionEnded) => "Claude's session has ended",
+            (Locale::En, Text::HoverConnected) => "Claude Code: connected on port {}",
+            (Locale::En, Text::HoverWaiting) => "Claude Code: waiting for Claude on port {}",
+            (Locale::En, Text::HoverNoServer) => "Claude Code: no WebSocket server running",
+            (Locale::En, Text::HoverLastMention) => "Last at-mention: {}",
+            (Locale::En, Text::FixRequestSent) => "Asked Claude to fix {} problems in {}",
+            (Locale::En, Text::ActionRequestSent) => "Asked Claude about {}",
+            (Locale::En, Text::NoActionTarget) => "Claude Code: select code in an open file first",
+            (Locale::En, Text::StatusAuthFailed) => "Claude Code: rejected a connection with an invalid auth token on port {}",
+            (Locale::En, Text::DiagnosticsSent) => "Sent {} diagnostics in {} files to Claude",
+            (Locale::En, Text::NoDiagnostics) => "Claude Code: no diagnostics to send",
+            (Locale::En, Text::WebSocketStarting) => "Claude Code: starting, Claude can connect shortly",
+            (Locale::En, Text::WebSocketAlreadyStarted) => "Claude Code: already started",
+
+            (Locale::De, Text::TrustPrompt) => "Claude Code: Vertrauen Sie {}? Bis dahin erhält Claude nur lesende Werkzeuge und Linter laufen nicht.",
+            (Locale::De, Text::TrustAction) => "Arbeitsbereich vertrauen",
+            (Locale::De, Text::RestrictAction) => "Eingeschränkt lassen",
+            (Locale::De, Text::StartupWarning) => "Claude Code: {}",
+            (Locale::De, Text::FocusModeOn) => "Claude Code: Fokusmodus an",
+            (Locale::De, Text::FocusModeOff) => "Claude Code: Fokusmodus aus",
+            (Locale::De, Text::AtMentionSent) => "Erwähnung gesendet für {}:{}-{}",
+            (Locale::De, Text::UnknownCommand) => "Unbekannter Befehl: {}",
+            (Locale::De, Text::PermissionRequested) => "Claude möchte {} verwenden; vertrauen Sie diesem Arbeitsbereich, um es zu erlauben",
+            (Locale::De, Text::DiffAwaitingReview) => "Claude hat Änderungen an {} vorgeschlagen",
+            (Locale::De, Text::DiffReviewPrompt) => "Claude Code: Änderungen von Claude an {} speichern?",
+            (Locale::De, Text::DiffAcceptAction) => "Übernehmen",
+            (Locale::De, Text::DiffRejectAction) => "Ablehnen",
+            (Locale::De, Text::SessionEnded) => "Claudes Sitzung wurde beendet",
+            (Locale::De, Text::HoverConnected) => "Claude Code: verbunden auf Port {}",
+            (Locale::De, Text::HoverWaiting) => "Claude Code: wartet auf Claude auf Port {}",
+            (Locale::De, Text::HoverNoServer) => "Claude Code: kein WebSocket-Server aktiv",
+            (Locale::De, Text::HoverLastMention) => "Letzte Erwähnung: {}",
+            (Locale::De, Text::FixRequestSent) => "Claude wurde gebeten, {} Probleme in {} zu beheben",
+            (Locale::De, Text::ActionRequestSent) => "Claude wurde zu {} gefragt",
+            (Locale::De, Text::NoActionTarget) => "Claude Code: Wählen Sie zuerst Code in einer geöffneten Datei aus",
+            (Locale::De, Text::StatusAuthFailed) => "Claude Code: Verbindung mit ungültigem Auth-Token auf Port {} abgelehnt",
+            (Locale::De, Text::DiagnosticsSent) => "{} Diagnosen in {} Dateien an Claude gesendet",
+            (Locale::De, Text::NoDiagnostics) => "Claude Code: Keine Diagnosen zum Senden",
+            (Locale::De, Text::WebSocketStarting) => "Claude Code: wird gestartet, Claude kann sich gleich verbinden",
+            (Locale::De, Text::WebSocketAlreadyStarted) => "Claude Code: bereits gestartet",
+
+            (Locale::Es, Text::TrustPrompt) => "Claude Code: ¿confía en {}? Hasta que lo haga, Claude solo tendrá herramientas de lectura y los linters no se ejecutarán.",
+            (Locale::Es, Text::TrustAction) => "Confiar en el espacio de trabajo",
+            (Locale::Es, Text::RestrictAction) => "Mantener restringido",
+            (Locale::Es, Text::StartupWarning) => "Claude Code: {}",
+            (Locale::Es, Text::FocusModeOn) => "Claude Code: modo de concentración activado",
+            (Locale::Es, Text::FocusModeOff) => "Claude Code: modo de concentración desactivado",
+            (Locale::Es, Text::AtMentionSent) => "Mención enviada para {}:{}-{}",
+            (Locale::Es, Text::UnknownCommand) => "Comando desconocido: {}",
+            (Locale::Es, Text::PermissionRequested) => "Claude quiere usar {}; confíe en este espacio de trabajo para permitirlo",
+            (Locale::Es, Text::DiffAwaitingReview) => "Claude propuso cambios en {}",
+            (Locale::Es, Text::DiffReviewPrompt) => "Claude Code: ¿guardar los cambios de Claude en {}?",
+            (Locale::Es, Text::DiffAcceptAction) => "Aceptar",
+            (Locale::Es, Text::DiffRejectAction) => "Rechazar",
+            (Locale::Es, Text::SessionEnded) => "La sesión de Claude ha terminado",
+            (Locale::Es, Text::HoverConnected) => "Claude Code: conectado en el puerto {}",
+            (Locale::Es, Text::HoverWaiting) => "Claude Code: esperando a Claude en el puerto {}",
+            (Locale::Es, Text::HoverNoServer) => "Claude Code: no hay ningún servidor WebSocket en ejecución",
+            (Locale::Es, Text::HoverLastMention) => "Última mención: {}",
+            (Locale::Es, Text::FixRequestSent) => "Se pidió a Claude corregir {} problemas en {}",
+            (Locale::Es, Text::ActionRequestSent) => "Se consultó a Claude sobre {}",
+            (Locale::Es, Text::NoActionTarget) => "Claude Code: seleccione primero código en un archivo abierto",
+            (Locale::Es, Text::StatusAuthFailed) => "Claude Code: se rechazó una conexión con un token de autenticación no válido en el puerto {}",
+            (Locale::Es, Text::DiagnosticsSent) => "Se enviaron a Claude {} diagnósticos de {} archivos",
+            (Locale::Es, Text::NoDiagnostics) => "Claude Code: no hay diagnósticos que enviar",
+            (Locale::Es, Text::WebSocketStarting) => "Claude Code: iniciando, Claude podrá conectarse en breve",
+            (Locale::Es, Text::WebSocketAlreadyStarted) => "Claude Code: ya está iniciado",
+
+            (Locale::Fr, Text::TrustPrompt) => "Claude Code : faites-vous confiance à {} ? En attendant, Claude n'a que des outils en lecture seule et les linters ne s'exécutent pas.",
+            (Locale::Fr, Text::TrustAction) => "Faire confiance à l'espace de travail",
+            (Locale::Fr, Text::RestrictAction) => "Garder restreint",
+            (Locale::Fr, Text::StartupWarning) => "Claude Code : {}",
+            (Locale::Fr, Text::FocusModeOn) => "Claude Code : mode concentration activé",
+            (Locale::Fr, Text::FocusModeOff) => "Claude Code : mode concentration désactivé",
+            (Locale::Fr, Text::AtMentionSent) => "Mention envoyée pour {}:{}-{}",
+            (Locale::Fr, Text::UnknownCommand) => "Commande inconnue : {}",
+            (Locale::Fr, Text::PermissionRequested) => "Claude veut utiliser {} ; faites confiance à cet espace de travail pour l'autoriser",
+            (Locale::Fr, Text::DiffAwaitingReview) => "Claude a proposé des modifications de {}",
+            (Locale::Fr, Text::DiffReviewPrompt) => "Claude Code : enregistrer les modifications de Claude dans {} ?",
+            (Locale::Fr, Text::DiffAcceptAction) => "Accepter",
+            (Locale::Fr, Text::DiffRejectAction) => "Refuser",
+            (Locale::Fr, Text::SessionEnded) => "La session de Claude est terminée",
+            (Locale::Fr, Text::HoverConnected) => "Claude Code : connecté sur le port {}",
+            (Locale::Fr, Text::HoverWaiting) => "Claude Code : en attente de Claude sur le port {}",
+            (Locale::Fr, Text::HoverNoServer) => "Claude Code : aucun serveur WebSocket en cours d'exécution",
+            (Locale::Fr, Text::HoverLastMention) => "Dernière mention : {}",
+            (Locale::Fr, Text::FixRequestSent) => "Claude doit corriger {} problèmes dans {}",
+            (Locale::Fr, Text::ActionRequestSent) => "Claude a été sollicité pour {}",
+            (Locale::Fr, Text::NoActionTarget) => "Claude Code : sélectionnez d'abord du code dans un fichier ouvert",
+            (Locale::Fr, Text::StatusAuthFailed) => "Claude Code : connexion refusée sur le port {}, jeton d'authentification invalide",
+            (Locale::Fr, Text::DiagnosticsSent) => "{} diagnostics de {} fichiers envoyés à Claude",
+            (Locale::Fr, Text::NoDiagnostics) => "Claude Code : aucun diagnostic à envoyer",
+            (Locale::Fr, Text::WebSocketStarting) => "Claude Code : démarrage, Claude pourra se connecter sous peu",
+            (Locale::Fr, Text::WebSocketAlreadyStarted) => "Claude Code : déjà démarré",
+
+            (Locale::Ja, Text::TrustPrompt) => "Claude Code: {} を信頼しますか？信頼するまで、Claude は読み取り専用のツールのみを使用でき、リンターは実行されません。",
+            (Locale::Ja, Text::TrustAction) => "ワークスペースを信頼",
+            (Locale::Ja, Text::RestrictAction) => "制限したままにする",
+            (Locale::Ja, Text::StartupWarning) => "Claude Code: {}",
+            (Locale::Ja, Text::FocusModeOn) => "Claude Code: フォーカスモード オン",
+            (Locale::Ja, Text::FocusModeOff) => "Claude Code: フォーカスモード オフ",
+            (Locale::Ja, Text::AtMentionSent) => "{}:{}-{} のメンションを送信しました",
+            (Locale::Ja, Text::UnknownCommand) => "不明なコマンド: {}",
+            (Locale::Ja, Text::PermissionRequested) => "Claude が {} を使用しようとしています。許可するにはこのワークスペースを信頼してください",
+            (Locale::Ja, Text::DiffAwaitingReview) => "Claude が {} への変更を提案しました",
+            (Locale::Ja, Text::DiffReviewPrompt) => "Claude Code: Claude による {} への変更を保存しますか？",
+            (Locale::Ja, Text::DiffAcceptAction) => "適用",
+            (Locale::Ja, Text::DiffRejectAction) => "拒否",
+            (Locale::Ja, Text::SessionEnded) => "Claude のセッションが終了しました",
+            (Locale::Ja, Text::HoverConnected) => "Claude Code: ポート {} で接続中",
+            (Locale::Ja, Text::HoverWaiting) => "Claude Code: ポート {} で Claude を待機中",
+            (Locale::Ja, Text::HoverNoServer) => "Claude Code: WebSocket サーバーは実行されていません",
+            (Locale::Ja, Text::HoverLastMention) => "最後のメンション: {}",
+            (Locale::Ja, Text::FixRequestSent) => "{} 件の問題の修正を Claude に依頼しました: {}",
+            (Locale::Ja, Text::ActionRequestSent) => "{} について Claude に依頼しました",
+            (Locale::Ja, Text::NoActionTarget) => "Claude Code: まず開いているファイルでコードを選択してください",
+            (Locale::Ja, Text::StatusAuthFailed) => "Claude Code: ポート {} で無効な認証トークンの接続を拒否しました",
+            (Locale::Ja, Text::DiagnosticsSent) => "{} 件の診断 ({} ファイル) を Claude に送信しました",
+            (Locale::Ja, Text::NoDiagnostics) => "Claude Code: 送信する診断はありません",
+            (Locale::Ja, Text::WebSocketStarting) => "Claude Code: 起動しています。まもなく Claude が接続できます",
+            (Locale::Ja, Text::WebSocketAlreadyStarted) => "Claude Code: すでに起動しています",
+
+            (Locale::Zh, Text::TrustPrompt) => "Claude Code：是否信任 {}？在信任之前，Claude 只能使用只读工具，且不会运行代码检查。",
+            (Locale::Zh, Text::TrustAction) => "信任工作区",
+            (Locale::Zh, Text::RestrictAction) => "保持受限",
+            (Locale::Zh, Text::StartupWarning) => "Claude Code：{}",
+            (Locale::Zh, Text::FocusModeOn) => "Claude Code：专注模式已开启",
+            (Locale::Zh, Text::FocusModeOff) => "Claude Code：专注模式已关闭",
+            (Locale::Zh, Text::AtMentionSent) => "已发送 {}:{}-{} 的提及",
+            (Locale::Zh, Text::UnknownCommand) => "未知命令：{}",
+            (Locale::Zh, Text::PermissionRequested) => "Claude 想要使用 {}；信任此工作区以允许",
+            (Locale::Zh, Text::DiffAwaitingReview) => "Claude 提议修改 {}",
+            (Locale::Zh, Text::DiffReviewPrompt) => "Claude Code：是否保存 Claude 对 {} 的更改？",
+            (Locale::Zh, Text::DiffAcceptAction) => "接受",
+            (Locale::Zh, Text::DiffRejectAction) => "拒绝",
+            (Locale::Zh, Text::SessionEnded) => "Claude 的会话已结束",
+            (Locale::Zh, Text::HoverConnected) => "Claude Code：已在端口 {} 上连接",
+            (Locale::Zh, Text::HoverWaiting) => "Claude Code：正在端口 {} 上等待 Claude",
+            (Locale::Zh, Text::HoverNoServer) => "Claude Code：没有运行 WebSocket 服务器",
+            (Locale::Zh, Text::HoverLastMention) => "最近一次提及：{}",
+            (Locale::Zh, Text::FixRequestSent) => "已请 Claude 修复 {} 个问题：{}",
+            (Locale::Zh, Text::ActionRequestSent) => "已就 {} 询问 Claude",
+            (Locale::Zh, Text::NoActionTarget) => "Claude Code：请先在打开的文件中选择代码",
+            (Locale::Zh, Text::StatusAuthFailed) => "Claude Code：已拒绝端口 {} 上使用无效认证令牌的连接",
+            (Locale::Zh, Text::DiagnosticsSent) => "已将 {} 条诊断（{} 个文件）发送给 Claude",
+            (Locale::Zh, Text::NoDiagnostics) => "Claude Code：没有可发送的诊断",
+            (Locale::Zh, Text::WebSocketStarting) => "Claude Code：正在启动，Claude 稍后即可连接",
+            (Locale::Zh, Text::WebSocketAlreadyStarted) => "Claude Code：已经启动",
+        }
+    }
+}
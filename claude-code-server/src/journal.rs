@@ -0,0 +1,425 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::file_locks::write_atomic;
+
+/// A file a change set rewrites, with the contents to restore it to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournaledFile {
+    pub path: PathBuf,
+    pub original: String,
+    pub updated: String,
+}
+
+/// How far a file of an interrupted change set got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileState {
+    /// The file still has its original contents
+    Pending,
+    /// The new contents were written
+    Applied,
+    /// The file has neither, e.g. it was edited since
+    Modified,
+    Missing,
+}
+
+impl FileState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileState::Pending => "pending",
+            FileState::Applied => "applied",
+            FileState::Modified => "modified",
+            FileState::Missing => "missing",
+        }
+    }
+
+    fn of(file: &JournaledFile) -> Self {
+        match fs::read_to_string(&file.path) {
+            Ok(contents) if contents == file.original => FileState::Pending,
+            Ok(contents) if contents == file.updated => FileState::Applied,
+            Ok(_) => FileState::Modified,
+            Err(_) => FileState::Missing,
+        }
+    }
+}
+
+/// A change set recorded before it was applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: String,
+    /// Tool that made the change
+    pub tool: String,
+    /// Milliseconds since the Unix epoch
+    pub started_at: u64,
+    pub files: Vec<JournaledFile>,
+}
+
+impl JournalEntry {
+    /// Whether some files of the change set were written and others were not
+    pub fn is_partially_applied(&self) -> bool {
+        let states: Vec<FileState> = self.files.iter().map(FileState::of).collect();
+        states.contains(&FileState::Applied) && states.iter().any(|s| *s != FileState::Applied)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id,
+            "tool": self.tool,
+            "startedAt": self.started_at,
+            "partiallyApplied": self.is_partially_applied(),
+            "files": self
+                .files
+                .iter()
+                .map(|file| serde_json::json!({
+                    "filePath": file.path,
+                    "state": FileState::of(file).as_str()
+                }))
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+/// Write-ahead journal of edits to workspace files, kept per worktree. A change set is
+/// recorded before its files are written and forgotten once they all were (or were rolled
+/// back), so entries left behind belong to change sets a crash interrupted, and hold the
+/// original contents to restore. The server applying a change set holds a lock on its
+/// entry file, which marks it as in progress to every server sharing the journal and is
+/// released when the server exits, however it exits.
+#[derive(Debug, Clone, Default)]
+pub struct EditJournal {
+    /// Directory of pending entries; nothing is journaled when unset
+    dir: Option<PathBuf>,
+}
+
+/// A recorded change set being applied. Its entry is removed by [`JournalRecord::complete`];
+/// dropping the record without completing it unlocks the entry to be restored.
+#[must_use]
+pub struct JournalRecord {
+    /// The locked entry file
+    entry: Option<(PathBuf, File)>,
+}
+
+impl JournalRecord {
+    /// Forget the change set, whose files are now all written or all restored
+    pub fn complete(mut self) {
+        if let Some((path, file)) = self.entry.take() {
+            // Emptied first for a server that opened the entry before it was removed and
+            // locks it once this one lets go
+            let _ = file.set_len(0);
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to complete journal entry {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl EditJournal {
+    /// The journal for a worktree (the current directory when unset)
+    pub fn for_worktree(worktree: Option<&Path>) -> Self {
+        Self {
+            dir: journal_dir(worktree),
+        }
+    }
+
+    /// Whether change sets are journaled, which needs a data directory
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Record a change set before writing any of its files
+    pub fn begin(&self, tool: &str, files: Vec<JournaledFile>) -> Result<JournalRecord> {
+        let Some(dir) = &self.dir else {
+            return Ok(JournalRecord { entry: None });
+        };
+
+        let entry = JournalEntry {
+            id: Uuid::new_v4().to_string(),
+            tool: tool.to_string(),
+            started_at: unix_millis(),
+            files,
+        };
+        let path = dir.join(format!("{}.json", entry.id));
+        fs::create_dir_all(dir)?;
+        // Lock the entry before writing it, so no other server reads it as interrupted
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        file.lock()?;
+        let written = file
+            .write_all(serde_json::to_string(&entry)?.as_bytes())
+            .and_then(|_| file.sync_all());
+        if let Err(e) = written {
+            let _ = fs::remove_file(&path);
+            return Err(e.into());
+        }
+        Ok(JournalRecord {
+            entry: Some((path, file)),
+        })
+    }
+
+    /// Change sets that were never completed, oldest first
+    pub fn interrupted(&self) -> Vec<JournalEntry> {
+        let mut interrupted: Vec<JournalEntry> = self
+            .entry_paths()
+            .into_iter()
+            .filter_map(|path| load_unlocked(&path))
+            .map(|(_, entry)| entry)
+            .collect();
+        interrupted.sort_by_key(|entry| entry.started_at);
+        interrupted
+    }
+
+    /// Put the files of an interrupted change set back to their original contents and
+    /// forget it, returning the files that were rewritten. Files edited since are left
+    /// alone unless `force` is set.
+    pub fn restore(&self, id: &str, force: bool) -> Result<Vec<PathBuf>> {
+        // Holding the entry's lock keeps another server from restoring it at the same time
+        let (path, _file, entry) = self.find(id)?;
+        let modified: Vec<String> = entry
+            .files
+            .iter()
+            .filter(|file| FileState::of(file) == FileState::Modified)
+            .map(|file| file.path.display().to_string())
+            .collect();
+        if !modified.is_empty() && !force {
+            bail!(
+                "Files changed since the interrupted edit: {}; pass force to overwrite them",
+                modified.join(", ")
+            );
+        }
+
+        let mut restored = Vec::new();
+        for file in &entry.files {
+            if FileState::of(file) != FileState::Pending {
+                write_atomic(&file.path, &file.original)?;
+                restored.push(file.path.clone());
+            }
+        }
+        fs::remove_file(path)?;
+        Ok(restored)
+    }
+
+    /// Forget an interrupted change set, keeping its files as they are
+    pub fn discard(&self, id: &str) -> Result<()> {
+        let (path, _file, _) = self.find(id)?;
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// An interrupted change set's entry file, locked, and the entry
+    fn find(&self, id: &str) -> Result<(PathBuf, File, JournalEntry)> {
+        let found = self
+            .entry_paths()
+            .into_iter()
+            .filter(|path| path.file_stem().is_some_and(|stem| stem == id))
+            .find_map(|path| load_unlocked(&path).map(|(file, entry)| (path, file, entry)));
+        match found {
+            Some(found) => Ok(found),
+            None => bail!("No interrupted edit with id {}", id),
+        }
+    }
+
+    fn entry_paths(&self) -> Vec<PathBuf> {
+        let Some(entries) = self.dir.as_ref().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect()
+    }
+}
+
+/// Lock and read an entry, unless a server is still applying it
+fn load_unlocked(path: &Path) -> Option<(File, JournalEntry)> {
+    let mut file = File::open(path).ok()?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => return None,
+        Err(TryLockError::Error(e)) => {
+            warn!("Failed to lock journal entry {}: {}", path.display(), e);
+            return None;
+        }
+    }
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    // Entries are only written under their lock, so an empty one was completed, or its
+    // server crashed before writing anything to restore
+    if contents.is_empty() {
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    let entry = serde_json::from_str::<JournalEntry>(&contents)
+        .inspect_err(|e| warn!("Ignoring invalid journal entry {}: {}", path.display(), e))
+        .ok()?;
+    Some((file, entry))
+}
+
+/// Journal directory for a worktree, named after its path
+fn journal_dir(worktree: Option<&Path>) -> Option<PathBuf> {
+    let worktree = match worktree {
+        Some(path) => path.to_path_buf(),
+        None => std::env::current_dir().ok()?,
+    };
+    let name: String = worktree
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    dirs::data_dir().map(|dir| dir.join("claude-code-zed").join("journal").join(name))
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A journal and a workspace in a fresh directory
+    fn journal() -> (EditJournal, PathBuf) {
+        let root = std::env::temp_dir().join(format!("claude-code-journal-{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("workspace")).unwrap();
+        let journal = EditJournal {
+            dir: Some(root.join("journal")),
+        };
+        (journal, root)
+    }
+
+    /// A workspace file currently holding `contents`, journaled as changing `original` to
+    /// `updated`
+    fn file(
+        root: &Path,
+        name: &str,
+        contents: &str,
+        original: &str,
+        updated: &str,
+    ) -> JournaledFile {
+        let path = root.join("workspace").join(name);
+        fs::write(&path, contents).unwrap();
+        JournaledFile {
+            path,
+            original: original.to_string(),
+            updated: updated.to_string(),
+        }
+    }
+
+    #[test]
+    fn entries_in_progress_are_not_interrupted() {
+        let (journal, root) = journal();
+        let first = journal.begin("openDiff", Vec::new()).unwrap();
+        let second = journal.begin("openDiff", Vec::new()).unwrap();
+        assert!(journal.interrupted().is_empty());
+        assert_eq!(journal.entry_paths().len(), 2);
+
+        first.complete();
+        drop(second);
+        assert_eq!(journal.entry_paths().len(), 1);
+        let interrupted = journal.interrupted();
+        assert_eq!(interrupted.len(), 1);
+        assert!(Uuid::parse_str(&interrupted[0].id).is_ok());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn empty_entries_are_removed() {
+        let (journal, root) = journal();
+        let dir = journal.dir.clone().unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("empty.json"), "").unwrap();
+        assert!(journal.interrupted().is_empty());
+        assert!(journal.entry_paths().is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn restore_puts_back_the_files_that_were_written() {
+        let (journal, root) = journal();
+        let files = vec![
+            file(&root, "a.rs", "new a", "old a", "new a"),
+            file(&root, "b.rs", "old b", "old b", "new b"),
+        ];
+        drop(journal.begin("searchAndReplace", files.clone()).unwrap());
+        let entry = &journal.interrupted()[0];
+        assert!(entry.is_partially_applied());
+        assert_eq!(entry.to_json()["files"][0]["state"], "applied");
+        assert_eq!(entry.to_json()["files"][1]["state"], "pending");
+
+        let restored = journal.restore(&entry.id, false).unwrap();
+        assert_eq!(restored, vec![files[0].path.clone()]);
+        assert_eq!(fs::read_to_string(&files[0].path).unwrap(), "old a");
+        assert_eq!(fs::read_to_string(&files[1].path).unwrap(), "old b");
+        assert!(journal.interrupted().is_empty());
+        assert!(journal.restore(&entry.id, false).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn restore_needs_force_to_overwrite_files_edited_since() {
+        let (journal, root) = journal();
+        let edited = file(&root, "a.rs", "edited a", "old a", "new a");
+        drop(journal.begin("openDiff", vec![edited.clone()]).unwrap());
+        let id = journal.interrupted()[0].id.clone();
+        assert!(!journal.interrupted()[0].is_partially_applied());
+
+        assert!(journal.restore(&id, false).is_err());
+        assert_eq!(fs::read_to_string(&edited.path).unwrap(), "edited a");
+        assert_eq!(
+            journal.restore(&id, true).unwrap(),
+            vec![edited.path.clone()]
+        );
+        assert_eq!(fs::read_to_string(&edited.path).unwrap(), "old a");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn discard_keeps_the_files_as_they_are() {
+        let (journal, root) = journal();
+        let applied = file(&root, "a.rs", "new a", "old a", "new a");
+        drop(journal.begin("openDiff", vec![applied.clone()]).unwrap());
+        let id = journal.interrupted()[0].id.clone();
+        journal.discard(&id).unwrap();
+        assert_eq!(fs::read_to_string(&applied.path).unwrap(), "new a");
+        assert!(journal.interrupted().is_empty());
+        assert!(journal.discard(&id).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn entries_being_applied_cannot_be_restored() {
+        let (journal, root) = journal();
+        let applied = file(&root, "a.rs", "new a", "old a", "new a");
+        let record = journal.begin("openDiff", vec![applied]).unwrap();
+        let id = journal.entry_paths()[0]
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+        assert!(journal.restore(&id, true).is_err());
+        record.complete();
+        assert!(journal.entry_paths().is_empty());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn disabled_journals_record_nothing() {
+        let journal = EditJournal::default();
+        assert!(!journal.is_enabled());
+        journal.begin("openDiff", Vec::new()).unwrap().complete();
+        assert!(journal.interrupted().is_empty());
+    }
+}
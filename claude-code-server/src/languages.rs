@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Upper bound on files visited when detecting workspace languages
+const MAX_SCANNED_FILES: usize = 5000;
+
+/// Map a file path to the LSP language ID used by the extension (see extension.toml)
+pub fn language_id_for_path(path: &Path) -> Option<&'static str> {
+    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+        match name {
+            "Dockerfile" => return Some("dockerfile"),
+            "Makefile" | "makefile" | "GNUmakefile" => return Some("makefile"),
+            _ => {}
+        }
+    }
+
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let language = match extension.as_str() {
+        "txt" => "plaintext",
+        "rs" => "rust",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "go" => "go",
+        "zig" => "zig",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "py" | "pyi" => "python",
+        "rb" => "ruby",
+        "php" => "php",
+        "pl" | "pm" => "perl",
+        "lua" => "lua",
+        "sh" | "bash" | "zsh" => "shellscript",
+        "fish" => "fish",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" | "sc" => "scala",
+        "clj" | "cljs" | "cljc" | "edn" => "clojure",
+        "ex" | "exs" => "elixir",
+        "erl" | "hrl" => "erlang",
+        "hs" => "haskell",
+        "ml" | "mli" => "ocaml",
+        "fs" | "fsi" | "fsx" => "fsharp",
+        "swift" => "swift",
+        "dart" => "dart",
+        "json" | "jsonc" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "tex" => "latex",
+        "typ" => "typst",
+        "sql" => "sql",
+        "graphql" | "gql" => "graphql",
+        "proto" => "protobuf",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Count source files per language under the workspace root, skipping hidden and ignored
+/// directories
+pub fn detect_languages(root: &Path, ignored_dirs: &[String]) -> HashMap<&'static str, usize> {
+    let mut counts = HashMap::new();
+    let mut scanned = 0;
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !ignored_dirs.iter().any(|dir| *dir == name) {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() {
+                scanned += 1;
+                if scanned > MAX_SCANNED_FILES {
+                    return counts;
+                }
+                if let Some(language) = language_id_for_path(&path) {
+                    *counts.entry(language).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    counts
+}
@@ -0,0 +1,136 @@
+/// Line terminator used throughout a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "lf",
+            LineEnding::Crlf => "crlf",
+        }
+    }
+}
+
+/// How a file terminates its lines, so rewritten content can match it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConventions {
+    pub line_ending: LineEnding,
+    /// Whether the file ends with a line terminator
+    pub trailing_newline: bool,
+}
+
+impl LineConventions {
+    /// Detect the conventions of `text`; mixed files count as whichever ending is more common
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        Self {
+            line_ending: if crlf > lf {
+                LineEnding::Crlf
+            } else {
+                LineEnding::Lf
+            },
+            trailing_newline: text.ends_with('\n'),
+        }
+    }
+
+    /// Rewrite `text` to use these conventions, so edits don't churn every line
+    pub fn apply(&self, text: &str) -> String {
+        let mut normalized = text.replace("\r\n", "\n");
+        match (self.trailing_newline, normalized.ends_with('\n')) {
+            (true, false) if !normalized.is_empty() => normalized.push('\n'),
+            (false, true) => {
+                normalized.pop();
+            }
+            _ => {}
+        }
+        match self.line_ending {
+            LineEnding::Lf => normalized,
+            LineEnding::Crlf => normalized.replace('\n', "\r\n"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conventions(line_ending: LineEnding, trailing_newline: bool) -> LineConventions {
+        LineConventions {
+            line_ending,
+            trailing_newline,
+        }
+    }
+
+    #[test]
+    fn detect_finds_the_line_ending_and_final_newline() {
+        assert_eq!(
+            LineConventions::detect("a\nb\n"),
+            conventions(LineEnding::Lf, true)
+        );
+        assert_eq!(
+            LineConventions::detect("a\r\nb"),
+            conventions(LineEnding::Crlf, false)
+        );
+        assert_eq!(
+            LineConventions::detect(""),
+            conventions(LineEnding::Lf, false)
+        );
+        assert_eq!(
+            LineConventions::detect("a"),
+            conventions(LineEnding::Lf, false)
+        );
+    }
+
+    #[test]
+    fn detect_goes_by_the_more_common_ending_in_mixed_files() {
+        assert_eq!(
+            LineConventions::detect("a\r\nb\r\nc\n").line_ending,
+            LineEnding::Crlf
+        );
+        assert_eq!(
+            LineConventions::detect("a\r\nb\nc\n").line_ending,
+            LineEnding::Lf
+        );
+        // Ties keep the default
+        assert_eq!(
+            LineConventions::detect("a\r\nb\n").line_ending,
+            LineEnding::Lf
+        );
+    }
+
+    #[test]
+    fn apply_converts_line_endings() {
+        assert_eq!(
+            conventions(LineEnding::Crlf, true).apply("a\nb\r\nc\n"),
+            "a\r\nb\r\nc\r\n"
+        );
+        assert_eq!(
+            conventions(LineEnding::Lf, true).apply("a\r\nb\r\n"),
+            "a\nb\n"
+        );
+    }
+
+    #[test]
+    fn apply_matches_the_final_newline() {
+        assert_eq!(conventions(LineEnding::Lf, true).apply("a"), "a\n");
+        assert_eq!(conventions(LineEnding::Crlf, true).apply("a"), "a\r\n");
+        assert_eq!(conventions(LineEnding::Lf, false).apply("a\n"), "a");
+        assert_eq!(conventions(LineEnding::Crlf, false).apply("a\r\n"), "a");
+        // Empty content stays empty rather than becoming a blank line
+        assert_eq!(conventions(LineEnding::Lf, true).apply(""), "");
+        assert_eq!(conventions(LineEnding::Lf, false).apply(""), "");
+    }
+
+    #[test]
+    fn apply_keeps_text_already_following_the_conventions() {
+        for text in ["a\nb\n", "a\r\nb", "", "\n"] {
+            let conventions = LineConventions::detect(text);
+            assert_eq!(conventions.apply(text), text);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Result};
+use std::sync::OnceLock;
+use tracing::info;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+/// Handle used to change the log level after startup (e.g. from LSP initialization options)
+static RELOAD_HANDLE: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// Initialize logging with enhanced formatting for debugging
+pub fn init(level: tracing::Level) -> Result<()> {
+    let (filter, handle) = reload::Layer::new(LevelFilter::from_level(level));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            fmt::layer()
+                .with_file(true)
+                .with_line_number(true)
+                .with_thread_ids(true)
+                .with_target(false)
+                .with_writer(std::io::stderr), // Force all logs to stderr for LSP compatibility
+        )
+        .try_init()?;
+
+    RELOAD_HANDLE
+        .set(handle)
+        .map_err(|_| anyhow!("Logging already initialized"))?;
+
+    info!("Logging initialized at level: {:?}", level);
+    Ok(())
+}
+
+/// Change the active log level at runtime
+pub fn set_level(level: tracing::Level) {
+    if let Some(handle) = RELOAD_HANDLE.get() {
+        match handle.modify(|filter| *filter = LevelFilter::from_level(level)) {
+            Ok(()) => info!("Log level changed to: {:?}", level),
+            Err(e) => tracing::warn!("Failed to change log level: {}", e),
+        }
+    }
+}
+
+/// Parse a log level name as used by `RUST_LOG` and client settings
+pub fn parse_level(name: &str) -> Option<tracing::Level> {
+    match name.to_ascii_lowercase().as_str() {
+        "trace" => Some(tracing::Level::TRACE),
+        "debug" => Some(tracing::Level::DEBUG),
+        "info" => Some(tracing::Level::INFO),
+        "warn" => Some(tracing::Level::WARN),
+        "error" => Some(tracing::Level::ERROR),
+        _ => None,
+    }
+}
@@ -0,0 +1,133 @@
+use tower_lsp::lsp_types::{Diagnostic, Range, Url};
+
+use super::notifications::ActionRequestedNotification;
+use crate::documents::SharedDocuments;
+
+/// Lines included before and after the range an action is about
+const ACTION_CONTEXT_LINES: u32 = 5;
+
+/// What the `claude-code.explain`, `claude-code.improve`, `claude-code.fix` and
+/// `claude-code.write-tests` commands ask Claude to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaudeAction {
+    Explain,
+    Improve,
+    Fix,
+    WriteTests,
+}
+
+impl ClaudeAction {
+    pub fn from_command(command: &str) -> Option<Self> {
+        match command {
+            "claude-code.explain" => Some(ClaudeAction::Explain),
+            "claude-code.improve" => Some(ClaudeAction::Improve),
+            "claude-code.fix" => Some(ClaudeAction::Fix),
+            "claude-code.write-tests" => Some(ClaudeAction::WriteTests),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClaudeAction::Explain => "explain",
+            ClaudeAction::Improve => "improve",
+            ClaudeAction::Fix => "fix",
+            ClaudeAction::WriteTests => "writeTests",
+        }
+    }
+
+    /// Instruction for Claude about `range` of `file_path`
+    fn prompt(&self, file_path: &str, range: Range, diagnostics: &[Diagnostic]) -> String {
+        let location = format!(
+            "{} (lines {}-{})",
+            file_path,
+            range.start.line + 1,
+            range.end.line + 1
+        );
+        match self {
+            ClaudeAction::Explain => format!("Explain this code from {}.", location),
+            ClaudeAction::Improve => format!(
+                "Suggest improvements to this code from {}: readability, correctness and performance.",
+                location
+            ),
+            ClaudeAction::WriteTests => format!(
+                "Write tests for this code from {}, following the project's existing test conventions.",
+                location
+            ),
+            ClaudeAction::Fix if diagnostics.is_empty() => {
+                format!("Find and fix the problems in this code from {}.", location)
+            }
+            ClaudeAction::Fix => {
+                let problems: Vec<String> = diagnostics
+                    .iter()
+                    .map(|diagnostic| {
+                        format!(
+                            "line {}: {}",
+                            diagnostic.range.start.line + 1,
+                            diagnostic.message
+                        )
+                    })
+                    .collect();
+                format!(
+                    "Fix these problems in {}:\n{}",
+                    file_path,
+                    problems.join("\n")
+                )
+            }
+        }
+    }
+}
+
+/// A request for Claude to act on `range` of an open document, with the range's text and
+/// the surrounding code. Without a range it covers the `diagnostics`. `None` when the
+/// document is not open or there is nothing to act on.
+pub async fn action_request(
+    documents: &SharedDocuments,
+    action: ClaudeAction,
+    uri: &Url,
+    range: Option<Range>,
+    diagnostics: Vec<Diagnostic>,
+) -> Option<ActionRequestedNotification> {
+    let documents = documents.read().await;
+    let document = documents.get(uri)?;
+
+    let range = range.or_else(|| {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.range)
+            .reduce(|a, b| Range {
+                start: a.start.min(b.start),
+                end: a.end.max(b.end),
+            })
+    })?;
+    let last_line = document.text.len_lines().saturating_sub(1) as u32;
+    let start = range
+        .start
+        .line
+        .saturating_sub(ACTION_CONTEXT_LINES)
+        .min(last_line);
+    let end = range
+        .end
+        .line
+        .saturating_add(ACTION_CONTEXT_LINES)
+        .min(last_line);
+    let code = document
+        .text
+        .slice(
+            document.text.line_to_char(start as usize)
+                ..document.text.line_to_char(end as usize + 1),
+        )
+        .to_string();
+
+    Some(ActionRequestedNotification {
+        action: action.as_str().to_string(),
+        prompt: action.prompt(&document.file_path(), range, &diagnostics),
+        file_path: document.file_path(),
+        file_url: uri.to_string(),
+        range,
+        text: document.text_in_range(range),
+        code,
+        code_start_line: start,
+        diagnostics,
+    })
+}
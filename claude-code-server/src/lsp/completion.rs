@@ -0,0 +1,163 @@
+use std::path::Path;
+use tower_lsp::lsp_types::{
+    Command, CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, Position,
+    Range, SymbolKind, TextEdit,
+};
+
+use super::utils::char_pos_to_byte_pos;
+use crate::claude_commands::{CustomCommand, COMMANDS_DIR};
+use crate::documents::OpenDocument;
+use crate::file_index::FileIndex;
+use crate::state;
+use crate::symbols::SymbolIndex;
+
+/// File completions offered for one `@` query
+const MAX_FILE_COMPLETIONS: usize = 50;
+
+/// Symbol completions offered for one `@` query
+const MAX_SYMBOL_COMPLETIONS: usize = 50;
+
+/// The `@` mention being typed before `position`: where it starts and what follows the `@`
+pub fn mention_query(document: &OpenDocument, position: Position) -> Option<(Position, String)> {
+    let line = document.text.get_line(position.line as usize)?.to_string();
+    let encoding = state::position_encoding();
+    let end = char_pos_to_byte_pos(&line, position.character as usize, encoding)?;
+    let before = &line[..end];
+    let token_start = before
+        .rfind(char::is_whitespace)
+        .map_or(0, |whitespace| whitespace + 1);
+    let query = before[token_start..].strip_prefix('@')?;
+
+    let token_units: usize = before[token_start..]
+        .chars()
+        .map(|c| encoding.len_of(c))
+        .sum();
+    let start = Position {
+        line: position.line,
+        character: position.character - token_units as u32,
+    };
+    Some((start, query.to_string()))
+}
+
+/// `@claude <command>` completions for the project's custom slash commands, described as
+/// in their files
+pub fn custom_command_completions(commands: &[CustomCommand]) -> Vec<CompletionItem> {
+    commands
+        .iter()
+        .map(|command| {
+            let text = format!("@claude {}", command.name);
+            CompletionItem {
+                label: text.clone(),
+                kind: Some(CompletionItemKind::TEXT),
+                detail: command.description.clone(),
+                documentation: Some(Documentation::String(format!(
+                    "Custom Claude command from {}/{}",
+                    COMMANDS_DIR, command.path
+                ))),
+                insert_text: Some(text),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Completions for the workspace files matching `query`, each replacing the typed mention
+/// from `start` to `end` with `@path` and at-mentioning the file when accepted
+pub fn file_completions(
+    index: &FileIndex,
+    query: &str,
+    start: Position,
+    end: Position,
+) -> Vec<CompletionItem> {
+    index
+        .search(query, MAX_FILE_COMPLETIONS)
+        .into_iter()
+        .enumerate()
+        .map(|(rank, file)| {
+            let mention = format!("@{}", file);
+            let absolute = index.root().join(file);
+            CompletionItem {
+                label: file.to_string(),
+                kind: Some(CompletionItemKind::FILE),
+                detail: Path::new(file)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+                filter_text: Some(mention.clone()),
+                // Keep the index's ranking rather than the client's alphabetical order
+                sort_text: Some(format!("{:04}", rank)),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: Range { start, end },
+                    new_text: mention,
+                })),
+                command: Some(Command {
+                    title: "Add file to Claude context".to_string(),
+                    command: "claude-code.at-mention".to_string(),
+                    // Without a line range the whole file is mentioned
+                    arguments: Some(vec![serde_json::json!({
+                        "filePath": absolute.to_string_lossy(),
+                    })]),
+                }),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Completions for the workspace symbols matching `query`, each replacing the typed mention
+/// from `start` to `end` with `@Container::name` and at-mentioning the lines declaring the
+/// symbol when accepted
+pub fn symbol_completions(
+    index: &SymbolIndex,
+    query: &str,
+    start: Position,
+    end: Position,
+) -> Vec<CompletionItem> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    index
+        .search(query, MAX_SYMBOL_COMPLETIONS)
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (file, symbol))| {
+            let name = symbol.qualified_name();
+            let mention = format!("@{}", name);
+            let absolute = index.root().join(file);
+            CompletionItem {
+                label: name,
+                kind: Some(completion_kind(symbol.kind)),
+                detail: Some(format!("{}:{}", file, symbol.start_line + 1)),
+                filter_text: Some(mention.clone()),
+                sort_text: Some(format!("{:04}", rank)),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: Range { start, end },
+                    new_text: mention,
+                })),
+                command: Some(Command {
+                    title: "Add symbol to Claude context".to_string(),
+                    command: "claude-code.at-mention".to_string(),
+                    arguments: Some(vec![serde_json::json!({
+                        "filePath": absolute.to_string_lossy(),
+                        "lineStart": symbol.start_line,
+                        "lineEnd": symbol.end_line
+                    })]),
+                }),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::METHOD => CompletionItemKind::METHOD,
+        SymbolKind::STRUCT => CompletionItemKind::STRUCT,
+        SymbolKind::CLASS => CompletionItemKind::CLASS,
+        SymbolKind::INTERFACE => CompletionItemKind::INTERFACE,
+        SymbolKind::ENUM => CompletionItemKind::ENUM,
+        SymbolKind::MODULE => CompletionItemKind::MODULE,
+        SymbolKind::CONSTANT => CompletionItemKind::CONSTANT,
+        SymbolKind::TYPE_PARAMETER => CompletionItemKind::TYPE_PARAMETER,
+        _ => CompletionItemKind::FUNCTION,
+    }
+}
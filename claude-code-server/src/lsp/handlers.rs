@@ -1,12 +1,38 @@
 use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 use tower_lsp::jsonrpc::Result as LspResult;
 use tower_lsp::lsp_types::*;
 use tower_lsp::LanguageServer;
-use tracing::info;
+use tracing::{info, warn};
 
-use super::notifications::{AtMentionedNotification, SelectionChangedNotification, SelectionInfo};
+use super::actions::ClaudeAction;
+use super::completion::{
+    custom_command_completions, file_completions, mention_query, symbol_completions,
+};
+use super::hover::{mention_preview, status_markdown};
+use super::linters::{collect_diagnostics, count_diagnostics, run_linters};
+use super::notifications::{
+    DiagnosticsSummaryNotification, FileDiagnosticsSummary, FileSavedNotification, SelectionInfo,
+    TaskResultNotification,
+};
+use super::outline::{code_lenses, document_symbols, selection_range};
+use super::server::publish_notification;
 use super::server::ClaudeCodeLanguageServer;
-use super::utils::read_text_from_range;
+use super::utils::LineIndex;
+use super::utils::PositionEncoding;
+use crate::claude_commands;
+use crate::config::ClaudeCodeOptions;
+use crate::diagnostics::LINTER_SOURCE;
+use crate::encoding::read_text_file;
+use crate::event_tap::EventTap;
+use crate::i18n::{Locale, Text};
+use crate::mention;
+use crate::state::{self, StatusEvent};
+use crate::symbols;
+
+/// Symbols returned for one `workspace/symbol` query
+const MAX_WORKSPACE_SYMBOLS: usize = 200;
 
 #[tower_lsp::async_trait]
 impl LanguageServer for ClaudeCodeLanguageServer {
@@ -21,11 +47,15 @@ impl LanguageServer for ClaudeCodeLanguageServer {
             info!("Window capabilities: {:?}", window);
             if let Some(show_document) = &window.show_document {
                 info!("  showDocument support: {:?}", show_document);
+                self.show_document
+                    .store(show_document.support, Ordering::SeqCst);
             } else {
                 info!("  showDocument: NOT SUPPORTED");
             }
             if let Some(work_done_progress) = &window.work_done_progress {
                 info!("  workDoneProgress: {}", work_done_progress);
+                self.work_done_progress
+                    .store(*work_done_progress, Ordering::SeqCst);
             }
         } else {
             info!("Window capabilities: NONE");
@@ -40,6 +70,22 @@ impl LanguageServer for ClaudeCodeLanguageServer {
             if let Some(workspace_edit) = &workspace.workspace_edit {
                 info!("  workspaceEdit: {:?}", workspace_edit);
             }
+            if let Some(watched_files) = &workspace.did_change_watched_files {
+                info!("  didChangeWatchedFiles: {:?}", watched_files);
+                self.watched_files.store(
+                    watched_files.dynamic_registration.unwrap_or(false),
+                    Ordering::SeqCst,
+                );
+                self.relative_watch_patterns.store(
+                    watched_files.relative_pattern_support.unwrap_or(false),
+                    Ordering::SeqCst,
+                );
+            }
+            if let Some(configuration) = workspace.configuration {
+                info!("  configuration: {}", configuration);
+                self.workspace_configuration
+                    .store(configuration, Ordering::SeqCst);
+            }
             if let Some(did_change_config) = &workspace.did_change_configuration {
                 info!("  didChangeConfiguration: {:?}", did_change_config);
             }
@@ -80,6 +126,64 @@ impl LanguageServer for ClaudeCodeLanguageServer {
                 info!("Workspace folder: {}", folder.uri);
             }
         }
+        self.state.set_workspace_folders(project_folders(&params));
+
+        let position_encoding = PositionEncoding::negotiate(
+            params
+                .capabilities
+                .general
+                .as_ref()
+                .and_then(|general| general.position_encodings.as_deref()),
+        );
+        info!("Position encoding: {:?}", position_encoding);
+        state::set_position_encoding(position_encoding);
+
+        let mut options =
+            ClaudeCodeOptions::from_initialization_options(params.initialization_options.as_ref());
+        *self.initialization_options.write().await = params.initialization_options.clone();
+        if let Some(mismatch) = options.version_mismatch() {
+            options.startup_warnings.push(mismatch);
+        }
+        info!("Claude Code initialization options: {:?}", options);
+
+        // The extension reports fallbacks taken while resolving the server binary
+        for warning in &options.startup_warnings {
+            warn!("Started in degraded mode: {}", warning);
+        }
+        *self.startup_warnings.write().await = options.startup_warnings.clone();
+        self.state.set_degraded(
+            "extension",
+            (!options.startup_warnings.is_empty()).then(|| options.startup_warnings.join("; ")),
+        );
+        let interrupted = self.state.journal.interrupted();
+        if !interrupted.is_empty() {
+            warn!(
+                "{} interrupted workspace edits in the journal",
+                interrupted.len()
+            );
+            self.startup_warnings.write().await.push(format!(
+                "{} workspace edits were interrupted before all their files were written; \
+                 ask Claude to list them with getInterruptedEdits and restore them with \
+                 restoreCheckpoint",
+                interrupted.len()
+            ));
+        }
+        self.apply_options(&options).await;
+        if let Some(path) = &options.event_tap {
+            match EventTap::start(Path::new(path)) {
+                Ok(tap) => self.state.set_event_tap(tap),
+                Err(e) => warn!("Failed to start the event tap at {}: {}", path, e),
+            }
+        }
+        if let (None, Some(locale)) = (&options.locale, &params.locale) {
+            info!("Using locale {}", locale);
+            self.state.set_locale(Locale::from_tag(locale));
+        }
+
+        // In hybrid mode the WebSocket server waits for these options before binding
+        if let Some(sender) = self.options_sender.lock().await.take() {
+            let _ = sender.send(options);
+        }
 
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
@@ -87,6 +191,7 @@ impl LanguageServer for ClaudeCodeLanguageServer {
                     TextDocumentSyncKind::INCREMENTAL,
                 )),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
                     trigger_characters: Some(vec!["@".to_string()]),
@@ -100,20 +205,43 @@ impl LanguageServer for ClaudeCodeLanguageServer {
                 document_symbol_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: vec![
                         "claude-code.explain".to_string(),
                         "claude-code.improve".to_string(),
                         "claude-code.fix".to_string(),
+                        "claude-code.write-tests".to_string(),
                         "claude-code.at-mention".to_string(),
+                        "claude-code.focus-mode".to_string(),
+                        "claude-code.reportTaskResult".to_string(),
+                        "claude-code.sendDiagnostics".to_string(),
+                        "claude-code.start".to_string(),
                     ],
                     work_done_progress_options: Default::default(),
                 }),
+                position_encoding: Some(position_encoding.kind()),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
+                experimental: Some(serde_json::json!({
+                    "claudeCode": {
+                        "selectionDidChange": true,
+                        "publishDiagnostics": true,
+                        "atMention": true
+                    }
+                })),
                 ..ServerCapabilities::default()
             },
             server_info: Some(ServerInfo {
                 name: "Claude Code Language Server".to_string(),
-                version: Some("0.1.0".to_string()),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
             }),
         })
     }
@@ -124,16 +252,145 @@ impl LanguageServer for ClaudeCodeLanguageServer {
         self.client
             .log_message(MessageType::INFO, "Claude Code Language Server is ready!")
             .await;
+
+        for warning in self.startup_warnings.read().await.iter() {
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    self.state.localize(Text::StartupWarning, &[warning]),
+                )
+                .await;
+        }
+
+        self.set_attached(true).await;
+        self.serve_editor_requests();
+        // Events from before Zed attached were dropped, so it starts from a snapshot
+        self.state.publish_status(if self.state.has_mcp_sessions() {
+            StatusEvent::Connected
+        } else {
+            StatusEvent::Listening
+        });
+        self.pull_configuration().await;
+        self.watch_files().await;
+        self.start_symbol_indexer().await;
+        self.establish_trust().await;
     }
 
     async fn shutdown(&self) -> LspResult<()> {
         info!("LSP Server shutting down...");
+        // Claude's sessions close and the lock file goes now, not once Zed kills the process
+        self.state.start_shutdown();
+        self.set_attached(false).await;
+        self.state.editor.detach();
+        self.stop_symbol_indexer().await;
         Ok(())
     }
 
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Clients that don't push the settings expect them to be pulled
+        if params.settings.is_null() {
+            self.pull_configuration().await;
+        } else if !self.apply_settings(&params.settings).await {
+            info!("Configuration changed without claudeCode settings");
+        }
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let paths = |folders: Vec<WorkspaceFolder>| -> Vec<std::path::PathBuf> {
+            folders
+                .into_iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect()
+        };
+        let added = paths(params.event.added);
+        let removed = paths(params.event.removed);
+        info!(
+            "Workspace folders changed: added {:?}, removed {:?}",
+            added, removed
+        );
+        self.state.change_workspace_folders(added, &removed);
+        self.state.invalidate_tool_cache();
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let changes: Vec<FileEvent> = params
+            .changes
+            .into_iter()
+            .filter(|change| {
+                change
+                    .uri
+                    .to_file_path()
+                    .is_ok_and(|path| !self.state.is_ignored_path(&path))
+            })
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        info!("{} files changed on disk", changes.len());
+        self.state.invalidate_tool_cache();
+
+        // Open documents compare their text with what is on disk now, read off the async
+        // runtime and outside the documents lock
+        let open: Vec<(Url, Option<PathBuf>)> = {
+            let documents = self.state.documents.read().await;
+            changes
+                .iter()
+                .filter(|change| documents.get(&change.uri).is_some())
+                .map(|change| {
+                    let path = match change.typ {
+                        FileChangeType::DELETED => None,
+                        _ => change.uri.to_file_path().ok(),
+                    };
+                    (change.uri.clone(), path)
+                })
+                .collect()
+        };
+        if !open.is_empty() {
+            let on_disk = tokio::task::spawn_blocking(move || {
+                open.into_iter()
+                    .map(|(uri, path)| (uri, path.and_then(|path| read_text_file(path).ok())))
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default();
+            let mut documents = self.state.documents.write().await;
+            for (uri, text) in on_disk {
+                documents.disk_changed(&uri, text);
+            }
+        }
+
+        // The background indexer walks the workspace again and re-reads whatever was
+        // modified
+        self.reindex.notify_one();
+    }
+
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         info!("Document opened: {}", params.text_document.uri);
 
+        // The buffer is UTF-8 over LSP; the encoding on disk is reported alongside selections.
+        // A buffer opened with edits (e.g. after the server restarted) or for a file that
+        // doesn't exist yet starts out dirty. The file is read off the async runtime.
+        let file = match params.text_document.uri.to_file_path() {
+            Ok(path) => tokio::task::spawn_blocking(move || read_text_file(path))
+                .await
+                .ok()
+                .and_then(Result::ok),
+            Err(_) => None,
+        };
+        let (encoding, dirty) = match file {
+            Some(file) => (file.encoding, file.text != params.text_document.text),
+            None => (Default::default(), true),
+        };
+        self.state.documents.write().await.open(
+            params.text_document.uri.clone(),
+            params.text_document.language_id.clone(),
+            params.text_document.version,
+            &params.text_document.text,
+            encoding,
+            dirty,
+        );
+        self.state.invalidate_tool_cache();
+
         self.client
             .log_message(
                 MessageType::INFO,
@@ -144,14 +401,177 @@ impl LanguageServer for ClaudeCodeLanguageServer {
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         info!("Document changed: {}", params.text_document.uri);
+
+        // Typing a space or new line right after a mention completes it
+        let completed: Vec<Position> = params
+            .content_changes
+            .iter()
+            .filter(|change| change.text.starts_with(char::is_whitespace))
+            .filter_map(|change| change.range.map(|range| range.start))
+            .collect();
+        self.state.documents.write().await.apply_changes(
+            &params.text_document.uri,
+            params.text_document.version,
+            params.content_changes,
+        );
+        self.state.invalidate_tool_cache();
+        if !completed.is_empty() && self.options.read().await.auto_mention {
+            self.mention_typed(&params.text_document.uri, &completed)
+                .await;
+        }
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
         info!("Document saved: {}", params.text_document.uri);
+
+        let uri = params.text_document.uri;
+        let options = self.options.read().await.clone();
+        self.state.documents.write().await.mark_saved(&uri);
+        self.state.invalidate_tool_cache();
+
+        // Linters, the symbol index and Claude all work with files on disk
+        let Ok(path) = uri.to_file_path() else {
+            info!("Not a file, skipping save handling for {}", uri);
+            return;
+        };
+        let file_path = path.to_string_lossy().into_owned();
+
+        let indexed = self
+            .state
+            .symbol_index
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|index| index.contains_path(&path));
+        // Copy the buffer out so the documents lock isn't held while parsing or waiting
+        // for the index's lock
+        let saved = if indexed {
+            self.state
+                .documents
+                .read()
+                .await
+                .get(&uri)
+                .map(|document| (document.language_id.clone(), document.text.clone()))
+        } else {
+            None
+        };
+        if let Some((language_id, text)) = saved {
+            let parse_path = path.clone();
+            let parsed = tokio::task::spawn_blocking(move || {
+                symbols::IndexedFile::parse(&parse_path, &language_id, &text.to_string())
+            })
+            .await;
+            match parsed {
+                Ok(Some(indexed)) => {
+                    if let Some(index) = self.state.symbol_index.write().await.as_mut() {
+                        index.update_file(&path, indexed);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to index the symbols of {}: {}", file_path, e),
+            }
+        }
+
+        // Let Claude know promptly that edits (possibly its own suggestions) were saved
+        if options.notify_on_save {
+            let saved_notification = FileSavedNotification {
+                file_path: file_path.clone(),
+                file_url: uri.to_string(),
+            };
+            match serde_json::to_value(saved_notification) {
+                Ok(params) => self.send_notification("file_saved", params).await,
+                Err(e) => warn!("Failed to serialize file_saved for {}: {}", file_path, e),
+            }
+        }
+
+        // Re-run configured linters in the background so the save isn't blocked.
+        // Linters execute commands, so untrusted workspaces never run them.
+        if !options.linters.is_empty() && !self.state.is_trusted() {
+            info!("Workspace not trusted, skipping linters for {}", file_path);
+        } else if !options.linters.is_empty() {
+            let client = self.client.clone();
+            let worktree = self.worktree.clone();
+            let sender = self.notification_sender.clone();
+            let state = self.state.clone();
+            let lint_counts = self.lint_counts.clone();
+            tokio::spawn(async move {
+                let runs = run_linters(&options.linters, &file_path, worktree.as_ref()).await;
+                for run in &runs {
+                    let message_type = if run.exit_code == Some(0) {
+                        MessageType::INFO
+                    } else {
+                        MessageType::WARNING
+                    };
+                    client
+                        .log_message(
+                            message_type,
+                            format!(
+                                "Linter {} finished for {} (exit code {:?})",
+                                run.command, file_path, run.exit_code
+                            ),
+                        )
+                        .await;
+                }
+                if runs.is_empty() {
+                    return;
+                }
+
+                let mut linted = Vec::new();
+                {
+                    let mut stored = state.diagnostics.write().await;
+                    for (path, diagnostics) in
+                        collect_diagnostics(&runs, &file_path, worktree.as_ref())
+                    {
+                        if let Ok(uri) = Url::from_file_path(&path) {
+                            stored.set(uri.clone(), LINTER_SOURCE, diagnostics);
+                            linted.push(uri);
+                        }
+                    }
+                }
+                state.invalidate_tool_cache();
+                // Show the findings in Zed too, where a clean run clears the previous ones
+                for uri in linted {
+                    state.show_diagnostics(uri).await;
+                }
+
+                // Only files whose counts moved are worth Claude's attention
+                let counts = count_diagnostics(&runs, &file_path, worktree.as_ref());
+                let mut files: Vec<_> = {
+                    let mut previous = lint_counts.lock().await;
+                    counts
+                        .into_iter()
+                        .filter(|(path, counts)| {
+                            previous.insert(path.clone(), *counts) != Some(*counts)
+                        })
+                        .map(|(file_path, counts)| FileDiagnosticsSummary { file_path, counts })
+                        .collect()
+                };
+                if files.is_empty() {
+                    return;
+                }
+                files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+                match serde_json::to_value(DiagnosticsSummaryNotification { files }) {
+                    Ok(params) => publish_notification(
+                        sender.as_deref(),
+                        &state,
+                        "diagnostics_summary",
+                        params,
+                    ),
+                    Err(e) => warn!("Failed to serialize diagnostics_summary: {}", e),
+                }
+            });
+        }
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
         info!("Document closed: {}", params.text_document.uri);
+
+        self.state
+            .documents
+            .write()
+            .await
+            .close(&params.text_document.uri);
+        self.state.invalidate_tool_cache();
     }
 
     async fn hover(&self, params: HoverParams) -> LspResult<Option<Hover>> {
@@ -161,17 +581,137 @@ impl LanguageServer for ClaudeCodeLanguageServer {
             position.line, position.character
         );
 
+        let uri = &params.text_document_position_params.text_document.uri;
+        self.track_cursor(uri, position).await;
+
+        // Hovering an `@path` mention previews the file, anything else shows the status
+        let token = self
+            .state
+            .documents
+            .read()
+            .await
+            .get(uri)
+            .and_then(|document| document.token_at(position));
+        let worktree = self.worktree.clone();
+        let preview = match token {
+            Some(token) => {
+                tokio::task::spawn_blocking(move || mention_preview(&token, worktree.as_deref()))
+                    .await
+                    .ok()
+                    .flatten()
+            }
+            None => None,
+        };
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: preview.unwrap_or_else(|| status_markdown(&self.state)),
+            }),
+            range: None,
+        }))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> LspResult<Option<Vec<DocumentHighlight>>> {
+        // Zed requests highlights whenever the cursor moves, so this tracks the cursor
+        let position = params.text_document_position_params.position;
+        let uri = &params.text_document_position_params.text_document.uri;
+        self.track_cursor(uri, position).await;
+
         Ok(None)
     }
 
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> LspResult<Option<DocumentSymbolResponse>> {
+        let Some((language_id, text)) = self
+            .state
+            .documents
+            .read()
+            .await
+            .get(&params.text_document.uri)
+            .map(|document| (document.language_id.clone(), document.text.to_string()))
+        else {
+            return Ok(None);
+        };
+        // Parsing a large file would stall a runtime thread
+        let outline_language = language_id.clone();
+        let symbols = tokio::task::spawn_blocking(move || {
+            let outline = symbols::outline(&outline_language, &text)?;
+            Some(document_symbols(&outline, &LineIndex::new(&text)))
+        })
+        .await
+        .ok()
+        .flatten();
+        if symbols.is_none() {
+            info!(
+                "No outline for {} ({})",
+                params.text_document.uri, language_id
+            );
+        }
+        Ok(symbols.map(DocumentSymbolResponse::Nested))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> LspResult<Option<Vec<SymbolInformation>>> {
+        let index = self.state.symbol_index.read().await;
+        let Some(index) = index.as_ref() else {
+            info!("Workspace symbols requested before the index was built");
+            return Ok(None);
+        };
+        let symbols: Vec<SymbolInformation> = index
+            .search(&params.query, MAX_WORKSPACE_SYMBOLS)
+            .into_iter()
+            .filter_map(|(file, symbol)| {
+                #[allow(deprecated)]
+                Some(SymbolInformation {
+                    name: symbol.name.clone(),
+                    kind: symbol.kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: Url::from_file_path(index.root().join(file)).ok()?,
+                        range: symbol.lsp_range(),
+                    },
+                    container_name: symbol.container.clone(),
+                })
+            })
+            .collect();
+        info!(
+            "Workspace symbols for {:?}: {}",
+            params.query,
+            symbols.len()
+        );
+        Ok(Some(symbols))
+    }
+
     async fn completion(&self, params: CompletionParams) -> LspResult<Option<CompletionResponse>> {
         let position = params.text_document_position.position;
         info!(
             "Completion requested at {}:{}",
             position.line, position.character
         );
+        let uri = &params.text_document_position.text_document.uri;
+        self.state
+            .documents
+            .write()
+            .await
+            .touch(uri, [position.line]);
 
-        let completions = vec![
+        let mention = self
+            .state
+            .documents
+            .read()
+            .await
+            .get(uri)
+            .and_then(|document| mention_query(document, position));
+        let mut completions = vec![
             CompletionItem {
                 label: "@claude explain".to_string(),
                 kind: Some(CompletionItemKind::TEXT),
@@ -203,6 +743,32 @@ impl LanguageServer for ClaudeCodeLanguageServer {
                 ..Default::default()
             },
         ];
+        // The project's own commands from `.claude/commands`, read afresh so new ones show up
+        if let Some(worktree) = self.worktree.clone() {
+            let commands =
+                tokio::task::spawn_blocking(move || claude_commands::custom_commands(&worktree))
+                    .await
+                    .unwrap_or_default();
+            completions.extend(custom_command_completions(&commands));
+        }
+
+        if let Some((start, query)) = mention {
+            // Typing `@` offers the workspace's files and symbols along with the matching
+            // commands
+            completions.retain(|item| item.label[1..].starts_with(query.as_str()));
+            if let Some(index) = self.file_index().await.as_ref() {
+                completions.extend(file_completions(index, &query, start, position));
+            }
+            if let Some(index) = self.state.symbol_index.read().await.as_ref() {
+                completions.extend(symbol_completions(index, &query, start, position));
+            }
+            info!("Offering {} completions for @{}", completions.len(), query);
+            // The client asks again as the query grows, narrowing the matches
+            return Ok(Some(CompletionResponse::List(CompletionList {
+                is_incomplete: true,
+                items: completions,
+            })));
+        }
 
         Ok(Some(CompletionResponse::Array(completions)))
     }
@@ -210,74 +776,174 @@ impl LanguageServer for ClaudeCodeLanguageServer {
     async fn code_action(&self, params: CodeActionParams) -> LspResult<Option<CodeActionResponse>> {
         info!("Code action requested for range: {:?}", params.range);
 
-        // Send selection_changed notification when code action is requested
-        let selected_text = read_text_from_range(params.text_document.uri.path(), params.range);
-        let selection_notification = SelectionChangedNotification {
-            text: selected_text,
-            file_path: params.text_document.uri.path().to_string(),
-            file_url: params.text_document.uri.to_string(),
-            selection: SelectionInfo {
-                start: params.range.start,
-                end: params.range.end,
-                is_empty: params.range.start == params.range.end,
-            },
+        self.state.documents.write().await.touch(
+            &params.text_document.uri,
+            [params.range.start.line, params.range.end.line],
+        );
+
+        // Send selection_changed notification when code action is requested, unless the
+        // client pushes real selections
+        if self.infers_selection().await {
+            info!(
+                "Sending selection_changed notification for range: {:?}",
+                params.range
+            );
+            self.publish_selection(
+                params.text_document.uri.clone(),
+                params.range,
+                SelectionInfo {
+                    start: params.range.start,
+                    end: params.range.end,
+                    is_empty: params.range.start == params.range.end,
+                },
+            );
+        }
+
+        // With diagnostics under the cursor, offer to send them to Claude for a fix instead
+        // of the generic explanation
+        let diagnostics = params.context.diagnostics;
+        let mut actions = if diagnostics.is_empty() {
+            [
+                ("Explain with Claude", "claude-code.explain"),
+                ("Improve with Claude", "claude-code.improve"),
+            ]
+            .into_iter()
+            .map(|(title, command)| {
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: title.to_string(),
+                    kind: Some(CodeActionKind::REFACTOR),
+                    diagnostics: None,
+                    edit: None,
+                    command: Some(Command {
+                        title: title.to_string(),
+                        command: command.to_string(),
+                        arguments: Some(vec![serde_json::json!({
+                            "uri": params.text_document.uri,
+                            "range": params.range
+                        })]),
+                    }),
+                    is_preferred: Some(false),
+                    disabled: None,
+                    data: None,
+                })
+            })
+            .collect()
+        } else {
+            vec![CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Fix with Claude".to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(diagnostics.clone()),
+                edit: None,
+                command: Some(Command {
+                    title: "Fix with Claude".to_string(),
+                    command: "claude-code.fix".to_string(),
+                    arguments: Some(vec![serde_json::json!({
+                        "uri": params.text_document.uri,
+                        "diagnostics": diagnostics
+                    })]),
+                }),
+                is_preferred: Some(false),
+                disabled: None,
+                data: None,
+            })]
         };
 
-        info!(
-            "Sending selection_changed notification for range: {:?}",
-            params.range
-        );
-        self.send_notification(
-            "selection_changed",
-            serde_json::to_value(selection_notification).unwrap(),
-        )
-        .await;
-
-        let actions = vec![CodeActionOrCommand::CodeAction(CodeAction {
-            title: "Explain with Claude".to_string(),
-            kind: Some(CodeActionKind::REFACTOR),
-            diagnostics: None,
-            edit: None,
-            command: None,
-            is_preferred: Some(false),
-            disabled: None,
-            data: Some(serde_json::json!({
-                "action": "explain",
-                "uri": params.text_document.uri,
-                "range": params.range
-            })),
-        })];
+        // Until the WebSocket server held back by `autoStart` is started, offer to start it
+        if self.state.is_websocket_deferred() {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Start Claude Code".to_string(),
+                kind: Some(CodeActionKind::SOURCE),
+                diagnostics: None,
+                edit: None,
+                command: Some(Command {
+                    title: "Start Claude Code".to_string(),
+                    command: "claude-code.start".to_string(),
+                    arguments: None,
+                }),
+                is_preferred: Some(false),
+                disabled: None,
+                data: None,
+            }));
+        }
+
+        // Share the whole file through the at-mention command, whatever is selected
+        let file_path = self
+            .state
+            .documents
+            .read()
+            .await
+            .get(&params.text_document.uri)
+            .map(|document| document.file_path());
+        if let Some(file_path) = file_path {
+            if !self.state.is_ignored_path(Path::new(&file_path)) {
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: "Add file to Claude context".to_string(),
+                    kind: Some(CodeActionKind::SOURCE),
+                    diagnostics: None,
+                    edit: None,
+                    command: Some(Command {
+                        title: "Add file to Claude context".to_string(),
+                        command: "claude-code.at-mention".to_string(),
+                        // Without a line range the whole file is mentioned
+                        arguments: Some(vec![serde_json::json!({
+                            "filePath": file_path
+                        })]),
+                    }),
+                    is_preferred: Some(false),
+                    disabled: None,
+                    data: None,
+                }));
+            }
+        }
 
         Ok(Some(actions))
     }
 
+    async fn code_lens(&self, params: CodeLensParams) -> LspResult<Option<Vec<CodeLens>>> {
+        if !self.options.read().await.code_lens {
+            return Ok(None);
+        }
+        let uri = params.text_document.uri;
+        let Some((file_path, language_id, text)) =
+            self.state.documents.read().await.get(&uri).map(|document| {
+                (
+                    document.file_path(),
+                    document.language_id.clone(),
+                    document.text.to_string(),
+                )
+            })
+        else {
+            return Ok(None);
+        };
+        if self.state.is_ignored_path(Path::new(&file_path)) {
+            return Ok(None);
+        }
+        // Parsing a large file would stall a runtime thread
+        let lenses = tokio::task::spawn_blocking(move || {
+            let outline = symbols::outline(&language_id, &text)?;
+            Some(code_lenses(&outline, &LineIndex::new(&text), &uri))
+        })
+        .await
+        .ok()
+        .flatten();
+        Ok(lenses)
+    }
+
     async fn execute_command(&self, params: ExecuteCommandParams) -> LspResult<Option<Value>> {
         info!("Execute command: {}", params.command);
+        let _progress = self
+            .state
+            .editor
+            .progress(format!("Claude Code: {}", params.command));
 
         match params.command.as_str() {
-            "claude-code.explain" => {
-                self.client
-                    .show_message(
-                        MessageType::INFO,
-                        "Claude Code: Explain command executed (not yet implemented)",
-                    )
-                    .await;
-            }
-            "claude-code.improve" => {
-                self.client
-                    .show_message(
-                        MessageType::INFO,
-                        "Claude Code: Improve command executed (not yet implemented)",
-                    )
-                    .await;
-            }
-            "claude-code.fix" => {
-                self.client
-                    .show_message(
-                        MessageType::INFO,
-                        "Claude Code: Fix command executed (not yet implemented)",
-                    )
-                    .await;
+            "claude-code.explain"
+            | "claude-code.improve"
+            | "claude-code.fix"
+            | "claude-code.write-tests" => {
+                if let Some(action) = ClaudeAction::from_command(&params.command) {
+                    self.request_action(action, params.arguments.first()).await;
+                }
             }
             "claude-code.at-mention" => {
                 info!(
@@ -294,44 +960,98 @@ impl LanguageServer for ClaudeCodeLanguageServer {
                             .get("filePath")
                             .and_then(|v| v.as_str())
                             .unwrap_or("");
-                        let line_start = mention_data
-                            .get("lineStart")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(0) as u32;
-                        let line_end = mention_data
-                            .get("lineEnd")
-                            .and_then(|v| v.as_u64())
-                            .unwrap_or(0) as u32;
-
-                        let at_mention_notification = AtMentionedNotification {
-                            file_path: file_path.to_string(),
-                            line_start,
-                            line_end,
-                        };
-
-                        self.send_notification(
-                            "at_mentioned",
-                            serde_json::to_value(at_mention_notification).unwrap(),
+                        let (line_start, line_end) = mention::requested_lines(
+                            &self.state,
+                            Path::new(file_path),
+                            &mention_data,
                         )
                         .await;
 
+                        mention::mention(&self.state, file_path, line_start, line_end).await;
+
                         self.client
                             .show_message(
                                 MessageType::INFO,
-                                format!(
-                                    "At-mention sent for {}:{}-{}",
-                                    file_path, line_start, line_end
+                                self.state.localize(
+                                    Text::AtMentionSent,
+                                    &[&file_path, &line_start, &line_end],
                                 ),
                             )
                             .await;
                     }
                 }
             }
+            "claude-code.focus-mode" => {
+                // An optional `{"enabled": bool}` argument sets the mode, otherwise it toggles
+                let enabled = params
+                    .arguments
+                    .first()
+                    .and_then(|args| args.get("enabled"))
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(!self.state.is_focus_mode());
+                self.state.set_focus_mode(enabled);
+
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        self.state.localize(
+                            if enabled {
+                                Text::FocusModeOn
+                            } else {
+                                Text::FocusModeOff
+                            },
+                            &[],
+                        ),
+                    )
+                    .await;
+            }
+            "claude-code.sendDiagnostics" => {
+                self.send_diagnostics(params.arguments.first()).await;
+            }
+            "claude-code.start" => {
+                let text = if self.state.start_websocket() {
+                    Text::WebSocketStarting
+                } else {
+                    Text::WebSocketAlreadyStarted
+                };
+                self.client
+                    .show_message(MessageType::INFO, self.state.localize(text, &[]))
+                    .await;
+            }
+            "claude-code.reportTaskResult" => {
+                let task = params
+                    .arguments
+                    .first()
+                    .and_then(TaskResultNotification::from_arguments)
+                    .ok_or_else(|| {
+                        tower_lsp::jsonrpc::Error::invalid_params(
+                            "expected {command, exitCode, output} arguments",
+                        )
+                    })?;
+                info!(
+                    "Task {} finished with exit code {:?}",
+                    task.command, task.exit_code
+                );
+                let command = task.command.clone();
+                match serde_json::to_value(task) {
+                    Ok(params) => {
+                        self.send_notification("task_result", params).await;
+                        self.client
+                            .log_message(
+                                MessageType::INFO,
+                                format!("Sent the result of {} to Claude", command),
+                            )
+                            .await;
+                    }
+                    Err(e) => warn!("Failed to serialize task_result for {}: {}", command, e),
+                }
+            }
             _ => {
                 self.client
                     .show_message(
                         MessageType::WARNING,
-                        format!("Unknown command: {}", params.command),
+                        self.state
+                            .localize(Text::UnknownCommand, &[&params.command]),
                     )
                     .await;
             }
@@ -349,57 +1069,89 @@ impl LanguageServer for ClaudeCodeLanguageServer {
             params.positions.len()
         );
 
-        // For each position, create a selection range and notify about the selection
+        self.state.documents.write().await.touch(
+            &params.text_document.uri,
+            params.positions.iter().map(|position| position.line),
+        );
+
+        // Each position expands through the syntax nodes around it; documents without a
+        // grammar only get the position itself
+        let document = self
+            .state
+            .documents
+            .read()
+            .await
+            .get(&params.text_document.uri)
+            .map(|document| {
+                (
+                    document.language_id.clone(),
+                    document.text.to_string(),
+                    params
+                        .positions
+                        .iter()
+                        .map(|position| document.offset_at(*position))
+                        .collect::<Vec<_>>(),
+                )
+            });
+        let infers_selection = self.infers_selection().await;
         let mut ranges = Vec::new();
 
-        for position in &params.positions {
+        for (i, position) in params.positions.iter().enumerate() {
             info!("Selection at {}:{}", position.line, position.character);
 
-            // Create a basic selection range (this would normally be more sophisticated)
-            let range = Range {
-                start: *position,
-                end: Position {
-                    line: position.line,
-                    character: position.character + 1,
-                },
-            };
-
-            ranges.push(SelectionRange {
-                range,
-                parent: None,
-            });
-
-            // Send selection_changed notification
-            let selection_range = Range {
-                start: *position,
-                end: Position {
-                    line: position.line,
-                    character: position.character + 1,
-                },
-            };
-            let selected_text =
-                read_text_from_range(params.text_document.uri.path(), selection_range);
-            let selection_notification = SelectionChangedNotification {
-                text: selected_text,
-                file_path: params.text_document.uri.path().to_string(),
-                file_url: params.text_document.uri.to_string(),
-                selection: SelectionInfo {
-                    start: *position,
-                    end: Position {
-                        line: position.line,
-                        character: position.character + 1,
+            let selection = document
+                .as_ref()
+                .and_then(|(language_id, text, offsets)| {
+                    let spans = symbols::enclosing_spans(language_id, text, offsets[i])?;
+                    selection_range(&spans, text)
+                })
+                .unwrap_or(SelectionRange {
+                    range: Range {
+                        start: *position,
+                        end: *position,
                     },
-                    is_empty: true,
-                },
-            };
+                    parent: None,
+                });
+            let range = selection.range;
+            ranges.push(selection);
 
-            self.send_notification(
-                "selection_changed",
-                serde_json::to_value(selection_notification).unwrap(),
-            )
-            .await;
+            // Send selection_changed notification for the innermost span, unless the client
+            // pushes real selections
+            if infers_selection {
+                self.publish_selection(
+                    params.text_document.uri.clone(),
+                    range,
+                    SelectionInfo {
+                        start: range.start,
+                        end: range.end,
+                        is_empty: range.start == range.end,
+                    },
+                );
+            }
         }
 
         Ok(Some(ranges))
     }
 }
+
+/// Folders of a project with several worktrees: those the extension lists in the
+/// initialization options, then those Zed sends in `initialize`
+fn project_folders(params: &InitializeParams) -> Vec<std::path::PathBuf> {
+    let from_options = params
+        .initialization_options
+        .as_ref()
+        .and_then(|options| options.get("workspaceFolders"))
+        .and_then(|folders| folders.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|folder| folder.get("uri")?.as_str()?.parse::<Url>().ok());
+    let from_client = params
+        .workspace_folders
+        .iter()
+        .flatten()
+        .map(|folder| folder.uri.clone());
+    from_options
+        .chain(from_client)
+        .filter_map(|uri| uri.to_file_path().ok())
+        .collect()
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use crate::encoding::read_text_file;
+use crate::i18n::Text;
+use crate::languages::language_id_for_path;
+use crate::mention::{resolve_mention, MENTION_PUNCTUATION};
+use crate::state::SharedState;
+
+/// Lines of a mentioned file shown in its hover preview
+const PREVIEW_LINES: u32 = 20;
+
+/// Markdown describing whether Claude is connected, on which port, and what was last
+/// at-mentioned
+pub fn status_markdown(state: &SharedState) -> String {
+    let status = match state.websocket_port() {
+        Some(port) if state.has_mcp_sessions() => state.localize(Text::HoverConnected, &[&port]),
+        Some(port) => state.localize(Text::HoverWaiting, &[&port]),
+        None => state.localize(Text::HoverNoServer, &[]),
+    };
+    let mut markdown = format!("**{}**", status);
+
+    if let Some(mention) = state.last_at_mention() {
+        let field = |key: &str| mention.get(key).cloned().unwrap_or_default();
+        let location = format!(
+            "`{}:{}-{}`",
+            field("filePath").as_str().unwrap_or_default(),
+            field("lineStart"),
+            field("lineEnd")
+        );
+        markdown.push_str("\n\n");
+        markdown.push_str(&state.localize(Text::HoverLastMention, &[&location]));
+    }
+    markdown
+}
+
+/// A preview of the file an `@path` or `@path:START-END` token refers to, with relative
+/// paths resolved against the worktree. `None` unless the token names a readable file.
+pub fn mention_preview(token: &str, worktree: Option<&Path>) -> Option<String> {
+    let (_, mention) = token.split_once('@')?;
+    let (path, lines) = resolve_mention(mention, worktree)?;
+    let mention = mention.trim_end_matches(MENTION_PUNCTUATION);
+    let file = read_text_file(&path).ok()?;
+
+    let (first, last) = lines.unwrap_or((1, PREVIEW_LINES));
+    let last = last.min(first.saturating_add(PREVIEW_LINES - 1));
+    let preview: Vec<&str> = file
+        .text
+        .lines()
+        .skip(first as usize - 1)
+        .take((last - first + 1) as usize)
+        .collect();
+    let total_lines = file.text.lines().count();
+
+    Some(format!(
+        "**@{}** ({}-{} of {} lines)\n\n```{}\n{}\n```",
+        mention,
+        first,
+        (first as usize + preview.len()).saturating_sub(1),
+        total_lines,
+        language_id_for_path(&path).unwrap_or_default(),
+        preview.join("\n")
+    ))
+}
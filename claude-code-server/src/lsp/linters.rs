@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use tracing::{debug, info, warn};
+
+use super::notifications::DiagnosticCounts;
+use crate::config::{LinterConfig, FILE_PLACEHOLDER};
+
+/// Maximum time a single linter run may take before it is abandoned
+const LINTER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Outcome of running one linter against a file
+#[derive(Debug, Clone)]
+pub struct LintRun {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+impl LinterConfig {
+    fn applies_to(&self, file_path: &Path) -> bool {
+        file_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e == ext))
+    }
+
+    fn build_args(&self, file_path: &str) -> Vec<String> {
+        let mut args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| arg.replace(FILE_PLACEHOLDER, file_path))
+            .collect();
+        if !self.args.iter().any(|arg| arg.contains(FILE_PLACEHOLDER)) {
+            args.push(file_path.to_string());
+        }
+        args
+    }
+}
+
+/// Run every configured linter that applies to the file, in the worktree directory
+pub async fn run_linters(
+    linters: &[LinterConfig],
+    file_path: &str,
+    worktree: Option<&PathBuf>,
+) -> Vec<LintRun> {
+    let mut runs = Vec::new();
+
+    for linter in linters
+        .iter()
+        .filter(|l| l.applies_to(Path::new(file_path)))
+    {
+        let args = linter.build_args(file_path);
+        info!("Running linter: {} {:?}", linter.command, args);
+
+        let mut command = Command::new(&linter.command);
+        command
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(dir) = worktree {
+            command.current_dir(dir);
+        }
+
+        let run = match tokio::time::timeout(LINTER_TIMEOUT, command.output()).await {
+            Ok(Ok(output)) => {
+                let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                LintRun {
+                    command: linter.command.clone(),
+                    exit_code: output.status.code(),
+                    output: text,
+                }
+            }
+            Ok(Err(e)) => {
+                warn!("Failed to run linter {}: {}", linter.command, e);
+                continue;
+            }
+            Err(_) => {
+                warn!(
+                    "Linter {} timed out after {:?}",
+                    linter.command, LINTER_TIMEOUT
+                );
+                continue;
+            }
+        };
+
+        debug!("Linter {} output:\n{}", run.command, run.output);
+        runs.push(run);
+    }
+
+    runs
+}
+
+/// Count the errors and warnings reported by linter runs for `file_path`, per file. Lines
+/// starting with `path:line` count towards that path, others towards `file_path`, which is
+/// always present so a clean run reports zero counts.
+pub fn count_diagnostics(
+    runs: &[LintRun],
+    file_path: &str,
+    worktree: Option<&PathBuf>,
+) -> HashMap<String, DiagnosticCounts> {
+    let mut counts = HashMap::from([(file_path.to_string(), DiagnosticCounts::default())]);
+    for line in runs.iter().flat_map(|run| run.output.lines()) {
+        let Some(is_error) = line_severity(line) else {
+            continue;
+        };
+        let path = line_location(line, worktree).unwrap_or_else(|| file_path.to_string());
+        let entry = counts.entry(path).or_default();
+        if is_error {
+            entry.errors += 1;
+        } else {
+            entry.warnings += 1;
+        }
+    }
+    counts
+}
+
+/// The errors and warnings reported by linter runs for `file_path` as diagnostics, per file,
+/// attributed like in [`count_diagnostics`]. Lines without a location are reported at the
+/// start of `file_path`, which is always present so a clean run clears its diagnostics.
+pub fn collect_diagnostics(
+    runs: &[LintRun],
+    file_path: &str,
+    worktree: Option<&PathBuf>,
+) -> HashMap<String, Vec<Diagnostic>> {
+    let mut diagnostics = HashMap::from([(file_path.to_string(), Vec::new())]);
+    for run in runs {
+        for line in run.output.lines() {
+            let Some(is_error) = line_severity(line) else {
+                continue;
+            };
+            let path = line_location(line, worktree).unwrap_or_else(|| file_path.to_string());
+            let position = line_position(line).unwrap_or_default();
+            diagnostics.entry(path).or_default().push(Diagnostic {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                severity: Some(if is_error {
+                    DiagnosticSeverity::ERROR
+                } else {
+                    DiagnosticSeverity::WARNING
+                }),
+                source: Some(run.command.clone()),
+                message: line.trim().to_string(),
+                ..Diagnostic::default()
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Whether a line of linter output reports an error (`true`) or a warning (`false`), going by
+/// the first severity word on it
+fn line_severity(line: &str) -> Option<bool> {
+    line.split(|c: char| !c.is_ascii_alphabetic())
+        .find_map(|word| match word.to_ascii_lowercase().as_str() {
+            "error" => Some(true),
+            "warning" | "warn" => Some(false),
+            _ => None,
+        })
+}
+
+/// The file a `path:line[:column]: ...` line of linter output refers to
+fn line_location(line: &str, worktree: Option<&PathBuf>) -> Option<String> {
+    let (path, rest) = line.trim_start().split_once(':')?;
+    if path.is_empty()
+        || path.contains(char::is_whitespace)
+        || !rest.starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let path = match worktree {
+        Some(root) if Path::new(path).is_relative() => root.join(path),
+        _ => PathBuf::from(path),
+    };
+    Some(path.to_string_lossy().into_owned())
+}
+
+/// The 0-based position of a `path:line[:column]: ...` line of linter output, which gives
+/// them 1-based
+fn line_position(line: &str) -> Option<Position> {
+    let mut parts = line.trim_start().split(':').skip(1);
+    let line_number: u32 = parts.next()?.parse().ok()?;
+    let column: u32 = parts
+        .next()
+        .and_then(|column| column.trim().parse().ok())
+        .unwrap_or(1);
+    Some(Position {
+        line: line_number.saturating_sub(1),
+        character: column.saturating_sub(1),
+    })
+}
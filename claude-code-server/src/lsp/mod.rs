@@ -1,9 +1,21 @@
+mod actions;
+mod completion;
 mod handlers;
+mod hover;
+mod linters;
 mod notifications;
+mod outline;
+mod progress;
 mod server;
 mod utils;
 mod watchdog;
 
 // Re-export public items
-pub use notifications::NotificationReceiver;
+pub use notifications::{
+    AtMentionedNotification, AtMentionedRangesNotification, ClaudeStatusParams,
+    FileSavedNotification, JsonRpcNotification, NotificationReceiver, NotificationSender,
+    SelectionChangedNotification,
+};
+pub(crate) use server::publish_notification;
+pub use utils::{position_at, read_text_from_range, PositionEncoding};
 pub use watchdog::{run_lsp_server, run_lsp_server_with_notifications};
@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
-use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::{Diagnostic, Position, Range, TextDocumentIdentifier};
+
+use crate::encoding::TextEncoding;
 
 /// Notification sent when the user's selection changes in the editor
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SelectionChangedNotification {
     pub text: String,
     #[serde(rename = "filePath")]
@@ -11,9 +13,12 @@ pub struct SelectionChangedNotification {
     #[serde(rename = "fileUrl")]
     pub file_url: String,
     pub selection: SelectionInfo,
+    /// Encoding the file was transcoded from; omitted for UTF-8 files
+    #[serde(default, skip_serializing_if = "TextEncoding::is_utf8")]
+    pub encoding: TextEncoding,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SelectionInfo {
     pub start: Position,
     pub end: Position,
@@ -21,6 +26,83 @@ pub struct SelectionInfo {
     pub is_empty: bool,
 }
 
+/// Custom LSP notification a client sends when the editor selection actually changes,
+/// instead of the server inferring it from other requests
+pub const SELECTION_DID_CHANGE: &str = "$/claudeCode/selectionDidChange";
+
+/// Parameters of `$/claudeCode/selectionDidChange`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionDidChangeParams {
+    pub text_document: TextDocumentIdentifier,
+    /// Current selections, primary first; an empty range is a cursor
+    pub selections: Vec<Range>,
+}
+
+/// Custom LSP notification a client sends to forward the diagnostics other language servers
+/// published for a file, with `textDocument/publishDiagnostics` parameters
+pub const PUBLISH_DIAGNOSTICS: &str = "$/claudeCode/publishDiagnostics";
+
+/// Custom LSP request sharing a file or a range of it with Claude, for keymaps and tasks
+/// that cannot build `workspace/executeCommand` arguments
+pub const AT_MENTION: &str = "claude/atMention";
+
+/// Parameters of `claude/atMention`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AtMentionParams {
+    pub text_document: TextDocumentIdentifier,
+    /// Range to share; the whole file when omitted
+    #[serde(default)]
+    pub range: Option<Range>,
+}
+
+/// Result of `claude/atMention`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AtMentionResult {
+    pub success: bool,
+    /// What was shared, with 0-based lines as in `at_mentioned`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mention: Option<AtMentionedNotification>,
+    /// Why nothing was shared
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AtMentionResult {
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            mention: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Custom LSP notification telling Zed about the WebSocket server: that it is listening, a
+/// Claude session connected or disconnected, or a connection failed authentication
+pub enum ClaudeStatus {}
+
+impl tower_lsp::lsp_types::notification::Notification for ClaudeStatus {
+    type Params = ClaudeStatusParams;
+    const METHOD: &'static str = "claude/status";
+}
+
+/// Parameters of `claude/status`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeStatusParams {
+    /// `listening`, `connected`, `disconnected` or `authFailed`
+    pub event: String,
+    /// Port Claude connects to
+    pub port: u16,
+    /// Connected Claude sessions
+    pub sessions: usize,
+    /// Human-readable status, e.g. "Claude Code: connected on port 59801"
+    pub message: String,
+}
+
 /// Notification sent when the user @mentions a file or code range
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AtMentionedNotification {
@@ -32,6 +114,146 @@ pub struct AtMentionedNotification {
     pub line_end: u32,
 }
 
+/// Notification sharing several file ranges at once, e.g. every failing test location,
+/// instead of one `at_mentioned` per range
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AtMentionedRangesNotification {
+    pub mentions: Vec<AtMentionedNotification>,
+}
+
+/// Error and warning counts the on-save linters reported for a file
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiagnosticCounts {
+    pub errors: u32,
+    pub warnings: u32,
+}
+
+/// Per-file diagnostic counts of a file whose counts changed in a linter pass
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileDiagnosticsSummary {
+    #[serde(rename = "filePath")]
+    pub file_path: String,
+    #[serde(flatten)]
+    pub counts: DiagnosticCounts,
+}
+
+/// Notification sent after an on-save linter pass changed a file's diagnostic counts, so
+/// Claude can decide whether to fetch full diagnostics
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiagnosticsSummaryNotification {
+    pub files: Vec<FileDiagnosticsSummary>,
+}
+
+/// Lines of a task's output kept for Claude; builds and test runs put the summary last
+const MAX_TASK_OUTPUT_LINES: usize = 40;
+
+/// Upper bound on the task output kept, in bytes, for runs with very long lines
+const MAX_TASK_OUTPUT_BYTES: usize = 8 * 1024;
+
+/// Notification sent when a Zed task reports how it finished through the
+/// `claude-code.reportTaskResult` command, so Claude sees builds and tests the user ran
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskResultNotification {
+    /// Task label as shown in Zed, when the task passes one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub command: String,
+    pub exit_code: Option<i64>,
+    /// The tail of the task's output
+    pub output: String,
+    /// Whether earlier output was cut off
+    pub truncated: bool,
+}
+
+impl TaskResultNotification {
+    /// Build the notification from `{command, exitCode, output, label}` command arguments,
+    /// keeping only the tail of the output. `None` without a command.
+    pub fn from_arguments(arguments: &serde_json::Value) -> Option<Self> {
+        let command = arguments.get("command")?.as_str()?.to_string();
+        let output = arguments
+            .get("output")
+            .and_then(|output| output.as_str())
+            .unwrap_or_default()
+            .trim_end();
+
+        let mut tail_start = output
+            .char_indices()
+            .rev()
+            .filter(|(_, c)| *c == '\n')
+            .nth(MAX_TASK_OUTPUT_LINES - 1)
+            .map_or(0, |(newline, _)| newline + 1);
+        if output.len() - tail_start > MAX_TASK_OUTPUT_BYTES {
+            tail_start = output.len() - MAX_TASK_OUTPUT_BYTES;
+            while !output.is_char_boundary(tail_start) {
+                tail_start += 1;
+            }
+        }
+
+        Some(Self {
+            label: arguments
+                .get("label")
+                .and_then(|label| label.as_str())
+                .map(str::to_string),
+            command,
+            exit_code: arguments.get("exitCode").and_then(|code| code.as_i64()),
+            output: output[tail_start..].to_string(),
+            truncated: tail_start > 0,
+        })
+    }
+}
+
+/// Notification asking Claude to act on a range of code from a Zed command or code action,
+/// e.g. to fix the diagnostics reported for it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionRequestedNotification {
+    /// What was asked for: `explain`, `improve` or `fix`
+    pub action: String,
+    /// Instruction for Claude
+    pub prompt: String,
+    pub file_path: String,
+    pub file_url: String,
+    /// The range the action is about
+    pub range: Range,
+    /// The text of the range
+    pub text: String,
+    /// The range's lines with some surrounding lines for context
+    pub code: String,
+    /// 0-based line `code` starts at
+    pub code_start_line: u32,
+    /// Diagnostics the action is about, as Zed reported them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A file's diagnostics sent with `claude-code.sendDiagnostics`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiagnostics {
+    pub file_path: String,
+    pub file_url: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Notification sent when the user forwards the stored diagnostics of the active file or
+/// the workspace with `claude-code.sendDiagnostics`, asking Claude to fix them
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiagnosticsSentNotification {
+    /// Instruction for Claude
+    pub prompt: String,
+    pub files: Vec<FileDiagnostics>,
+}
+
+/// Notification sent when the user saves a document in the editor
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileSavedNotification {
+    #[serde(rename = "filePath")]
+    pub file_path: String,
+    #[serde(rename = "fileUrl")]
+    pub file_url: String,
+}
+
 /// JSON-RPC notification structure for IDE to Claude communication
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JsonRpcNotification {
@@ -0,0 +1,156 @@
+use std::ops::Range as ByteRange;
+use tower_lsp::lsp_types::{
+    CodeLens, Command, DocumentSymbol, Position, Range, SelectionRange, SymbolKind, Url,
+};
+
+use super::utils::LineIndex;
+use crate::symbols::OutlineSymbol;
+
+/// The outline of a text as LSP document symbols, in the negotiated position encoding
+pub fn document_symbols(outline: &[OutlineSymbol], lines: &LineIndex) -> Vec<DocumentSymbol> {
+    outline
+        .iter()
+        .map(|symbol| {
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: symbol.name.clone(),
+                detail: symbol.container.clone(),
+                kind: symbol.kind,
+                tags: None,
+                deprecated: None,
+                range: lines.range(&symbol.range),
+                selection_range: lines.range(&symbol.name_range),
+                children: (!symbol.children.is_empty())
+                    .then(|| document_symbols(&symbol.children, lines)),
+            }
+        })
+        .collect()
+}
+
+/// Nested selection ranges for byte `spans` of `text`, innermost first, in the negotiated
+/// position encoding
+pub fn selection_range(spans: &[ByteRange<usize>], text: &str) -> Option<SelectionRange> {
+    let lines = LineIndex::new(text);
+    spans.iter().rev().fold(None, |parent, span| {
+        Some(SelectionRange {
+            range: lines.range(span),
+            parent: parent.map(Box::new),
+        })
+    })
+}
+
+/// Lenses shown above each definition, each sending the definition to Claude
+const LENSES: [(&str, &str); 3] = [
+    ("Explain", "claude-code.explain"),
+    ("Improve", "claude-code.improve"),
+    ("Write tests", "claude-code.write-tests"),
+];
+
+/// Explain, Improve and Write tests lenses above the functions, methods and types in the
+/// outline of the document at `uri`. Functions nested in other functions get none.
+pub fn code_lenses(outline: &[OutlineSymbol], lines: &LineIndex, uri: &Url) -> Vec<CodeLens> {
+    let mut lenses = Vec::new();
+    for symbol in outline {
+        match symbol.kind {
+            SymbolKind::FUNCTION
+            | SymbolKind::METHOD
+            | SymbolKind::CLASS
+            | SymbolKind::STRUCT
+            | SymbolKind::ENUM
+            | SymbolKind::INTERFACE => {
+                let range = lines.range(&symbol.range);
+                let line = Position {
+                    line: range.start.line,
+                    character: 0,
+                };
+                lenses.extend(LENSES.iter().map(|(title, command)| CodeLens {
+                    range: Range {
+                        start: line,
+                        end: line,
+                    },
+                    command: Some(Command {
+                        title: title.to_string(),
+                        command: command.to_string(),
+                        arguments: Some(vec![serde_json::json!({
+                            "uri": uri,
+                            "range": range
+                        })]),
+                    }),
+                    data: None,
+                }));
+            }
+            _ => {}
+        }
+        if !matches!(symbol.kind, SymbolKind::FUNCTION | SymbolKind::METHOD) {
+            lenses.extend(code_lenses(&symbol.children, lines, uri));
+        }
+    }
+    lenses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::outline;
+
+    const RUST: &str = "struct Point {\n    x: f64,\n}\n\nimpl Point {\n    fn norm(&self) -> f64 {\n        fn square(v: f64) -> f64 { v * v }\n        square(self.x)\n    }\n}\n";
+
+    fn symbols(text: &str) -> Vec<DocumentSymbol> {
+        document_symbols(&outline("rust", text).unwrap(), &LineIndex::new(text))
+    }
+
+    #[test]
+    fn document_symbols_nest_like_the_outline() {
+        let symbols = symbols(RUST);
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Point", "impl Point"]);
+        assert_eq!(symbols[0].kind, SymbolKind::STRUCT);
+        assert_eq!(symbols[0].range.start, Position::new(0, 0));
+        assert_eq!(symbols[0].range.end, Position::new(2, 1));
+        assert_eq!(symbols[0].selection_range.start, Position::new(0, 7));
+        assert_eq!(symbols[0].selection_range.end, Position::new(0, 12));
+
+        let methods = symbols[1].children.as_ref().unwrap();
+        assert_eq!(methods.len(), 1);
+        assert_eq!(methods[0].name, "norm");
+        assert_eq!(methods[0].range.start, Position::new(5, 4));
+        assert_eq!(methods[0].range.end, Position::new(8, 5));
+    }
+
+    #[test]
+    fn document_symbols_leave_out_empty_children() {
+        let symbols = symbols("fn main() {}\n");
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].children, None);
+        assert!(document_symbols(&[], &LineIndex::new("")).is_empty());
+    }
+
+    #[test]
+    fn code_lenses_sit_above_functions_and_types() {
+        let uri = Url::parse("file:///src/point.rs").unwrap();
+        let lenses = code_lenses(&outline("rust", RUST).unwrap(), &LineIndex::new(RUST), &uri);
+        // The struct and the method get lenses; the impl block and the function nested
+        // in the method don't
+        let lines: Vec<u32> = lenses.iter().map(|lens| lens.range.start.line).collect();
+        assert_eq!(lines, [0, 0, 0, 5, 5, 5]);
+        for lens in &lenses {
+            assert_eq!(lens.range.start.character, 0);
+            assert_eq!(lens.range.start, lens.range.end);
+        }
+
+        let titles: Vec<&str> = lenses[..3]
+            .iter()
+            .map(|lens| lens.command.as_ref().unwrap().title.as_str())
+            .collect();
+        assert_eq!(titles, ["Explain", "Improve", "Write tests"]);
+        let command = lenses[3].command.as_ref().unwrap();
+        assert_eq!(command.command, "claude-code.explain");
+        assert_eq!(
+            command.arguments,
+            Some(vec![serde_json::json!({
+                "uri": "file:///src/point.rs",
+                "range": { "start": { "line": 5, "character": 4 }, "end": { "line": 8, "character": 5 } }
+            })])
+        );
+    }
+}
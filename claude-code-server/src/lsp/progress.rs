@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressCreateParams, WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+use tower_lsp::Client;
+use tracing::debug;
+
+/// Work finishing sooner than this shows no progress, so quick commands don't flicker
+const PROGRESS_DELAY: Duration = Duration::from_millis(500);
+
+/// How often the elapsed time of running work is reported
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// Show `title` as work-done progress in Zed once the work has run for a moment, reporting
+/// the elapsed time until `done` fires or is dropped
+pub async fn show_progress(client: Client, title: String, mut done: oneshot::Receiver<()>) {
+    tokio::select! {
+        _ = &mut done => return,
+        _ = tokio::time::sleep(PROGRESS_DELAY) => {}
+    }
+
+    let token = NumberOrString::String(format!(
+        "claude-code/{}",
+        NEXT_TOKEN.fetch_add(1, Ordering::SeqCst)
+    ));
+    if let Err(e) = client
+        .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+            token: token.clone(),
+        })
+        .await
+    {
+        debug!("Client refused progress for {}: {}", title, e);
+        return;
+    }
+    let send = |progress: WorkDoneProgress| {
+        client.send_notification::<Progress>(ProgressParams {
+            token: token.clone(),
+            value: ProgressParamsValue::WorkDone(progress),
+        })
+    };
+
+    send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+        title,
+        cancellable: Some(false),
+        message: None,
+        percentage: None,
+    }))
+    .await;
+    let started = Instant::now();
+    loop {
+        tokio::select! {
+            _ = &mut done => break,
+            _ = tokio::time::sleep(REPORT_INTERVAL) => {
+                send(WorkDoneProgress::Report(WorkDoneProgressReport {
+                    cancellable: Some(false),
+                    message: Some(format!("{}s", started.elapsed().as_secs())),
+                    percentage: None,
+                }))
+                .await;
+            }
+        }
+    }
+    send(WorkDoneProgress::End(WorkDoneProgressEnd { message: None })).await;
+}
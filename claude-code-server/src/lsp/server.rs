@@ -1,9 +1,39 @@
-use std::path::PathBuf;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Mutex, Notify, RwLock, RwLockReadGuard};
+use tokio::task::JoinHandle;
+use tower_lsp::jsonrpc::Result as LspResult;
+use tower_lsp::lsp_types::{
+    ConfigurationItem, Diagnostic, DidChangeWatchedFilesRegistrationOptions, FileSystemWatcher,
+    GlobPattern, MessageActionItem, MessageType, OneOf, Position, PublishDiagnosticsParams, Range,
+    Registration, RelativePattern, Url,
+};
 use tower_lsp::Client;
-use tracing::debug;
+use tracing::{debug, info, warn};
 
-use super::notifications::{JsonRpcNotification, NotificationSender};
+use super::actions::{action_request, ClaudeAction};
+use super::completion::mention_query;
+use super::notifications::{
+    AtMentionParams, AtMentionResult, AtMentionedNotification, ClaudeStatus, DiagnosticCounts,
+    DiagnosticsSentNotification, FileDiagnostics, JsonRpcNotification, NotificationSender,
+    SelectionChangedNotification, SelectionDidChangeParams, SelectionInfo,
+};
+use super::progress::show_progress;
+use super::utils::read_document_range;
+use crate::config::{ClaudeCodeOptions, SelectionSource};
+use crate::diagnostics::{CLAUDE_SOURCE, CLIENT_SOURCE};
+use crate::editor::EditorRequest;
+use crate::file_index::FileIndex;
+use crate::i18n::{Locale, Text};
+use crate::logging;
+use crate::mention::{self, resolve_mention};
+use crate::state::{self, SharedState};
+use crate::symbols::SymbolIndex;
+use crate::trust;
 
 #[derive(Debug)]
 pub struct ClaudeCodeLanguageServer {
@@ -11,6 +41,84 @@ pub struct ClaudeCodeLanguageServer {
     #[allow(dead_code)]
     pub(crate) worktree: Option<PathBuf>,
     pub(crate) notification_sender: Option<Arc<NotificationSender>>,
+    /// Degraded-mode warnings reported by the extension, shown once initialized
+    pub(crate) startup_warnings: RwLock<Vec<String>>,
+    /// Options in effect, from `initialize` and later settings
+    pub(crate) options: RwLock<ClaudeCodeOptions>,
+    /// Raw initialization options, which settings are layered over
+    pub(crate) initialization_options: RwLock<Option<serde_json::Value>>,
+    /// Hands the parsed initialization options to the WebSocket server (hybrid mode)
+    pub(crate) options_sender: Mutex<Option<oneshot::Sender<ClaudeCodeOptions>>>,
+    /// Editor state shared with MCP sessions
+    pub(crate) state: SharedState,
+    /// Last cursor position inferred from position-based requests, to skip repeats
+    pub(crate) last_cursor: Mutex<Option<(Url, Position)>>,
+    /// Whether the client has pushed `$/claudeCode/selectionDidChange`, making inferred
+    /// selections redundant
+    pub(crate) selection_pushed: AtomicBool,
+    /// Diagnostic counts from the last linter pass per file, to summarize only changes
+    pub(crate) lint_counts: Arc<Mutex<HashMap<String, DiagnosticCounts>>>,
+    /// Bumped for every selection reported, so a background extraction that was overtaken
+    /// by a newer selection is dropped instead of published out of order
+    pub(crate) selection_generation: Arc<AtomicU64>,
+    /// How long a selection must stay unchanged before it is published
+    pub(crate) selection_debounce_ms: Arc<AtomicU64>,
+    /// Whether the client accepts progress created by the server
+    pub(crate) work_done_progress: AtomicBool,
+    /// Whether the client supports `window/showDocument`
+    pub(crate) show_document: AtomicBool,
+    /// Whether the client answers `workspace/configuration`
+    pub(crate) workspace_configuration: AtomicBool,
+    /// Whether the client accepts file watchers registered by the server
+    pub(crate) watched_files: AtomicBool,
+    /// Whether file watchers may use patterns relative to a base URI
+    pub(crate) relative_watch_patterns: AtomicBool,
+    /// Background task refreshing the workspace symbol index
+    pub(crate) symbol_indexer: Mutex<Option<JoinHandle<()>>>,
+    /// Wakes the symbol indexer before its next pass, e.g. when files changed on disk
+    pub(crate) reindex: Arc<Notify>,
+}
+
+/// How often the background symbol index picks up files changed outside Zed
+const SYMBOL_INDEX_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Registration ID of the worktree file watchers
+const WATCHED_FILES_REGISTRATION: &str = "claude-code-watched-files";
+
+/// Watchers for the files at the top of `root` and under each of its directories that is
+/// not ignored, since glob patterns can't exclude the ignored ones. Ignored directories
+/// deeper down are still watched, and their changes dropped when they arrive. Patterns
+/// are absolute paths for clients without `relative` pattern support.
+fn worktree_watchers(
+    root: &Path,
+    ignored_dirs: &[String],
+    relative: bool,
+) -> Option<Vec<FileSystemWatcher>> {
+    let base_uri = Url::from_directory_path(root).ok()?;
+    let root_pattern = root.to_string_lossy().replace('\\', "/");
+    let watcher = |pattern: String| FileSystemWatcher {
+        glob_pattern: if relative {
+            GlobPattern::Relative(RelativePattern {
+                base_uri: OneOf::Right(base_uri.clone()),
+                pattern,
+            })
+        } else {
+            GlobPattern::String(format!(
+                "{}/{}",
+                root_pattern.trim_end_matches('/'),
+                pattern
+            ))
+        },
+        kind: None,
+    };
+    let mut watchers = vec![watcher("*".to_string())];
+    for entry in std::fs::read_dir(root).ok()?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) && !ignored_dirs.contains(&name) {
+            watchers.push(watcher(format!("{}/**/*", name)));
+        }
+    }
+    Some(watchers)
 }
 
 impl ClaudeCodeLanguageServer {
@@ -19,6 +127,25 @@ impl ClaudeCodeLanguageServer {
             client,
             worktree,
             notification_sender: None,
+            startup_warnings: RwLock::new(Vec::new()),
+            options: RwLock::new(ClaudeCodeOptions::default()),
+            initialization_options: RwLock::new(None),
+            options_sender: Mutex::new(None),
+            state: SharedState::default(),
+            last_cursor: Mutex::new(None),
+            selection_pushed: AtomicBool::new(false),
+            lint_counts: Arc::default(),
+            selection_generation: Arc::default(),
+            selection_debounce_ms: Arc::new(AtomicU64::new(
+                ClaudeCodeOptions::default().selection_debounce_ms,
+            )),
+            work_done_progress: AtomicBool::new(false),
+            show_document: AtomicBool::new(false),
+            workspace_configuration: AtomicBool::new(false),
+            watched_files: AtomicBool::new(false),
+            relative_watch_patterns: AtomicBool::new(false),
+            symbol_indexer: Mutex::new(None),
+            reindex: Arc::default(),
         }
     }
 
@@ -27,17 +154,851 @@ impl ClaudeCodeLanguageServer {
         self
     }
 
+    pub fn with_shared_state(mut self, state: SharedState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn with_options_sender(mut self, sender: oneshot::Sender<ClaudeCodeOptions>) -> Self {
+        self.options_sender = Mutex::new(Some(sender));
+        self
+    }
+
     pub(crate) async fn send_notification(&self, method: &str, params: serde_json::Value) {
-        if let Some(sender) = &self.notification_sender {
-            let notification = JsonRpcNotification {
-                jsonrpc: "2.0".to_string(),
-                method: method.to_string(),
-                params,
+        publish_notification(
+            self.notification_sender.as_deref(),
+            &self.state,
+            method,
+            params,
+        );
+    }
+
+    /// Apply the options that can change while the server runs, given in `initialize` or
+    /// by a settings change
+    pub(crate) async fn apply_options(&self, options: &ClaudeCodeOptions) {
+        if let Some(level) = options.log_level() {
+            logging::set_level(level);
+        }
+        self.state
+            .set_os_notifications(options.os_notifications.clone());
+        self.state.set_focus_mode(options.focus_mode);
+        state::set_relative_paths(options.relative_paths);
+        self.selection_debounce_ms
+            .store(options.selection_debounce_ms, Ordering::SeqCst);
+        self.state.set_hooks(options.hooks.clone());
+        self.state.set_ignored_dirs(options.ignored_dirs.clone());
+        if let Some(locale) = &options.locale {
+            info!("Using locale {}", locale);
+            self.state.set_locale(Locale::from_tag(locale));
+        }
+        self.state
+            .tool_cache
+            .lock()
+            .unwrap()
+            .configure(options.tool_cache.ttl());
+        self.state
+            .rate_limiter
+            .lock()
+            .unwrap()
+            .configure(options.rate_limit_intervals());
+        if self.state.set_enabled_tools(options.enabled_tools.clone())
+            && self.state.has_mcp_sessions()
+        {
+            info!("Enabled tools changed, tool list changed");
+            self.send_notification("notifications/tools/list_changed", serde_json::json!({}))
+                .await;
+        }
+        if let Some(range) = options.port_range() {
+            self.state.set_port_range(range);
+        }
+        self.state
+            .set_watchdog_interval(options.watchdog_interval());
+        *self.options.write().await = options.clone();
+    }
+
+    /// Ask the client to report changes to the worktree's files, so caches built from them
+    /// can be invalidated when they change outside Zed
+    pub(crate) async fn watch_files(&self) {
+        if !self.watched_files.load(Ordering::SeqCst) {
+            return;
+        }
+        let watchers = match self.worktree.clone() {
+            Some(root) => {
+                let ignored_dirs = self.state.ignored_dirs();
+                let relative = self.relative_watch_patterns.load(Ordering::SeqCst);
+                tokio::task::spawn_blocking(move || {
+                    worktree_watchers(&root, &ignored_dirs, relative)
+                })
+                .await
+                .ok()
+                .flatten()
+            }
+            None => None,
+        };
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: watchers.unwrap_or_else(|| {
+                vec![FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/*".to_string()),
+                    kind: None,
+                }]
+            }),
+        };
+        let registration = Registration {
+            id: WATCHED_FILES_REGISTRATION.to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        let registered = self.client.register_capability(vec![registration]).await;
+        if let Err(e) = &registered {
+            warn!("Failed to register file watchers: {}", e);
+        }
+        self.state.set_degraded(
+            "fileWatcher",
+            registered.err().map(|e| {
+                format!(
+                    "Zed did not accept the file watchers ({}), so files changed outside Zed \
+                     are only noticed every 30 seconds",
+                    e
+                )
+            }),
+        );
+    }
+
+    /// Ask the client for the `claudeCode` settings with `workspace/configuration` and
+    /// apply them over the initialization options
+    pub(crate) async fn pull_configuration(&self) {
+        if !self.workspace_configuration.load(Ordering::SeqCst) {
+            return;
+        }
+        let item = ConfigurationItem {
+            scope_uri: None,
+            section: Some("claudeCode".to_string()),
+        };
+        match self.client.configuration(vec![item]).await {
+            Ok(mut settings) if !settings.is_empty() => {
+                if !self.apply_settings(&settings.swap_remove(0)).await {
+                    debug!("Client has no claudeCode settings");
+                }
+            }
+            Ok(_) => debug!("Client returned no configuration"),
+            Err(e) => warn!("Failed to pull claudeCode settings: {}", e),
+        }
+    }
+
+    /// Apply `claudeCode` settings from the client, returning false if there are none
+    pub(crate) async fn apply_settings(&self, settings: &serde_json::Value) -> bool {
+        let initialization_options = self.initialization_options.read().await.clone();
+        let Some(mut options) =
+            ClaudeCodeOptions::from_settings(initialization_options.as_ref(), settings)
+        else {
+            return false;
+        };
+        info!("Claude Code settings: {:?}", options);
+        // Reported by the extension when it starts the server, not part of the settings
+        let current = self.options.read().await.clone();
+        options.startup_warnings = current.startup_warnings;
+        options.extension_version = current.extension_version;
+        self.apply_options(&options).await;
+        true
+    }
+
+    /// Handle the explain, improve and fix commands: send Claude the code they are about,
+    /// given as `{uri, range, diagnostics}` arguments by code actions, else the last
+    /// selection reported
+    pub(crate) async fn request_action(
+        &self,
+        action: ClaudeAction,
+        arguments: Option<&serde_json::Value>,
+    ) {
+        let target: Option<(Url, Option<Range>, Vec<Diagnostic>)> =
+            match arguments.filter(|args| args.get("uri").is_some()) {
+                Some(args) => field(args, "/uri").map(|uri| {
+                    (
+                        uri,
+                        field(args, "/range"),
+                        field(args, "/diagnostics").unwrap_or_default(),
+                    )
+                }),
+                None => self.state.last_selection().and_then(|selection| {
+                    let range = Range {
+                        start: field(&selection, "/selection/start")?,
+                        end: field(&selection, "/selection/end")?,
+                    };
+                    Some((field(&selection, "/fileUrl")?, Some(range), Vec::new()))
+                }),
+            };
+        let request = match target {
+            Some((uri, range, diagnostics)) => {
+                action_request(&self.state.documents, action, &uri, range, diagnostics).await
+            }
+            None => None,
+        };
+
+        let Some(request) = request else {
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    self.state.localize(Text::NoActionTarget, &[]),
+                )
+                .await;
+            return;
+        };
+        info!(
+            "Asking Claude to {} {}:{}-{}",
+            request.action,
+            request.file_path,
+            request.range.start.line + 1,
+            request.range.end.line + 1
+        );
+        let message = if request.diagnostics.is_empty() {
+            let location = format!(
+                "{}:{}-{}",
+                request.file_path,
+                request.range.start.line + 1,
+                request.range.end.line + 1
+            );
+            self.state.localize(Text::ActionRequestSent, &[&location])
+        } else {
+            self.state.localize(
+                Text::FixRequestSent,
+                &[&request.diagnostics.len(), &request.file_path],
+            )
+        };
+        self.send_notification(
+            "action_requested",
+            serde_json::to_value(request).unwrap_or_default(),
+        )
+        .await;
+        self.client.show_message(MessageType::INFO, message).await;
+    }
+
+    /// Handle `claude-code.sendDiagnostics`, forwarding the stored diagnostics of the file
+    /// named by a `uri` argument, of every file with `{"workspace": true}`, or else of the
+    /// active file, and asking Claude to fix them
+    pub(crate) async fn send_diagnostics(&self, arguments: Option<&serde_json::Value>) {
+        let workspace = arguments
+            .and_then(|args| args.get("workspace"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let file = match arguments.and_then(|args| field::<Url>(args, "/uri")) {
+            Some(uri) => Some(uri),
+            None => self
+                .state
+                .documents
+                .read()
+                .await
+                .active()
+                .map(|document| document.uri.clone()),
+        };
+
+        let files: Vec<FileDiagnostics> = {
+            let store = self.state.diagnostics.read().await;
+            let uris: Vec<Url> = if workspace {
+                store.files().into_iter().cloned().collect()
+            } else {
+                file.into_iter().collect()
+            };
+            // Claude's own findings are not sent back to it
+            uris.into_iter()
+                .filter_map(|uri| Some((uri.to_file_path().ok()?, uri)))
+                .filter(|(path, _)| !self.state.is_ignored_path(path))
+                .map(|(path, uri)| FileDiagnostics {
+                    file_path: path.to_string_lossy().to_string(),
+                    diagnostics: store
+                        .get_excluding(&uri, CLAUDE_SOURCE)
+                        .into_iter()
+                        .cloned()
+                        .collect(),
+                    file_url: uri.to_string(),
+                })
+                .filter(|file| !file.diagnostics.is_empty())
+                .collect()
+        };
+
+        if files.is_empty() {
+            self.client
+                .show_message(
+                    MessageType::INFO,
+                    self.state.localize(Text::NoDiagnostics, &[]),
+                )
+                .await;
+            return;
+        }
+        let count: usize = files.iter().map(|file| file.diagnostics.len()).sum();
+        info!(
+            "Sending {} diagnostics in {} files to Claude",
+            count,
+            files.len()
+        );
+        let message = self
+            .state
+            .localize(Text::DiagnosticsSent, &[&count, &files.len()]);
+        let notification = DiagnosticsSentNotification {
+            prompt: "Fix the problems these diagnostics report.".to_string(),
+            files,
+        };
+        let mut params = serde_json::to_value(notification).unwrap_or_default();
+        if let Some(files) = params["files"].as_array_mut() {
+            for file in files {
+                self.state.relativize_file_path(file);
+            }
+        }
+        self.send_notification("diagnostics_sent", params).await;
+        self.client.show_message(MessageType::INFO, message).await;
+    }
+
+    /// Whether selections should be inferred from other requests, i.e. the client does not
+    /// push real selection changes
+    pub(crate) async fn infers_selection(&self) -> bool {
+        !self.selection_pushed.load(Ordering::SeqCst)
+            && self.options.read().await.selection_source == SelectionSource::Inferred
+    }
+
+    /// Handle `$/claudeCode/selectionDidChange`, forwarding the primary selection to Claude
+    pub(crate) async fn selection_did_change(&self, params: SelectionDidChangeParams) {
+        if !self.selection_pushed.swap(true, Ordering::SeqCst) {
+            info!("Client pushes selection changes, no longer inferring them");
+        }
+        let Some(range) = params.selections.first().copied() else {
+            return;
+        };
+        let uri = &params.text_document.uri;
+
+        self.state
+            .documents
+            .write()
+            .await
+            .touch(uri, [range.start.line, range.end.line]);
+
+        debug!("Selection changed to {:?} in {}", range, uri);
+        self.publish_selection(
+            uri.clone(),
+            range,
+            SelectionInfo {
+                start: range.start,
+                end: range.end,
+                is_empty: range.start == range.end,
+            },
+        );
+    }
+
+    /// Handle `$/claudeCode/publishDiagnostics`, storing the diagnostics the client forwards
+    /// for a file so Claude can fetch them
+    pub(crate) async fn client_published_diagnostics(&self, params: PublishDiagnosticsParams) {
+        debug!(
+            "Client forwarded {} diagnostics for {}",
+            params.diagnostics.len(),
+            params.uri
+        );
+        self.state
+            .diagnostics
+            .write()
+            .await
+            .set(params.uri, CLIENT_SOURCE, params.diagnostics);
+        self.state.invalidate_tool_cache();
+    }
+
+    /// Handle `claude/atMention`, sharing a file or range with Claude and reporting whether it
+    /// reached a session
+    pub(crate) async fn at_mention(&self, params: AtMentionParams) -> LspResult<AtMentionResult> {
+        let uri = &params.text_document.uri;
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(AtMentionResult::failed(format!("{} is not a file", uri)));
+        };
+        if self.state.is_ignored_path(&path) {
+            return Ok(AtMentionResult::failed(format!(
+                "{} is in an ignored directory",
+                path.display()
+            )));
+        }
+        if !self.state.has_mcp_sessions() {
+            return Ok(AtMentionResult::failed("No Claude session is connected"));
+        }
+
+        let (line_start, line_end) = match params.range {
+            // A selection of whole lines ends at the start of the line after them
+            Some(range) if range.end.line > range.start.line && range.end.character == 0 => {
+                (range.start.line, range.end.line - 1)
+            }
+            Some(range) => (range.start.line, range.end.line),
+            None => mention::whole_file_lines(&self.state, &path).await,
+        };
+        let file_path = path.to_string_lossy().to_string();
+        info!(
+            "At-mention requested for {}:{}-{}",
+            file_path, line_start, line_end
+        );
+        mention::mention(&self.state, &file_path, line_start, line_end).await;
+
+        self.client
+            .show_message(
+                MessageType::INFO,
+                self.state
+                    .localize(Text::AtMentionSent, &[&file_path, &line_start, &line_end]),
+            )
+            .await;
+        Ok(AtMentionResult {
+            success: true,
+            mention: Some(AtMentionedNotification {
+                file_path,
+                line_start,
+                line_end,
+            }),
+            error: None,
+        })
+    }
+
+    /// At-mention the files named by `@path` mentions typed in the document at `uri`, each
+    /// ending at one of `positions`. A mention repeating the last one is skipped, e.g. one
+    /// just accepted from the completions.
+    pub(crate) async fn mention_typed(&self, uri: &Url, positions: &[Position]) {
+        let mentions: Vec<String> = {
+            let documents = self.state.documents.read().await;
+            let Some(document) = documents.get(uri) else {
+                return;
+            };
+            positions
+                .iter()
+                .filter_map(|position| mention_query(document, *position))
+                .map(|(_, query)| query)
+                .collect()
+        };
+
+        for query in mentions {
+            let Some((path, lines)) = resolve_mention(&query, self.worktree.as_deref()) else {
+                continue;
+            };
+            if self.state.is_ignored_path(&path) {
+                continue;
+            }
+            // Mentions are typed with lines as Zed shows them; notifications use 0-based lines
+            let (line_start, line_end) = match lines {
+                Some((start, end)) => (start - 1, end - 1),
+                None => mention::whole_file_lines(&self.state, &path).await,
+            };
+            let file_path = path.to_string_lossy().to_string();
+            let repeated = self.state.last_at_mention().is_some_and(|last| {
+                last.get("filePath").and_then(|v| v.as_str()) == Some(file_path.as_str())
+                    && last.get("lineStart").and_then(|v| v.as_u64()) == Some(line_start.into())
+                    && last.get("lineEnd").and_then(|v| v.as_u64()) == Some(line_end.into())
+            });
+            if repeated {
+                continue;
+            }
+            info!(
+                "Typed mention of {}:{}-{} in {}",
+                file_path, line_start, line_end, uri
+            );
+            mention::mention(&self.state, &file_path, line_start, line_end).await;
+        }
+    }
+
+    /// Publish `selection_changed` for the text in `range` from a background task, so the
+    /// request revealing the selection is answered without waiting for the text to be
+    /// extracted (from disk, for files that are not open). The selection is published once
+    /// it has stayed unchanged for the debounce interval, and only if it differs from the
+    /// last one published.
+    pub(crate) fn publish_selection(&self, uri: Url, range: Range, selection: SelectionInfo) {
+        let generation = self.selection_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let latest = self.selection_generation.clone();
+        let debounce = Duration::from_millis(self.selection_debounce_ms.load(Ordering::SeqCst));
+        let sender = self.notification_sender.clone();
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            if !debounce.is_zero() {
+                tokio::time::sleep(debounce).await;
+                if latest.load(Ordering::SeqCst) != generation {
+                    debug!("Selection in {} was superseded while debouncing", uri);
+                    return;
+                }
+            }
+
+            let started = Instant::now();
+            let (selected, utf16_range) = read_document_range(&state.documents, &uri, range).await;
+            // Claude counts offsets in UTF-16, like the LSP default
+            let selection = SelectionInfo {
+                start: utf16_range.start,
+                end: utf16_range.end,
+                ..selection
+            };
+            debug!(
+                "Extracted {} bytes of selection text from {} in {:?}",
+                selected.text.len(),
+                uri,
+                started.elapsed()
+            );
+            if latest.load(Ordering::SeqCst) != generation {
+                debug!("Selection in {} was superseded, not publishing it", uri);
+                return;
+            }
+
+            let selection_notification = SelectionChangedNotification {
+                text: selected.text,
+                file_path: uri.path().to_string(),
+                file_url: uri.to_string(),
+                selection,
+                encoding: selected.encoding,
             };
+            if !state.record_selection_sent(&selection_notification) {
+                debug!("Selection in {} is unchanged, not publishing it", uri);
+                return;
+            }
+            publish_notification(
+                sender.as_deref(),
+                &state,
+                "selection_changed",
+                serde_json::to_value(selection_notification).unwrap(),
+            );
+        });
+    }
+
+    /// Report the cursor at `position` as an empty selection, inferred from requests Zed
+    /// sends as the cursor or pointer moves (hover, document highlight)
+    pub(crate) async fn track_cursor(&self, uri: &Url, position: Position) {
+        self.state
+            .documents
+            .write()
+            .await
+            .touch(uri, [position.line]);
+        if !self.infers_selection().await {
+            return;
+        }
+        {
+            let mut last_cursor = self.last_cursor.lock().await;
+            if last_cursor.as_ref() == Some(&(uri.clone(), position)) {
+                return;
+            }
+            *last_cursor = Some((uri.clone(), position));
+        }
+
+        debug!(
+            "Cursor at {}:{} in {}",
+            position.line, position.character, uri
+        );
+        self.publish_selection(
+            uri.clone(),
+            Range {
+                start: position,
+                end: position,
+            },
+            SelectionInfo {
+                start: position,
+                end: position,
+                is_empty: true,
+            },
+        );
+    }
+
+    /// Publish LSP attachment to MCP sessions, which see a different tool list while detached
+    pub(crate) async fn set_attached(&self, attached: bool) {
+        if self.state.set_lsp_attached(attached) {
+            info!(
+                "LSP bridge {}, tool list changed",
+                if attached { "attached" } else { "detached" }
+            );
+            self.send_notification("notifications/tools/list_changed", serde_json::json!({}))
+                .await;
+        }
+    }
+
+    /// The workspace file index, walking the workspace first when the index is missing, or
+    /// stale while the background indexer that keeps it current is not running
+    pub(crate) async fn file_index(&self) -> RwLockReadGuard<'_, Option<FileIndex>> {
+        let root = self
+            .worktree
+            .clone()
+            .or_else(|| std::env::current_dir().ok());
+        if let Some(root) = root {
+            let indexing = self.symbol_indexer.lock().await.is_some();
+            let stale = self
+                .state
+                .file_index
+                .read()
+                .await
+                .as_ref()
+                .is_none_or(|index| !indexing && index.is_stale(&root));
+            if stale {
+                let ignored_dirs = self.state.ignored_dirs();
+                // Directory walking is blocking work, keep it off the async runtime threads
+                match tokio::task::spawn_blocking(move || FileIndex::build(&root, &ignored_dirs))
+                    .await
+                {
+                    Ok(index) => *self.state.file_index.write().await = Some(index),
+                    Err(e) => warn!("Failed to index workspace files: {}", e),
+                }
+            }
+        }
+        self.state.file_index.read().await
+    }
+
+    /// Keep the workspace file and symbol indexes current in the background, re-reading
+    /// only the files modified since the last pass
+    pub(crate) async fn start_symbol_indexer(&self) {
+        let Some(root) = self
+            .worktree
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+        else {
+            return;
+        };
+        let state = self.state.clone();
+        let reindex = self.reindex.clone();
+        let task = tokio::spawn(async move {
+            // The shared index is updated in place, alongside saves updating single files
+            state
+                .symbol_index
+                .write()
+                .await
+                .get_or_insert_with(|| SymbolIndex::new(&root));
+            loop {
+                let Some(snapshot) = state
+                    .symbol_index
+                    .read()
+                    .await
+                    .as_ref()
+                    .map(SymbolIndex::snapshot)
+                else {
+                    return;
+                };
+                let ignored_dirs = state.ignored_dirs();
+                let walk_root = root.clone();
+                // Walking and parsing are blocking work, keep them off the async runtime
+                let refreshed = tokio::task::spawn_blocking(move || {
+                    // The walk skips directories it can't read, so an unreadable worktree
+                    // would look empty
+                    let readable = std::fs::read_dir(&walk_root).map(|_| ());
+                    let files = FileIndex::build(&walk_root, &ignored_dirs);
+                    let refresh = snapshot.refresh(files.files());
+                    (readable, files, refresh)
+                })
+                .await;
+                match refreshed {
+                    Ok((readable, files, refresh)) => {
+                        state.set_degraded(
+                            "symbolIndex",
+                            readable.err().map(|e| {
+                                format!("cannot read the worktree {}: {}", root.display(), e)
+                            }),
+                        );
+                        *state.file_index.write().await = Some(files);
+                        if let Some(index) = state.symbol_index.write().await.as_mut() {
+                            index.apply(refresh);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to index workspace symbols: {}", e);
+                        state.set_degraded(
+                            "symbolIndex",
+                            Some(format!("the background indexer stopped: {}", e)),
+                        );
+                        return;
+                    }
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(SYMBOL_INDEX_INTERVAL) => {}
+                    _ = reindex.notified() => {}
+                }
+            }
+        });
+        if let Some(previous) = self.symbol_indexer.lock().await.replace(task) {
+            previous.abort();
+        }
+    }
+
+    pub(crate) async fn stop_symbol_indexer(&self) {
+        if let Some(task) = self.symbol_indexer.lock().await.take() {
+            task.abort();
+        }
+    }
+
+    /// Carry out requests MCP sessions make of the editor, until another LSP session
+    /// attaches or this one detaches
+    pub(crate) fn serve_editor_requests(&self) {
+        let mut requests = self.state.editor.attach();
+        let client = self.client.clone();
+        let work_done_progress = self.work_done_progress.load(Ordering::SeqCst);
+        let show_document = self.show_document.load(Ordering::SeqCst);
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                match request {
+                    EditorRequest::Progress { title, done } => {
+                        if work_done_progress {
+                            tokio::spawn(show_progress(client.clone(), title, done));
+                        }
+                    }
+                    EditorRequest::ShowDocument { params, reply } => {
+                        let client = client.clone();
+                        tokio::spawn(async move {
+                            let shown = if show_document {
+                                info!("Showing {} in Zed", params.uri);
+                                client.show_document(params).await.map_err(|e| {
+                                    anyhow::anyhow!("window/showDocument failed: {}", e)
+                                })
+                            } else {
+                                Err(anyhow::anyhow!("Zed does not support window/showDocument"))
+                            };
+                            let _ = reply.send(shown);
+                        });
+                    }
+                    EditorRequest::PublishDiagnostics { uri, diagnostics } => {
+                        client.publish_diagnostics(uri, diagnostics, None).await;
+                    }
+                    EditorRequest::Status(params) => {
+                        client.send_notification::<ClaudeStatus>(params).await;
+                    }
+                    EditorRequest::ShowMessageRequest {
+                        message,
+                        actions,
+                        reply,
+                    } => {
+                        let client = client.clone();
+                        tokio::spawn(async move {
+                            let actions = actions
+                                .into_iter()
+                                .map(|title| MessageActionItem {
+                                    title,
+                                    properties: Default::default(),
+                                })
+                                .collect();
+                            let answer = client
+                                .show_message_request(MessageType::INFO, message, Some(actions))
+                                .await
+                                .map(|action| action.map(|action| action.title))
+                                .map_err(|e| {
+                                    anyhow::anyhow!("window/showMessageRequest failed: {}", e)
+                                });
+                            let _ = reply.send(answer);
+                        });
+                    }
+                }
+            }
+        });
+    }
+
+    /// Apply the persisted trust decision for this worktree, asking the user on first use.
+    /// The prompt runs in the background so an unanswered dialog doesn't hold up the server.
+    pub(crate) async fn establish_trust(&self) {
+        let Some(workspace) = self
+            .worktree
+            .clone()
+            .or_else(|| std::env::current_dir().ok())
+        else {
+            warn!("No workspace path, leaving the workspace untrusted");
+            return;
+        };
 
+        if trust::is_trusted(Some(&workspace)) {
+            mark_trusted(self.notification_sender.as_deref(), &self.state);
+            return;
+        }
+
+        // Focus mode answers permission prompts with a deny instead of interrupting the user
+        if self.state.is_focus_mode() {
+            info!("Focus mode, leaving {} restricted", workspace.display());
+            return;
+        }
+
+        let client = self.client.clone();
+        let sender = self.notification_sender.clone();
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let trust_action = state.localize(Text::TrustAction, &[]);
+            let actions = [
+                trust_action.clone(),
+                state.localize(Text::RestrictAction, &[]),
+            ]
+            .into_iter()
+            .map(|title| MessageActionItem {
+                title,
+                properties: Default::default(),
+            })
+            .collect();
+            let message = state.localize(Text::TrustPrompt, &[&workspace.display()]);
+
+            match client
+                .show_message_request(MessageType::WARNING, message, Some(actions))
+                .await
+            {
+                Ok(Some(action)) if action.title == trust_action => {
+                    if let Err(e) = trust::trust_workspace(&workspace) {
+                        warn!("Failed to persist workspace trust: {}", e);
+                    }
+                    mark_trusted(sender.as_deref(), &state);
+                }
+                Ok(_) => info!("Workspace {} left restricted", workspace.display()),
+                Err(e) => warn!("Workspace trust prompt failed: {}", e),
+            }
+        });
+    }
+}
+
+fn mark_trusted(sender: Option<&NotificationSender>, state: &SharedState) {
+    if state.set_trusted(true) {
+        info!("Workspace trusted, tool list changed");
+        publish_notification(
+            sender,
+            state,
+            "notifications/tools/list_changed",
+            serde_json::json!({}),
+        );
+    }
+}
+
+/// Forward a notification to connected MCP sessions, or queue it until one connects
+pub(crate) fn publish_notification(
+    sender: Option<&NotificationSender>,
+    state: &SharedState,
+    method: &str,
+    params: serde_json::Value,
+) {
+    if state.is_ignored_notification(method, &params) {
+        debug!("Dropping {} for a file in an ignored directory", method);
+        return;
+    }
+    match method {
+        "selection_changed" => state.set_last_selection(params.clone()),
+        "at_mentioned" => state.set_last_at_mention(params.clone()),
+        "at_mentioned_ranges" => {
+            if let Some(mention) = params.get("mentions").and_then(|m| m.as_array()?.last()) {
+                state.set_last_at_mention(mention.clone());
+            }
+        }
+        _ => {}
+    }
+    // Dropped after recording the selection, so getLatestSelection stays current
+    if !state.rate_limiter.lock().unwrap().admit(method) {
+        debug!("Rate limiting {}", method);
+        return;
+    }
+    // Selections name files the way tool responses do; the recorded one stays absolute
+    let mut params = params;
+    if method == "selection_changed" {
+        state.relativize_file_path(&mut params);
+    }
+
+    let notification = JsonRpcNotification {
+        jsonrpc: "2.0".to_string(),
+        method: method.to_string(),
+        params,
+    };
+    state.mirror_to_event_tap(&notification);
+
+    match sender {
+        Some(sender) if state.has_mcp_sessions() => {
             if let Err(e) = sender.send(notification) {
                 debug!("Failed to send notification: {}", e);
             }
         }
+        Some(_) => {
+            debug!("No MCP session connected, queueing {}", notification.method);
+            state.queue_notification(notification);
+        }
+        // Without a WebSocket server (`lsp` mode) no session will ever drain the queue
+        None => debug!("No MCP server running, dropping {}", notification.method),
     }
 }
+
+/// The value at `pointer` in `value`, if it deserializes as `T`
+fn field<T: DeserializeOwned>(value: &serde_json::Value, pointer: &str) -> Option<T> {
+    serde_json::from_value(value.pointer(pointer)?.clone()).ok()
+}
@@ -1,94 +1,275 @@
-use std::fs;
-use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::{Position, PositionEncodingKind, Range, Url};
 use tracing::warn;
 
-/// Convert LSP UTF-16 code unit position to Rust UTF-8 byte position
-/// LSP uses UTF-16 code units for character positions per the specification
-pub fn char_pos_to_byte_pos(line: &str, utf16_pos: usize) -> Option<usize> {
-    let mut current_utf16_pos = 0;
+use crate::documents::SharedDocuments;
+use crate::encoding::{read_text_file, DecodedText};
+use crate::state;
+
+/// What the `character` of an LSP position counts, negotiated in `initialize`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PositionEncoding {
+    /// Bytes, which Rust strings are indexed by, so positions need no conversion
+    Utf8,
+    /// UTF-16 code units, the LSP default every client supports
+    #[default]
+    Utf16,
+    /// Unicode scalar values, i.e. `char`s
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// The encoding to use out of those the client offers: UTF-8 when it can, then
+    /// UTF-32, which are both cheaper to map onto Rust strings than the mandatory UTF-16
+    pub fn negotiate(offered: Option<&[PositionEncodingKind]>) -> Self {
+        let offered = offered.unwrap_or_default();
+        if offered.contains(&PositionEncodingKind::UTF8) {
+            PositionEncoding::Utf8
+        } else if offered.contains(&PositionEncodingKind::UTF32) {
+            PositionEncoding::Utf32
+        } else {
+            PositionEncoding::Utf16
+        }
+    }
+
+    pub fn kind(&self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// Length of a character in this encoding's units
+    pub fn len_of(&self, ch: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => ch.len_utf8(),
+            PositionEncoding::Utf16 => ch.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// Convert the `character` of an LSP position, in the negotiated encoding's units, to a
+/// byte position in `line`. A position inside a character maps to its start.
+pub fn char_pos_to_byte_pos(line: &str, pos: usize, encoding: PositionEncoding) -> Option<usize> {
+    if encoding == PositionEncoding::Utf8 {
+        return (pos <= line.len())
+            .then(|| (0..=pos).rev().find(|&i| line.is_char_boundary(i)))
+            .flatten();
+    }
+
+    let mut current_pos = 0;
 
     for (byte_pos, ch) in line.char_indices() {
-        if current_utf16_pos == utf16_pos {
+        if current_pos == pos {
             return Some(byte_pos);
         }
 
-        let char_utf16_len = ch.len_utf16();
+        let char_len = encoding.len_of(ch);
 
-        // If utf16_pos falls within this character's UTF-16 span, return this char's byte position
-        if utf16_pos < current_utf16_pos + char_utf16_len {
+        // If pos falls within this character's span, return this char's byte position
+        if pos < current_pos + char_len {
             return Some(byte_pos);
         }
 
-        current_utf16_pos += char_utf16_len;
+        current_pos += char_len;
     }
 
-    // If utf16_pos is at the end of the string
-    if current_utf16_pos == utf16_pos {
+    // If pos is at the end of the string
+    if current_pos == pos {
         return Some(line.len());
     }
 
     None
 }
 
-/// Read text content from a file within a specified range
-pub fn read_text_from_range(file_path: &str, range: Range) -> String {
+/// The LSP position of a byte offset in `text`, in the negotiated position encoding
+pub fn position_at(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let encoding = state::position_encoding();
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..]
+            .chars()
+            .map(|c| encoding.len_of(c))
+            .sum::<usize>() as u32,
+    }
+}
+
+/// `position`, whose `character` is in `encoding`'s units, with the `character` in the
+/// UTF-16 code units Claude counts, given the text of its line
+pub fn utf16_position(line: &str, position: Position, encoding: PositionEncoding) -> Position {
+    let byte =
+        char_pos_to_byte_pos(line, position.character as usize, encoding).unwrap_or(line.len());
+    Position {
+        line: position.line,
+        character: line[..byte].encode_utf16().count() as u32,
+    }
+}
+
+/// Where the lines of a text start, so byte offsets map to LSP positions without scanning
+/// the text before each one
+pub struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+    encoding: PositionEncoding,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(newline, _)| newline + 1))
+            .collect();
+        Self {
+            text,
+            line_starts,
+            encoding: state::position_encoding(),
+        }
+    }
+
+    /// The LSP position of a byte offset, in the negotiated position encoding
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        Position {
+            line: line as u32,
+            character: self.text[self.line_starts[line]..offset]
+                .chars()
+                .map(|c| self.encoding.len_of(c))
+                .sum::<usize>() as u32,
+        }
+    }
+
+    pub fn range(&self, range: &std::ops::Range<usize>) -> Range {
+        Range {
+            start: self.position(range.start),
+            end: self.position(range.end),
+        }
+    }
+}
+
+/// Read text content from a file within a range in `encoding`'s units, transcoding files
+/// that are not UTF-8 and reporting the encoding they were stored in
+pub fn read_text_from_range(
+    file_path: &str,
+    range: Range,
+    encoding: PositionEncoding,
+) -> DecodedText {
     let file_path = file_path.strip_prefix("file://").unwrap_or(file_path);
 
-    match fs::read_to_string(file_path) {
-        Ok(content) => {
-            let lines: Vec<&str> = content.lines().collect();
+    match read_text_file(file_path) {
+        Ok(file) => DecodedText {
+            text: text_in_range(&file.text, range, encoding),
+            encoding: file.encoding,
+        },
+        Err(e) => {
+            warn!("Failed to read file {}: {}", file_path, e);
+            DecodedText::default()
+        }
+    }
+}
 
-            // Handle single line selection
-            if range.start.line == range.end.line {
-                if let Some(line) = lines.get(range.start.line as usize) {
-                    let start_char = range.start.character as usize;
-                    let end_char = range.end.character as usize;
+/// Read text within a range of a document from the editor's buffer, so unsaved edits are
+/// included, falling back to the file on disk for documents that are not open. The range
+/// is returned in UTF-16 positions for Claude, whatever encoding Zed uses.
+pub async fn read_document_range(
+    documents: &SharedDocuments,
+    uri: &Url,
+    range: Range,
+) -> (DecodedText, Range) {
+    if let Some(document) = documents.read().await.get(uri) {
+        let text = DecodedText {
+            text: document.text_in_range(range),
+            encoding: document.encoding,
+        };
+        let utf16_range = Range {
+            start: document.utf16_position(range.start),
+            end: document.utf16_position(range.end),
+        };
+        return (text, utf16_range);
+    }
+    // Reading a large file would stall a runtime thread
+    let path = uri.path().to_string();
+    tokio::task::spawn_blocking(move || {
+        let encoding = state::position_encoding();
+        let file = match read_text_file(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to read file {}: {}", path, e);
+                return (DecodedText::default(), range);
+            }
+        };
+        let line = |line: u32| file.text.lines().nth(line as usize).unwrap_or_default();
+        let utf16_range = Range {
+            start: utf16_position(line(range.start.line), range.start, encoding),
+            end: utf16_position(line(range.end.line), range.end, encoding),
+        };
+        let text = DecodedText {
+            text: text_in_range(&file.text, range, encoding),
+            encoding: file.encoding,
+        };
+        (text, utf16_range)
+    })
+    .await
+    .unwrap_or((DecodedText::default(), range))
+}
 
-                    if let (Some(start_byte), Some(end_byte)) = (
-                        char_pos_to_byte_pos(line, start_char),
-                        char_pos_to_byte_pos(line, end_char),
-                    ) {
-                        if start_byte <= end_byte {
-                            return line[start_byte..end_byte].to_string();
-                        }
-                    }
+/// Extract the text covered by an LSP range
+fn text_in_range(content: &str, range: Range, encoding: PositionEncoding) -> String {
+    // Only the covered lines are collected, so a selection near the top of a large file
+    // doesn't pay for the rest of it
+    let lines: Vec<&str> = content
+        .lines()
+        .skip(range.start.line as usize)
+        .take(range.end.line.saturating_sub(range.start.line) as usize + 1)
+        .collect();
+
+    // Handle single line selection
+    if range.start.line == range.end.line {
+        if let Some(line) = lines.first() {
+            let start_char = range.start.character as usize;
+            let end_char = range.end.character as usize;
+
+            if let (Some(start_byte), Some(end_byte)) = (
+                char_pos_to_byte_pos(line, start_char, encoding),
+                char_pos_to_byte_pos(line, end_char, encoding),
+            ) {
+                if start_byte <= end_byte {
+                    return line[start_byte..end_byte].to_string();
                 }
-            } else {
-                // Handle multi-line selection
-                let mut selected_text = String::new();
-
-                for (i, line_index) in (range.start.line..=range.end.line).enumerate() {
-                    if let Some(line) = lines.get(line_index as usize) {
-                        if i == 0 {
-                            // First line - from start character to end
-                            let start_char = range.start.character as usize;
-                            if let Some(start_byte) = char_pos_to_byte_pos(line, start_char) {
-                                selected_text.push_str(&line[start_byte..]);
-                            }
-                        } else if line_index == range.end.line {
-                            // Last line - from start to end character
-                            let end_char = range.end.character as usize;
-                            if let Some(end_byte) = char_pos_to_byte_pos(line, end_char) {
-                                selected_text.push_str(&line[..end_byte]);
-                            }
-                        } else {
-                            // Middle lines - entire line
-                            selected_text.push_str(line);
-                        }
-
-                        // Add newline except for the last line
-                        if line_index < range.end.line {
-                            selected_text.push('\n');
-                        }
+            }
+        }
+    } else {
+        // Handle multi-line selection
+        let mut selected_text = String::new();
+
+        for (i, line_index) in (range.start.line..=range.end.line).enumerate() {
+            if let Some(line) = lines.get(i) {
+                if i == 0 {
+                    // First line - from start character to end
+                    let start_char = range.start.character as usize;
+                    if let Some(start_byte) = char_pos_to_byte_pos(line, start_char, encoding) {
+                        selected_text.push_str(&line[start_byte..]);
                     }
+                } else if line_index == range.end.line {
+                    // Last line - from start to end character
+                    let end_char = range.end.character as usize;
+                    if let Some(end_byte) = char_pos_to_byte_pos(line, end_char, encoding) {
+                        selected_text.push_str(&line[..end_byte]);
+                    }
+                } else {
+                    // Middle lines - entire line
+                    selected_text.push_str(line);
                 }
 
-                return selected_text;
+                // Add newline except for the last line
+                if line_index < range.end.line {
+                    selected_text.push('\n');
+                }
             }
         }
-        Err(e) => {
-            warn!("Failed to read file {}: {}", file_path, e);
-        }
+
+        return selected_text;
     }
 
     String::new()
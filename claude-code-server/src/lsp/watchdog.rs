@@ -1,34 +1,63 @@
 use anyhow::Result;
+use std::io;
 use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{oneshot, Notify};
+use tower_lsp::jsonrpc::Request;
 use tower_lsp::{LspService, Server};
-use tracing::{error, info};
+use tower_service::Service;
+use tracing::{debug, error, info, warn};
 
 #[cfg(unix)]
 use std::os::unix::process::parent_id;
 
-use super::notifications::NotificationSender;
+use super::notifications::{
+    NotificationSender, AT_MENTION, PUBLISH_DIAGNOSTICS, SELECTION_DID_CHANGE,
+};
 use super::server::ClaudeCodeLanguageServer;
+use crate::config::ClaudeCodeOptions;
+use crate::state::{ServerMode, SharedState};
 
 pub async fn run_lsp_server(worktree: Option<PathBuf>) -> Result<()> {
-    run_lsp_server_with_notifications(worktree, None).await
+    let state = SharedState::new(ServerMode::Lsp, worktree.as_deref());
+    run_lsp_server_with_notifications(worktree, None, None, state).await?;
+    // Reading stdin blocks a runtime thread that would keep the process alive
+    std::process::exit(0);
 }
 
-/// Spawn a watchdog task that monitors the parent process.
-/// If the parent process dies (we get reparented to init/launchd), exit gracefully.
-/// This helps detect when Zed disconnects after Mac sleep/wake.
+/// Spawn a watchdog task that monitors the parent process, checking as often as the
+/// `watchdogIntervalMs` option says. The task ends once the parent died (we get reparented
+/// to init/launchd), so the server can shut down gracefully. This helps detect when Zed
+/// disconnects after Mac sleep/wake.
 #[cfg(unix)]
-fn spawn_parent_watchdog() -> tokio::task::JoinHandle<()> {
+fn spawn_parent_watchdog(state: &SharedState) -> tokio::task::JoinHandle<()> {
     let initial_ppid = parent_id();
     info!(
         "Starting parent process watchdog (initial PPID: {})",
         initial_ppid
     );
+    let mut intervals = state.watchdog_interval_changes();
 
     tokio::spawn(async move {
         loop {
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            let interval = *intervals.borrow_and_update();
+            match interval {
+                Some(interval) => tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    // Start over with the new interval
+                    Ok(()) = intervals.changed() => continue,
+                },
+                None => {
+                    debug!("Parent process watchdog disabled");
+                    if intervals.changed().await.is_err() {
+                        std::future::pending::<()>().await;
+                    }
+                    continue;
+                }
+            }
 
             let current_ppid = parent_id();
 
@@ -36,23 +65,23 @@ fn spawn_parent_watchdog() -> tokio::task::JoinHandle<()> {
             // On Unix, orphaned processes are reparented to init (PID 1) or launchd
             if current_ppid != initial_ppid {
                 error!(
-                    "Parent process changed from {} to {} - parent likely died, exiting",
+                    "Parent process changed from {} to {} - parent likely died, shutting down",
                     initial_ppid, current_ppid
                 );
-                std::process::exit(0);
+                return;
             }
 
             // Also check if reparented to init (PID 1) which means parent definitely died
             if current_ppid == 1 {
-                error!("Reparented to init (PPID=1) - parent died, exiting");
-                std::process::exit(0);
+                error!("Reparented to init (PPID=1) - parent died, shutting down");
+                return;
             }
         }
     })
 }
 
 #[cfg(not(unix))]
-fn spawn_parent_watchdog() -> tokio::task::JoinHandle<()> {
+fn spawn_parent_watchdog(_state: &SharedState) -> tokio::task::JoinHandle<()> {
     // On non-Unix platforms, just return a no-op task
     tokio::spawn(async {
         // No parent monitoring on Windows
@@ -63,6 +92,8 @@ fn spawn_parent_watchdog() -> tokio::task::JoinHandle<()> {
 pub async fn run_lsp_server_with_notifications(
     worktree: Option<PathBuf>,
     notification_sender: Option<Arc<NotificationSender>>,
+    options_sender: Option<oneshot::Sender<ClaudeCodeOptions>>,
+    state: SharedState,
 ) -> Result<()> {
     info!("Starting LSP server mode");
     if let Some(path) = &worktree {
@@ -70,20 +101,142 @@ pub async fn run_lsp_server_with_notifications(
     }
 
     // Spawn watchdog to detect parent process death (e.g., after Mac sleep/wake)
-    let _watchdog = spawn_parent_watchdog();
+    let watchdog = spawn_parent_watchdog(&state);
 
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
+    // Zed dying without the parent changing (e.g. over a remote connection) leaves a dead
+    // pipe, which ends the session like a parent that died
+    let hung_up = Arc::new(Notify::new());
+    let stdin = Hangup {
+        inner: tokio::io::stdin(),
+        hung_up: hung_up.clone(),
+    };
+    let stdout = Hangup {
+        inner: tokio::io::stdout(),
+        hung_up: hung_up.clone(),
+    };
 
-    let (service, socket) = LspService::new(|client| {
-        let mut server = ClaudeCodeLanguageServer::new(client, worktree.clone());
+    let mut options_sender = options_sender;
+    let (service, socket) = LspService::build(|client| {
+        let mut server = ClaudeCodeLanguageServer::new(client, worktree.clone())
+            .with_shared_state(state.clone());
         if let Some(sender) = notification_sender.clone() {
             server = server.with_notification_sender(sender);
         }
+        if let Some(sender) = options_sender.take() {
+            server = server.with_options_sender(sender);
+        }
         server
-    });
-    Server::new(stdin, stdout, socket).serve(service).await;
+    })
+    .custom_method(
+        SELECTION_DID_CHANGE,
+        ClaudeCodeLanguageServer::selection_did_change,
+    )
+    .custom_method(
+        PUBLISH_DIAGNOSTICS,
+        ClaudeCodeLanguageServer::client_published_diagnostics,
+    )
+    .custom_method(AT_MENTION, ClaudeCodeLanguageServer::at_mention)
+    .finish();
+    let exited = Arc::new(Notify::new());
+    let service = ExitSignal {
+        inner: service,
+        exited: exited.clone(),
+    };
+    // Ending the LSP session shuts the server down the same way as Zed's `exit`
+    tokio::select! {
+        _ = Server::new(stdin, stdout, socket).serve(service) => {}
+        _ = exited.notified() => info!("Exit notification received"),
+        _ = watchdog => {}
+        _ = hung_up.notified() => error!("Zed closed the LSP connection, shutting down"),
+    }
 
     info!("LSP server stopped");
     Ok(())
 }
+
+/// Passes messages through to the language server, signalling once Zed sent `exit`.
+/// tower-lsp only stops serving at the next message or when stdin closes, which Zed may
+/// not do before killing the process.
+struct ExitSignal<S> {
+    inner: S,
+    exited: Arc<Notify>,
+}
+
+impl<S: Service<Request>> Service<Request> for ExitSignal<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let exit = request.method() == "exit";
+        let response = self.inner.call(request);
+        if exit {
+            self.exited.notify_one();
+        }
+        response
+    }
+}
+
+/// Stdin or stdout of the LSP session, signalling when Zed's end of the pipe is gone: stdin
+/// reaches EOF or either fails. tower-lsp stops serving at EOF only once every pending
+/// request finished, which a request waiting on Zed never does.
+struct Hangup<T> {
+    inner: T,
+    hung_up: Arc<Notify>,
+}
+
+impl<T> Hangup<T> {
+    fn check<R>(&self, result: &Poll<io::Result<R>>, eof: bool) {
+        match result {
+            Poll::Ready(Err(e)) => {
+                warn!("LSP connection failed: {}", e);
+                self.hung_up.notify_one();
+            }
+            Poll::Ready(Ok(_)) if eof => {
+                info!("LSP input closed");
+                self.hung_up.notify_one();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Hangup<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let eof = buf.filled().len() == before && buf.remaining() > 0;
+        self.check(&result, eof);
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Hangup<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.check(&result, false);
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_flush(cx);
+        self.check(&result, false);
+        result
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -1,17 +1,63 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::net::IpAddr;
 use std::path::PathBuf;
-use tracing::{error, info};
+use std::time::Duration;
+use tracing::{error, info, warn};
 
+mod claude_commands;
+mod config;
+mod connection_stats;
+mod context;
+mod control;
+mod diagnostics;
+mod diff_tabs;
+mod documents;
+mod editor;
+mod encoding;
+mod event_tap;
+mod file_index;
+mod file_locks;
+mod forward;
+mod hooks;
+mod i18n;
+mod journal;
+mod languages;
+mod line_endings;
+mod logging;
 mod lsp;
 mod mcp;
+mod mention;
+mod os_notify;
+mod rate_limit;
+mod service;
+mod state;
+mod symbols;
+mod systemd;
+mod takeover;
+mod tool_cache;
+mod trust;
 mod websocket;
 
+use config::ClaudeCodeOptions;
+use context::SessionContext;
 use lsp::{run_lsp_server, run_lsp_server_with_notifications};
-use websocket::{cleanup_lock_file, run_websocket_server, run_websocket_server_full};
+use mention::MentionTarget;
+use state::{ServerMode, SharedState, Teardown};
+use websocket::{
+    cleanup_lock_file, run_websocket_server, run_websocket_server_full, AdvertiseSettings,
+    WebSocketSettings,
+};
+
+/// How long hybrid mode waits for the WebSocket server to close its sessions and remove its
+/// lock file after the LSP session ended
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long hybrid mode waits for the LSP `initialize` request before binding with defaults
+const INIT_OPTIONS_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Parser)]
-#[command(name = "claude-code-server")]
+#[command(name = "claude-code-server", version)]
 #[command(about = "Claude Code Server - WebSocket and LSP server for Claude Code integration")]
 struct Cli {
     #[command(subcommand)]
@@ -24,6 +70,24 @@ struct Cli {
     /// Worktree root path (for LSP mode)
     #[arg(long)]
     worktree: Option<PathBuf>,
+
+    /// Have write tools describe the changes they would make instead of making them
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Reject WebSocket clients that don't request the `mcp` subprotocol
+    #[arg(long, global = true)]
+    strict_subprotocol: bool,
+
+    /// Give file paths in tool responses relative to the worktree, with the absolute path
+    /// in a separate `absolutePath` field
+    #[arg(long, global = true)]
+    relative_paths: bool,
+
+    /// Token admitting read-only MCP sessions that can observe but not change anything;
+    /// sessions must then present it or the lock file's token
+    #[arg(long, global = true)]
+    read_only_token: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -39,12 +103,89 @@ enum Mode {
         /// WebSocket server port (default: 59791)
         #[arg(long, short)]
         port: Option<u16>,
+        /// Serve on the socket passed by systemd socket activation (LISTEN_FDS)
+        #[arg(long)]
+        systemd_socket: bool,
+    },
+    /// Run a long-lived service hosting one WebSocket session per worktree
+    Service {
+        /// Worktree to serve (repeat for several)
+        #[arg(long = "worktree", required = true)]
+        worktrees: Vec<PathBuf>,
+        /// Serve on the sockets passed by systemd socket activation, one per worktree in order
+        #[arg(long)]
+        systemd_socket: bool,
+    },
+    /// Run inside a container for a Claude CLI on the host, advertising host-side paths and port
+    Container {
+        /// Port to bind inside the container (default: first free port in the default range)
+        #[arg(long, short)]
+        port: Option<u16>,
+        /// Address to bind; must be reachable through the container's port mapping
+        #[arg(long, default_value = "0.0.0.0")]
+        bind: IpAddr,
+        /// Host-mounted directory to write the lock file to (the host's ~/.claude/ide)
+        #[arg(long)]
+        lock_dir: PathBuf,
+        /// Workspace path on the host, written to the lock file
+        #[arg(long)]
+        host_workspace: PathBuf,
+        /// Host port mapped to the container port (`docker run -p <host>:<container>`)
+        #[arg(long)]
+        host_port: Option<u16>,
+    },
+    /// Relay a local port to a server on a remote host (e.g. an SSH remote project)
+    Forward {
+        /// Address of the remote server, usually the local end of an SSH tunnel (`ssh -L`)
+        #[arg(long)]
+        remote: String,
+        /// Local port to listen on (default: first free port in the default range)
+        #[arg(long, short)]
+        port: Option<u16>,
+        /// Workspace folder advertised to Claude (default: current directory)
+        #[arg(long)]
+        workspace: Option<PathBuf>,
+        /// Auth token to advertise, e.g. the one from the remote server's lock file
+        #[arg(long)]
+        auth_token: Option<String>,
+    },
+    /// Trust a workspace, enabling write tools and linters for it
+    Trust {
+        /// Workspace root to trust (default: current directory)
+        path: Option<PathBuf>,
+    },
+    /// Print the session context recorded for the worktree (default: current directory)
+    Context {
+        /// IDs of entries to remove before printing
+        #[arg(long)]
+        prune: Vec<u64>,
+    },
+    /// Share a file, a line range or the editor's selection with Claude through the server
+    /// running for the worktree (default: current directory)
+    Mention {
+        /// File to mention, absolute or relative to the worktree
+        #[arg(required_unless_present_any = ["selection", "range"])]
+        file: Option<PathBuf>,
+        /// 1-based inclusive line range, as `START-END` or a single line
+        #[arg(long, value_parser = mention::parse_line_range, requires = "file")]
+        lines: Option<(u32, u32)>,
+        /// Mention the current selection in the editor instead of a file
+        #[arg(long, conflicts_with = "file")]
+        selection: bool,
+        /// Mention several files or ranges at once, each as `FILE[:START-END]`; they reach
+        /// Claude in a single notification
+        #[arg(long, value_parser = mention::parse_file_range, conflicts_with_all = ["file", "selection"])]
+        range: Vec<(PathBuf, Option<(u32, u32)>)>,
     },
     /// Run both LSP and WebSocket servers
     Hybrid {
         /// WebSocket server port (default: 59791)
         #[arg(long, short)]
         port: Option<u16>,
+        /// Inclusive port range to allocate the WebSocket server from, unless the
+        /// initialization options set one
+        #[arg(long, num_args = 2, value_names = ["START", "END"])]
+        port_range: Option<Vec<u16>>,
         /// Worktree root path
         #[arg(long)]
         worktree: Option<PathBuf>,
@@ -60,39 +201,113 @@ async fn main() -> Result<()> {
         tracing::Level::DEBUG
     } else {
         // Check environment variable for log level override
-        match std::env::var("RUST_LOG").as_deref() {
-            Ok("trace") => tracing::Level::TRACE,
-            Ok("debug") => tracing::Level::DEBUG,
-            Ok("info") => tracing::Level::INFO,
-            Ok("warn") => tracing::Level::WARN,
-            Ok("error") => tracing::Level::ERROR,
-            _ => tracing::Level::INFO,
-        }
+        std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|level| logging::parse_level(&level))
+            .unwrap_or(tracing::Level::INFO)
     };
-
-    let subscriber = tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_file(true)
-        .with_line_number(true)
-        .with_thread_ids(true)
-        .with_target(false)
-        .with_writer(std::io::stderr) // Force all logs to stderr for LSP compatibility
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
-
-    info!("Logging initialized at level: {:?}", log_level);
+    logging::init(log_level)?;
 
     info!("Claude Code Server starting...");
+    if cli.dry_run {
+        info!("Dry run: write tools will only describe their changes");
+        state::set_dry_run(true);
+    }
+    if cli.relative_paths {
+        state::set_relative_paths_flag(true);
+    }
+    if cli.strict_subprotocol {
+        state::set_strict_subprotocol(true);
+    }
+    state::set_read_only_token(cli.read_only_token.clone());
 
     match cli.mode {
         Some(Mode::Lsp { worktree }) => {
             let worktree_path = cli.worktree.or(worktree);
             run_lsp_server(worktree_path).await
         }
-        Some(Mode::Websocket { port }) => run_websocket_server(port).await,
-        Some(Mode::Hybrid { port, worktree }) => {
+        Some(Mode::Websocket {
+            port,
+            systemd_socket: false,
+        }) => run_websocket_server(port).await,
+        Some(Mode::Websocket {
+            port,
+            systemd_socket: true,
+        }) => {
+            let settings = WebSocketSettings {
+                listener: systemd::take_listeners()?.into_iter().next(),
+                ..WebSocketSettings::default()
+            };
+            let state = SharedState::new(ServerMode::WebSocket, cli.worktree.as_deref());
+            run_websocket_server_full(port, cli.worktree, None, None, settings, state).await
+        }
+        Some(Mode::Service {
+            worktrees,
+            systemd_socket,
+        }) => service::run_service(worktrees, systemd_socket).await,
+        Some(Mode::Container {
+            port,
+            bind,
+            lock_dir,
+            host_workspace,
+            host_port,
+        }) => {
+            let settings = WebSocketSettings {
+                bind_address: bind,
+                advertise: AdvertiseSettings {
+                    lock_dir: Some(lock_dir),
+                    port: host_port.or(port),
+                    workspace: Some(host_workspace),
+                    ..AdvertiseSettings::default()
+                },
+                ..WebSocketSettings::default()
+            };
+            let state = SharedState::new(ServerMode::WebSocket, cli.worktree.as_deref());
+            run_websocket_server_full(port, cli.worktree, None, None, settings, state).await
+        }
+        Some(Mode::Forward {
+            remote,
+            port,
+            workspace,
+            auth_token,
+        }) => forward::run_forwarder(remote, port, workspace, auth_token).await,
+        Some(Mode::Trust { path }) => {
+            let path = match path {
+                Some(path) => path,
+                None => std::env::current_dir()?,
+            };
+            trust::trust_workspace(&path)
+        }
+        Some(Mode::Context { prune }) => {
+            let mut context = SessionContext::load(cli.worktree.as_deref());
+            if !prune.is_empty() {
+                info!("Pruned {} entries", context.prune(&prune));
+                context.flush();
+            }
+            println!("{}", serde_json::to_string_pretty(context.entries())?);
+            Ok(())
+        }
+        Some(Mode::Mention {
+            file,
+            lines,
+            selection,
+            range,
+        }) => {
+            let target = match file {
+                _ if !range.is_empty() => MentionTarget::Ranges(range),
+                Some(path) if !selection => MentionTarget::File { path, lines },
+                _ => MentionTarget::Selection,
+            };
+            mention::run_mention(cli.worktree, target).await
+        }
+        Some(Mode::Hybrid {
+            port,
+            port_range,
+            worktree,
+        }) => {
             let worktree_path = cli.worktree.or(worktree);
-            run_hybrid_server(port, worktree_path).await
+            let port_range = port_range.map(|range| (range[0], range[1]));
+            run_hybrid_server(port, port_range, worktree_path).await
         }
         None => {
             // Default mode: try to detect what we should run based on arguments
@@ -101,35 +316,88 @@ async fn main() -> Result<()> {
                 run_lsp_server(cli.worktree).await
             } else {
                 info!("No mode specified, running in hybrid mode...");
-                run_hybrid_server(None, cli.worktree).await
+                run_hybrid_server(None, None, cli.worktree).await
             }
         }
     }
 }
 
-async fn run_hybrid_server(port: Option<u16>, worktree: Option<PathBuf>) -> Result<()> {
+async fn run_hybrid_server(
+    port: Option<u16>,
+    port_range: Option<(u16, u16)>,
+    worktree: Option<PathBuf>,
+) -> Result<()> {
     info!("Starting hybrid server (LSP + WebSocket)");
     if let Some(path) = &worktree {
         info!("Worktree path: {}", path.display());
     }
 
-    // Create notification channel for LSP -> WebSocket communication
-    let (notification_sender, notification_receiver) = tokio::sync::broadcast::channel(100);
-    let notification_sender = std::sync::Arc::new(notification_sender);
-
     // Create channel to receive the actual bound port from WebSocket server
     let (port_sender, port_receiver) = tokio::sync::oneshot::channel::<u16>();
 
+    // Create channel to receive the extension's initialization options from the LSP server
+    let (options_sender, options_receiver) = tokio::sync::oneshot::channel::<ClaudeCodeOptions>();
+
     // In hybrid mode, we run both servers with notification bridge
-    let websocket_handle = tokio::spawn(run_websocket_server_full(
-        port,
+    // Editor state tracked by the LSP server and read by MCP tools
+    let state = SharedState::new(ServerMode::Hybrid, worktree.as_deref());
+
+    // Notification channel for LSP -> WebSocket communication, owned by the shared state
+    let notification_sender = state.notification_sender();
+    let notification_receiver = state.subscribe_notifications();
+
+    let mut lsp_handle = tokio::spawn(run_lsp_server_with_notifications(
         worktree.clone(),
-        Some(notification_receiver),
-        Some(port_sender),
+        Some(notification_sender),
+        Some(options_sender),
+        state.clone(),
     ));
-    let lsp_handle = tokio::spawn(run_lsp_server_with_notifications(
+
+    // The WebSocket server is configured from the extension's options, so wait for them
+    let options = match tokio::time::timeout(INIT_OPTIONS_TIMEOUT, options_receiver).await {
+        Ok(Ok(options)) => options,
+        _ => {
+            warn!("No LSP initialization options received, using default WebSocket settings");
+            ClaudeCodeOptions::default()
+        }
+    };
+
+    let mut settings = WebSocketSettings::from_options(&options);
+    if let (None, Some((start, end))) = (options.port_range(), port_range) {
+        if start <= end {
+            settings.port_start = start;
+            settings.port_end = end;
+        } else {
+            warn!("Ignoring invalid port range {}-{}", start, end);
+        }
+    }
+
+    // With `autoStart` off, Claude can't connect until the user starts the server
+    if !options.websocket.auto_start {
+        info!("Not starting the WebSocket server until claude-code.start is run");
+        let start = state.defer_websocket_start();
+        tokio::select! {
+            _ = start => info!("Starting the WebSocket server"),
+            result = &mut lsp_handle => {
+                match result {
+                    Ok(Ok(())) => info!("LSP server completed"),
+                    Ok(Err(e)) => error!("LSP server error: {}", e),
+                    Err(e) => error!("LSP server task panicked: {}", e),
+                }
+                // No lock file was written, so there is nothing to clean up
+                info!("Shut down, exiting");
+                std::process::exit(0);
+            }
+        }
+    }
+
+    let mut websocket_handle = tokio::spawn(run_websocket_server_full(
+        port,
         worktree,
-        Some(notification_sender),
+        Some(notification_receiver),
+        Some(port_sender),
+        settings,
+        state.clone(),
     ));
 
     // Wait to receive the actual port from WebSocket server
@@ -140,29 +408,52 @@ async fn run_hybrid_server(port: Option<u16>, worktree: Option<PathBuf>) -> Resu
 
     // Wait for either to complete (or fail)
     tokio::select! {
-        result = websocket_handle => {
+        result = &mut websocket_handle => {
             match result {
+                // Zed's stdio may still be open, so don't wait for the LSP to end
+                Ok(Ok(())) if state.teardown() == Some(Teardown::Takeover) => {
+                    info!("Handed the worktree over to a new server, exiting");
+                    std::process::exit(0);
+                }
+                // `shutdown` closed the sessions; the LSP answers until Zed sends `exit`
+                Ok(Ok(())) if state.teardown() == Some(Teardown::Shutdown) => {
+                    info!("WebSocket server closed, waiting for the LSP session to end");
+                    let _ = (&mut lsp_handle).await;
+                    info!("Shut down, exiting");
+                    std::process::exit(0);
+                }
                 Ok(Ok(())) => info!("WebSocket server completed"),
                 Ok(Err(e)) => error!("WebSocket server error: {}", e),
                 Err(e) => error!("WebSocket server task panicked: {}", e),
             }
         }
-        result = lsp_handle => {
+        result = &mut lsp_handle => {
             match result {
                 Ok(Ok(())) => info!("LSP server completed"),
                 Ok(Err(e)) => error!("LSP server error: {}", e),
                 Err(e) => error!("LSP server task panicked: {}", e),
             }
 
-            // LSP server exited - clean up the WebSocket server's lock file
-            if let Some(p) = actual_port {
-                info!("LSP server exited, cleaning up lock file for port {}", p);
-                if let Err(e) = cleanup_lock_file(p).await {
-                    error!("Failed to cleanup lock file: {}", e);
+            // Zed sent `exit` or closed stdio: close Claude's sessions and remove the lock
+            // file, which `shutdown` usually started already
+            state.start_shutdown();
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut websocket_handle)
+                .await
+                .is_err()
+            {
+                // Fall back to removing the lock file, wherever the server has moved since
+                if let Some(p) = state.websocket_port().or(actual_port) {
+                    warn!("WebSocket server did not stop, cleaning up lock file for port {}", p);
+                    if let Err(e) = cleanup_lock_file(p).await {
+                        error!("Failed to cleanup lock file: {}", e);
+                    }
                 }
             }
+            // Reading stdin blocks a runtime thread that would keep the process alive
+            info!("Shut down, exiting");
+            std::process::exit(0);
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
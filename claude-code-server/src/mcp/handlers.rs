@@ -2,12 +2,17 @@ use anyhow::Result;
 use serde_json::Value;
 use tracing::{debug, info};
 
-use super::tools::dispatch_tool;
+use super::tools::{dispatch_tool, is_dry_run, is_idempotent, tool_definitions, tool_matrix};
 use super::types::{
     LoggingCapability, MCPError, MCPRequest, MCPResponse, PromptsCapability, ServerCapabilities,
-    ServerInfo, Tool, ToolsCapability,
+    ServerInfo, TextContent, ToolsCapability,
 };
 use super::MCPServer;
+use crate::config::HookEvent;
+use crate::hooks::run_hooks;
+
+/// MCP protocol version implemented by this server
+const PROTOCOL_VERSION: &str = "2024-11-05";
 
 impl MCPServer {
     pub async fn handle_request(&self, request: MCPRequest) -> Result<MCPResponse> {
@@ -46,13 +51,34 @@ impl MCPServer {
     async fn handle_initialize(&self, params: Option<Value>) -> Result<Value> {
         info!("Initializing MCP session");
 
-        if let Some(params) = params {
+        if let Some(params) = &params {
             debug!("Initialize params: {}", params);
+            if let Some(requested) = params.get("protocolVersion").and_then(|v| v.as_str()) {
+                info!(
+                    "Client requested protocol version {}, using {}",
+                    requested, PROTOCOL_VERSION
+                );
+            }
         }
+        *self.protocol_version.write().await = Some(PROTOCOL_VERSION.to_string());
+
+        // Advertise which mode we run in and how each tool is served, so clients can
+        // tell live editor data from disk fallbacks
+        let mut capabilities = serde_json::to_value(&self.capabilities)?;
+        capabilities["experimental"] = serde_json::json!({
+            "zed": {
+                "mode": self.state.mode().as_str(),
+                "lspAttached": self.state.lsp_attached(),
+                "trusted": self.state.is_trusted(),
+                "readOnly": self.read_only,
+                "toolBackends": tool_matrix(&self.state, self.read_only)
+            }
+        });
 
         Ok(serde_json::json!({
-            "protocolVersion": "2024-11-05",
-            "capabilities": self.capabilities,
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": capabilities,
+            "degradedCapabilities": self.state.degraded_capabilities(),
             "serverInfo": ServerInfo {
                 name: "claude-code-server".to_string(),
                 version: "0.1.0".to_string()
@@ -63,50 +89,7 @@ impl MCPServer {
     async fn handle_tools_list(&self) -> Result<Value> {
         info!("Listing available tools");
 
-        // Only list tools that are actually implemented and working
-        let tools: Vec<Tool> = vec![
-            Tool {
-                name: "getCurrentSelection".to_string(),
-                description: Some("Get the current text selection in the active editor".to_string()),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }),
-            },
-            Tool {
-                name: "getLatestSelection".to_string(),
-                description: Some("Get the most recent text selection".to_string()),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }),
-            },
-            Tool {
-                name: "getWorkspaceFolders".to_string(),
-                description: Some("Get the workspace folders open in the IDE".to_string()),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }),
-            },
-            Tool {
-                name: "getDiagnostics".to_string(),
-                description: Some("Get diagnostics (errors, warnings) for files in the workspace".to_string()),
-                input_schema: serde_json::json!({
-                    "type": "object",
-                    "properties": {
-                        "uri": {
-                            "type": "string",
-                            "description": "Optional URI of a specific file to get diagnostics for"
-                        }
-                    },
-                    "required": []
-                }),
-            },
-        ];
+        let tools = tool_definitions(&self.state, self.read_only);
 
         Ok(serde_json::json!({
             "tools": tools
@@ -126,16 +109,93 @@ impl MCPServer {
 
         info!("Calling tool: {}", tool_name);
         debug!("Tool arguments: {}", arguments);
+        let _progress = self.state.editor.progress(format!("Claude: {}", tool_name));
+
+        // A dry run describes the hooks it would run instead of running them
+        let hooks = if is_dry_run(tool_name, arguments) {
+            Vec::new()
+        } else {
+            self.state.hooks()
+        };
+        let worktree = self.worktree.as_deref();
+        let pre_payload = serde_json::json!({
+            "event": HookEvent::PreToolCall.as_str(),
+            "tool": tool_name,
+            "arguments": arguments
+        });
+        if let Err(reason) = run_hooks(
+            &hooks,
+            HookEvent::PreToolCall,
+            tool_name,
+            &pre_payload,
+            worktree,
+        )
+        .await
+        {
+            return Ok(serde_json::json!({
+                "content": [TextContent {
+                    type_: "text".to_string(),
+                    text: format!("BLOCKED_BY_HOOK: {}", reason),
+                }],
+                "isError": true
+            }));
+        }
 
-        let content =
-            dispatch_tool(tool_name, arguments, &self.selection_state, &self.worktree).await?;
+        let result = self.call_tool(tool_name, arguments).await;
+
+        let mut post_payload = serde_json::json!({
+            "event": HookEvent::PostToolCall.as_str(),
+            "tool": tool_name,
+            "arguments": arguments,
+            "isError": result.is_err()
+        });
+        match &result {
+            Ok(content) => post_payload["content"] = content.clone(),
+            Err(e) => post_payload["error"] = serde_json::json!(e.to_string()),
+        }
+        // Post-call hook failures are logged and never change the tool result
+        let _ = run_hooks(
+            &hooks,
+            HookEvent::PostToolCall,
+            tool_name,
+            &post_payload,
+            worktree,
+        )
+        .await;
 
         Ok(serde_json::json!({
-            "content": content,
+            "content": result?,
             "isError": false
         }))
     }
 
+    /// Run a tool, serving idempotent ones from the result cache while fresh
+    async fn call_tool(&self, tool_name: &str, arguments: &Value) -> Result<Value> {
+        let cacheable = is_idempotent(tool_name);
+        if cacheable {
+            if let Some(content) = self
+                .state
+                .tool_cache
+                .lock()
+                .unwrap()
+                .get(tool_name, arguments)
+            {
+                debug!("Serving {} from the tool cache", tool_name);
+                return Ok(content);
+            }
+        }
+
+        let content = serde_json::to_value(dispatch_tool(tool_name, arguments, self).await?)?;
+        if cacheable {
+            self.state
+                .tool_cache
+                .lock()
+                .unwrap()
+                .insert(tool_name, arguments, content.clone());
+        }
+        Ok(content)
+    }
+
     async fn handle_logging_set_level(&self, params: Option<Value>) -> Result<Value> {
         if let Some(params) = params {
             let level = params
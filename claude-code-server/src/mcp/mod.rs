@@ -5,4 +5,5 @@ pub mod types;
 
 // Re-export public items
 pub use server::MCPServer;
+pub use tools::is_observation_tool;
 pub use types::{MCPError, MCPRequest, MCPResponse};
@@ -1,8 +1,14 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tower_lsp::lsp_types::{Position, Range};
+use tracing::debug;
 
-use crate::lsp::NotificationReceiver;
+use crate::lsp::{
+    read_text_from_range, FileSavedNotification, JsonRpcNotification, NotificationReceiver,
+    PositionEncoding,
+};
+use crate::state::SharedState;
 
 use super::handlers::create_capabilities;
 use super::types::{SelectionState, ServerCapabilities};
@@ -11,16 +17,22 @@ pub struct MCPServer {
     pub(crate) capabilities: ServerCapabilities,
     pub(crate) selection_state: Arc<RwLock<Option<SelectionState>>>,
     pub(crate) worktree: Option<PathBuf>,
+    pub(crate) state: SharedState,
+    /// MCP protocol version agreed during `initialize`
+    pub(crate) protocol_version: RwLock<Option<String>>,
+    /// Whether the session may only use observation tools, e.g. a pairing teammate's Claude
+    pub(crate) read_only: bool,
 }
 
 impl MCPServer {
     pub fn new() -> Self {
-        Self::with_notifications(None, None)
+        Self::with_notifications(None, None, SharedState::default())
     }
 
     pub fn with_notifications(
         receiver: Option<NotificationReceiver>,
         worktree: Option<PathBuf>,
+        state: SharedState,
     ) -> Self {
         let capabilities = create_capabilities();
         let selection_state = Arc::new(RwLock::new(None));
@@ -30,13 +42,7 @@ impl MCPServer {
             let state = selection_state.clone();
             tokio::spawn(async move {
                 while let Ok(notification) = rx.recv().await {
-                    if notification.method == "selection_changed" {
-                        if let Ok(selection) =
-                            serde_json::from_value::<SelectionState>(notification.params.clone())
-                        {
-                            *state.write().await = Some(selection);
-                        }
-                    }
+                    track_notification(&state, &notification).await;
                 }
             });
         }
@@ -45,8 +51,70 @@ impl MCPServer {
             capabilities,
             selection_state,
             worktree,
+            state,
+            protocol_version: RwLock::new(None),
+            read_only: false,
         }
     }
+
+    /// Restrict the session to observation tools
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+}
+
+impl MCPServer {
+    /// Update session state from an IDE notification delivered outside the broadcast channel
+    pub async fn apply_notification(&self, notification: &JsonRpcNotification) {
+        track_notification(&self.selection_state, notification).await;
+    }
+}
+
+async fn track_notification(
+    state: &RwLock<Option<SelectionState>>,
+    notification: &JsonRpcNotification,
+) {
+    match notification.method.as_str() {
+        "selection_changed" => {
+            if let Ok(selection) =
+                serde_json::from_value::<SelectionState>(notification.params.clone())
+            {
+                *state.write().await = Some(selection);
+            }
+        }
+        "file_saved" => {
+            if let Ok(saved) =
+                serde_json::from_value::<FileSavedNotification>(notification.params.clone())
+            {
+                refresh_selection_text(state, &saved.file_path).await;
+            }
+        }
+        "session_reset" => *state.write().await = None,
+        _ => {}
+    }
+}
+
+/// Re-read the tracked selection's text if it belongs to a file that was just saved
+async fn refresh_selection_text(state: &RwLock<Option<SelectionState>>, file_path: &str) {
+    let mut state = state.write().await;
+    if let Some(selection) = state.as_mut().filter(|s| s.file_path == file_path) {
+        let range = Range {
+            start: Position::new(
+                selection.selection.start.line,
+                selection.selection.start.character,
+            ),
+            end: Position::new(
+                selection.selection.end.line,
+                selection.selection.end.character,
+            ),
+        };
+        // Selections reach Claude in UTF-16 positions
+        let refreshed = read_text_from_range(file_path, range, PositionEncoding::Utf16);
+        selection.text = refreshed.text;
+        selection.encoding = refreshed.encoding;
+        debug!("Refreshed selection text after save of {}", file_path);
+    }
 }
 
 impl Default for MCPServer {
@@ -0,0 +1,90 @@
+use tracing::info;
+
+use crate::mcp::types::TextContent;
+use crate::state::SharedState;
+
+use super::is_dry_run;
+
+/// Change sets a crash interrupted while their files were being written, with how far
+/// each file got
+pub fn get_interrupted_edits(state: &SharedState) -> Vec<TextContent> {
+    let edits: Vec<serde_json::Value> = state
+        .journal
+        .interrupted()
+        .iter()
+        .map(|entry| {
+            let mut edit = entry.to_json();
+            for file in edit["files"].as_array_mut().into_iter().flatten() {
+                state.relativize_file_path(file);
+            }
+            edit
+        })
+        .collect();
+    info!("Found {} interrupted edits", edits.len());
+
+    let response = serde_json::json!({ "edits": edits });
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
+
+/// Restore the files of an interrupted change set to the checkpoint taken before it, or
+/// with `discard` forget it and keep the files as they are
+pub async fn restore_checkpoint(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let id = arguments
+        .get("id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: id"))?;
+    let flag = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    };
+
+    let Some(entry) = state
+        .journal
+        .interrupted()
+        .into_iter()
+        .find(|entry| entry.id == id)
+    else {
+        anyhow::bail!("No interrupted edit with id {}", id);
+    };
+    if is_dry_run("restoreCheckpoint", arguments) {
+        let mut response = entry.to_json();
+        response["dryRun"] = true.into();
+        return Ok(vec![TextContent {
+            type_: "text".to_string(),
+            text: response.to_string(),
+        }]);
+    }
+
+    let response = if flag("discard") {
+        state.journal.discard(id)?;
+        info!("Discarded interrupted edit {}", id);
+        serde_json::json!({ "success": true, "discarded": id })
+    } else {
+        let mut guards = Vec::with_capacity(entry.files.len());
+        for file in &entry.files {
+            guards.push(state.file_locks.lock(&file.path).await);
+        }
+        let restored = state.journal.restore(id, flag("force"))?;
+        drop(guards);
+        state.invalidate_tool_cache();
+        info!(
+            "Restored {} files of interrupted edit {}",
+            restored.len(),
+            id
+        );
+        serde_json::json!({ "success": true, "restored": restored })
+    };
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
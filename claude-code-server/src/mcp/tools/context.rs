@@ -0,0 +1,99 @@
+use tracing::info;
+
+use crate::context::SharedContext;
+use crate::mcp::types::TextContent;
+use crate::mention;
+use crate::state::SharedState;
+
+/// Review the session context, removing any entries listed in `prune` first
+pub async fn get_session_context(
+    arguments: &serde_json::Value,
+    context: &SharedContext,
+) -> Vec<TextContent> {
+    let prune: Vec<u64> = arguments
+        .get("prune")
+        .and_then(|v| v.as_array())
+        .map(|ids| ids.iter().filter_map(|id| id.as_u64()).collect())
+        .unwrap_or_default();
+
+    let mut context = context.write().await;
+    let pruned = if prune.is_empty() {
+        0
+    } else {
+        context.prune(&prune)
+    };
+    info!(
+        "Session context has {} entries ({} pruned)",
+        context.entries().len(),
+        pruned
+    );
+
+    let response = serde_json::json!({
+        "success": true,
+        "entries": context.entries(),
+        "pruned": pruned
+    });
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
+
+/// Forget everything shared with Claude so far, keeping the current selection
+pub async fn clear_context(state: &SharedState) -> Vec<TextContent> {
+    let cleared = state.context.write().await.clear();
+    info!("Cleared {} session context entries", cleared);
+    state.publish("context_cleared", serde_json::json!({ "entries": cleared }));
+
+    let response = serde_json::json!({
+        "success": true,
+        "entries": cleared
+    });
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
+
+/// Start a fresh conversation: clear the session context, the mentions queued for the next
+/// session and, through the `session_reset` notification, every session's tracked selection
+pub async fn reset_session(state: &SharedState) -> Vec<TextContent> {
+    let cleared = state.context.write().await.clear();
+    let dropped = state.clear_queued_notifications();
+    state.forget_selection_sent();
+    info!(
+        "Session reset: cleared {} context entries and {} queued notifications",
+        cleared, dropped
+    );
+    state.publish(
+        "session_reset",
+        serde_json::json!({ "entries": cleared, "notifications": dropped }),
+    );
+
+    let response = serde_json::json!({
+        "success": true,
+        "entries": cleared,
+        "notifications": dropped
+    });
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
+
+/// Share several files or line ranges with Claude in one `at_mentioned_ranges` notification
+pub async fn at_mention_ranges(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let mut response = mention::handle_mention_ranges_request(state, arguments).await?;
+    response["success"] = true.into();
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
@@ -0,0 +1,579 @@
+use crate::mcp::types::Tool;
+use crate::state::SharedState;
+
+/// Where a tool gets its data from in the current session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolBackend {
+    /// Live editor state from the attached Zed language server
+    Lsp,
+    /// Files and temporary documents on disk
+    Disk,
+    /// Information local to the server process
+    Server,
+}
+
+impl ToolBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolBackend::Lsp => "lsp",
+            ToolBackend::Disk => "disk",
+            ToolBackend::Server => "server",
+        }
+    }
+}
+
+/// Feature parity matrix: the backend serving a tool depending on whether the LSP bridge
+/// is attached, or `None` when the tool cannot be served without it
+pub fn tool_backend(tool_name: &str, lsp_attached: bool) -> Option<ToolBackend> {
+    match tool_name {
+        "getCurrentSelection"
+        | "getLatestSelection"
+        | "getOpenEditors"
+        | "checkDocumentDirty"
+        | "getActiveFileContext"
+        | "reportFindings" => lsp_attached.then_some(ToolBackend::Lsp),
+        // Without the LSP only the on-disk language scan is available, no open documents
+        "get_workspace_info" if lsp_attached => Some(ToolBackend::Lsp),
+        "get_workspace_info" | "getWorkspaceFolders" => Some(ToolBackend::Disk),
+        "openDiff"
+        | "searchAndReplace"
+        | "closeAllDiffTabs"
+        | "close_tab"
+        | "openFile"
+        | "getInterruptedEdits"
+        | "getDocumentOutline"
+        | "restoreCheckpoint" => Some(ToolBackend::Disk),
+        _ => Some(ToolBackend::Server),
+    }
+}
+
+/// Tools that write to the workspace, withheld until the user trusts it
+pub fn modifies_workspace(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "openDiff" | "searchAndReplace" | "restoreCheckpoint"
+    )
+}
+
+/// Tools that only observe the editor and workspace, the ones read-only sessions may call
+pub fn is_observation_tool(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "getCurrentSelection"
+            | "getLatestSelection"
+            | "getOpenEditors"
+            | "checkDocumentDirty"
+            | "getActiveFileContext"
+            | "getDocumentOutline"
+            | "getSessionContext"
+            | "getDiagnostics"
+            | "get_workspace_info"
+            | "getWorkspaceFolders"
+            | "exportContextSnapshot"
+            | "getInterruptedEdits"
+            | "openFile"
+            | "echo"
+    )
+}
+
+/// Tools whose results depend only on their arguments and the files on disk, so they can
+/// be cached until files change
+pub fn is_idempotent(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "getDiagnostics" | "get_workspace_info" | "getWorkspaceFolders"
+    )
+}
+
+/// Whether a tool can be served in the current session
+fn is_available(tool_name: &str, state: &SharedState, read_only: bool) -> bool {
+    tool_backend(tool_name, state.lsp_attached()).is_some()
+        && state.is_tool_enabled(tool_name)
+        && (state.is_trusted() || !modifies_workspace(tool_name))
+        && (!read_only || is_observation_tool(tool_name))
+}
+
+/// Definitions of the tools advertised in `tools/list`.
+/// Only list tools that are actually implemented and usable in the current session.
+pub fn tool_definitions(state: &SharedState, read_only: bool) -> Vec<Tool> {
+    all_tool_definitions()
+        .into_iter()
+        .filter(|tool| is_available(&tool.name, state, read_only))
+        .collect()
+}
+
+/// Backend of every known tool in the current session; `unavailable` for tools missing
+/// their backend, `disabled` for tools left out of the `enabledTools` setting and
+/// `restricted` for write tools in an untrusted workspace and tools withheld from
+/// read-only sessions
+pub fn tool_matrix(state: &SharedState, read_only: bool) -> serde_json::Value {
+    all_tool_definitions()
+        .into_iter()
+        .map(|tool| {
+            let backend = match tool_backend(&tool.name, state.lsp_attached()) {
+                None => "unavailable",
+                Some(_) if !state.is_tool_enabled(&tool.name) => "disabled",
+                Some(_) if !is_available(&tool.name, state, read_only) => "restricted",
+                Some(backend) => backend.as_str(),
+            };
+            (tool.name, serde_json::Value::from(backend))
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn all_tool_definitions() -> Vec<Tool> {
+    vec![
+        Tool {
+            name: "getCurrentSelection".to_string(),
+            description: Some(
+                "Get the current text selection in the active editor".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "getLatestSelection".to_string(),
+            description: Some("Get the most recent text selection".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "getOpenEditors".to_string(),
+            description: Some(
+                "List the documents open in Zed, with their language, whether each is the active one and whether it has unsaved changes".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "getActiveFileContext".to_string(),
+            description: Some(
+                "Get the file active in Zed and the lines around where the user was recently \
+                 working, approximating the visible viewport"
+                    .to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "contextLines": {
+                        "type": "integer",
+                        "description": "Lines to include before and after the recently requested lines (default 20)"
+                    }
+                }
+            }),
+        },
+        Tool {
+            name: "checkDocumentDirty".to_string(),
+            description: Some(
+                "Check whether a document open in Zed has unsaved changes".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "filePath": {
+                        "type": "string",
+                        "description": "Absolute path of the document"
+                    }
+                },
+                "required": ["filePath"]
+            }),
+        },
+        Tool {
+            name: "getDocumentOutline".to_string(),
+            description: Some(
+                "Get the functions, types and other declarations in a file, nested as in the source, with their line ranges".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "filePath": {
+                        "type": "string",
+                        "description": "Absolute path of the file"
+                    }
+                },
+                "required": ["filePath"]
+            }),
+        },
+        Tool {
+            name: "getWorkspaceFolders".to_string(),
+            description: Some("Get the workspace folders open in the IDE".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "get_workspace_info".to_string(),
+            description: Some("Get workspace overview: primary languages, open documents per language, and the active file".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "getSessionContext".to_string(),
+            description: Some(
+                "List the files and ranges at-mentioned or read in this session, optionally pruning entries by ID".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "prune": {
+                        "type": "array",
+                        "items": { "type": "number" },
+                        "description": "IDs of entries to remove from the session context"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "clearContext".to_string(),
+            description: Some(
+                "Clear the files and ranges recorded in the session context".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "resetSession".to_string(),
+            description: Some(
+                "Start a fresh conversation: clear the tracked selection, pending at-mentions and session context".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "exportContextSnapshot".to_string(),
+            description: Some(
+                "Export the tracked selection, pending at-mentions and session context as a snapshot for importContextSnapshot".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "importContextSnapshot".to_string(),
+            description: Some(
+                "Restore a snapshot from exportContextSnapshot, e.g. to resume a session on another machine or after a reboot".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "snapshot": {
+                        "type": "object",
+                        "description": "Snapshot returned by exportContextSnapshot"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File containing a saved snapshot, used when snapshot is omitted"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "atMentionRanges".to_string(),
+            description: Some(
+                "Share several files or line ranges with Claude in a single at-mention notification, e.g. every failing test location".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "mentions": {
+                        "type": "array",
+                        "description": "Files and 0-based inclusive line ranges to mention",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "filePath": { "type": "string" },
+                                "lineStart": { "type": "integer" },
+                                "lineEnd": { "type": "integer" }
+                            },
+                            "required": ["filePath"]
+                        }
+                    }
+                },
+                "required": ["mentions"]
+            }),
+        },
+        Tool {
+            name: "focusMode".to_string(),
+            description: Some(
+                "Toggle focus mode: selection updates are no longer sent and proposed diffs are rejected, leaving Claude observing without interacting".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "enabled": {
+                        "type": "boolean",
+                        "description": "Enter (true) or leave (false) focus mode; toggles when omitted"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "echo".to_string(),
+            description: Some(
+                "Connectivity probe returning server version, protocol version, capabilities and round-trip timestamps".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "message": {
+                        "type": "string",
+                        "description": "Optional message echoed back in the response"
+                    },
+                    "timestamp": {
+                        "type": "number",
+                        "description": "Optional client send time (ms since epoch) for round-trip measurement"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "openFile".to_string(),
+            description: Some(
+                "Open a file in Zed, optionally selecting text from startText through endText. With makeFrontmost: false, only describe the file (language, line count, whether it is open in Zed) without focusing it".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "filePath": {
+                        "type": "string",
+                        "description": "Path of the file"
+                    },
+                    "makeFrontmost": {
+                        "type": "boolean",
+                        "description": "Whether to open and focus the file; false only describes it",
+                        "default": true
+                    },
+                    "startText": {
+                        "type": "string",
+                        "description": "Text at which the selection starts"
+                    },
+                    "endText": {
+                        "type": "string",
+                        "description": "Text at which the selection ends; the selection is startText alone when not given or not found"
+                    },
+                    "selectToEndOfLine": {
+                        "type": "boolean",
+                        "description": "Extend the selection to the end of its last line",
+                        "default": false
+                    }
+                },
+                "required": ["filePath"]
+            }),
+        },
+        Tool {
+            name: "openDiff".to_string(),
+            description: Some("Open a diff of proposed changes to a file".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "old_file_path": {
+                        "type": "string",
+                        "description": "Path of the file being changed"
+                    },
+                    "new_file_path": {
+                        "type": "string",
+                        "description": "Path of the file after the change"
+                    },
+                    "new_file_contents": {
+                        "type": "string",
+                        "description": "Proposed contents of the file"
+                    },
+                    "tab_name": {
+                        "type": "string",
+                        "description": "Name of the diff tab"
+                    },
+                    "dryRun": {
+                        "type": "boolean",
+                        "description": "Describe the change (path, hunks and hook commands) without opening the diff"
+                    }
+                },
+                "required": ["old_file_path", "new_file_contents", "tab_name"]
+            }),
+        },
+        Tool {
+            name: "searchAndReplace".to_string(),
+            description: Some(
+                "Replace regex matches across workspace files, or preview the changes".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression to search for"
+                    },
+                    "replacement": {
+                        "type": "string",
+                        "description": "Replacement text; $1 or ${name} insert capture groups"
+                    },
+                    "include": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Globs of files to search (default: all). Globs without '/' match file names, others paths from the workspace root"
+                    },
+                    "exclude": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Globs of files to skip"
+                    },
+                    "preview": {
+                        "type": "boolean",
+                        "description": "Return the changed lines per file without writing them"
+                    },
+                    "dryRun": {
+                        "type": "boolean",
+                        "description": "Same as preview"
+                    }
+                },
+                "required": ["pattern", "replacement"]
+            }),
+        },
+        Tool {
+            name: "getInterruptedEdits".to_string(),
+            description: Some(
+                "List workspace edits a crash interrupted part way through, and which of their files were written"
+                    .to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "restoreCheckpoint".to_string(),
+            description: Some(
+                "Restore the files of an interrupted edit to their contents before it, or discard it"
+                    .to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Id of the interrupted edit, from getInterruptedEdits"
+                    },
+                    "discard": {
+                        "type": "boolean",
+                        "description": "Forget the edit and keep the files as they are"
+                    },
+                    "force": {
+                        "type": "boolean",
+                        "description": "Also restore files that were changed after the edit"
+                    },
+                    "dryRun": {
+                        "type": "boolean",
+                        "description": "Describe the edit without restoring anything"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+        Tool {
+            name: "closeAllDiffTabs".to_string(),
+            description: Some("Close all diff tabs opened by Claude".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "required": []
+            }),
+        },
+        Tool {
+            name: "close_tab".to_string(),
+            description: Some("Close a diff tab by name".to_string()),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "tab_name": {
+                        "type": "string",
+                        "description": "Name of the tab to close"
+                    }
+                },
+                "required": ["tab_name"]
+            }),
+        },
+        Tool {
+            name: "getDiagnostics".to_string(),
+            description: Some(
+                "Get diagnostics (errors, warnings) for files in the workspace".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "uri": {
+                        "type": "string",
+                        "description": "Optional URI of a specific file to get diagnostics for"
+                    }
+                },
+                "required": []
+            }),
+        },
+        Tool {
+            name: "reportFindings".to_string(),
+            description: Some(
+                "Show issues found in a file as diagnostics in Zed, inline with the language servers' own. Replaces the findings reported for the file before; an empty list clears them".to_string(),
+            ),
+            input_schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "filePath": {
+                        "type": "string",
+                        "description": "Path of the file, absolute or relative to the workspace"
+                    },
+                    "findings": {
+                        "type": "array",
+                        "description": "Issues in the file, with 0-based lines and characters",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "line": { "type": "integer" },
+                                "endLine": { "type": "integer" },
+                                "character": {
+                                    "type": "integer",
+                                    "description": "Start of the issue on its line; whole lines when omitted"
+                                },
+                                "endCharacter": { "type": "integer" },
+                                "severity": {
+                                    "type": "string",
+                                    "enum": ["error", "warning", "information", "hint"],
+                                    "description": "Default warning"
+                                },
+                                "message": { "type": "string" },
+                                "code": { "type": "string" }
+                            },
+                            "required": ["line", "message"]
+                        }
+                    }
+                },
+                "required": ["filePath", "findings"]
+            }),
+        },
+    ]
+}
@@ -0,0 +1,353 @@
+use std::path::Path;
+use tower_lsp::lsp_types::Url;
+use tracing::info;
+
+use crate::config::{HookEvent, OsNotificationEvent};
+use crate::diff_tabs::SharedDiffTabs;
+use crate::encoding::read_text_file;
+use crate::file_locks::write_atomic;
+use crate::i18n::Text;
+use crate::line_endings::LineConventions;
+use crate::mcp::types::TextContent;
+use crate::state::SharedState;
+
+use super::is_dry_run;
+
+/// Unchanged lines shown around each change in dry-run hunks
+const HUNK_CONTEXT_LINES: usize = 3;
+
+pub async fn open_diff(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let path = state.resolve_path(required_str(arguments, "old_file_path")?);
+    let old_file_path = path.to_string_lossy();
+    let old_file_path = old_file_path.as_ref();
+    let new_file_contents = required_str(arguments, "new_file_contents")?;
+    let tab_name = required_str(arguments, "tab_name")?;
+
+    // In focus mode Claude observes without changing anything, so edits are denied
+    if state.is_focus_mode() {
+        info!(
+            "Focus mode, rejecting diff '{}' for {}",
+            tab_name, old_file_path
+        );
+        return Ok(diff_rejected(tab_name));
+    }
+
+    info!("Opening diff '{}' for {}", tab_name, old_file_path);
+
+    // Hold the file's lock so a concurrent diff for it can't interleave with this one
+    let _guard = state.file_locks.lock(Path::new(old_file_path)).await;
+
+    // Keep the file's line endings and final newline so the edit doesn't churn every line
+    let original = read_text_file(old_file_path).map(|original| original.text);
+    let new_file_contents = match &original {
+        Ok(original) => LineConventions::detect(original).apply(new_file_contents),
+        Err(_) => new_file_contents.to_string(),
+    };
+
+    if is_dry_run("openDiff", arguments) {
+        info!("Dry run, describing diff '{}' without opening it", tab_name);
+        let commands: Vec<serde_json::Value> = state
+            .hooks()
+            .iter()
+            .filter(|hook| {
+                hook.applies_to(HookEvent::PreToolCall, "openDiff")
+                    || hook.applies_to(HookEvent::PostToolCall, "openDiff")
+            })
+            .map(|hook| {
+                serde_json::json!({
+                    "event": hook.event.as_str(),
+                    "command": hook.command,
+                    "args": hook.args
+                })
+            })
+            .collect();
+        let response = serde_json::json!({
+            "dryRun": true,
+            "tool": "openDiff",
+            "changes": [{
+                "path": old_file_path,
+                "exists": original.is_ok(),
+                "hunks": hunks(
+                    original.as_deref().unwrap_or_default(),
+                    &new_file_contents
+                )
+            }],
+            "commands": commands
+        });
+        return Ok(vec![TextContent {
+            type_: "text".to_string(),
+            text: response.to_string(),
+        }]);
+    }
+
+    state
+        .diff_tabs
+        .write()
+        .await
+        .open(tab_name, Path::new(old_file_path), &new_file_contents)?;
+    state.invalidate_tool_cache();
+    state.notify_os(
+        OsNotificationEvent::DiffAwaitingReview,
+        &state.localize(Text::DiffAwaitingReview, &[&old_file_path]),
+    );
+
+    // Claude only writes the file itself after `FILE_SAVED`, so the content is saved here
+    // once the user accepts it in Zed, and anything else rejects the diff
+    match review_and_save(state, Path::new(old_file_path), &new_file_contents).await {
+        Ok(()) => {
+            info!("Saved diff '{}' to {}", tab_name, old_file_path);
+            Ok(vec![
+                TextContent {
+                    type_: "text".to_string(),
+                    text: "FILE_SAVED".to_string(),
+                },
+                TextContent {
+                    type_: "text".to_string(),
+                    text: new_file_contents,
+                },
+            ])
+        }
+        Err(reason) => {
+            info!(
+                "Rejected diff '{}' for {}: {}",
+                tab_name, old_file_path, reason
+            );
+            state.diff_tabs.write().await.close(tab_name);
+            let mut response = diff_rejected(tab_name);
+            response.push(TextContent {
+                type_: "text".to_string(),
+                text: reason,
+            });
+            Ok(response)
+        }
+    }
+}
+
+fn diff_rejected(tab_name: &str) -> Vec<TextContent> {
+    vec![
+        TextContent {
+            type_: "text".to_string(),
+            text: "DIFF_REJECTED".to_string(),
+        },
+        TextContent {
+            type_: "text".to_string(),
+            text: tab_name.to_string(),
+        },
+    ]
+}
+
+/// Ask the user in Zed whether to keep the proposed contents and write them to `path` if
+/// they accept, returning why the change was not saved otherwise. Zed reloads an open,
+/// unmodified buffer from disk, where the change can be undone. Claude computed the
+/// contents from the file on disk, so a buffer with unsaved changes is never overwritten.
+async fn review_and_save(
+    state: &SharedState,
+    path: &Path,
+    new_contents: &str,
+) -> Result<(), String> {
+    let has_unsaved_changes = || async {
+        let uri = Url::from_file_path(path).ok()?;
+        state
+            .documents
+            .read()
+            .await
+            .get(&uri)
+            .map(|document| document.dirty)
+    };
+    if has_unsaved_changes().await == Some(true) {
+        return Err("the file has unsaved changes in Zed".to_string());
+    }
+
+    let accept = state.localize(Text::DiffAcceptAction, &[]);
+    let actions = vec![accept.clone(), state.localize(Text::DiffRejectAction, &[])];
+    let message = state.localize(Text::DiffReviewPrompt, &[&path.display()]);
+    match state.editor.ask(message, actions).await {
+        Ok(Some(action)) if action == accept => {}
+        Ok(_) => return Err("the user rejected the change".to_string()),
+        Err(e) => return Err(format!("the change could not be reviewed in Zed: {}", e)),
+    }
+    // The buffer may have been edited while the question was open
+    if has_unsaved_changes().await == Some(true) {
+        return Err("the file has unsaved changes in Zed".to_string());
+    }
+
+    write_atomic(path, new_contents).map_err(|e| format!("the file could not be saved: {}", e))
+}
+
+pub async fn close_all_diff_tabs(diff_tabs: &SharedDiffTabs) -> Vec<TextContent> {
+    let closed = diff_tabs.write().await.close_all();
+    info!("Closed {} diff tabs", closed.len());
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: format!("CLOSED_{}_DIFF_TABS", closed.len()),
+    }]
+}
+
+pub async fn close_tab(
+    arguments: &serde_json::Value,
+    diff_tabs: &SharedDiffTabs,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let tab_name = required_str(arguments, "tab_name")?;
+
+    let text = match diff_tabs.write().await.close(tab_name) {
+        Some(tab) => {
+            info!(
+                "Closed diff tab '{}' for {}",
+                tab.tab_name,
+                tab.original_path.display()
+            );
+            "TAB_CLOSED".to_string()
+        }
+        None => format!("TAB_NOT_FOUND: No open tab named '{}'", tab_name),
+    };
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text,
+    }])
+}
+
+fn required_str<'a>(arguments: &'a serde_json::Value, key: &str) -> Result<&'a str, anyhow::Error> {
+    arguments
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: {}", key))
+}
+
+/// Describe how `new` differs from `old` as unified-diff hunks. The changed region is found
+/// by trimming the lines both share at the start and end, which yields a single hunk.
+fn hunks(old: &str, new: &str) -> Vec<String> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_lines[prefix..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix == old_lines.len() && prefix == new_lines.len() {
+        return Vec::new();
+    }
+
+    let start = prefix.saturating_sub(HUNK_CONTEXT_LINES);
+    let old_end = (old_lines.len() - suffix + HUNK_CONTEXT_LINES).min(old_lines.len());
+    let new_end = (new_lines.len() - suffix + HUNK_CONTEXT_LINES).min(new_lines.len());
+
+    let mut hunk = format!(
+        "@@ -{},{} +{},{} @@\n",
+        start + 1,
+        old_end - start,
+        start + 1,
+        new_end - start
+    );
+    for line in &old_lines[start..prefix] {
+        hunk.push_str(&format!(" {}\n", line));
+    }
+    for line in &old_lines[prefix..old_lines.len() - suffix] {
+        hunk.push_str(&format!("-{}\n", line));
+    }
+    for line in &new_lines[prefix..new_lines.len() - suffix] {
+        hunk.push_str(&format!("+{}\n", line));
+    }
+    for line in &old_lines[old_lines.len() - suffix..old_end] {
+        hunk.push_str(&format!(" {}\n", line));
+    }
+    vec![hunk]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::EditorRequest;
+
+    /// A file with `old` contents in a fresh temp directory
+    fn temp_file(old: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("claude-code-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.rs");
+        std::fs::write(&path, old).unwrap();
+        path
+    }
+
+    /// Answer every question Zed is asked with the action at `choice`
+    fn answer_with(state: &SharedState, choice: usize) {
+        let mut requests = state.editor.attach();
+        tokio::spawn(async move {
+            while let Some(request) = requests.recv().await {
+                if let EditorRequest::ShowMessageRequest { actions, reply, .. } = request {
+                    let _ = reply.send(Ok(actions.get(choice).cloned()));
+                }
+            }
+        });
+    }
+
+    async fn propose(state: &SharedState, path: &Path, contents: &str) -> Vec<String> {
+        let arguments = serde_json::json!({
+            "old_file_path": path.to_string_lossy(),
+            "new_file_contents": contents,
+            "tab_name": "proposal"
+        });
+        open_diff(&arguments, state)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|content| content.text)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn open_diff_saves_accepted_changes() {
+        let path = temp_file("old\n");
+        let state = SharedState::default();
+        answer_with(&state, 0);
+
+        let response = propose(&state, &path, "new\n").await;
+        assert_eq!(response, ["FILE_SAVED", "new\n"]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+    }
+
+    #[tokio::test]
+    async fn open_diff_rejects_changes_the_user_rejects() {
+        let path = temp_file("old\n");
+        let state = SharedState::default();
+        answer_with(&state, 1);
+
+        let response = propose(&state, &path, "new\n").await;
+        assert_eq!(response[..2], ["DIFF_REJECTED", "proposal"]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+    }
+
+    #[tokio::test]
+    async fn open_diff_rejects_accepted_changes_it_cannot_write() {
+        let dir = temp_file("").with_file_name("missing");
+        let path = dir.join("new.rs");
+        let state = SharedState::default();
+        answer_with(&state, 0);
+
+        let response = propose(&state, &path, "new\n").await;
+        assert_eq!(response[..2], ["DIFF_REJECTED", "proposal"]);
+        assert!(response[2].contains("could not be saved"));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn open_diff_rejects_changes_without_zed_to_review_them() {
+        let path = temp_file("old\n");
+        let state = SharedState::default();
+
+        let response = propose(&state, &path, "new\n").await;
+        assert_eq!(response[..2], ["DIFF_REJECTED", "proposal"]);
+        assert!(response[2].contains("could not be reviewed"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+    }
+}
@@ -1,19 +1,476 @@
-use std::path::PathBuf;
+use std::path::Path;
+use tower_lsp::lsp_types::{Range, ShowDocumentParams, Url};
 use tracing::info;
 
+use crate::encoding::read_text_file;
+use crate::languages::language_id_for_path;
+use crate::line_endings::LineConventions;
+use crate::lsp::position_at;
 use crate::mcp::types::TextContent;
+use crate::state::SharedState;
+use crate::symbols::{self, OutlineSymbol};
 
-pub fn get_diagnostics(worktree: &Option<PathBuf>) -> Vec<TextContent> {
-    info!("Getting diagnostics for workspace: {:?}", worktree);
+/// Diagnostics stored from the on-save linters and forwarded by the client, for the file
+/// at `uri` or for every file
+pub async fn get_diagnostics(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let store = state.diagnostics.read().await;
+    let files = match arguments.get("uri").and_then(|v| v.as_str()) {
+        Some(uri) => {
+            let uri = Url::parse(uri)
+                .or_else(|_| Url::from_file_path(uri))
+                .map_err(|_| anyhow::anyhow!("Invalid uri: {}", uri))?;
+            vec![uri]
+        }
+        None => store.files().into_iter().cloned().collect(),
+    };
+    let diagnostics: Vec<serde_json::Value> = files
+        .iter()
+        .map(|uri| {
+            let mut entry = serde_json::json!({
+                "uri": uri.to_string(),
+                "filePath": uri.path(),
+                "diagnostics": store.get(uri)
+            });
+            state.relativize_file_path(&mut entry);
+            entry
+        })
+        .collect();
+    info!("Reporting diagnostics for {} files", diagnostics.len());
 
-    // Return empty diagnostics for now
-    // TODO: This could be enhanced to collect diagnostics from the LSP
     let response = serde_json::json!({
-        "diagnostics": []
+        "diagnostics": diagnostics
     });
 
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+/// Describe a file for `openFile` with `makeFrontmost: false`, which asks for the file without
+/// focusing it. Metadata comes from the open document when Zed has the file open, else from disk.
+pub async fn open_file(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let file_path = arguments
+        .get("filePath")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: filePath"))?;
+    info!("Describing file {} without focusing it", file_path);
+
+    let path = state.resolve_path(file_path);
+    let path = path.as_path();
+    let decoded =
+        read_text_file(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", file_path, e))?;
+    let open_language = match Url::from_file_path(path) {
+        Ok(uri) => state
+            .documents
+            .read()
+            .await
+            .get(&uri)
+            .map(|document| document.language_id.clone()),
+        Err(_) => None,
+    };
+    let already_open = open_language.is_some();
+    let language_id = open_language
+        .or_else(|| language_id_for_path(path).map(str::to_string))
+        .unwrap_or_else(|| "plaintext".to_string());
+    let conventions = LineConventions::detect(&decoded.text);
+
+    let mut response = serde_json::json!({
+        "success": true,
+        "filePath": path.to_string_lossy(),
+        "languageId": language_id,
+        "lineCount": decoded.text.lines().count(),
+        "alreadyOpen": already_open,
+        "encoding": decoded.encoding,
+        "lineEnding": conventions.line_ending.as_str(),
+        // Zed cannot focus files for a language server, so the file is only described
+        "focused": false
+    });
+    state.relativize_file_path(&mut response);
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+/// Open a file in Zed for `openFile`, selecting from `startText` through `endText` (or just
+/// `startText`), optionally to the end of that line, when given
+pub async fn show_file(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let file_path = arguments
+        .get("filePath")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: filePath"))?;
+    let path = state.resolve_path(file_path);
+    let uri = Url::from_file_path(&path)
+        .map_err(|_| anyhow::anyhow!("Invalid filePath: {}", file_path))?;
+
+    let selection = match arguments.get("startText").and_then(|v| v.as_str()) {
+        Some(start_text) if !start_text.is_empty() => {
+            let open_text = state
+                .documents
+                .read()
+                .await
+                .get(&uri)
+                .map(|document| document.text.to_string());
+            let text = match open_text {
+                Some(text) => text,
+                None => {
+                    read_text_file(&path)
+                        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", file_path, e))?
+                        .text
+                }
+            };
+            text_selection(
+                &text,
+                start_text,
+                arguments.get("endText").and_then(|v| v.as_str()),
+                arguments
+                    .get("selectToEndOfLine")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            )
+        }
+        _ => None,
+    };
+
+    info!("Opening {} in Zed", file_path);
+    let shown = state
+        .editor
+        .show_document(ShowDocumentParams {
+            uri,
+            external: Some(false),
+            take_focus: Some(true),
+            selection,
+        })
+        .await?;
+
+    let mut response = serde_json::json!({
+        "success": shown,
+        "filePath": path.to_string_lossy(),
+        "selection": selection
+    });
+    state.relativize_file_path(&mut response);
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+/// The range from the first occurrence of `start_text` through the following `end_text`,
+/// or of `start_text` alone when there is no `end_text` after it
+fn text_selection(
+    text: &str,
+    start_text: &str,
+    end_text: Option<&str>,
+    to_line_end: bool,
+) -> Option<Range> {
+    let start = text.find(start_text)?;
+    let mut end = end_text
+        .filter(|end_text| !end_text.is_empty())
+        .and_then(|end_text| {
+            text[start..]
+                .find(end_text)
+                .map(|offset| start + offset + end_text.len())
+        })
+        .unwrap_or(start + start_text.len());
+    if to_line_end {
+        end = text[end..]
+            .find('\n')
+            .map_or(text.len(), |offset| end + offset);
+    }
+    Some(Range {
+        start: position_at(text, start),
+        end: position_at(text, end),
+    })
+}
+
+/// The documents open in Zed as editor tabs, with the active one marked and whether each
+/// has unsaved changes
+pub async fn get_open_editors(state: &SharedState) -> Vec<TextContent> {
+    let documents = state.documents.read().await;
+    let tabs: Vec<serde_json::Value> = documents
+        .documents()
+        .into_iter()
+        .map(|document| {
+            let file_path = document.file_path();
+            let mut tab = serde_json::json!({
+                "uri": document.uri.to_string(),
+                "filePath": file_path,
+                "label": Path::new(&file_path)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default(),
+                "languageId": document.language_id,
+                "isActive": documents.is_active(&document.uri),
+                "isDirty": document.dirty
+            });
+            state.relativize_file_path(&mut tab);
+            tab
+        })
+        .collect();
+    info!("Listing {} open editors", tabs.len());
+
+    let response = serde_json::json!({ "tabs": tabs });
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
+
+/// Whether the open document at `filePath` has unsaved changes, so Claude can warn before
+/// proposing edits that would conflict with them
+pub async fn check_document_dirty(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let file_path = arguments
+        .get("filePath")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: filePath"))?;
+
+    let path = state.resolve_path(file_path);
+    let documents = state.documents.read().await;
+    let document = Url::from_file_path(&path)
+        .ok()
+        .and_then(|uri| documents.get(&uri));
+    let mut response = match document {
+        Some(document) => {
+            info!(
+                "{} is {}",
+                file_path,
+                if document.dirty { "dirty" } else { "clean" }
+            );
+            serde_json::json!({
+                "success": true,
+                "filePath": path.to_string_lossy(),
+                "isDirty": document.dirty,
+                "isUntitled": !path.exists()
+            })
+        }
+        None => serde_json::json!({
+            "success": false,
+            "message": format!("Document not open: {}", file_path)
+        }),
+    };
+    state.relativize_file_path(&mut response);
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+fn outline_json(outline: &[OutlineSymbol]) -> Vec<serde_json::Value> {
+    outline
+        .iter()
+        .map(|symbol| {
+            let mut value = serde_json::json!({
+                "name": symbol.name,
+                "kind": symbols::kind_name(symbol.kind),
+                "startLine": symbol.start_line + 1,
+                "endLine": symbol.end_line + 1
+            });
+            if let Some(container) = &symbol.container {
+                value["container"] = container.clone().into();
+            }
+            if !symbol.children.is_empty() {
+                value["children"] = outline_json(&symbol.children).into();
+            }
+            value
+        })
+        .collect()
+}
+
+/// The declarations in a file, from its Zed buffer when open so unsaved edits count, else
+/// from disk
+pub async fn get_document_outline(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let file_path = arguments
+        .get("filePath")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: filePath"))?;
+    let path = state.resolve_path(file_path);
+    let path = path.as_path();
+
+    let open = match Url::from_file_path(path) {
+        Ok(uri) => state
+            .documents
+            .read()
+            .await
+            .get(&uri)
+            .map(|document| (document.language_id.clone(), document.text.to_string())),
+        Err(_) => None,
+    };
+    let (language_id, text) = match open {
+        Some(open) => open,
+        None => {
+            let decoded = read_text_file(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", file_path, e))?;
+            let language_id = language_id_for_path(path).unwrap_or("plaintext");
+            (language_id.to_string(), decoded.text)
+        }
+    };
+
+    let mut response = match symbols::outline(&language_id, &text) {
+        Some(outline) => {
+            info!("Outline of {}: {} symbols", file_path, outline.len());
+            serde_json::json!({
+                "success": true,
+                "filePath": path.to_string_lossy(),
+                "languageId": language_id,
+                "symbols": outline_json(&outline)
+            })
+        }
+        None => serde_json::json!({
+            "success": false,
+            "message": format!("No outline is available for {} files", language_id)
+        }),
+    };
+    state.relativize_file_path(&mut response);
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+/// Lines shown around the approximate viewport when `contextLines` is not given
+const DEFAULT_CONTEXT_LINES: u32 = 20;
+
+/// The active document and the text around the lines Zed recently made requests for. Zed
+/// doesn't tell language servers what is on screen, so this approximates the viewport.
+pub async fn get_active_file_context(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Vec<TextContent> {
+    let context_lines = arguments
+        .get("contextLines")
+        .and_then(|v| v.as_u64())
+        .map_or(DEFAULT_CONTEXT_LINES, |lines| {
+            lines.min(u32::MAX as u64) as u32
+        });
+
+    let documents = state.documents.read().await;
+    let mut response = match documents.active() {
+        Some(document) => {
+            let total_lines = document.text.len_lines() as u32;
+            let last_line = total_lines.saturating_sub(1);
+            let (focus_start, focus_end) = documents
+                .recent_line_span()
+                .map_or((0, 0), |span| (*span.start(), *span.end()));
+            let focus_end = focus_end.min(last_line);
+            let focus_start = focus_start.min(focus_end);
+            let start = focus_start.saturating_sub(context_lines);
+            let end = focus_end.saturating_add(context_lines).min(last_line);
+            let text = document
+                .text
+                .slice(
+                    document.text.line_to_char(start as usize)
+                        ..document.text.line_to_char(end as usize + 1),
+                )
+                .to_string();
+            info!(
+                "Active file context for {}: lines {}-{}",
+                document.uri,
+                start + 1,
+                end + 1
+            );
+            serde_json::json!({
+                "success": true,
+                "filePath": document.file_path(),
+                "languageId": document.language_id,
+                "isDirty": document.dirty,
+                "totalLines": total_lines,
+                "focusStartLine": focus_start + 1,
+                "focusEndLine": focus_end + 1,
+                "startLine": start + 1,
+                "endLine": end + 1,
+                "text": text
+            })
+        }
+        None => serde_json::json!({
+            "success": false,
+            "message": "No file is active in Zed"
+        }),
+    };
+    state.relativize_file_path(&mut response);
+
     vec![TextContent {
         type_: "text".to_string(),
         text: response.to_string(),
     }]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    const TEXT: &str = "fn one() {\n    body();\n}\nfn two() {}";
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range {
+            start: Position::new(start.0, start.1),
+            end: Position::new(end.0, end.1),
+        }
+    }
+
+    #[test]
+    fn text_selection_spans_the_start_text() {
+        assert_eq!(
+            text_selection(TEXT, "fn two", None, false),
+            Some(range((3, 0), (3, 6)))
+        );
+        assert_eq!(text_selection(TEXT, "fn three", None, false), None);
+    }
+
+    #[test]
+    fn text_selection_extends_to_the_end_text_after_the_start() {
+        assert_eq!(
+            text_selection(TEXT, "fn one", Some("}"), false),
+            Some(range((0, 0), (2, 1)))
+        );
+        // "body" only occurs before "fn two", and an empty end text is ignored
+        assert_eq!(
+            text_selection(TEXT, "fn two", Some("body"), false),
+            Some(range((3, 0), (3, 6)))
+        );
+        assert_eq!(
+            text_selection(TEXT, "fn two", Some(""), false),
+            Some(range((3, 0), (3, 6)))
+        );
+    }
+
+    #[test]
+    fn text_selection_extends_to_the_line_end() {
+        assert_eq!(
+            text_selection(TEXT, "body", None, true),
+            Some(range((1, 4), (1, 11)))
+        );
+        // The last line has no newline to stop at
+        assert_eq!(
+            text_selection(TEXT, "fn two", None, true),
+            Some(range((3, 0), (3, 11)))
+        );
+    }
+
+    #[test]
+    fn text_selection_counts_utf16_characters() {
+        assert_eq!(
+            text_selection("let café = \"😀\";", "\"", Some(";"), false),
+            Some(range((0, 11), (0, 16)))
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, Url};
+use tracing::info;
+
+use crate::mcp::types::TextContent;
+use crate::state::SharedState;
+
+/// Show issues Claude found in a file as diagnostics in Zed, replacing those it reported for
+/// the file before; an empty list clears them
+pub async fn report_findings(
+    arguments: &serde_json::Value,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let file_path = arguments
+        .get("filePath")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: filePath"))?;
+    let findings = arguments
+        .get("findings")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: findings"))?;
+
+    let path = state.resolve_path(file_path);
+    let uri = Url::from_file_path(&path)
+        .map_err(|_| anyhow::anyhow!("Invalid filePath: {}", file_path))?;
+    let diagnostics = findings
+        .iter()
+        .map(finding_diagnostic)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let count = diagnostics.len();
+    let shown = state.report_findings(uri, diagnostics).await;
+    info!(
+        "Claude reported {} findings for {}{}",
+        count,
+        path.display(),
+        if shown { "" } else { ", no editor attached" }
+    );
+
+    let mut response = serde_json::json!({
+        "success": true,
+        "filePath": path.to_string_lossy(),
+        "findings": count,
+        "shownInEditor": shown
+    });
+    state.relativize_file_path(&mut response);
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+/// A diagnostic for one finding: 0-based `line` (through `endLine`), narrowed to
+/// `character`..`endCharacter` when given, else spanning the whole lines. Ends before the
+/// start are moved to it.
+fn finding_diagnostic(finding: &serde_json::Value) -> Result<Diagnostic, anyhow::Error> {
+    let number = |key: &str| finding.get(key).and_then(|v| v.as_u64()).map(|n| n as u32);
+    let message = finding
+        .get("message")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Every finding needs a message"))?;
+    let line = number("line").ok_or_else(|| anyhow::anyhow!("Every finding needs a line"))?;
+    let end_line = number("endLine").unwrap_or(line).max(line);
+    let range = match number("character") {
+        Some(character) => Range {
+            start: Position { line, character },
+            end: Position {
+                line: end_line,
+                character: match number("endCharacter") {
+                    Some(end) if end_line > line => end,
+                    end => end.unwrap_or(character).max(character),
+                },
+            },
+        },
+        None => Range {
+            start: Position { line, character: 0 },
+            end: Position {
+                line: end_line + 1,
+                character: 0,
+            },
+        },
+    };
+    let severity = match finding.get("severity").and_then(|v| v.as_str()) {
+        Some("error") => DiagnosticSeverity::ERROR,
+        Some("information") | Some("info") => DiagnosticSeverity::INFORMATION,
+        Some("hint") => DiagnosticSeverity::HINT,
+        _ => DiagnosticSeverity::WARNING,
+    };
+
+    Ok(Diagnostic {
+        range,
+        severity: Some(severity),
+        code: finding
+            .get("code")
+            .and_then(|v| v.as_str())
+            .map(|code| NumberOrString::String(code.to_string())),
+        source: Some("Claude".to_string()),
+        message: message.to_string(),
+        ..Diagnostic::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range {
+            start: Position::new(start.0, start.1),
+            end: Position::new(end.0, end.1),
+        }
+    }
+
+    fn diagnostic(finding: serde_json::Value) -> Diagnostic {
+        finding_diagnostic(&finding).unwrap()
+    }
+
+    #[test]
+    fn findings_without_characters_span_whole_lines() {
+        let finding = diagnostic(serde_json::json!({ "message": "unused", "line": 3 }));
+        assert_eq!(finding.range, range((3, 0), (4, 0)));
+        assert_eq!(finding.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(finding.source.as_deref(), Some("Claude"));
+        assert_eq!(finding.code, None);
+
+        let finding = diagnostic(serde_json::json!({ "message": "m", "line": 3, "endLine": 5 }));
+        assert_eq!(finding.range, range((3, 0), (6, 0)));
+    }
+
+    #[test]
+    fn findings_with_characters_are_narrowed() {
+        let finding = diagnostic(serde_json::json!({
+            "message": "m", "line": 2, "character": 4, "endCharacter": 9
+        }));
+        assert_eq!(finding.range, range((2, 4), (2, 9)));
+        let finding = diagnostic(serde_json::json!({ "message": "m", "line": 2, "character": 4 }));
+        assert_eq!(finding.range, range((2, 4), (2, 4)));
+        let finding = diagnostic(serde_json::json!({
+            "message": "m", "line": 2, "endLine": 4, "character": 8, "endCharacter": 1
+        }));
+        assert_eq!(finding.range, range((2, 8), (4, 1)));
+    }
+
+    #[test]
+    fn finding_ends_before_the_start_are_moved_to_it() {
+        let finding = diagnostic(serde_json::json!({ "message": "m", "line": 7, "endLine": 2 }));
+        assert_eq!(finding.range, range((7, 0), (8, 0)));
+        let finding = diagnostic(serde_json::json!({
+            "message": "m", "line": 2, "character": 9, "endCharacter": 4
+        }));
+        assert_eq!(finding.range, range((2, 9), (2, 9)));
+    }
+
+    #[test]
+    fn finding_severities_default_to_warning() {
+        let severity = |severity: &str| {
+            diagnostic(serde_json::json!({ "message": "m", "line": 0, "severity": severity }))
+                .severity
+        };
+        assert_eq!(severity("error"), Some(DiagnosticSeverity::ERROR));
+        assert_eq!(severity("info"), Some(DiagnosticSeverity::INFORMATION));
+        assert_eq!(
+            severity("information"),
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(severity("hint"), Some(DiagnosticSeverity::HINT));
+        assert_eq!(severity("fatal"), Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn findings_need_a_message_and_a_line() {
+        assert!(finding_diagnostic(&serde_json::json!({ "line": 1 })).is_err());
+        assert!(finding_diagnostic(&serde_json::json!({ "message": "m" })).is_err());
+        assert!(finding_diagnostic(&serde_json::json!({ "message": "m", "line": -1 })).is_err());
+        let finding = diagnostic(serde_json::json!({ "message": "m", "line": 0, "code": "E1" }));
+        assert_eq!(finding.code, Some(NumberOrString::String("E1".to_string())));
+    }
+}
@@ -0,0 +1,28 @@
+use tracing::info;
+
+use crate::mcp::types::TextContent;
+use crate::state::SharedState;
+
+/// Enter or leave focus mode, toggling it when `enabled` is not given
+pub fn focus_mode(arguments: &serde_json::Value, state: &SharedState) -> Vec<TextContent> {
+    let enabled = arguments
+        .get("enabled")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(!state.is_focus_mode());
+    if state.set_focus_mode(enabled) {
+        info!(
+            "Focus mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    let response = serde_json::json!({
+        "success": true,
+        "focusMode": enabled
+    });
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
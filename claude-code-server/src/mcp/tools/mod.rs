@@ -1,33 +1,129 @@
+mod checkpoint;
+mod context;
+mod definitions;
+mod diff;
 mod document;
+mod findings;
+mod focus;
+mod probe;
+mod replace;
 mod selection;
+mod snapshot;
+mod unsupported;
 mod workspace;
 
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+pub use definitions::{is_idempotent, is_observation_tool, tool_definitions, tool_matrix};
 
-use super::types::{SelectionState, TextContent};
+use definitions::{modifies_workspace, tool_backend};
+
+use tracing::info;
+
+use super::types::TextContent;
+use super::MCPServer;
+use crate::config::OsNotificationEvent;
+use crate::i18n::Text;
+use crate::state;
+use unsupported::{
+    disabled_response, lsp_unavailable_response, not_supported_response, read_only_response,
+    untrusted_response,
+};
+
+/// Whether a call should only describe its changes: the server runs with `--dry-run` or the
+/// call passes `dryRun: true`. Read-only tools always run.
+pub fn is_dry_run(tool_name: &str, arguments: &serde_json::Value) -> bool {
+    modifies_workspace(tool_name)
+        && (state::is_dry_run()
+            || arguments
+                .get("dryRun")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false))
+}
+
+/// Whether an `openFile` call asks to focus the file, which it does unless told otherwise
+fn makes_frontmost(arguments: &serde_json::Value) -> bool {
+    arguments
+        .get("makeFrontmost")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
+}
 
 /// Dispatch a tool call to the appropriate handler
 pub async fn dispatch_tool(
     tool_name: &str,
-    _arguments: &serde_json::Value,
-    selection_state: &Arc<RwLock<Option<SelectionState>>>,
-    worktree: &Option<PathBuf>,
+    arguments: &serde_json::Value,
+    server: &MCPServer,
 ) -> Result<Vec<TextContent>, anyhow::Error> {
+    let worktree = &server.worktree;
+    let selection_state = &server.selection_state;
+
+    if tool_backend(tool_name, server.state.lsp_attached()).is_none() {
+        return Ok(lsp_unavailable_response(tool_name));
+    }
+    if !server.state.is_tool_enabled(tool_name) {
+        info!("{} is disabled in the settings", tool_name);
+        return Ok(disabled_response(tool_name));
+    }
+    if server.read_only && !is_observation_tool(tool_name) {
+        info!("Read-only session, rejecting {}", tool_name);
+        return Ok(read_only_response(tool_name));
+    }
+    if modifies_workspace(tool_name) && !server.state.is_trusted() {
+        // Focus mode denies permission requests without bothering the user
+        if !server.state.is_focus_mode() {
+            server.state.notify_os(
+                OsNotificationEvent::PermissionRequested,
+                &server
+                    .state
+                    .localize(Text::PermissionRequested, &[&tool_name]),
+            );
+        }
+        return Ok(untrusted_response(tool_name));
+    }
+
     let content = match tool_name {
         // Working tools
-        "getWorkspaceFolders" => workspace::get_workspace_folders(worktree),
-        "getCurrentSelection" => selection::get_current_selection(selection_state).await,
-        "getLatestSelection" => selection::get_latest_selection(selection_state).await,
-        "getDiagnostics" => document::get_diagnostics(worktree),
+        "getWorkspaceFolders" => workspace::get_workspace_folders(worktree, &server.state),
+        "get_workspace_info" => workspace::get_workspace_info(worktree, &server.state).await,
+        "getCurrentSelection" => {
+            selection::get_current_selection(selection_state, &server.state).await
+        }
+        "getLatestSelection" => {
+            selection::get_latest_selection(selection_state, &server.state).await
+        }
+        "getSessionContext" => context::get_session_context(arguments, &server.state.context).await,
+        "clearContext" => context::clear_context(&server.state).await,
+        "resetSession" => context::reset_session(&server.state).await,
+        "focusMode" => focus::focus_mode(arguments, &server.state),
+        "atMentionRanges" => context::at_mention_ranges(arguments, &server.state).await?,
+        "exportContextSnapshot" => snapshot::export_context_snapshot(server).await,
+        "importContextSnapshot" => snapshot::import_context_snapshot(arguments, server).await?,
+        "getDiagnostics" => document::get_diagnostics(arguments, &server.state).await?,
+        "reportFindings" => findings::report_findings(arguments, &server.state).await?,
+        "getOpenEditors" => document::get_open_editors(&server.state).await,
+        "getActiveFileContext" => document::get_active_file_context(arguments, &server.state).await,
+        "checkDocumentDirty" => document::check_document_dirty(arguments, &server.state).await?,
+        "getDocumentOutline" => document::get_document_outline(arguments, &server.state).await?,
+        "echo" => probe::echo(arguments, server).await,
+        "openDiff" => diff::open_diff(arguments, &server.state).await?,
+        "searchAndReplace" => {
+            replace::search_and_replace(arguments, worktree, &server.state).await?
+        }
+        "getInterruptedEdits" => checkpoint::get_interrupted_edits(&server.state),
+        "restoreCheckpoint" => checkpoint::restore_checkpoint(arguments, &server.state).await?,
+        "openFile" if !makes_frontmost(arguments) => {
+            document::open_file(arguments, &server.state).await?
+        }
+        // Only describing a file is an observation; opening it steers the user's editor
+        "openFile" if server.read_only => read_only_response(tool_name),
+        "openFile" if server.state.lsp_attached() => {
+            document::show_file(arguments, &server.state).await?
+        }
+        "openFile" => lsp_unavailable_response(tool_name),
+        "closeAllDiffTabs" => diff::close_all_diff_tabs(&server.state.diff_tabs).await,
+        "close_tab" => diff::close_tab(arguments, &server.state.diff_tabs).await?,
 
         // IDE tools not supported in Zed - return graceful response
-        "openDiff" | "openFile" | "getOpenEditors" | "closeAllDiffTabs" | "close_tab"
-        | "checkDocumentDirty" | "saveDocument" | "echo" | "get_workspace_info"
-        | "executeCode" => {
-            not_supported_response(tool_name)
-        }
+        "saveDocument" | "executeCode" => not_supported_response(tool_name),
 
         // Unknown tools
         _ => not_supported_response(tool_name),
@@ -35,10 +131,3 @@ pub async fn dispatch_tool(
 
     Ok(content)
 }
-
-fn not_supported_response(tool_name: &str) -> Vec<TextContent> {
-    vec![TextContent {
-        type_: "text".to_string(),
-        text: format!("NOT_SUPPORTED: Tool '{}' is not available in Zed integration. File operations should be performed directly.", tool_name),
-    }]
-}
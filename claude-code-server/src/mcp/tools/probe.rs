@@ -0,0 +1,58 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use super::{tool_definitions, tool_matrix};
+use crate::mcp::types::TextContent;
+use crate::mcp::MCPServer;
+
+/// Connectivity probe: echoes the request back with server, protocol and timing details
+/// so the CLI (or a user) can verify the channel end-to-end.
+pub async fn echo(arguments: &serde_json::Value, server: &MCPServer) -> Vec<TextContent> {
+    let received_at = unix_millis();
+    info!("Echo probe received");
+
+    let protocol_version = server.protocol_version.read().await.clone();
+    let lsp_attached = server.state.lsp_attached();
+    let tools: Vec<String> = tool_definitions(&server.state, server.read_only)
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+
+    let mut response = serde_json::json!({
+        "success": true,
+        "message": arguments.get("message").cloned().unwrap_or(serde_json::Value::Null),
+        "server": {
+            "name": "claude-code-server",
+            "version": env!("CARGO_PKG_VERSION"),
+            "pid": std::process::id(),
+            "mode": server.state.mode().as_str(),
+            "lspAttached": lsp_attached,
+            "trusted": server.state.is_trusted(),
+            "focusMode": server.state.is_focus_mode(),
+            "readOnly": server.read_only
+        },
+        "protocolVersion": protocol_version,
+        "capabilities": server.capabilities,
+        "tools": tools,
+        "toolBackends": tool_matrix(&server.state, server.read_only),
+        "connections": server.state.connection_stats.to_json(),
+        "rateLimits": server.state.rate_limiter.lock().unwrap().to_json(),
+        "timestamps": {
+            "clientSentAt": arguments.get("timestamp").cloned().unwrap_or(serde_json::Value::Null),
+            "serverReceivedAt": received_at
+        }
+    });
+    response["timestamps"]["serverRespondedAt"] = serde_json::json!(unix_millis());
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
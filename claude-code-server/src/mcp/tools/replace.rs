@@ -0,0 +1,360 @@
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::file_locks::write_atomic;
+use crate::journal::JournaledFile;
+use crate::mcp::types::TextContent;
+use crate::state::SharedState;
+
+use super::is_dry_run;
+
+/// Upper bound on files visited when searching the workspace
+const MAX_SCANNED_FILES: usize = 20000;
+
+/// Maximum edits listed in a preview, so a sweeping pattern doesn't flood Claude's context
+const MAX_PREVIEW_EDITS: usize = 200;
+
+/// Files a search covers: `include` globs (all files when empty) minus `exclude` globs.
+/// Globs without a `/` match file names anywhere, others match paths from the root, which
+/// absolute globs under the root are taken relative to.
+struct Scope {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Scope {
+    fn from_arguments(arguments: &serde_json::Value, root: &Path) -> Result<Self, anyhow::Error> {
+        let patterns = |key: &str| -> Result<Vec<Pattern>, anyhow::Error> {
+            arguments
+                .get(key)
+                .and_then(|globs| globs.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|glob| glob.as_str())
+                .map(|glob| match Path::new(glob).strip_prefix(root) {
+                    Ok(relative) => relative.to_string_lossy().into_owned(),
+                    Err(_) => glob.to_string(),
+                })
+                .map(|glob| {
+                    Pattern::new(&glob)
+                        .map_err(|e| anyhow::anyhow!("Invalid glob '{}': {}", glob, e))
+                })
+                .collect()
+        };
+        Ok(Self {
+            include: patterns("include")?,
+            exclude: patterns("exclude")?,
+        })
+    }
+
+    fn contains(&self, relative: &Path) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| glob_matches(p, relative)))
+            && !self.exclude.iter().any(|p| glob_matches(p, relative))
+    }
+}
+
+fn glob_matches(pattern: &Pattern, relative: &Path) -> bool {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::default()
+    };
+    if pattern.as_str().contains('/') {
+        pattern.matches_path_with(relative, options)
+    } else {
+        relative
+            .file_name()
+            .is_some_and(|name| pattern.matches_with(&name.to_string_lossy(), options))
+    }
+}
+
+/// The rewrite of one file
+struct FileChange {
+    path: PathBuf,
+    original: String,
+    updated: String,
+    replacements: usize,
+}
+
+/// Replace every match of `pattern` with `replacement` (which may refer to groups as `$1`
+/// or `${name}`) in the workspace files within the `include`/`exclude` globs. With `preview`
+/// (or in a dry run) the changes are only described; otherwise all files are rewritten, or
+/// none if any write fails.
+pub async fn search_and_replace(
+    arguments: &serde_json::Value,
+    worktree: &Option<PathBuf>,
+    state: &SharedState,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let pattern = required_str(arguments, "pattern")?;
+    let replacement = required_str(arguments, "replacement")?;
+    let regex =
+        Regex::new(pattern).map_err(|e| anyhow::anyhow!("Invalid pattern '{}': {}", pattern, e))?;
+    let preview = is_dry_run("searchAndReplace", arguments)
+        || arguments
+            .get("preview")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+    // In focus mode Claude observes without changing anything, so edits are denied
+    if state.is_focus_mode() && !preview {
+        info!("Focus mode, rejecting replacement of '{}'", pattern);
+        return Ok(vec![TextContent {
+            type_: "text".to_string(),
+            text: "REPLACE_REJECTED".to_string(),
+        }]);
+    }
+
+    let root = worktree
+        .clone()
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    let scope = Scope::from_arguments(arguments, &root)?;
+
+    // Directory walking is blocking work, keep it off the async runtime threads
+    let ignored_dirs = state.ignored_dirs();
+    let search_regex = regex.clone();
+    let candidates = tokio::task::spawn_blocking(move || {
+        matching_files(&root, &scope, &ignored_dirs, &search_regex)
+    })
+    .await?;
+    info!(
+        "Replacing '{}' in {} matching files (preview: {})",
+        pattern,
+        candidates.len(),
+        preview
+    );
+
+    if preview {
+        let mut listed = 0;
+        let mut truncated = false;
+        let files: Vec<serde_json::Value> = candidates
+            .iter()
+            .filter_map(|path| plan_change(path, &regex, replacement))
+            .map(|change| {
+                let mut edits = line_edits(&change.original, &regex, replacement);
+                let remaining = MAX_PREVIEW_EDITS - listed;
+                truncated |= edits.len() > remaining;
+                edits.truncate(remaining);
+                listed += edits.len();
+                let mut file = serde_json::json!({
+                    "filePath": change.path,
+                    "replacements": change.replacements,
+                    "edits": edits
+                });
+                state.relativize_file_path(&mut file);
+                file
+            })
+            .collect();
+        let total: u64 = files
+            .iter()
+            .filter_map(|file| file["replacements"].as_u64())
+            .sum();
+        let response = serde_json::json!({
+            "preview": true,
+            "files": files,
+            "totalReplacements": total,
+            "truncated": truncated
+        });
+        return Ok(vec![TextContent {
+            type_: "text".to_string(),
+            text: response.to_string(),
+        }]);
+    }
+
+    // Hold every file's lock, in path order, so concurrent edits can't interleave with the
+    // rewrite; contents are re-read under the locks
+    let mut guards = Vec::with_capacity(candidates.len());
+    for path in &candidates {
+        guards.push(state.file_locks.lock(path).await);
+    }
+    let changes: Vec<FileChange> = candidates
+        .iter()
+        .filter_map(|path| plan_change(path, &regex, replacement))
+        .collect();
+
+    // Journal the change set first, so a crash part way through the writes can be detected
+    // and undone on the next start
+    let record = state
+        .journal
+        .begin(
+            "searchAndReplace",
+            changes
+                .iter()
+                .map(|change| JournaledFile {
+                    path: change.path.clone(),
+                    original: change.original.clone(),
+                    updated: change.updated.clone(),
+                })
+                .collect(),
+        )
+        .map_err(|e| anyhow::anyhow!("Failed to journal the change set: {}", e))?;
+
+    // Restore the files already written if a later write fails, leaving the workspace as
+    // it was before the call
+    for (written, change) in changes.iter().enumerate() {
+        if let Err(e) = write_atomic(&change.path, &change.updated) {
+            warn!("Failed to write {}: {}", change.path.display(), e);
+            let mut restored = true;
+            for done in &changes[..written] {
+                if let Err(e) = write_atomic(&done.path, &done.original) {
+                    warn!("Failed to restore {}: {}", done.path.display(), e);
+                    restored = false;
+                }
+            }
+            // Keep the entry of a change set left half applied, to restore it later
+            if restored {
+                record.complete();
+            }
+            anyhow::bail!(
+                "Failed to write {}: {}; no files were changed",
+                change.path.display(),
+                e
+            );
+        }
+    }
+    record.complete();
+    drop(guards);
+    state.invalidate_tool_cache();
+
+    let total: usize = changes.iter().map(|change| change.replacements).sum();
+    info!("Replaced {} matches in {} files", total, changes.len());
+    let response = serde_json::json!({
+        "success": true,
+        "files": changes
+            .iter()
+            .map(|change| {
+                let mut file = serde_json::json!({
+                    "filePath": change.path,
+                    "replacements": change.replacements
+                });
+                state.relativize_file_path(&mut file);
+                file
+            })
+            .collect::<Vec<_>>(),
+        "totalReplacements": total
+    });
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
+
+fn required_str<'a>(arguments: &'a serde_json::Value, key: &str) -> Result<&'a str, anyhow::Error> {
+    arguments
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: {}", key))
+}
+
+/// UTF-8 files under `root` within `scope` that contain a match, in path order. Hidden and
+/// ignored directories are skipped, as are files that are not valid UTF-8.
+fn matching_files(
+    root: &Path,
+    scope: &Scope,
+    ignored_dirs: &[String],
+    regex: &Regex,
+) -> Vec<PathBuf> {
+    let mut matches = Vec::new();
+    let mut scanned = 0;
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !ignored_dirs.iter().any(|dir| *dir == name) {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() {
+                scanned += 1;
+                if scanned > MAX_SCANNED_FILES {
+                    warn!("Stopped searching after {} files", MAX_SCANNED_FILES);
+                    pending.clear();
+                    break;
+                }
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                if !scope.contains(relative) {
+                    continue;
+                }
+                if fs::read_to_string(&path).is_ok_and(|text| regex.is_match(&text)) {
+                    matches.push(path);
+                }
+            }
+        }
+    }
+
+    matches.sort();
+    matches
+}
+
+/// The rewrite of `path`, or `None` if it can't be read as UTF-8 or nothing changes
+fn plan_change(path: &Path, regex: &Regex, replacement: &str) -> Option<FileChange> {
+    let original = fs::read_to_string(path).ok()?;
+    let replacements = regex.find_iter(&original).count();
+    let updated = regex.replace_all(&original, replacement).into_owned();
+    (updated != original).then(|| FileChange {
+        path: path.to_path_buf(),
+        original,
+        updated,
+        replacements,
+    })
+}
+
+/// The changed lines of `text` as `{line, before, after}` edits, where `line` is the 1-based
+/// first line and matches on the same or overlapping lines share an edit
+fn line_edits(text: &str, regex: &Regex, replacement: &str) -> Vec<serde_json::Value> {
+    // (start, end, cursor, replaced): the lines spanned and how much of them is rewritten
+    let mut spans: Vec<(usize, usize, usize, String)> = Vec::new();
+    for captures in regex.captures_iter(text) {
+        let Some(found) = captures.get(0) else {
+            continue;
+        };
+        let line_start = text[..found.start()].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[found.end()..]
+            .find('\n')
+            .map_or(text.len(), |i| found.end() + i);
+
+        match spans.last_mut() {
+            Some((_, end, cursor, replaced)) if line_start <= *end => {
+                replaced.push_str(&text[*cursor..found.start()]);
+                captures.expand(replacement, replaced);
+                *cursor = found.end();
+                *end = (*end).max(line_end);
+            }
+            _ => {
+                let mut replaced = text[line_start..found.start()].to_string();
+                captures.expand(replacement, &mut replaced);
+                spans.push((line_start, line_end, found.end(), replaced));
+            }
+        }
+    }
+
+    spans
+        .into_iter()
+        .filter_map(|(start, end, cursor, mut replaced)| {
+            replaced.push_str(&text[cursor..end]);
+            let before = &text[start..end];
+            (before != replaced).then(|| {
+                serde_json::json!({
+                    "line": text[..start].matches('\n').count() + 1,
+                    "before": before,
+                    "after": replaced
+                })
+            })
+        })
+        .collect()
+}
@@ -2,22 +2,20 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::info;
 
+use crate::context::{ContextSource, SharedContext};
 use crate::mcp::types::{SelectionState, TextContent};
+use crate::state::SharedState;
 
 pub async fn get_current_selection(
     selection_state: &Arc<RwLock<Option<SelectionState>>>,
+    shared: &SharedState,
 ) -> Vec<TextContent> {
     info!("Getting current selection");
 
     let state = selection_state.read().await;
     let response = if let Some(selection) = state.as_ref() {
-        serde_json::json!({
-            "success": true,
-            "text": selection.text,
-            "filePath": selection.file_path,
-            "fileUrl": selection.file_url,
-            "selection": selection.selection
-        })
+        record_selection(&shared.context, selection).await;
+        selection_response(selection, shared)
     } else {
         serde_json::json!({
             "success": false,
@@ -33,18 +31,14 @@ pub async fn get_current_selection(
 
 pub async fn get_latest_selection(
     selection_state: &Arc<RwLock<Option<SelectionState>>>,
+    shared: &SharedState,
 ) -> Vec<TextContent> {
     info!("Getting latest selection");
 
     let state = selection_state.read().await;
     let response = if let Some(selection) = state.as_ref() {
-        serde_json::json!({
-            "success": true,
-            "text": selection.text,
-            "filePath": selection.file_path,
-            "fileUrl": selection.file_url,
-            "selection": selection.selection
-        })
+        record_selection(&shared.context, selection).await;
+        selection_response(selection, shared)
     } else {
         serde_json::json!({
             "success": false,
@@ -57,3 +51,35 @@ pub async fn get_latest_selection(
         text: response.to_string(),
     }]
 }
+
+/// Remember that Claude has read this selection
+async fn record_selection(context: &SharedContext, selection: &SelectionState) {
+    let range = &selection.selection;
+    let (line_start, line_end) = if range.is_empty {
+        (None, None)
+    } else {
+        (Some(range.start.line), Some(range.end.line))
+    };
+    context.write().await.record(
+        &selection.file_path,
+        line_start,
+        line_end,
+        ContextSource::Selection,
+    );
+}
+
+fn selection_response(selection: &SelectionState, shared: &SharedState) -> serde_json::Value {
+    let mut response = serde_json::json!({
+        "success": true,
+        "text": selection.text,
+        "filePath": selection.file_path,
+        "fileUrl": selection.file_url,
+        "selection": selection.selection
+    });
+    // Flag text transcoded to UTF-8 from the file's original encoding
+    if !selection.encoding.is_utf8() {
+        response["encoding"] = serde_json::json!(selection.encoding);
+    }
+    shared.relativize_file_path(&mut response);
+    response
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::context::ContextEntry;
+use crate::mcp::types::{SelectionState, TextContent};
+use crate::mcp::MCPServer;
+
+/// Snapshot format version, bumped on incompatible changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Session state that can be carried over to another server: the tracked selection,
+/// at-mentions not yet delivered to Claude and the session context
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContextSnapshot {
+    version: u32,
+    selection: Option<SelectionState>,
+    /// Parameters of queued `at_mentioned` notifications
+    #[serde(default)]
+    mentions: Vec<serde_json::Value>,
+    #[serde(default)]
+    context: Vec<ContextEntry>,
+}
+
+/// Capture the session state as a snapshot for `importContextSnapshot`
+pub async fn export_context_snapshot(server: &MCPServer) -> Vec<TextContent> {
+    let snapshot = ContextSnapshot {
+        version: SNAPSHOT_VERSION,
+        selection: server.selection_state.read().await.clone(),
+        mentions: server
+            .state
+            .queued_notifications()
+            .into_iter()
+            .flat_map(|n| match n.method.as_str() {
+                "at_mentioned" => vec![n.params],
+                // Batched mentions are restored one by one
+                "at_mentioned_ranges" => {
+                    n.params["mentions"].as_array().cloned().unwrap_or_default()
+                }
+                _ => Vec::new(),
+            })
+            .collect(),
+        context: server.state.context.read().await.entries().to_vec(),
+    };
+    info!(
+        "Exported context snapshot with {} mentions and {} context entries",
+        snapshot.mentions.len(),
+        snapshot.context.len()
+    );
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: serde_json::to_string(&snapshot).unwrap_or_default(),
+    }]
+}
+
+/// Load a snapshot, given inline as `snapshot` or read from the file at `path`, into the
+/// session: the selection and mentions are re-sent to Claude and the context entries merged
+pub async fn import_context_snapshot(
+    arguments: &serde_json::Value,
+    server: &MCPServer,
+) -> Result<Vec<TextContent>, anyhow::Error> {
+    let snapshot: ContextSnapshot = match (arguments.get("snapshot"), arguments.get("path")) {
+        (Some(snapshot), _) => serde_json::from_value(snapshot.clone())?,
+        (None, Some(path)) => {
+            let path = path
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("path must be a string"))?;
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        }
+        (None, None) => anyhow::bail!("Missing required argument: snapshot or path"),
+    };
+    if snapshot.version != SNAPSHOT_VERSION {
+        anyhow::bail!("Unsupported snapshot version {}", snapshot.version);
+    }
+
+    let restored_selection = snapshot.selection.is_some();
+    if let Some(selection) = snapshot.selection {
+        server.state.publish(
+            "selection_changed",
+            serde_json::to_value(&selection).unwrap_or_default(),
+        );
+        *server.selection_state.write().await = Some(selection);
+    }
+
+    let mentions = snapshot.mentions.len();
+    for mention in snapshot.mentions {
+        server.state.publish("at_mentioned", mention);
+    }
+
+    let merged = server.state.context.write().await.import(snapshot.context);
+    info!(
+        "Imported context snapshot: selection {}, {} mentions, {} context entries",
+        restored_selection, mentions, merged
+    );
+
+    let response = serde_json::json!({
+        "success": true,
+        "selection": restored_selection,
+        "mentions": mentions,
+        "entries": merged
+    });
+
+    Ok(vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }])
+}
@@ -0,0 +1,93 @@
+use serde_json::json;
+
+use crate::mcp::types::TextContent;
+
+/// Why a tool is unavailable in Zed and what Claude should do instead
+struct Unsupported {
+    reason: &'static str,
+    alternative: &'static str,
+}
+
+fn lookup(tool_name: &str) -> Unsupported {
+    match tool_name {
+        "saveDocument" => Unsupported {
+            reason: "Zed does not allow language servers to save buffers",
+            alternative: "ask the user to save the file in Zed, or write the file directly",
+        },
+        "executeCode" => Unsupported {
+            reason: "Zed has no Jupyter kernel bridge for language servers",
+            alternative: "use bash to run the code in a terminal",
+        },
+        _ => Unsupported {
+            reason: "Unknown tool",
+            alternative: "use tools/list to discover the available tools",
+        },
+    }
+}
+
+/// Structured response for tools that cannot be served, so Claude can switch to the alternative
+/// instead of retrying
+pub fn not_supported_response(tool_name: &str) -> Vec<TextContent> {
+    structured_response(tool_name, lookup(tool_name))
+}
+
+/// Response for tools that need editor state while no Zed language server is attached
+pub fn lsp_unavailable_response(tool_name: &str) -> Vec<TextContent> {
+    structured_response(
+        tool_name,
+        Unsupported {
+            reason: "No Zed language server session is attached to this server",
+            alternative:
+                "open the workspace in Zed with the Claude Code extension enabled, then retry",
+        },
+    )
+}
+
+/// Response for write tools called while the workspace is not trusted
+pub fn untrusted_response(tool_name: &str) -> Vec<TextContent> {
+    structured_response(
+        tool_name,
+        Unsupported {
+            reason: "The workspace is not trusted, so only read-only tools are available",
+            alternative: "ask the user to trust the workspace from the Zed prompt or with `claude-code-server trust <path>`",
+        },
+    )
+}
+
+/// Response for tools that change the workspace or session, called from a read-only session
+pub fn read_only_response(tool_name: &str) -> Vec<TextContent> {
+    structured_response(
+        tool_name,
+        Unsupported {
+            reason: "This session is read-only: it can observe the editor but not change anything",
+            alternative: "describe the change so the owner of the workspace can make it",
+        },
+    )
+}
+
+/// Response for tools left out of the user's `enabledTools` setting
+pub fn disabled_response(tool_name: &str) -> Vec<TextContent> {
+    structured_response(
+        tool_name,
+        Unsupported {
+            reason: "The user disabled this tool in the Claude Code settings",
+            alternative:
+                "use tools/list to discover the enabled tools, or ask the user to enable it",
+        },
+    )
+}
+
+fn structured_response(tool_name: &str, unsupported: Unsupported) -> Vec<TextContent> {
+    let response = json!({
+        "status": "NOT_SUPPORTED",
+        "tool": tool_name,
+        "supported": false,
+        "reason": unsupported.reason,
+        "alternative": unsupported.alternative,
+    });
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
@@ -1,9 +1,12 @@
 use std::path::PathBuf;
 use tracing::info;
 
+use crate::languages::detect_languages;
 use crate::mcp::types::TextContent;
+use crate::state::SharedState;
 
-pub fn get_workspace_folders(worktree: &Option<PathBuf>) -> Vec<TextContent> {
+/// The worktree followed by the project's other folders, when it has several worktrees
+pub fn get_workspace_folders(worktree: &Option<PathBuf>, state: &SharedState) -> Vec<TextContent> {
     let workspace_info = worktree
         .as_ref()
         .map(|p| p.to_string_lossy().to_string())
@@ -16,16 +19,27 @@ pub fn get_workspace_folders(worktree: &Option<PathBuf>) -> Vec<TextContent> {
 
     info!("Getting workspace folders");
 
+    let folders: Vec<serde_json::Value> = std::iter::once(workspace_info.clone())
+        .chain(
+            state
+                .extra_workspace_folders()
+                .iter()
+                .map(|folder| folder.to_string_lossy().to_string()),
+        )
+        .map(|path| {
+            serde_json::json!({
+                "name": std::path::Path::new(&path)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("workspace"),
+                "uri": format!("file://{}", path),
+                "path": path
+            })
+        })
+        .collect();
     let response = serde_json::json!({
         "success": true,
-        "folders": [{
-            "name": std::path::Path::new(&workspace_info)
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("workspace"),
-            "uri": format!("file://{}", workspace_info),
-            "path": workspace_info
-        }],
+        "folders": folders,
         "rootPath": workspace_info
     });
 
@@ -33,4 +47,61 @@ pub fn get_workspace_folders(worktree: &Option<PathBuf>) -> Vec<TextContent> {
         type_: "text".to_string(),
         text: response.to_string(),
     }]
-}
\ No newline at end of file
+}
+
+/// Number of detected languages reported as "primary"
+const PRIMARY_LANGUAGE_COUNT: usize = 5;
+
+pub async fn get_workspace_info(
+    worktree: &Option<PathBuf>,
+    state: &SharedState,
+) -> Vec<TextContent> {
+    info!("Getting workspace info");
+
+    let root = worktree
+        .clone()
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+
+    // Directory walking is blocking work, keep it off the async runtime threads
+    let scan_root = root.clone();
+    let ignored_dirs = state.ignored_dirs();
+    let language_counts =
+        tokio::task::spawn_blocking(move || detect_languages(&scan_root, &ignored_dirs))
+            .await
+            .unwrap_or_default();
+    let mut primary_languages: Vec<(&str, usize)> = language_counts.into_iter().collect();
+    primary_languages.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    primary_languages.truncate(PRIMARY_LANGUAGE_COUNT);
+
+    let documents = state.documents.read().await;
+    let active_file = documents.active().map(|document| {
+        let mut file = serde_json::json!({
+            "filePath": document.file_path(),
+            "fileUrl": document.uri.to_string(),
+            "languageId": document.language_id
+        });
+        state.relativize_file_path(&mut file);
+        file
+    });
+
+    let response = serde_json::json!({
+        "success": true,
+        "name": root.file_name().and_then(|n| n.to_str()).unwrap_or("workspace"),
+        "rootPath": root.to_string_lossy(),
+        "primaryLanguages": primary_languages
+            .iter()
+            .map(|(language, files)| serde_json::json!({ "language": language, "fileCount": files }))
+            .collect::<Vec<_>>(),
+        "openDocuments": {
+            "count": documents.len(),
+            "byLanguage": documents.count_by_language()
+        },
+        "activeFile": active_file
+    });
+
+    vec![TextContent {
+        type_: "text".to_string(),
+        text: response.to_string(),
+    }]
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::encoding::TextEncoding;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MCPRequest {
     pub jsonrpc: String,
@@ -77,6 +79,9 @@ pub struct SelectionState {
     #[serde(rename = "fileUrl")]
     pub file_url: String,
     pub selection: SelectionRange,
+    /// Encoding the file was transcoded from; omitted for UTF-8 files
+    #[serde(default, skip_serializing_if = "TextEncoding::is_utf8")]
+    pub encoding: TextEncoding,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -0,0 +1,423 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::Url;
+use tracing::info;
+
+use crate::context::ContextSource;
+use crate::control::{self, AT_MENTION_METHOD, AT_MENTION_RANGES_METHOD};
+use crate::encoding::read_text_file;
+use crate::lsp::{AtMentionedNotification, AtMentionedRangesNotification};
+use crate::state::SharedState;
+use crate::websocket::find_running_server;
+
+/// Characters around an `@path` token that are not part of the path
+pub const MENTION_PUNCTUATION: &[char] = &[',', '.', ';', ':', ')', ']', '}', '`', '\'', '"'];
+
+/// The file and 1-based line range a `path` or `path:START-END` mention typed after an `@`
+/// refers to, with relative paths resolved against the worktree. `None` unless it names an
+/// existing file.
+pub fn resolve_mention(
+    mention: &str,
+    worktree: Option<&Path>,
+) -> Option<(PathBuf, Option<(u32, u32)>)> {
+    let mention = mention.trim_end_matches(MENTION_PUNCTUATION);
+    let (path, lines) = parse_file_range(mention).ok()?;
+    let path = match worktree {
+        Some(root) if path.is_relative() => root.join(path),
+        _ => path,
+    };
+    path.is_file().then_some((path, lines))
+}
+
+/// The 0-based line range at-mentioning the whole of `path`: through its last line as open
+/// in Zed, or as on disk when it isn't open
+pub async fn whole_file_lines(state: &SharedState, path: &Path) -> (u32, u32) {
+    let open = match Url::from_file_path(path) {
+        Ok(uri) => state
+            .documents
+            .read()
+            .await
+            .get(&uri)
+            .map(|document| document.last_line()),
+        Err(_) => None,
+    };
+    let last_line = match open {
+        Some(last_line) => last_line,
+        None => {
+            let path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || read_text_file(path))
+                .await
+                .ok()
+                .and_then(Result::ok)
+                // A trailing newline ends the last line rather than starting another
+                .map_or(0, |file| file.text.lines().count().saturating_sub(1) as u32)
+        }
+    };
+    (0, last_line)
+}
+
+/// The 0-based `lineStart`/`lineEnd` of at-mention arguments, or the whole file at `path`
+/// when they are left out. A missing `lineEnd` ends the range on its first line.
+pub async fn requested_lines(
+    state: &SharedState,
+    path: &Path,
+    arguments: &serde_json::Value,
+) -> (u32, u32) {
+    let line = |key: &str| {
+        arguments
+            .get(key)
+            .and_then(|line| line.as_u64())
+            .map(|line| line as u32)
+    };
+    match (line("lineStart"), line("lineEnd")) {
+        (Some(start), end) => (start, end.unwrap_or(start)),
+        (None, _) => whole_file_lines(state, path).await,
+    }
+}
+
+/// What an at-mention requested from outside the editor session shares with Claude
+#[derive(Debug, Clone)]
+pub enum MentionTarget {
+    /// A file, optionally narrowed to a 1-based inclusive line range
+    File {
+        path: PathBuf,
+        lines: Option<(u32, u32)>,
+    },
+    /// Whatever the editor last reported as selected
+    Selection,
+    /// Several files or line ranges, shared in one notification
+    Ranges(Vec<(PathBuf, Option<(u32, u32)>)>),
+}
+
+/// Record an at-mention in the session context and notify Claude of it
+pub async fn mention(state: &SharedState, file_path: &str, line_start: u32, line_end: u32) {
+    state.context.write().await.record(
+        file_path,
+        Some(line_start),
+        Some(line_end),
+        ContextSource::AtMention,
+    );
+
+    let notification = AtMentionedNotification {
+        file_path: file_path.to_string(),
+        line_start,
+        line_end,
+    };
+    state.publish(
+        "at_mentioned",
+        serde_json::to_value(notification).unwrap_or_default(),
+    );
+}
+
+/// Record several at-mentions in the session context and notify Claude of all of them in
+/// one `at_mentioned_ranges` notification
+pub async fn mention_ranges(state: &SharedState, mentions: Vec<AtMentionedNotification>) {
+    {
+        let mut context = state.context.write().await;
+        for mention in &mentions {
+            context.record(
+                &mention.file_path,
+                Some(mention.line_start),
+                Some(mention.line_end),
+                ContextSource::AtMention,
+            );
+        }
+    }
+
+    let notification = AtMentionedRangesNotification { mentions };
+    state.publish(
+        "at_mentioned_ranges",
+        serde_json::to_value(notification).unwrap_or_default(),
+    );
+}
+
+/// Answer a batch at-mention request, listing `mentions` as `{filePath, lineStart, lineEnd}`
+/// entries. Files in ignored directories are skipped and reported back.
+pub async fn handle_mention_ranges_request(
+    state: &SharedState,
+    params: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    let entries = params
+        .get("mentions")
+        .and_then(|mentions| mentions.as_array())
+        .ok_or_else(|| anyhow!("Missing required parameter: mentions"))?;
+
+    let mut mentions = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in entries {
+        let file_path = entry
+            .get("filePath")
+            .and_then(|path| path.as_str())
+            .ok_or_else(|| anyhow!("Every mention needs a filePath"))?;
+        if state.is_ignored_path(Path::new(file_path)) {
+            skipped.push(file_path.to_string());
+            continue;
+        }
+        let (line_start, line_end) = requested_lines(state, Path::new(file_path), entry).await;
+        mentions.push(AtMentionedNotification {
+            file_path: file_path.to_string(),
+            line_start,
+            line_end,
+        });
+    }
+    if mentions.is_empty() {
+        bail!("No mentions to share");
+    }
+
+    info!(
+        "At-mention requested for {} ranges ({} skipped)",
+        mentions.len(),
+        skipped.len()
+    );
+    let mentioned = serde_json::to_value(&mentions)?;
+    mention_ranges(state, mentions).await;
+
+    Ok(json!({
+        "mentions": mentioned,
+        "skipped": skipped
+    }))
+}
+
+/// Answer an at-mention control request, for a file or the last reported selection
+pub async fn handle_mention_request(
+    state: &SharedState,
+    params: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    let (file_path, line_start, line_end) = if params.get("selection").is_some() {
+        let selection = state
+            .last_selection()
+            .ok_or_else(|| anyhow!("No selection has been reported by the editor"))?;
+        let line = |end: &str| {
+            selection
+                .pointer(&format!("/selection/{}/line", end))
+                .and_then(|line| line.as_u64())
+                .unwrap_or(0) as u32
+        };
+        let file_path = selection
+            .get("filePath")
+            .and_then(|path| path.as_str())
+            .ok_or_else(|| anyhow!("The reported selection has no file"))?
+            .to_string();
+        (file_path, line("start"), line("end"))
+    } else {
+        let file_path = params
+            .get("filePath")
+            .and_then(|path| path.as_str())
+            .ok_or_else(|| anyhow!("Missing required parameter: filePath"))?
+            .to_string();
+        let (line_start, line_end) = requested_lines(state, Path::new(&file_path), params).await;
+        (file_path, line_start, line_end)
+    };
+
+    if state.is_ignored_path(Path::new(&file_path)) {
+        bail!("{} is in an ignored directory", file_path);
+    }
+    mention(state, &file_path, line_start, line_end).await;
+    info!(
+        "At-mention requested for {}:{}-{}",
+        file_path, line_start, line_end
+    );
+
+    Ok(json!({
+        "filePath": file_path,
+        "lineStart": line_start,
+        "lineEnd": line_end
+    }))
+}
+
+/// Ask the server running for `worktree` (default: current directory) to share `target`
+/// with Claude, printing what was mentioned
+pub async fn run_mention(worktree: Option<PathBuf>, target: MentionTarget) -> Result<()> {
+    let workspace = match worktree {
+        Some(path) => path,
+        None => std::env::current_dir()?,
+    };
+    let (port, lock_file) = find_running_server(&workspace)?;
+
+    let (method, params) = match target {
+        MentionTarget::Selection => (AT_MENTION_METHOD, json!({ "selection": true })),
+        MentionTarget::File { path, lines } => {
+            (AT_MENTION_METHOD, file_mention(&workspace, path, lines)?)
+        }
+        MentionTarget::Ranges(ranges) => {
+            let mentions = ranges
+                .into_iter()
+                .map(|(path, lines)| file_mention(&workspace, path, lines))
+                .collect::<Result<Vec<_>>>()?;
+            (AT_MENTION_RANGES_METHOD, json!({ "mentions": mentions }))
+        }
+    };
+
+    let result = control::send_request(port, &lock_file.auth_token, method, params).await?;
+    let mentioned = match result.get("mentions").and_then(|m| m.as_array()) {
+        Some(mentions) => mentions.clone(),
+        None => vec![result],
+    };
+    for mention in &mentioned {
+        let line = |key: &str| mention.get(key).and_then(|line| line.as_u64()).unwrap_or(0) + 1;
+        println!(
+            "{}:{}-{}",
+            mention
+                .get("filePath")
+                .and_then(|path| path.as_str())
+                .unwrap_or_default(),
+            line("lineStart"),
+            line("lineEnd")
+        );
+    }
+    Ok(())
+}
+
+/// Control request parameters mentioning `path` (relative to `workspace`), optionally
+/// narrowed to 1-based `lines`; without them the server mentions the whole file
+fn file_mention(
+    workspace: &Path,
+    path: PathBuf,
+    lines: Option<(u32, u32)>,
+) -> Result<serde_json::Value> {
+    let path = if path.is_absolute() {
+        path
+    } else {
+        workspace.join(path)
+    };
+    if !path.is_file() {
+        bail!("No such file: {}", path.display());
+    }
+    // Lines are given as shown in the editor; notifications use 0-based lines
+    Ok(match lines {
+        Some((start, end)) => json!({
+            "filePath": path.to_string_lossy(),
+            "lineStart": start.saturating_sub(1),
+            "lineEnd": end.saturating_sub(1)
+        }),
+        None => json!({ "filePath": path.to_string_lossy() }),
+    })
+}
+
+/// Parse a `FILE[:START-END]` range for a batch mention
+pub fn parse_file_range(range: &str) -> Result<(PathBuf, Option<(u32, u32)>), String> {
+    match range.rsplit_once(':') {
+        Some((file, lines)) if lines.chars().next().is_some_and(|c| c.is_ascii_digit()) => {
+            Ok((PathBuf::from(file), Some(parse_line_range(lines)?)))
+        }
+        _ => Ok((PathBuf::from(range), None)),
+    }
+}
+
+/// Parse a `START-END` (or single `LINE`) 1-based line range
+pub fn parse_line_range(range: &str) -> Result<(u32, u32), String> {
+    let parse = |line: &str| {
+        line.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|line| *line > 0)
+            .ok_or_else(|| format!("invalid line number: {}", line))
+    };
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => {
+            let line = parse(range)?;
+            (line, line)
+        }
+    };
+    if start > end {
+        return Err(format!("line range {} ends before it starts", range));
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_file_range_splits_off_line_ranges() {
+        assert_eq!(
+            parse_file_range("src/main.rs:3-7"),
+            Ok((PathBuf::from("src/main.rs"), Some((3, 7))))
+        );
+        assert_eq!(
+            parse_file_range("src/main.rs:12"),
+            Ok((PathBuf::from("src/main.rs"), Some((12, 12))))
+        );
+        assert_eq!(
+            parse_file_range("src/main.rs"),
+            Ok((PathBuf::from("src/main.rs"), None))
+        );
+    }
+
+    #[test]
+    fn parse_file_range_keeps_colons_not_followed_by_lines() {
+        assert_eq!(
+            parse_file_range("C:\\src\\main.rs"),
+            Ok((PathBuf::from("C:\\src\\main.rs"), None))
+        );
+        assert_eq!(
+            parse_file_range("notes:todo.md"),
+            Ok((PathBuf::from("notes:todo.md"), None))
+        );
+    }
+
+    #[test]
+    fn parse_file_range_rejects_invalid_lines() {
+        assert!(parse_file_range("main.rs:0").is_err());
+        assert!(parse_file_range("main.rs:0-3").is_err());
+        assert!(parse_file_range("main.rs:7-3").is_err());
+        assert!(parse_file_range("main.rs:3-x").is_err());
+        assert!(parse_file_range("main.rs:3-").is_err());
+    }
+
+    /// A fresh directory holding an empty `src/lib.rs`
+    fn worktree() -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("claude-code-mention-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        root
+    }
+
+    #[test]
+    fn resolve_mention_resolves_against_the_worktree() {
+        let root = worktree();
+        let file = root.join("src/lib.rs");
+        assert_eq!(
+            resolve_mention("src/lib.rs", Some(&root)),
+            Some((file.clone(), None))
+        );
+        assert_eq!(
+            resolve_mention("src/lib.rs:2-4", Some(&root)),
+            Some((file.clone(), Some((2, 4))))
+        );
+        assert_eq!(
+            resolve_mention(&file.to_string_lossy(), None),
+            Some((file.clone(), None))
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn resolve_mention_ignores_trailing_punctuation() {
+        let root = worktree();
+        let file = root.join("src/lib.rs");
+        assert_eq!(
+            resolve_mention("src/lib.rs,", Some(&root)),
+            Some((file.clone(), None))
+        );
+        assert_eq!(
+            resolve_mention("src/lib.rs:3).", Some(&root)),
+            Some((file, Some((3, 3))))
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn resolve_mention_needs_an_existing_file() {
+        let root = worktree();
+        assert_eq!(resolve_mention("src/main.rs", Some(&root)), None);
+        assert_eq!(resolve_mention("src", Some(&root)), None);
+        assert_eq!(resolve_mention("src/lib.rs:0", Some(&root)), None);
+        assert_eq!(resolve_mention("", Some(&root)), None);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
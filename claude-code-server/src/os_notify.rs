@@ -0,0 +1,65 @@
+use std::process::Stdio;
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Title shown on every OS notification
+const NOTIFICATION_TITLE: &str = "Claude Code";
+
+/// Show a native OS notification without waiting for it to be displayed.
+/// Uses `osascript` on macOS and `notify-send` on other Unix systems; elsewhere the
+/// notification is only logged.
+pub fn show(message: &str) {
+    let Some(mut command) = notification_command(message) else {
+        debug!("OS notifications unsupported on this platform: {}", message);
+        return;
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let message = message.to_string();
+    tokio::spawn(async move {
+        match command.output().await {
+            Ok(output) if output.status.success() => debug!("OS notification shown: {}", message),
+            Ok(output) => warn!(
+                "OS notification failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => warn!("Failed to run OS notification command: {}", e),
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(message: &str) -> Option<Command> {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        applescript_string(message),
+        applescript_string(NOTIFICATION_TITLE)
+    ));
+    Some(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn notification_command(message: &str) -> Option<Command> {
+    let mut command = Command::new("notify-send");
+    command
+        .arg("--app-name")
+        .arg(NOTIFICATION_TITLE)
+        .arg(NOTIFICATION_TITLE)
+        .arg(message);
+    Some(command)
+}
+
+#[cfg(not(unix))]
+fn notification_command(_message: &str) -> Option<Command> {
+    None
+}
+
+/// Quote text as an AppleScript string literal
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
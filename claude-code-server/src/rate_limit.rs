@@ -0,0 +1,122 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Per-method minimum intervals between notifications to Claude, trading the freshness of
+/// noisy events (e.g. `selection_changed`) for less context churn. Methods without an
+/// interval are never limited.
+#[derive(Debug, Default)]
+pub struct NotificationRateLimiter {
+    intervals: HashMap<String, Duration>,
+    last_sent: HashMap<String, Instant>,
+    /// Notifications dropped per method for arriving too soon after the previous one
+    suppressed: BTreeMap<String, u64>,
+}
+
+pub type SharedRateLimiter = Arc<Mutex<NotificationRateLimiter>>;
+
+impl NotificationRateLimiter {
+    /// Replace the per-method intervals; zero intervals leave a method unlimited
+    pub fn configure(&mut self, intervals: HashMap<String, Duration>) {
+        self.intervals = intervals
+            .into_iter()
+            .filter(|(_, interval)| !interval.is_zero())
+            .collect();
+        self.last_sent.clear();
+    }
+
+    /// Whether a `method` notification may be sent now, counting it as suppressed if not
+    pub fn admit(&mut self, method: &str) -> bool {
+        let Some(interval) = self.intervals.get(method) else {
+            return true;
+        };
+        let now = Instant::now();
+        match self.last_sent.get(method) {
+            Some(last) if now.duration_since(*last) < *interval => {
+                *self.suppressed.entry(method.to_string()).or_insert(0) += 1;
+                false
+            }
+            _ => {
+                self.last_sent.insert(method.to_string(), now);
+                true
+            }
+        }
+    }
+
+    /// Configured intervals in milliseconds and suppressed counts, per method
+    pub fn to_json(&self) -> serde_json::Value {
+        let intervals: BTreeMap<&str, u64> = self
+            .intervals
+            .iter()
+            .map(|(method, interval)| (method.as_str(), interval.as_millis() as u64))
+            .collect();
+        serde_json::json!({
+            "intervalsMs": intervals,
+            "suppressed": self.suppressed
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(intervals: &[(&str, u64)]) -> NotificationRateLimiter {
+        let mut limiter = NotificationRateLimiter::default();
+        limiter.configure(
+            intervals
+                .iter()
+                .map(|(method, ms)| (method.to_string(), Duration::from_millis(*ms)))
+                .collect(),
+        );
+        limiter
+    }
+
+    #[test]
+    fn methods_without_an_interval_are_never_limited() {
+        let mut limiter = limiter(&[("selection_changed", 0)]);
+        for _ in 0..3 {
+            assert!(limiter.admit("selection_changed"));
+            assert!(limiter.admit("at_mentioned"));
+        }
+        assert_eq!(limiter.to_json()["suppressed"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn notifications_within_the_interval_are_suppressed() {
+        let mut limiter = limiter(&[("selection_changed", 60_000)]);
+        assert!(limiter.admit("selection_changed"));
+        assert!(!limiter.admit("selection_changed"));
+        assert!(!limiter.admit("selection_changed"));
+        assert!(limiter.admit("at_mentioned"));
+        assert_eq!(
+            limiter.to_json(),
+            serde_json::json!({
+                "intervalsMs": { "selection_changed": 60_000 },
+                "suppressed": { "selection_changed": 2 }
+            })
+        );
+    }
+
+    #[test]
+    fn notifications_after_the_interval_are_admitted() {
+        let mut limiter = limiter(&[("selection_changed", 20)]);
+        assert!(limiter.admit("selection_changed"));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.admit("selection_changed"));
+    }
+
+    #[test]
+    fn reconfiguring_forgets_when_methods_were_last_sent() {
+        let mut limiter = limiter(&[("selection_changed", 60_000)]);
+        assert!(limiter.admit("selection_changed"));
+        assert!(!limiter.admit("selection_changed"));
+        limiter.configure(HashMap::from([(
+            "selection_changed".to_string(),
+            Duration::from_secs(60),
+        )]));
+        assert!(limiter.admit("selection_changed"));
+        // Suppressed counts are kept across configurations
+        assert_eq!(limiter.to_json()["suppressed"]["selection_changed"], 1);
+    }
+}
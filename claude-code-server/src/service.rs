@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+
+use crate::state::{ServerMode, SharedState};
+use crate::systemd;
+use crate::websocket::{run_websocket_server_full, WebSocketSettings};
+
+/// Run a long-lived service hosting one WebSocket session per worktree in a single process,
+/// for users who prefer one daemon over one server per Zed window.
+///
+/// Each worktree gets its own port, lock file and editor state. With `systemd_socket`, the
+/// sockets passed by systemd are assigned to the worktrees in order.
+pub async fn run_service(worktrees: Vec<PathBuf>, systemd_socket: bool) -> Result<()> {
+    info!("Starting service for {} worktree(s)", worktrees.len());
+
+    let listeners = if systemd_socket {
+        systemd::take_listeners()?
+    } else {
+        Vec::new()
+    };
+    if systemd_socket && listeners.len() != worktrees.len() {
+        warn!(
+            "systemd passed {} socket(s) for {} worktree(s); unmatched worktrees bind dynamically",
+            listeners.len(),
+            worktrees.len()
+        );
+    }
+    let mut listeners = listeners.into_iter();
+
+    let mut sessions = JoinSet::new();
+    for worktree in worktrees {
+        info!("Starting session for worktree {}", worktree.display());
+        let settings = WebSocketSettings {
+            listener: listeners.next(),
+            ..WebSocketSettings::default()
+        };
+        let state = SharedState::new(ServerMode::WebSocket, Some(&worktree));
+        sessions.spawn(run_websocket_server_full(
+            None,
+            Some(worktree),
+            None,
+            None,
+            settings,
+            state,
+        ));
+    }
+
+    while let Some(result) = sessions.join_next().await {
+        match result {
+            Ok(Ok(())) => info!("Worktree session completed"),
+            Ok(Err(e)) => error!("Worktree session error: {}", e),
+            Err(e) => error!("Worktree session task panicked: {}", e),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,849 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Display;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot, watch, RwLock};
+use tower_lsp::lsp_types::{Diagnostic, Url};
+use tracing::debug;
+
+use crate::config::{
+    ClaudeCodeOptions, HookConfig, OsNotificationEvent, OsNotificationOptions, DEFAULT_IGNORED_DIRS,
+};
+use crate::connection_stats::ConnectionStats;
+use crate::context::{self, SessionContext, SharedContext};
+use crate::diagnostics::{SharedDiagnostics, CLAUDE_SOURCE, CLIENT_SOURCE};
+use crate::diff_tabs::SharedDiffTabs;
+use crate::documents::SharedDocuments;
+use crate::editor::EditorBridge;
+use crate::event_tap::EventTap;
+use crate::file_index::SharedFileIndex;
+use crate::file_locks::PathLocks;
+use crate::i18n::{Locale, Text};
+use crate::journal::EditJournal;
+use crate::lsp::{
+    publish_notification, ClaudeStatusParams, JsonRpcNotification, NotificationReceiver,
+    NotificationSender, PositionEncoding, SelectionChangedNotification,
+};
+use crate::os_notify;
+use crate::rate_limit::SharedRateLimiter;
+use crate::symbols::SharedSymbolIndex;
+use crate::tool_cache::SharedToolCache;
+
+/// Maximum notifications buffered while no MCP session is connected; the oldest are dropped first
+const MAX_QUEUED_NOTIFICATIONS: usize = 100;
+
+/// Notifications withheld from Claude in focus mode
+const NON_ESSENTIAL_NOTIFICATIONS: &[&str] = &["selection_changed"];
+
+/// Notifications about a file, which are dropped for files in ignored directories
+const FILE_NOTIFICATIONS: &[&str] = &["selection_changed", "at_mentioned"];
+
+/// Notifications buffered per MCP session before a slow session starts missing them
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 100;
+
+/// Set by `--dry-run`: write tools only describe their changes, in every session
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// Set by `--strict-subprotocol`: WebSocket clients must negotiate the `mcp` subprotocol,
+/// in every session
+static STRICT_SUBPROTOCOL: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_subprotocol(enabled: bool) {
+    STRICT_SUBPROTOCOL.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_strict_subprotocol() -> bool {
+    STRICT_SUBPROTOCOL.load(Ordering::SeqCst)
+}
+
+/// Set by `--relative-paths`: tool responses give paths relative to the worktree, in
+/// every session, whatever the `relativePaths` option says
+static RELATIVE_PATHS_FLAG: AtomicBool = AtomicBool::new(false);
+
+/// Follows the `relativePaths` option, turning relative paths on and off as it changes
+static RELATIVE_PATHS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_relative_paths_flag(enabled: bool) {
+    RELATIVE_PATHS_FLAG.store(enabled, Ordering::SeqCst);
+}
+
+pub fn set_relative_paths(enabled: bool) {
+    RELATIVE_PATHS.store(enabled, Ordering::SeqCst);
+}
+
+pub fn uses_relative_paths() -> bool {
+    RELATIVE_PATHS_FLAG.load(Ordering::SeqCst) || RELATIVE_PATHS.load(Ordering::SeqCst)
+}
+
+/// Set by `--read-only-token`: the token admitting read-only MCP sessions, in every session
+static READ_ONLY_TOKEN: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_read_only_token(token: Option<String>) {
+    *READ_ONLY_TOKEN.lock().unwrap() = token;
+}
+
+pub fn read_only_token() -> Option<String> {
+    READ_ONLY_TOKEN.lock().unwrap().clone()
+}
+
+/// Position encoding negotiated with the LSP client, which every position the server
+/// receives or sends is in
+static POSITION_ENCODING: Mutex<PositionEncoding> = Mutex::new(PositionEncoding::Utf16);
+
+pub fn set_position_encoding(encoding: PositionEncoding) {
+    *POSITION_ENCODING.lock().unwrap() = encoding;
+}
+
+pub fn position_encoding() -> PositionEncoding {
+    *POSITION_ENCODING.lock().unwrap()
+}
+
+/// Which halves of the server are running in this process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ServerMode {
+    /// LSP on stdio plus MCP over WebSocket
+    #[default]
+    Hybrid,
+    /// MCP over WebSocket only; editor state comes from disk
+    WebSocket,
+    /// LSP only; notifications are queued since there is no MCP client
+    Lsp,
+}
+
+impl ServerMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerMode::Hybrid => "hybrid",
+            ServerMode::WebSocket => "websocket",
+            ServerMode::Lsp => "lsp",
+        }
+    }
+}
+
+/// What happened to the WebSocket server, as reported to Zed with `claude/status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusEvent {
+    Listening,
+    Connected,
+    Disconnected,
+    AuthFailed,
+}
+
+impl StatusEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusEvent::Listening => "listening",
+            StatusEvent::Connected => "connected",
+            StatusEvent::Disconnected => "disconnected",
+            StatusEvent::AuthFailed => "authFailed",
+        }
+    }
+}
+
+/// Why the WebSocket server releases its port and lock file and closes its sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Teardown {
+    /// A new server took the worktree over
+    Takeover,
+    /// The server is exiting, e.g. after Zed shut the language server down
+    Shutdown,
+}
+
+/// State shared between the LSP server and the MCP sessions served over WebSocket
+#[derive(Debug, Clone)]
+pub struct SharedState {
+    pub documents: SharedDocuments,
+    pub diff_tabs: SharedDiffTabs,
+    /// Latest diagnostics per file, from linters and the client
+    pub diagnostics: SharedDiagnostics,
+    /// Files and ranges shared with Claude during the session
+    pub context: SharedContext,
+    pub tool_cache: SharedToolCache,
+    /// Serializes operations that mutate the same file
+    pub file_locks: PathLocks,
+    /// The workspace's files, for `@` file completions
+    pub file_index: SharedFileIndex,
+    /// Declarations in the workspace's source files, for `@` symbol completions
+    pub symbol_index: SharedSymbolIndex,
+    /// Change sets being written to the workspace, to detect ones a crash interrupted
+    pub journal: EditJournal,
+    /// How WebSocket connections ended, for reconnect diagnostics
+    pub connection_stats: ConnectionStats,
+    /// Per-method notification rate limits and how many notifications they suppressed
+    pub rate_limiter: SharedRateLimiter,
+    /// Requests from MCP sessions that the attached LSP session carries out in Zed
+    pub editor: EditorBridge,
+    mode: ServerMode,
+    /// Worktree root, against which ignored directories are matched
+    worktree: Option<Arc<Path>>,
+    /// The project's other folders when it has several worktrees, as reported by Zed
+    extra_workspace_folders: Arc<watch::Sender<Vec<PathBuf>>>,
+    /// Whether a Zed language server session is currently attached
+    lsp_attached: Arc<AtomicBool>,
+    /// The process that started the server, where the platform reports it
+    parent_pid: Option<u32>,
+    /// Whether the user trusts the workspace; untrusted ones get read-only tools
+    trusted: Arc<AtomicBool>,
+    /// Whether Claude should observe without interacting: selection updates are not
+    /// forwarded and permission requests are denied
+    focus_mode: Arc<AtomicBool>,
+    /// Number of connected MCP sessions
+    mcp_sessions: Arc<AtomicUsize>,
+    /// Notifications raised while no MCP session was connected, replayed to the next one
+    queued_notifications: Arc<Mutex<VecDeque<JsonRpcNotification>>>,
+    /// Channel delivering notifications to every connected MCP session
+    notification_sender: Arc<NotificationSender>,
+    /// Which events are surfaced as native OS notifications
+    os_notifications: Arc<Mutex<OsNotificationOptions>>,
+    /// Commands run around MCP tool calls
+    hooks: Arc<Mutex<Vec<HookConfig>>>,
+    /// Generated directories whose files are skipped by scans and notifications
+    ignored_dirs: Arc<Mutex<Vec<String>>>,
+    /// Parameters of the most recent `selection_changed` notification
+    last_selection: Arc<Mutex<Option<serde_json::Value>>>,
+    /// The last `selection_changed` published, so identical ones are not sent again until
+    /// the session is reset or a new MCP session connects
+    last_selection_sent: Arc<Mutex<Option<SelectionChangedNotification>>>,
+    /// The most recent file or range at-mentioned, as `at_mentioned` parameters
+    last_at_mention: Arc<Mutex<Option<serde_json::Value>>>,
+    /// Port Claude connects to, once the WebSocket server is listening
+    websocket_port: Arc<Mutex<Option<u16>>>,
+    /// Language of prompts, messages and OS notifications
+    locale: Arc<Mutex<Locale>>,
+    /// Set once a new server took the worktree over or Zed shut the server down; sessions
+    /// close and the server exits
+    draining: Arc<watch::Sender<Option<Teardown>>>,
+    /// Port range set at runtime, which the WebSocket server moves into
+    port_range: Arc<watch::Sender<Option<(u16, u16)>>>,
+    /// The only tools offered to Claude, when the user's settings restrict them
+    enabled_tools: Arc<Mutex<Option<Vec<String>>>>,
+    /// How often the watchdog checks that Zed is alive; `None` disables it
+    watchdog_interval: Arc<watch::Sender<Option<Duration>>>,
+    /// Starts the WebSocket server when `autoStart` is off, until the user starts it
+    deferred_start: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    /// Mirror of every notification for external tools, when configured
+    event_tap: Arc<Mutex<Option<EventTap>>>,
+    /// Subsystems that failed to start, with why
+    degraded: Arc<Mutex<BTreeMap<&'static str, String>>>,
+}
+
+impl Default for SharedState {
+    fn default() -> Self {
+        let (notification_sender, _) = broadcast::channel(NOTIFICATION_CHANNEL_CAPACITY);
+        Self {
+            documents: SharedDocuments::default(),
+            diff_tabs: SharedDiffTabs::default(),
+            diagnostics: SharedDiagnostics::default(),
+            context: SharedContext::default(),
+            tool_cache: SharedToolCache::default(),
+            file_locks: PathLocks::default(),
+            file_index: SharedFileIndex::default(),
+            symbol_index: SharedSymbolIndex::default(),
+            journal: EditJournal::default(),
+            connection_stats: ConnectionStats::default(),
+            rate_limiter: SharedRateLimiter::default(),
+            editor: EditorBridge::default(),
+            mode: ServerMode::default(),
+            worktree: None,
+            extra_workspace_folders: Arc::new(watch::channel(Vec::new()).0),
+            lsp_attached: Arc::default(),
+            #[cfg(unix)]
+            parent_pid: Some(std::os::unix::process::parent_id()),
+            #[cfg(not(unix))]
+            parent_pid: None,
+            trusted: Arc::default(),
+            focus_mode: Arc::default(),
+            mcp_sessions: Arc::default(),
+            queued_notifications: Arc::default(),
+            notification_sender: Arc::new(notification_sender),
+            os_notifications: Arc::default(),
+            hooks: Arc::default(),
+            ignored_dirs: Arc::new(Mutex::new(
+                DEFAULT_IGNORED_DIRS.iter().map(|d| d.to_string()).collect(),
+            )),
+            last_selection: Arc::default(),
+            last_selection_sent: Arc::default(),
+            last_at_mention: Arc::default(),
+            websocket_port: Arc::default(),
+            locale: Arc::default(),
+            draining: Arc::new(watch::channel(None).0),
+            port_range: Arc::new(watch::channel(None).0),
+            enabled_tools: Arc::default(),
+            watchdog_interval: Arc::new(
+                watch::channel(ClaudeCodeOptions::default().watchdog_interval()).0,
+            ),
+            deferred_start: Arc::default(),
+            event_tap: Arc::default(),
+            degraded: Arc::default(),
+        }
+    }
+}
+
+impl SharedState {
+    /// State for a server in `mode`, loading the worktree's persisted session context
+    pub fn new(mode: ServerMode, worktree: Option<&Path>) -> Self {
+        let context = SessionContext::load(worktree);
+        let journal = EditJournal::for_worktree(worktree);
+        let state = Self {
+            mode,
+            worktree: worktree
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok())
+                .map(Arc::from),
+            ..Self::default()
+        };
+        if !context.is_persisted() {
+            state.set_degraded(
+                "sessionContext",
+                Some("no data directory, so the session context is not saved across restarts"),
+            );
+        }
+        if !journal.is_enabled() {
+            state.set_degraded(
+                "editJournal",
+                Some("no data directory, so edits interrupted by a crash cannot be restored"),
+            );
+        }
+        let state = Self {
+            context: Arc::new(RwLock::new(context)),
+            journal,
+            ..state
+        };
+        let draining = state.clone();
+        context::spawn_saver(
+            state.context.clone(),
+            async move { draining.drained().await },
+        );
+        state
+    }
+
+    /// Record that a subsystem failed to start and why, or with `None` that it works
+    pub fn set_degraded(&self, subsystem: &'static str, reason: Option<impl Into<String>>) {
+        let mut degraded = self.degraded.lock().unwrap();
+        match reason {
+            Some(reason) => degraded.insert(subsystem, reason.into()),
+            None => degraded.remove(subsystem),
+        };
+    }
+
+    /// Subsystems that are unavailable right now, as `{subsystem, reason}` entries, so
+    /// clients can tell why tools are missing or fall back to disk
+    pub fn degraded_capabilities(&self) -> Vec<serde_json::Value> {
+        let mut degraded = self.degraded.lock().unwrap().clone();
+        if self.mode == ServerMode::Hybrid && !self.lsp_attached() {
+            degraded.insert(
+                "lsp",
+                "the Zed language server is not attached, so editor tools read from disk or \
+                 are unsupported"
+                    .to_string(),
+            );
+        }
+        if !self.is_trusted() {
+            degraded.insert(
+                "trust",
+                "the workspace is not trusted, so tools that modify files are hidden".to_string(),
+            );
+        }
+        degraded
+            .into_iter()
+            .map(|(subsystem, reason)| {
+                serde_json::json!({ "subsystem": subsystem, "reason": reason })
+            })
+            .collect()
+    }
+
+    /// Store Claude's findings for a file, replacing those reported before, and show them
+    /// in Zed. Returns whether an LSP session was attached to show them.
+    pub async fn report_findings(&self, uri: Url, diagnostics: Vec<Diagnostic>) -> bool {
+        self.diagnostics
+            .write()
+            .await
+            .set(uri.clone(), CLAUDE_SOURCE, diagnostics);
+        self.invalidate_tool_cache();
+        self.show_diagnostics(uri).await
+    }
+
+    /// Show the diagnostics this server produced for a file (Claude's findings and the
+    /// on-save linters') in Zed, replacing those shown before. Diagnostics Zed forwarded
+    /// are already shown by the language servers that reported them. Returns whether an
+    /// LSP session was attached to show them.
+    pub async fn show_diagnostics(&self, uri: Url) -> bool {
+        let diagnostics = self
+            .diagnostics
+            .read()
+            .await
+            .get_excluding(&uri, CLIENT_SOURCE)
+            .into_iter()
+            .cloned()
+            .collect();
+        self.editor.publish_diagnostics(uri, diagnostics)
+    }
+
+    /// Forget cached tool results after the files they were computed from changed
+    pub fn invalidate_tool_cache(&self) {
+        self.tool_cache.lock().unwrap().invalidate();
+    }
+
+    pub fn mode(&self) -> ServerMode {
+        self.mode
+    }
+
+    pub fn lsp_attached(&self) -> bool {
+        self.lsp_attached.load(Ordering::SeqCst)
+    }
+
+    /// The process that started the server, where the platform reports it
+    pub fn parent_pid(&self) -> Option<u32> {
+        self.parent_pid
+    }
+
+    /// Record whether the LSP bridge is attached, returning true if this changed the state
+    pub fn set_lsp_attached(&self, attached: bool) -> bool {
+        let changed = self.lsp_attached.swap(attached, Ordering::SeqCst) != attached;
+        if changed {
+            // Tools fall back to other backends, so earlier results no longer apply
+            self.invalidate_tool_cache();
+        }
+        changed
+    }
+
+    /// `path` relative to the worktree, when tool responses use relative paths and it is
+    /// inside the worktree
+    pub fn relative_path(&self, path: &Path) -> Option<String> {
+        if !uses_relative_paths() {
+            return None;
+        }
+        let relative = path.strip_prefix(self.worktree.as_deref()?).ok()?;
+        Some(relative.to_string_lossy().into_owned())
+    }
+
+    /// Make the paths of a tool response or notification object relative to the worktree
+    /// when relative paths are on: `filePath` becomes a relative path and `uri` and
+    /// `fileUrl` relative references, with the absolute forms kept as `absolutePath`,
+    /// `absoluteUri` and `absoluteFileUrl`
+    pub fn relativize_file_path(&self, value: &mut serde_json::Value) {
+        let absolute = value.get("filePath").and_then(|path| path.as_str());
+        if let Some((absolute, relative)) = absolute.and_then(|absolute| {
+            Some((
+                absolute.to_string(),
+                self.relative_path(Path::new(absolute))?,
+            ))
+        }) {
+            value["absolutePath"] = absolute.into();
+            value["filePath"] = relative.into();
+        }
+        for (key, absolute_key) in [("uri", "absoluteUri"), ("fileUrl", "absoluteFileUrl")] {
+            let absolute = value
+                .get(key)
+                .and_then(|uri| uri.as_str())
+                .and_then(|uri| Url::parse(uri).ok());
+            if let Some((absolute, relative)) = absolute
+                .and_then(|absolute| Some((absolute.to_string(), self.relative_uri(&absolute)?)))
+            {
+                value[absolute_key] = absolute.into();
+                value[key] = relative.into();
+            }
+        }
+    }
+
+    /// `uri` as a reference relative to the worktree, under the same conditions as
+    /// [`Self::relative_path`]
+    fn relative_uri(&self, uri: &Url) -> Option<String> {
+        self.relative_path(&uri.to_file_path().ok()?)?;
+        Url::from_directory_path(self.worktree.as_deref()?)
+            .ok()?
+            .make_relative(uri)
+    }
+
+    /// A path given to a tool, with a relative one (as relative path mode reports them)
+    /// resolved against the worktree
+    pub fn resolve_path(&self, file_path: &str) -> PathBuf {
+        match self.worktree.as_deref() {
+            Some(worktree) if Path::new(file_path).is_relative() => worktree.join(file_path),
+            _ => PathBuf::from(file_path),
+        }
+    }
+
+    /// The project's folders besides the worktree
+    pub fn extra_workspace_folders(&self) -> Vec<PathBuf> {
+        self.extra_workspace_folders.borrow().clone()
+    }
+
+    /// Record the project's folders; the worktree and duplicates are skipped
+    pub fn set_workspace_folders(&self, folders: Vec<PathBuf>) {
+        let mut extra: Vec<PathBuf> = Vec::new();
+        for folder in folders {
+            if self.worktree.as_deref() != Some(folder.as_path()) && !extra.contains(&folder) {
+                extra.push(folder);
+            }
+        }
+        self.extra_workspace_folders.send_if_modified(|current| {
+            let changed = *current != extra;
+            *current = extra;
+            changed
+        });
+    }
+
+    /// Add and remove folders of the project, as `workspace/didChangeWorkspaceFolders`
+    /// reports them. The worktree itself is never removed.
+    pub fn change_workspace_folders(&self, added: Vec<PathBuf>, removed: &[PathBuf]) {
+        let mut folders = self.extra_workspace_folders();
+        folders.retain(|folder| !removed.contains(folder));
+        folders.extend(added);
+        self.set_workspace_folders(folders);
+    }
+
+    /// Changes to the project's folders besides the worktree
+    pub fn workspace_folder_changes(&self) -> watch::Receiver<Vec<PathBuf>> {
+        self.extra_workspace_folders.subscribe()
+    }
+
+    pub fn is_trusted(&self) -> bool {
+        self.trusted.load(Ordering::SeqCst)
+    }
+
+    /// Record whether the workspace is trusted, returning true if this changed the state
+    pub fn set_trusted(&self, trusted: bool) -> bool {
+        self.trusted.swap(trusted, Ordering::SeqCst) != trusted
+    }
+
+    pub fn is_focus_mode(&self) -> bool {
+        self.focus_mode.load(Ordering::SeqCst)
+    }
+
+    /// Enter or leave focus mode, returning true if this changed the state
+    pub fn set_focus_mode(&self, enabled: bool) -> bool {
+        self.focus_mode.swap(enabled, Ordering::SeqCst) != enabled
+    }
+
+    /// Whether an IDE notification should reach Claude; focus mode holds back the
+    /// non-essential ones, though sessions still track them for the selection tools
+    pub fn forwards_notification(&self, method: &str) -> bool {
+        !(self.is_focus_mode() && NON_ESSENTIAL_NOTIFICATIONS.contains(&method))
+    }
+
+    pub fn set_ignored_dirs(&self, dirs: Vec<String>) {
+        *self.ignored_dirs.lock().unwrap() = dirs;
+    }
+
+    pub fn ignored_dirs(&self) -> Vec<String> {
+        self.ignored_dirs.lock().unwrap().clone()
+    }
+
+    /// Whether `path` lies in an ignored directory; only components below the worktree root
+    /// count, so a worktree that itself lives under e.g. `build/` is not ignored wholesale
+    pub fn is_ignored_path(&self, path: &Path) -> bool {
+        let relative = self
+            .worktree
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        let ignored_dirs = self.ignored_dirs.lock().unwrap();
+        relative.parent().is_some_and(|dirs| {
+            dirs.components().any(|component| match component {
+                Component::Normal(name) => ignored_dirs.iter().any(|dir| name == dir.as_str()),
+                _ => false,
+            })
+        })
+    }
+
+    /// Whether a notification concerns a file in an ignored directory, making it noise
+    pub fn is_ignored_notification(&self, method: &str, params: &serde_json::Value) -> bool {
+        FILE_NOTIFICATIONS.contains(&method)
+            && params
+                .get("filePath")
+                .and_then(|path| path.as_str())
+                .is_some_and(|path| self.is_ignored_path(Path::new(path)))
+    }
+
+    pub fn set_locale(&self, locale: Locale) {
+        *self.locale.lock().unwrap() = locale;
+    }
+
+    /// A user-facing string in the session's locale
+    pub fn localize(&self, text: Text, args: &[&(dyn Display + Sync)]) -> String {
+        self.locale.lock().unwrap().format(text, args)
+    }
+
+    pub fn set_last_selection(&self, params: serde_json::Value) {
+        *self.last_selection.lock().unwrap() = Some(params);
+    }
+
+    /// The selection last reported by the editor, as `selection_changed` parameters
+    pub fn last_selection(&self) -> Option<serde_json::Value> {
+        self.last_selection.lock().unwrap().clone()
+    }
+
+    /// Record `notification` as the selection last published. Returns false, recording
+    /// nothing, when it is the one already published.
+    pub fn record_selection_sent(&self, notification: &SelectionChangedNotification) -> bool {
+        let mut last_sent = self.last_selection_sent.lock().unwrap();
+        if last_sent.as_ref() == Some(notification) {
+            return false;
+        }
+        *last_sent = Some(notification.clone());
+        true
+    }
+
+    /// Forget the selection last published, so the next one is sent even if unchanged
+    pub fn forget_selection_sent(&self) {
+        *self.last_selection_sent.lock().unwrap() = None;
+    }
+
+    pub fn set_last_at_mention(&self, params: serde_json::Value) {
+        *self.last_at_mention.lock().unwrap() = Some(params);
+    }
+
+    /// The file or range last at-mentioned, as `at_mentioned` parameters
+    pub fn last_at_mention(&self) -> Option<serde_json::Value> {
+        self.last_at_mention.lock().unwrap().clone()
+    }
+
+    pub fn set_websocket_port(&self, port: u16) {
+        *self.websocket_port.lock().unwrap() = Some(port);
+    }
+
+    pub fn websocket_port(&self) -> Option<u16> {
+        *self.websocket_port.lock().unwrap()
+    }
+
+    /// Tell Zed what happened to the WebSocket server, with the port and session count.
+    /// Nothing is sent before the server listens or while no LSP session is attached.
+    pub fn publish_status(&self, event: StatusEvent) {
+        let Some(port) = self.websocket_port() else {
+            return;
+        };
+        let sessions = self.mcp_sessions.load(Ordering::SeqCst);
+        let text = match event {
+            StatusEvent::AuthFailed => Text::StatusAuthFailed,
+            _ if sessions > 0 => Text::HoverConnected,
+            _ => Text::HoverWaiting,
+        };
+        debug!("Status: {} on port {}", event.as_str(), port);
+        self.editor.status(ClaudeStatusParams {
+            event: event.as_str().to_string(),
+            port,
+            sessions,
+            message: self.localize(text, &[&port]),
+        });
+    }
+
+    pub fn has_mcp_sessions(&self) -> bool {
+        self.mcp_sessions.load(Ordering::SeqCst) > 0
+    }
+
+    /// Hand the worktree over to a new server: the WebSocket server releases its port and
+    /// lock file and every MCP session closes
+    pub fn start_drain(&self) {
+        self.draining.send_replace(Some(Teardown::Takeover));
+    }
+
+    /// Tear the WebSocket server down the same way because the server is exiting, unless
+    /// it is already handing over
+    pub fn start_shutdown(&self) {
+        self.draining.send_if_modified(|draining| {
+            let starting = draining.is_none();
+            if starting {
+                *draining = Some(Teardown::Shutdown);
+            }
+            starting
+        });
+    }
+
+    /// Why the server is tearing down, once it is
+    pub fn teardown(&self) -> Option<Teardown> {
+        *self.draining.borrow()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.teardown().is_some()
+    }
+
+    /// Resolve once draining has started
+    pub async fn drained(&self) {
+        let mut draining = self.draining.subscribe();
+        let _ = draining.wait_for(Option::is_some).await;
+    }
+
+    /// Ask the WebSocket server to move to a port in `range` if it listens outside it
+    pub fn set_port_range(&self, range: (u16, u16)) {
+        self.port_range.send_if_modified(|current| {
+            let changed = *current != Some(range);
+            *current = Some(range);
+            changed
+        });
+    }
+
+    /// Port ranges set at runtime, as they change
+    pub fn port_range_changes(&self) -> watch::Receiver<Option<(u16, u16)>> {
+        self.port_range.subscribe()
+    }
+
+    /// Restrict the tools offered to Claude to `tools`, or offer all of them with `None`.
+    /// Returns whether the tool list changed.
+    pub fn set_enabled_tools(&self, tools: Option<Vec<String>>) -> bool {
+        let mut enabled = self.enabled_tools.lock().unwrap();
+        let changed = *enabled != tools;
+        *enabled = tools;
+        changed
+    }
+
+    pub fn is_tool_enabled(&self, tool_name: &str) -> bool {
+        self.enabled_tools
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_none_or(|tools| tools.iter().any(|tool| tool == tool_name))
+    }
+
+    /// Change how often the watchdog checks that Zed is alive, or disable it with `None`
+    pub fn set_watchdog_interval(&self, interval: Option<Duration>) {
+        self.watchdog_interval.send_if_modified(|current| {
+            let changed = *current != interval;
+            *current = interval;
+            changed
+        });
+    }
+
+    /// Watchdog intervals, as they change
+    pub fn watchdog_interval_changes(&self) -> watch::Receiver<Option<Duration>> {
+        self.watchdog_interval.subscribe()
+    }
+
+    /// Hold the WebSocket server back until [`SharedState::start_websocket`] is called; the
+    /// returned receiver resolves then
+    pub fn defer_websocket_start(&self) -> oneshot::Receiver<()> {
+        let (start, started) = oneshot::channel();
+        *self.deferred_start.lock().unwrap() = Some(start);
+        started
+    }
+
+    /// Whether the WebSocket server is waiting for the user to start it
+    pub fn is_websocket_deferred(&self) -> bool {
+        self.deferred_start.lock().unwrap().is_some()
+    }
+
+    /// Start a WebSocket server held back by `autoStart`, returning false if none was
+    pub fn start_websocket(&self) -> bool {
+        match self.deferred_start.lock().unwrap().take() {
+            Some(start) => start.send(()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Count an MCP session as connected until the returned guard is dropped
+    pub fn mcp_session_started(&self) -> McpSessionGuard {
+        self.mcp_sessions.fetch_add(1, Ordering::SeqCst);
+        // The new session hasn't seen the current selection
+        self.forget_selection_sent();
+        McpSessionGuard {
+            sessions: self.mcp_sessions.clone(),
+        }
+    }
+
+    pub fn queue_notification(&self, notification: JsonRpcNotification) {
+        let mut queue = self.queued_notifications.lock().unwrap();
+        if queue.len() >= MAX_QUEUED_NOTIFICATIONS {
+            if let Some(dropped) = queue.pop_front() {
+                debug!("Notification queue full, dropping {}", dropped.method);
+            }
+        }
+        queue.push_back(notification);
+    }
+
+    /// Drain the notifications queued while no MCP session was connected, oldest first
+    pub fn take_queued_notifications(&self) -> Vec<JsonRpcNotification> {
+        self.queued_notifications
+            .lock()
+            .unwrap()
+            .drain(..)
+            .collect()
+    }
+
+    /// Notifications waiting for the next MCP session, oldest first, left in the queue
+    pub fn queued_notifications(&self) -> Vec<JsonRpcNotification> {
+        self.queued_notifications
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Discard notifications queued for the next MCP session
+    pub fn clear_queued_notifications(&self) -> usize {
+        let mut queue = self.queued_notifications.lock().unwrap();
+        let cleared = queue.len();
+        queue.clear();
+        cleared
+    }
+
+    pub fn notification_sender(&self) -> Arc<NotificationSender> {
+        self.notification_sender.clone()
+    }
+
+    pub fn subscribe_notifications(&self) -> NotificationReceiver {
+        self.notification_sender.subscribe()
+    }
+
+    pub fn set_os_notifications(&self, options: OsNotificationOptions) {
+        *self.os_notifications.lock().unwrap() = options;
+    }
+
+    /// Surface an event as a native OS notification, if the user opted in to it
+    pub fn notify_os(&self, event: OsNotificationEvent, message: &str) {
+        if self.os_notifications.lock().unwrap().wants(event) {
+            os_notify::show(message);
+        }
+    }
+
+    pub fn set_event_tap(&self, tap: EventTap) {
+        *self.event_tap.lock().unwrap() = Some(tap);
+    }
+
+    /// Mirror a notification to the event tap, if one is configured
+    pub fn mirror_to_event_tap(&self, notification: &JsonRpcNotification) {
+        if let Some(tap) = self.event_tap.lock().unwrap().as_ref() {
+            tap.mirror(notification);
+        }
+    }
+
+    pub fn set_hooks(&self, hooks: Vec<HookConfig>) {
+        *self.hooks.lock().unwrap() = hooks;
+    }
+
+    /// Tool-call hooks to run; none in an untrusted workspace, as they execute commands
+    pub fn hooks(&self) -> Vec<HookConfig> {
+        if !self.is_trusted() {
+            return Vec::new();
+        }
+        self.hooks.lock().unwrap().clone()
+    }
+
+    /// Notify every connected MCP session, or queue the notification until one connects
+    pub fn publish(&self, method: &str, params: serde_json::Value) {
+        publish_notification(Some(&self.notification_sender), self, method, params);
+    }
+}
+
+/// Keeps an MCP session counted as connected while alive
+#[derive(Debug)]
+pub struct McpSessionGuard {
+    sessions: Arc<AtomicUsize>,
+}
+
+impl Drop for McpSessionGuard {
+    fn drop(&mut self) {
+        self.sessions.fetch_sub(1, Ordering::SeqCst);
+    }
+}
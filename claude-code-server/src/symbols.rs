@@ -0,0 +1,599 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::RwLock;
+use tower_lsp::lsp_types::{self, Position, SymbolKind};
+use tree_sitter::{Language, Node, Parser};
+
+use crate::file_index::fuzzy_score;
+use crate::languages::language_id_for_path;
+
+/// Files larger than this are skipped when indexing symbols
+const MAX_SYMBOL_FILE_BYTES: u64 = 512 * 1024;
+
+/// Upper bound on indexed symbols, so huge workspaces don't stall completions
+const MAX_INDEXED_SYMBOLS: usize = 100_000;
+
+/// Syntax tree depth below which declarations are not looked for, so deeply nested
+/// expressions (e.g. generated code) can't overflow the stack
+const MAX_OUTLINE_DEPTH: usize = 256;
+
+/// A function, type or other declaration found in a source file
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The type, class or module the symbol is declared in
+    pub container: Option<String>,
+    /// 0-based lines the declaration spans, inclusive
+    pub start_line: u32,
+    pub end_line: u32,
+}
+
+impl Symbol {
+    /// The name qualified by its container, e.g. `MyStruct::new`
+    pub fn qualified_name(&self) -> String {
+        match &self.container {
+            Some(container) => format!("{}::{}", container, self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// The lines the declaration spans as an LSP range, which ends at the start of the next
+    /// line so the last line is included
+    pub fn lsp_range(&self) -> lsp_types::Range {
+        lsp_types::Range {
+            start: Position {
+                line: self.start_line,
+                character: 0,
+            },
+            end: Position {
+                line: self.end_line + 1,
+                character: 0,
+            },
+        }
+    }
+}
+
+/// A declaration in a document's outline, with the declarations nested in it
+#[derive(Debug, Clone)]
+pub struct OutlineSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Type the declaration belongs to when that is not where it is nested, like the
+    /// receiver of a Go method
+    pub container: Option<String>,
+    /// Byte ranges of the whole declaration and of its name
+    pub range: Range<usize>,
+    pub name_range: Range<usize>,
+    /// 0-based lines the declaration spans, inclusive
+    pub start_line: u32,
+    pub end_line: u32,
+    pub children: Vec<OutlineSymbol>,
+    /// Name the declarations nested in this one are qualified with, e.g. `MyStruct` for
+    /// `impl MyStruct`
+    scope: Option<String>,
+}
+
+fn grammar(language_id: &str) -> Option<Language> {
+    let language = match language_id {
+        "rust" => tree_sitter_rust::LANGUAGE,
+        "python" => tree_sitter_python::LANGUAGE,
+        "javascript" => tree_sitter_javascript::LANGUAGE,
+        "typescript" => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+        "tsx" => tree_sitter_typescript::LANGUAGE_TSX,
+        "go" => tree_sitter_go::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+/// Whether declarations can be found in `language_id` documents
+pub fn supports(language_id: &str) -> bool {
+    grammar(language_id).is_some()
+}
+
+/// Name of a symbol kind as reported to Claude
+pub fn kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::METHOD => "method",
+        SymbolKind::STRUCT => "struct",
+        SymbolKind::CLASS => "class",
+        SymbolKind::INTERFACE => "interface",
+        SymbolKind::ENUM => "enum",
+        SymbolKind::ENUM_MEMBER => "enumMember",
+        SymbolKind::MODULE => "module",
+        SymbolKind::CONSTANT => "constant",
+        SymbolKind::TYPE_PARAMETER => "type",
+        SymbolKind::FIELD => "field",
+        SymbolKind::OBJECT => "impl",
+        _ => "function",
+    }
+}
+
+/// What a syntax node declares
+struct Declaration<'tree> {
+    kind: SymbolKind,
+    name: String,
+    name_node: Node<'tree>,
+    container: Option<String>,
+    scope: Option<String>,
+}
+
+fn node_text(node: Node, source: &[u8]) -> String {
+    node.utf8_text(source).unwrap_or_default().to_string()
+}
+
+/// The name of a type without its path or generic arguments, e.g. `Vec` for
+/// `std::vec::Vec<T>` or `*List[T]`
+fn base_type_name(node: Node, source: &[u8]) -> String {
+    match node.kind() {
+        "generic_type" => node.child_by_field_name("type").map_or_else(
+            || node_text(node, source),
+            |inner| base_type_name(inner, source),
+        ),
+        "scoped_type_identifier" => node
+            .child_by_field_name("name")
+            .map_or_else(|| node_text(node, source), |name| node_text(name, source)),
+        "pointer_type" | "reference_type" => node
+            .named_child((node.named_child_count() as u32).saturating_sub(1))
+            .map_or_else(
+                || node_text(node, source),
+                |inner| base_type_name(inner, source),
+            ),
+        _ => node_text(node, source),
+    }
+}
+
+/// The declaration `node` is, if any. `in_type` tells whether it is nested in a class, impl
+/// block or other type, where functions are methods.
+fn declaration<'tree>(
+    node: Node<'tree>,
+    source: &[u8],
+    in_type: bool,
+) -> Option<Declaration<'tree>> {
+    let named = |kind: SymbolKind| {
+        let name_node = node.child_by_field_name("name")?;
+        Some(Declaration {
+            kind,
+            name: node_text(name_node, source),
+            name_node,
+            container: None,
+            scope: None,
+        })
+    };
+    let scoped = |kind: SymbolKind| {
+        named(kind).map(|declaration| Declaration {
+            scope: Some(declaration.name.clone()),
+            ..declaration
+        })
+    };
+    let function = if in_type {
+        SymbolKind::METHOD
+    } else {
+        SymbolKind::FUNCTION
+    };
+
+    match node.kind() {
+        "function_item"
+        | "function_signature_item"
+        | "function_definition"
+        | "function_declaration"
+        | "generator_function_declaration"
+        | "macro_definition" => named(function),
+        "method_definition" | "method_signature" | "abstract_method_signature" => {
+            named(SymbolKind::METHOD)
+        }
+        // Go methods are declared at the top level, their type is the receiver's
+        "method_declaration" => {
+            let receiver = node
+                .child_by_field_name("receiver")
+                .and_then(|receiver| receiver.named_child(0))
+                .and_then(|parameter| parameter.child_by_field_name("type"))
+                .map(|receiver| base_type_name(receiver, source));
+            named(SymbolKind::METHOD).map(|declaration| Declaration {
+                container: receiver,
+                ..declaration
+            })
+        }
+        "struct_item" | "union_item" => scoped(SymbolKind::STRUCT),
+        "enum_item" | "enum_declaration" => scoped(SymbolKind::ENUM),
+        "trait_item" | "interface_declaration" => scoped(SymbolKind::INTERFACE),
+        "mod_item" | "internal_module" => scoped(SymbolKind::MODULE),
+        "class_definition" | "class_declaration" | "abstract_class_declaration" => {
+            scoped(SymbolKind::CLASS)
+        }
+        "type_item" | "type_alias_declaration" => named(SymbolKind::TYPE_PARAMETER),
+        "const_item" | "static_item" => named(SymbolKind::CONSTANT),
+        "enum_variant" => named(SymbolKind::ENUM_MEMBER),
+        "field_declaration" => named(SymbolKind::FIELD),
+        "impl_item" => {
+            let type_node = node.child_by_field_name("type")?;
+            let name = match node.child_by_field_name("trait") {
+                Some(trait_node) => format!(
+                    "impl {} for {}",
+                    node_text(trait_node, source),
+                    node_text(type_node, source)
+                ),
+                None => format!("impl {}", node_text(type_node, source)),
+            };
+            Some(Declaration {
+                kind: SymbolKind::OBJECT,
+                name,
+                name_node: type_node,
+                container: None,
+                scope: Some(base_type_name(type_node, source)),
+            })
+        }
+        "type_spec" => {
+            let kind = match node
+                .child_by_field_name("type")
+                .map(|type_node| type_node.kind())
+            {
+                Some("struct_type") => SymbolKind::STRUCT,
+                Some("interface_type") => SymbolKind::INTERFACE,
+                _ => SymbolKind::TYPE_PARAMETER,
+            };
+            scoped(kind)
+        }
+        // `const handler = () => {}` and the like
+        "variable_declarator" => {
+            let value = node.child_by_field_name("value")?;
+            matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function" | "generator_function"
+            )
+            .then(|| named(function))?
+        }
+        _ => None,
+    }
+}
+
+/// Append the declarations nested anywhere in `node`, at `depth` in the syntax tree, to
+/// `symbols`. Nodes deeper than [`MAX_OUTLINE_DEPTH`] are not searched.
+fn collect(
+    node: Node,
+    source: &[u8],
+    in_type: bool,
+    depth: usize,
+    symbols: &mut Vec<OutlineSymbol>,
+) {
+    if depth >= MAX_OUTLINE_DEPTH {
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let Some(declaration) = declaration(child, source, in_type) else {
+            collect(child, source, in_type, depth + 1, symbols);
+            continue;
+        };
+        let is_type = declaration.scope.is_some() && declaration.kind != SymbolKind::MODULE;
+        let mut children = Vec::new();
+        collect(child, source, is_type, depth + 1, &mut children);
+
+        let end = child.end_position();
+        // A declaration ending at the start of a line doesn't span that line
+        let end_line = if end.column == 0 && end.row > child.start_position().row {
+            end.row - 1
+        } else {
+            end.row
+        };
+        symbols.push(OutlineSymbol {
+            name: declaration.name,
+            kind: declaration.kind,
+            container: declaration.container,
+            range: child.byte_range(),
+            name_range: declaration.name_node.byte_range(),
+            start_line: child.start_position().row as u32,
+            end_line: end_line as u32,
+            children,
+            scope: declaration.scope,
+        });
+    }
+}
+
+/// The declarations in `text`, written in `language_id`, nested as in the source. `None`
+/// for languages without a grammar.
+pub fn outline(language_id: &str, text: &str) -> Option<Vec<OutlineSymbol>> {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar(language_id)?).ok()?;
+    let tree = parser.parse(text, None)?;
+    let mut symbols = Vec::new();
+    collect(tree.root_node(), text.as_bytes(), false, 0, &mut symbols);
+    Some(symbols)
+}
+
+fn flatten(outline: &[OutlineSymbol], scope: Option<&str>, symbols: &mut Vec<Symbol>) {
+    for symbol in outline {
+        // Impl blocks only qualify the methods in them
+        if symbol.kind != SymbolKind::OBJECT {
+            symbols.push(Symbol {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                container: symbol
+                    .container
+                    .clone()
+                    .or_else(|| scope.map(str::to_string)),
+                start_line: symbol.start_line,
+                end_line: symbol.end_line,
+            });
+        }
+        flatten(&symbol.children, symbol.scope.as_deref().or(scope), symbols);
+    }
+}
+
+/// The declarations in `text`, written in `language_id`, each qualified by the type or
+/// module it is declared in. Empty for languages without a grammar.
+pub fn extract_symbols(language_id: &str, text: &str) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    if let Some(outline) = outline(language_id, text) {
+        flatten(&outline, None, &mut symbols);
+    }
+    symbols
+}
+
+/// Byte ranges of the syntax nodes around `offset` in `text`, written in `language_id`,
+/// innermost first: the token, then its expression, statement, function and so on up to
+/// the whole text. `None` for languages without a grammar.
+pub fn enclosing_spans(language_id: &str, text: &str, offset: usize) -> Option<Vec<Range<usize>>> {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar(language_id)?).ok()?;
+    let tree = parser.parse(text, None)?;
+    let mut node = tree.root_node().descendant_for_byte_range(offset, offset);
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    while let Some(current) = node {
+        // Nodes spanning the same text as their child add no step, missing ones no text
+        let span = current.byte_range();
+        if !span.is_empty() && spans.last() != Some(&span) {
+            spans.push(span);
+        }
+        node = current.parent();
+    }
+    // The root node stops short of trailing whitespace and comments outside it
+    if spans.last() != Some(&(0..text.len())) {
+        spans.push(0..text.len());
+    }
+    Some(spans)
+}
+
+/// Symbols declared in one file, and when the file was last modified as they were read
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    modified: Option<SystemTime>,
+    symbols: Vec<Symbol>,
+}
+
+impl IndexedFile {
+    /// Read the symbols of the file at `path` from `text`, e.g. a buffer Zed just saved.
+    /// `None` for languages without symbol support. Parsing is CPU-bound, so async callers
+    /// run this with `spawn_blocking`.
+    pub fn parse(path: &Path, language_id: &str, text: &str) -> Option<Self> {
+        supports(language_id).then(|| Self {
+            modified: fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            symbols: extract_symbols(language_id, text),
+        })
+    }
+}
+
+/// Declarations in the workspace's source files, for `workspace/symbol` and `@` symbol
+/// completions. Refreshing re-reads only files modified since they were indexed.
+#[derive(Debug)]
+pub struct SymbolIndex {
+    root: PathBuf,
+    /// Keyed by path relative to the root
+    files: BTreeMap<String, IndexedFile>,
+}
+
+/// When each indexed file was modified as it was read, and how many symbols it has
+pub struct IndexSnapshot {
+    root: PathBuf,
+    files: HashMap<String, (Option<SystemTime>, usize)>,
+}
+
+/// New and modified files read by [`IndexSnapshot::refresh`], for [`SymbolIndex::apply`]
+pub struct IndexRefresh {
+    snapshot: IndexSnapshot,
+    listed: HashSet<String>,
+    /// Symbols re-read per file; `None` for files that are no longer indexed
+    changed: Vec<(String, Option<IndexedFile>)>,
+}
+
+impl IndexSnapshot {
+    /// Read the files among `files`, relative to the root, that are new or modified since
+    /// they were indexed
+    pub fn refresh(self, files: &[String]) -> IndexRefresh {
+        let mut changed = Vec::new();
+        let mut total: usize = self.files.values().map(|(_, symbols)| symbols).sum();
+        for file in files {
+            let path = self.root.join(file);
+            let Some(language_id) = language_id_for_path(&path).filter(|id| supports(id)) else {
+                continue;
+            };
+            let indexed = self.files.get(file);
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) if metadata.len() <= MAX_SYMBOL_FILE_BYTES => metadata,
+                _ => {
+                    if let Some((_, symbols)) = indexed {
+                        total -= symbols;
+                        changed.push((file.clone(), None));
+                    }
+                    continue;
+                }
+            };
+            let modified = metadata.modified().ok();
+            if indexed.is_some_and(|(indexed, _)| modified.is_some() && *indexed == modified)
+                || total >= MAX_INDEXED_SYMBOLS
+            {
+                continue;
+            }
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let symbols = extract_symbols(language_id, &text);
+            total += symbols.len();
+            if let Some((_, previous)) = indexed {
+                total -= previous;
+            }
+            changed.push((file.clone(), Some(IndexedFile { modified, symbols })));
+        }
+        IndexRefresh {
+            listed: files.iter().cloned().collect(),
+            snapshot: self,
+            changed,
+        }
+    }
+}
+
+pub type SharedSymbolIndex = Arc<RwLock<Option<SymbolIndex>>>;
+
+impl SymbolIndex {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// What a refresh needs to know of the indexed files, taken under the index's lock so
+    /// the files can be read without holding it
+    pub fn snapshot(&self) -> IndexSnapshot {
+        IndexSnapshot {
+            root: self.root.clone(),
+            files: self
+                .files
+                .iter()
+                .map(|(file, indexed)| (file.clone(), (indexed.modified, indexed.symbols.len())))
+                .collect(),
+        }
+    }
+
+    /// Apply a refresh: forget files no longer listed and store the ones re-read. Files
+    /// updated since the snapshot, e.g. by [`SymbolIndex::update_file`] on save, keep their
+    /// newer symbols.
+    pub fn apply(&mut self, refresh: IndexRefresh) {
+        let IndexRefresh {
+            snapshot,
+            listed,
+            changed,
+        } = refresh;
+        let unchanged = |file: &str, current: Option<&IndexedFile>| {
+            snapshot.files.get(file).map(|(modified, _)| *modified)
+                == current.map(|indexed| indexed.modified)
+        };
+        self.files.retain(|file, indexed| {
+            listed.contains(file.as_str()) || !unchanged(file, Some(indexed))
+        });
+        for (file, indexed) in changed {
+            if !unchanged(&file, self.files.get(&file)) {
+                continue;
+            }
+            match indexed {
+                Some(indexed) => self.files.insert(file, indexed),
+                None => self.files.remove(&file),
+            };
+        }
+    }
+
+    /// Whether the file at `path` belongs in this index
+    pub fn contains_path(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    /// Store the symbols of the file at `path`, parsed with [`IndexedFile::parse`]
+    pub fn update_file(&mut self, path: &Path, indexed: IndexedFile) {
+        if let Ok(relative) = path.strip_prefix(&self.root) {
+            self.files
+                .insert(relative.to_string_lossy().into_owned(), indexed);
+        }
+    }
+
+    /// Up to `limit` symbols whose qualified names match `query` fuzzily, best first, with
+    /// the files declaring them
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(&str, &Symbol)> {
+        let mut matches: Vec<(i64, usize, &str, &Symbol)> = self
+            .files
+            .iter()
+            .flat_map(|(file, indexed)| {
+                indexed
+                    .symbols
+                    .iter()
+                    .map(move |symbol| (file.as_str(), symbol))
+            })
+            .filter_map(|(file, symbol)| {
+                let name = symbol.qualified_name();
+                fuzzy_score(&name, query).map(|score| (score, name.len(), file, symbol))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, _, file, symbol)| (file, symbol))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lsp_range_includes_the_last_line_of_a_symbol() {
+        let text = "struct Point {\n    x: i32,\n}\n\nfn origin() {}\n";
+        let symbols = extract_symbols("rust", text);
+        let point = symbols
+            .iter()
+            .find(|symbol| symbol.name == "Point")
+            .unwrap();
+        assert_eq!((point.start_line, point.end_line), (0, 2));
+        assert_eq!(point.lsp_range().start, Position::new(0, 0));
+        assert_eq!(point.lsp_range().end, Position::new(3, 0));
+
+        let origin = symbols
+            .iter()
+            .find(|symbol| symbol.name == "origin")
+            .unwrap();
+        assert_eq!(origin.lsp_range().end, Position::new(5, 0));
+    }
+
+    #[test]
+    fn enclosing_spans_widen_from_the_token_to_the_whole_text() {
+        let text = "fn main() {\n    let total = 1 + 2;\n}\n";
+        let offset = text.find("total").unwrap();
+        let spans = enclosing_spans("rust", text, offset).unwrap();
+        assert_eq!(spans.first(), Some(&(offset..offset + "total".len())));
+        assert_eq!(spans.last(), Some(&(0..text.len())));
+        for pair in spans.windows(2) {
+            assert!(pair[1].start <= pair[0].start && pair[0].end <= pair[1].end);
+            assert_ne!(pair[0], pair[1]);
+        }
+        let statement = text.find("let").unwrap()..text.find(';').unwrap() + 1;
+        assert!(spans.contains(&statement));
+    }
+
+    #[test]
+    fn enclosing_spans_cover_the_text_at_its_edges() {
+        let text = "x = 1\n\n# trailing comment\n";
+        for offset in [0, text.len()] {
+            let spans = enclosing_spans("python", text, offset).unwrap();
+            assert_eq!(spans.last(), Some(&(0..text.len())));
+        }
+        let spans = enclosing_spans("rust", "", 0).unwrap();
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].is_empty());
+    }
+
+    #[test]
+    fn enclosing_spans_need_a_grammar() {
+        assert_eq!(enclosing_spans("markdown", "# Title", 2), None);
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Result};
+use std::net::TcpListener;
+use tracing::info;
+
+/// First file descriptor passed by systemd socket activation (`SD_LISTEN_FDS_START`)
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Take the listening sockets passed by systemd socket activation (`LISTEN_FDS`), in order.
+/// The activation variables are cleared so child processes do not inherit them.
+#[cfg(unix)]
+pub fn take_listeners() -> Result<Vec<TcpListener>> {
+    use std::env;
+    use std::os::unix::io::FromRawFd;
+
+    let listen_pid: u32 = env::var("LISTEN_PID")
+        .map_err(|_| anyhow!("LISTEN_PID is not set, not started by systemd socket activation"))?
+        .parse()?;
+    if listen_pid != std::process::id() {
+        return Err(anyhow!(
+            "LISTEN_PID {} does not match this process ({})",
+            listen_pid,
+            std::process::id()
+        ));
+    }
+    let count: i32 = env::var("LISTEN_FDS")
+        .map_err(|_| anyhow!("LISTEN_FDS is not set"))?
+        .parse()?;
+
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    let mut listeners = Vec::new();
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        // SAFETY: systemd hands these descriptors to this process and nothing else owns them
+        let listener = unsafe { TcpListener::from_raw_fd(fd) };
+        listener.set_nonblocking(true)?;
+        info!(
+            "Inherited systemd socket {} on {}",
+            fd,
+            listener.local_addr()?
+        );
+        listeners.push(listener);
+    }
+
+    if listeners.is_empty() {
+        return Err(anyhow!("systemd passed no sockets (LISTEN_FDS=0)"));
+    }
+    Ok(listeners)
+}
+
+#[cfg(not(unix))]
+pub fn take_listeners() -> Result<Vec<TcpListener>> {
+    Err(anyhow!(
+        "systemd socket activation is only supported on Unix"
+    ))
+}
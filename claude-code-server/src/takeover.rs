@@ -0,0 +1,119 @@
+use anyhow::{bail, Result};
+use serde_json::json;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::control::{self, TAKEOVER_METHOD};
+use crate::state::SharedState;
+use crate::websocket::find_running_server;
+
+/// How long a new server waits for the one it takes over from to answer
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a new server waits for the old one to release its port
+const PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the released port is probed
+const PORT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a handing-over server waits for its sessions to close before exiting
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Agree to hand over to the server that sent a takeover request, if this one was left
+/// behind by its editor. Draining starts once the response is sent.
+pub fn handle_takeover_request(
+    state: &SharedState,
+    params: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    if state.is_draining() {
+        bail!("Already handing over to another server");
+    }
+    if !is_abandoned(state) {
+        bail!("Still serving the editor that started it");
+    }
+    info!(
+        "Server {} is taking over this worktree",
+        params
+            .get("pid")
+            .and_then(|pid| pid.as_u64())
+            .map_or_else(|| "with unknown pid".to_string(), |pid| pid.to_string())
+    );
+    Ok(json!({ "pid": process::id() }))
+}
+
+/// Whether nothing depends on this server any more: no Zed session is attached and the
+/// process that started it is gone. Where the parent can't be checked, the server counts as
+/// abandoned once Claude isn't connected either.
+fn is_abandoned(state: &SharedState) -> bool {
+    if state.lsp_attached() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        // Orphans are reparented to init or a subreaper
+        state.parent_pid() != Some(std::os::unix::process::parent_id())
+    }
+    #[cfg(not(unix))]
+    {
+        !state.has_mcp_sessions()
+    }
+}
+
+/// Ask a live server already advertising `workspace` (e.g. one left behind when Zed
+/// crashed and restarted) to drain and exit, returning the port it is releasing. `None`
+/// when there is no such server or it doesn't answer, in which case its lock file is stale.
+pub async fn take_over(workspace: &Path) -> Option<u16> {
+    let (port, lock_file) = find_running_server(workspace).ok()?;
+    if lock_file.pid == process::id() {
+        return None;
+    }
+    // A server for another worktree of the same project lists this one among its folders,
+    // but is not one to replace
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let primary = lock_file.workspace_folders.first().map(Path::new)?;
+    if canonical(primary) != canonical(workspace) {
+        return None;
+    }
+
+    let params = json!({ "pid": process::id() });
+    let request = control::send_request(port, &lock_file.auth_token, TAKEOVER_METHOD, params);
+    match tokio::time::timeout(TAKEOVER_TIMEOUT, request).await {
+        Ok(Ok(_)) => {
+            info!("Taking over port {} from server {}", port, lock_file.pid);
+            Some(port)
+        }
+        Ok(Err(e)) => {
+            warn!(
+                "Could not take over from server {} on port {}: {}",
+                lock_file.pid, port, e
+            );
+            None
+        }
+        Err(_) => {
+            warn!(
+                "Server {} on port {} did not answer takeover",
+                lock_file.pid, port
+            );
+            None
+        }
+    }
+}
+
+/// Bind `port` once the server handing it over has released it
+pub async fn bind_released_port(bind_address: IpAddr, port: u16) -> Option<TcpListener> {
+    let deadline = Instant::now() + PORT_RELEASE_TIMEOUT;
+    loop {
+        match TcpListener::bind((bind_address, port)).await {
+            Ok(listener) => return Some(listener),
+            Err(_) if Instant::now() < deadline => tokio::time::sleep(PORT_RETRY_INTERVAL).await,
+            Err(e) => {
+                warn!("Port {} was not released: {}", port, e);
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+#[derive(Debug)]
+struct CachedResult {
+    stored_at: Instant,
+    content: serde_json::Value,
+}
+
+/// Short-lived results of idempotent tools, keyed by tool name and arguments, so repeated
+/// identical queries in a conversation skip the work. Disabled until given a TTL.
+#[derive(Debug, Default)]
+pub struct ToolCache {
+    ttl: Option<Duration>,
+    entries: HashMap<String, CachedResult>,
+}
+
+pub type SharedToolCache = Arc<Mutex<ToolCache>>;
+
+impl ToolCache {
+    /// Set how long results stay fresh, or disable caching with `None`
+    pub fn configure(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+        self.entries.clear();
+    }
+
+    pub fn get(
+        &mut self,
+        tool_name: &str,
+        arguments: &serde_json::Value,
+    ) -> Option<serde_json::Value> {
+        let ttl = self.ttl?;
+        let key = cache_key(tool_name, arguments);
+        match self.entries.get(&key) {
+            Some(cached) if cached.stored_at.elapsed() < ttl => Some(cached.content.clone()),
+            Some(_) => {
+                self.entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        tool_name: &str,
+        arguments: &serde_json::Value,
+        content: serde_json::Value,
+    ) {
+        if self.ttl.is_none() {
+            return;
+        }
+        self.entries.insert(
+            cache_key(tool_name, arguments),
+            CachedResult {
+                stored_at: Instant::now(),
+                content,
+            },
+        );
+    }
+
+    /// Drop every cached result, e.g. because files changed
+    pub fn invalidate(&mut self) {
+        if !self.entries.is_empty() {
+            debug!("Invalidating {} cached tool results", self.entries.len());
+            self.entries.clear();
+        }
+    }
+}
+
+fn cache_key(tool_name: &str, arguments: &serde_json::Value) -> String {
+    // Object keys serialize in sorted order, so equal arguments give equal keys
+    format!("{}\0{}", tool_name, arguments)
+}
@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::file_locks::write_atomic;
+
+/// Workspaces the user has trusted, persisted across sessions.
+///
+/// Untrusted workspaces only get read-only tools and never run commands (such as linters),
+/// mirroring the editor's workspace-trust semantics.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrustStore {
+    trusted: BTreeSet<PathBuf>,
+}
+
+impl TrustStore {
+    /// Load the store, treating a missing or unreadable file as trusting nothing
+    pub fn load() -> Self {
+        let Some(path) = store_path() else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring invalid trust store {}: {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn is_trusted(&self, workspace: &Path) -> bool {
+        self.trusted.contains(&normalize(workspace))
+    }
+
+    pub fn trust(&mut self, workspace: &Path) {
+        self.trusted.insert(normalize(workspace));
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = store_path().ok_or_else(|| anyhow!("Could not find config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Whether the user has trusted this workspace (the current directory when unset)
+pub fn is_trusted(workspace: Option<&Path>) -> bool {
+    match workspace_or_cwd(workspace) {
+        Some(workspace) => TrustStore::load().is_trusted(&workspace),
+        None => false,
+    }
+}
+
+/// Persist trust for a workspace
+pub fn trust_workspace(workspace: &Path) -> Result<()> {
+    let mut store = TrustStore::load();
+    store.trust(workspace);
+    store.save()?;
+    info!("Trusted workspace {}", workspace.display());
+    Ok(())
+}
+
+fn workspace_or_cwd(workspace: Option<&Path>) -> Option<PathBuf> {
+    match workspace {
+        Some(path) => Some(path.to_path_buf()),
+        None => std::env::current_dir().ok(),
+    }
+}
+
+fn normalize(workspace: &Path) -> PathBuf {
+    workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf())
+}
+
+fn store_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("claude-code-zed").join("trusted-workspaces.json"))
+}
@@ -2,24 +2,41 @@ use anyhow::{anyhow, Result};
 use dirs::home_dir;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::net::{TcpListener, TcpStream};
 use tokio_tungstenite::{
     accept_hdr_async,
-    tungstenite::handshake::server::{Request, Response},
+    tungstenite::handshake::server::{ErrorResponse, Request, Response},
+    tungstenite::http::{HeaderValue, StatusCode},
+    tungstenite::protocol::frame::coding::CloseCode,
+    tungstenite::protocol::CloseFrame,
     tungstenite::Message,
     WebSocketStream,
 };
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::config::{ClaudeCodeOptions, OsNotificationEvent};
+use crate::connection_stats::DisconnectReason;
+use crate::control::{self, AUTH_HEADER, CONTROL_PATH};
+use crate::file_locks::write_atomic;
+use crate::i18n::Text;
 use crate::lsp::NotificationReceiver;
-use crate::mcp::{MCPRequest, MCPResponse, MCPServer};
-use tokio::sync::oneshot;
+use crate::mcp::{is_observation_tool, MCPRequest, MCPResponse, MCPServer};
+use crate::state::{self, ServerMode, SharedState, StatusEvent, Teardown};
+use crate::takeover;
+use crate::trust;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LockFile {
@@ -46,20 +63,90 @@ pub async fn run_websocket_server_with_worktree(
     run_websocket_server_with_notifications(port, worktree, None).await
 }
 
+/// WebSocket subprotocol the Claude CLI requests for MCP sessions
+const MCP_SUBPROTOCOL: &str = "mcp";
+
+const SUBPROTOCOL_HEADER: &str = "Sec-WebSocket-Protocol";
+
 // Default port range for dynamic allocation
-const DEFAULT_PORT_START: u16 = 59792;
-const DEFAULT_PORT_END: u16 = 59892; // Allow up to 100 concurrent instances
+pub(crate) const DEFAULT_PORT_START: u16 = 59792;
+pub(crate) const DEFAULT_PORT_END: u16 = 59892; // Allow up to 100 concurrent instances
+
+/// Runtime configuration for the WebSocket server
+#[derive(Debug)]
+pub struct WebSocketSettings {
+    pub port_start: u16,
+    pub port_end: u16,
+    /// Fixed auth token; a random one is generated when unset
+    pub auth_token: Option<String>,
+    /// Pre-bound listener (e.g. from systemd socket activation); skips port allocation
+    pub listener: Option<std::net::TcpListener>,
+    /// Address to bind; loopback unless the server must be reachable from outside (containers)
+    pub bind_address: IpAddr,
+    pub advertise: AdvertiseSettings,
+    /// Reject clients that don't request the `mcp` subprotocol, instead of accepting them
+    /// without one
+    pub strict_subprotocol: bool,
+    /// Token admitting read-only sessions; when set, sessions must authenticate
+    pub read_only_token: Option<String>,
+}
+
+impl Default for WebSocketSettings {
+    fn default() -> Self {
+        Self {
+            port_start: DEFAULT_PORT_START,
+            port_end: DEFAULT_PORT_END,
+            auth_token: None,
+            listener: None,
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            advertise: AdvertiseSettings::default(),
+            strict_subprotocol: false,
+            read_only_token: None,
+        }
+    }
+}
+
+/// How the server is advertised to the Claude CLI through its lock file, when that differs
+/// from where it actually runs (e.g. inside a container with a host-mounted lock directory)
+#[derive(Debug, Clone, Default)]
+pub struct AdvertiseSettings {
+    /// Directory to write the lock file to instead of `~/.claude/ide`
+    pub lock_dir: Option<PathBuf>,
+    /// Port the CLI should connect to, when it is mapped to a different bound port
+    pub port: Option<u16>,
+    /// Workspace path as seen by the CLI, written to the lock file instead of the worktree
+    pub workspace: Option<PathBuf>,
+    /// The project's other folders, listed in the lock file after the workspace
+    pub extra_folders: Vec<PathBuf>,
+}
+
+impl WebSocketSettings {
+    /// Build settings from the extension's initialization options
+    pub fn from_options(options: &ClaudeCodeOptions) -> Self {
+        let (port_start, port_end) = options
+            .port_range()
+            .unwrap_or((DEFAULT_PORT_START, DEFAULT_PORT_END));
+        Self {
+            port_start,
+            port_end,
+            auth_token: options.fixed_auth_token(),
+            strict_subprotocol: options.websocket.strict_subprotocol,
+            read_only_token: options.auth.read_only_token.clone(),
+            ..Self::default()
+        }
+    }
+}
 
 /// Try to bind to a port in the given range, returning the listener and the actual port
-async fn find_available_port(
+pub(crate) async fn find_available_port(
+    bind_address: IpAddr,
     preferred_port: Option<u16>,
     port_start: u16,
     port_end: u16,
 ) -> Result<(TcpListener, u16)> {
     // If a specific port is requested, try it first
     if let Some(port) = preferred_port {
-        let addr = format!("127.0.0.1:{}", port);
-        if let Ok(listener) = TcpListener::bind(&addr).await {
+        if let Ok(listener) = TcpListener::bind((bind_address, port)).await {
             info!("Bound to requested port {}", port);
             return Ok((listener, port));
         }
@@ -71,8 +158,7 @@ async fn find_available_port(
 
     // Try ports in the range until we find an available one
     for port in port_start..=port_end {
-        let addr = format!("127.0.0.1:{}", port);
-        match TcpListener::bind(&addr).await {
+        match TcpListener::bind((bind_address, port)).await {
             Ok(listener) => {
                 info!("Found available port: {}", port);
                 return Ok((listener, port));
@@ -96,7 +182,16 @@ pub async fn run_websocket_server_with_notifications(
     worktree: Option<PathBuf>,
     notification_receiver: Option<NotificationReceiver>,
 ) -> Result<()> {
-    run_websocket_server_full(port, worktree, notification_receiver, None).await
+    let state = SharedState::new(ServerMode::WebSocket, worktree.as_deref());
+    run_websocket_server_full(
+        port,
+        worktree,
+        notification_receiver,
+        None,
+        WebSocketSettings::default(),
+        state,
+    )
+    .await
 }
 
 /// Run WebSocket server with optional port reporting for coordinated shutdown.
@@ -106,43 +201,182 @@ pub async fn run_websocket_server_with_notifications(
 pub async fn run_websocket_server_full(
     port: Option<u16>,
     worktree: Option<PathBuf>,
-    mut notification_receiver: Option<NotificationReceiver>,
+    notification_receiver: Option<NotificationReceiver>,
     port_sender: Option<oneshot::Sender<u16>>,
+    settings: WebSocketSettings,
+    state: SharedState,
 ) -> Result<()> {
     info!("Starting WebSocket server...");
 
-    // Find an available port (use dynamic allocation if preferred port is unavailable)
-    let (listener, actual_port) =
-        find_available_port(port, DEFAULT_PORT_START, DEFAULT_PORT_END).await?;
+    // Without an LSP bridge, sessions still receive notifications raised by MCP tools
+    let mut notification_receiver =
+        notification_receiver.or_else(|| Some(state.subscribe_notifications()));
+
+    // In hybrid mode the LSP asks the user; standalone servers rely on the persisted decision
+    if state.mode() != ServerMode::Hybrid {
+        state.set_trusted(trust::is_trusted(worktree.as_deref()));
+        if !state.is_trusted() {
+            warn!("Workspace is not trusted, only read-only tools are available");
+        }
+    }
+
+    // An inherited listener belongs to the service manager and can't be moved
+    let rebindable = settings.listener.is_none();
+    let bind_address = settings.bind_address;
+
+    // Use an inherited listener if given, otherwise find an available port
+    // (dynamic allocation if the preferred port is unavailable)
+    let (mut listener, mut actual_port) = match settings.listener {
+        Some(listener) => {
+            let port = listener.local_addr()?.port();
+            (TcpListener::from_std(listener)?, port)
+        }
+        None => {
+            // A live server advertising the same workspace hands over its port and lock
+            // file, so Claude never sees two lock files for one workspace
+            let released = match settings.advertise.lock_dir {
+                None => match worktree.clone().or_else(|| env::current_dir().ok()) {
+                    Some(workspace) => takeover::take_over(&workspace).await,
+                    None => None,
+                },
+                Some(_) => None,
+            };
+            let inherited = match released.filter(|_| port.is_none()) {
+                Some(released) => takeover::bind_released_port(settings.bind_address, released)
+                    .await
+                    .map(|listener| (listener, released)),
+                None => None,
+            };
+            match inherited {
+                Some(inherited) => inherited,
+                None => {
+                    find_available_port(
+                        settings.bind_address,
+                        port,
+                        settings.port_start,
+                        settings.port_end,
+                    )
+                    .await?
+                }
+            }
+        }
+    };
 
-    info!("WebSocket server listening on 127.0.0.1:{}", actual_port);
+    info!("WebSocket server listening on {}", listener.local_addr()?);
 
     // Report the bound port back to caller (for coordinated cleanup)
     if let Some(sender) = port_sender {
         let _ = sender.send(actual_port);
     }
 
-    // Clean up any stale lock file for this port (from crashed processes)
-    cleanup_lock_file(actual_port).await?;
-
-    // Create new lock file with the actual bound port
-    let auth_token = Uuid::new_v4().to_string();
-    create_lock_file(actual_port, worktree.clone(), &auth_token).await?;
-
-    // Setup graceful shutdown handler for Ctrl+C
-    let port_for_cleanup = actual_port;
-    tokio::spawn(async move {
-        tokio::signal::ctrl_c().await.ok();
-        info!("Shutdown signal received, cleaning up...");
-        if let Err(e) = cleanup_lock_file(port_for_cleanup).await {
-            error!("Error during cleanup: {}", e);
-        }
-        std::process::exit(0);
-    });
+    let admission = Admission {
+        auth_token: settings
+            .auth_token
+            .unwrap_or_else(|| Uuid::new_v4().to_string()),
+        read_only_token: settings.read_only_token.or_else(state::read_only_token),
+        strict_subprotocol: settings.strict_subprotocol || state::is_strict_subprotocol(),
+    };
+    if admission.strict_subprotocol {
+        info!(
+            "Only accepting clients that request the {} subprotocol",
+            MCP_SUBPROTOCOL
+        );
+    }
+    if admission.read_only_token.is_some() {
+        info!("Read-only sessions enabled, sessions must authenticate");
+    }
+    let mut advertise = settings.advertise;
+    // Paths remapped for the CLI (e.g. into a container) only cover the workspace itself
+    if advertise.workspace.is_none() {
+        advertise.extra_folders = state.extra_workspace_folders();
+    }
+    state.set_websocket_port(advertise.port.unwrap_or(actual_port));
+    let mut lock_file_path = advertise_server(
+        advertise.clone(),
+        actual_port,
+        worktree.clone(),
+        &admission.auth_token,
+    )?;
+    state.publish_status(StatusEvent::Listening);
+    let mut port_ranges = state.port_range_changes();
+    let mut workspace_folders = state.workspace_folder_changes();
 
-    while let Ok((stream, peer_addr)) = listener.accept().await {
+    loop {
+        let (stream, peer_addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(_) => break,
+            },
+            _ = state.drained() => {
+                hand_over(listener, &lock_file_path, &state).await;
+                return Ok(());
+            }
+            Ok(()) = port_ranges.changed() => {
+                let Some((start, end)) = *port_ranges.borrow_and_update() else {
+                    continue;
+                };
+                if (start..=end).contains(&actual_port) {
+                    continue;
+                }
+                if !rebindable || advertise.port.is_some() {
+                    warn!(
+                        "Port range changed to {}-{}, but port {} is fixed by the service manager or container",
+                        start, end, actual_port
+                    );
+                    continue;
+                }
+                let (new_listener, new_port) =
+                    match find_available_port(bind_address, None, start, end).await {
+                        Ok(bound) => bound,
+                        Err(e) => {
+                            warn!("Keeping port {}: {}", actual_port, e);
+                            continue;
+                        }
+                    };
+                // Sessions already connected keep their connections; new ones find the
+                // new port in the lock file
+                ACTIVE_LOCK_FILES
+                    .lock()
+                    .unwrap()
+                    .retain(|path| *path != lock_file_path);
+                release_lock_file(&lock_file_path);
+                lock_file_path = advertise_server(
+                    advertise.clone(),
+                    new_port,
+                    worktree.clone(),
+                    &admission.auth_token,
+                )?;
+                info!("Moved the WebSocket server from port {} to {}", actual_port, new_port);
+                listener = new_listener;
+                actual_port = new_port;
+                state.set_websocket_port(new_port);
+                state.publish_status(StatusEvent::Listening);
+                continue;
+            }
+            Ok(()) = workspace_folders.changed() => {
+                let folders = workspace_folders.borrow_and_update().clone();
+                if advertise.workspace.is_some() {
+                    continue;
+                }
+                // Claude reads the folders from the lock file, so it is rewritten in place
+                advertise.extra_folders = folders;
+                let lock_port = advertise.port.unwrap_or(actual_port);
+                let written = lock_dir(advertise.lock_dir.as_deref()).and_then(|dir| {
+                    create_lock_file(
+                        &dir,
+                        lock_port,
+                        worktree.clone(),
+                        &advertise.extra_folders,
+                        &admission.auth_token,
+                    )
+                });
+                if let Err(e) = written {
+                    warn!("Failed to update the workspace folders in the lock file: {}", e);
+                }
+                continue;
+            }
+        };
         info!("New connection from {}", peer_addr);
-        let auth_token_clone = auth_token.clone();
         let notification_receiver_clone = notification_receiver
             .as_mut()
             .map(|receiver| receiver.resubscribe());
@@ -150,21 +384,182 @@ pub async fn run_websocket_server_full(
         tokio::spawn(handle_connection(
             stream,
             peer_addr,
-            auth_token_clone,
+            admission.clone(),
             notification_receiver_clone,
             worktree_clone,
+            state.clone(),
         ));
     }
 
     Ok(())
 }
 
+/// Give up the worktree to the server taking it over, or before exiting: the lock file is
+/// removed before the port is released, so the new server's lock file can't be deleted by
+/// this one, then the sessions get a moment to close
+async fn hand_over(listener: TcpListener, lock_file_path: &Path, state: &SharedState) {
+    match state.teardown() {
+        Some(Teardown::Shutdown) => info!("Shutting down the WebSocket server"),
+        _ => info!("Handing over to a new server"),
+    }
+    ACTIVE_LOCK_FILES
+        .lock()
+        .unwrap()
+        .retain(|path| path != lock_file_path);
+    release_lock_file(lock_file_path);
+    drop(listener);
+
+    let deadline = Instant::now() + takeover::DRAIN_TIMEOUT;
+    while state.has_mcp_sessions() && Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    if state.has_mcp_sessions() {
+        warn!("Sessions still open after handing over, closing them");
+    }
+}
+
+/// Write the lock file announcing a server listening on `bound_port` and remove it again on
+/// Ctrl+C or SIGTERM
+pub(crate) fn advertise_server(
+    advertise: AdvertiseSettings,
+    bound_port: u16,
+    worktree: Option<PathBuf>,
+    auth_token: &str,
+) -> Result<PathBuf> {
+    // The lock file is named after the port the CLI connects to, which differs from the
+    // bound port when it is remapped (e.g. `docker run -p`)
+    let lock_port = advertise.port.unwrap_or(bound_port);
+    let lock_dir = lock_dir(advertise.lock_dir.as_deref())?;
+    if lock_port != bound_port {
+        info!("Advertising port {} to Claude", lock_port);
+    }
+
+    // Clean up any stale lock file for this port (from crashed processes)
+    remove_lock_file(&lock_dir, lock_port)?;
+
+    // Create new lock file with the advertised port
+    let workspace_folder = advertise.workspace.or(worktree);
+    let lock_file_path = create_lock_file(
+        &lock_dir,
+        lock_port,
+        workspace_folder,
+        &advertise.extra_folders,
+        auth_token,
+    )?;
+
+    // Setup graceful shutdown handler for Ctrl+C and SIGTERM. Every server in the process
+    // registers its lock file, so whichever handler fires first removes all of them.
+    ACTIVE_LOCK_FILES
+        .lock()
+        .unwrap()
+        .push(lock_file_path.clone());
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutdown signal received, cleaning up...");
+        let lock_files = std::mem::take(&mut *ACTIVE_LOCK_FILES.lock().unwrap());
+        for path in lock_files {
+            release_lock_file(&path);
+        }
+        std::process::exit(0);
+    });
+
+    Ok(lock_file_path)
+}
+
+/// Remove a lock file this process wrote, unless another server has since taken it over
+fn release_lock_file(path: &Path) {
+    let owner = fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str::<LockFile>(&data).ok())
+        .map(|lock_file| lock_file.pid);
+    if owner.is_some_and(|pid| pid != process::id()) {
+        info!(
+            "Lock file {} belongs to another server, keeping it",
+            path.display()
+        );
+        return;
+    }
+    info!("Removing lock file: {}", path.display());
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            error!("Error during cleanup: {}", e);
+        }
+    }
+}
+
+/// Lock files written by this process, removed on shutdown
+static ACTIVE_LOCK_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Resolve on Ctrl+C, or on SIGTERM where available (sent by service managers such as systemd)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    tokio::signal::ctrl_c().await.ok();
+}
+
 /// Clean up the lock file for the given port.
 /// This should be called when the server shuts down to remove stale lock files.
 pub async fn cleanup_lock_file(port: u16) -> Result<()> {
+    release_lock_file(&lock_dir(None)?.join(format!("{}.lock", port)));
+    Ok(())
+}
+
+/// Find the lock file of a running server advertising `workspace`, returning its port; the
+/// most recently written one wins when several servers share the workspace
+pub(crate) fn find_running_server(workspace: &Path) -> Result<(u16, LockFile)> {
+    let workspace = workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf());
+    let lock_dir = lock_dir(None)?;
+    let entries = fs::read_dir(&lock_dir)
+        .map_err(|e| anyhow!("Could not read {}: {}", lock_dir.display(), e))?;
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let port = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".lock")?
+                .parse::<u16>()
+                .ok()?;
+            let lock_file: LockFile =
+                serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            lock_file
+                .workspace_folders
+                .iter()
+                .any(|folder| Path::new(folder) == workspace)
+                .then_some((modified, port, lock_file))
+        })
+        .max_by_key(|(modified, _, _)| *modified)
+        .map(|(_, port, lock_file)| (port, lock_file))
+        .ok_or_else(|| anyhow!("No running server found for {}", workspace.display()))
+}
+
+/// Directory holding lock files: the given override, or `~/.claude/ide`
+fn lock_dir(custom: Option<&Path>) -> Result<PathBuf> {
+    if let Some(dir) = custom {
+        return Ok(dir.to_path_buf());
+    }
     let home = home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-    let claude_dir = home.join(".claude").join("ide");
+    Ok(home.join(".claude").join("ide"))
+}
 
+fn remove_lock_file(claude_dir: &Path, port: u16) -> Result<()> {
     if !claude_dir.exists() {
         // Directory doesn't exist, nothing to clean up
         return Ok(());
@@ -180,13 +575,16 @@ pub async fn cleanup_lock_file(port: u16) -> Result<()> {
     Ok(())
 }
 
-async fn create_lock_file(port: u16, worktree: Option<PathBuf>, auth_token: &str) -> Result<()> {
-    let home = home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-    let claude_dir = home.join(".claude").join("ide");
-
+fn create_lock_file(
+    claude_dir: &Path,
+    port: u16,
+    worktree: Option<PathBuf>,
+    extra_folders: &[PathBuf],
+    auth_token: &str,
+) -> Result<PathBuf> {
     // Create directories if they don't exist
     if !claude_dir.exists() {
-        fs::create_dir_all(&claude_dir)?;
+        fs::create_dir_all(claude_dir)?;
         info!("Created directory: {}", claude_dir.display());
     }
 
@@ -199,44 +597,118 @@ async fn create_lock_file(port: u16, worktree: Option<PathBuf>, auth_token: &str
 
     let lock_file_data = LockFile {
         pid: process::id(),
-        workspace_folders: vec![workspace_folder],
+        workspace_folders: std::iter::once(workspace_folder)
+            .chain(
+                extra_folders
+                    .iter()
+                    .map(|folder| folder.to_string_lossy().to_string()),
+            )
+            .collect(),
         ide_name: "Zed".to_string(),
         transport: "ws".to_string(),
-        running_in_windows: false,
+        running_in_windows: cfg!(windows),
         auth_token: auth_token.to_string(),
     };
 
     let lock_file_path = claude_dir.join(format!("{}.lock", port));
     let json_data = serde_json::to_string_pretty(&lock_file_data)?;
 
-    fs::write(&lock_file_path, json_data)?;
+    write_atomic(&lock_file_path, json_data)?;
     info!("Created lock file: {}", lock_file_path.display());
 
-    Ok(())
+    Ok(lock_file_path)
+}
+
+/// What a client must present during the handshake, and what it is granted
+#[derive(Debug, Clone)]
+struct Admission {
+    /// Token from the lock file, granting full sessions and control requests
+    auth_token: String,
+    /// Token granting read-only sessions; when set, sessions must present one of the two
+    read_only_token: Option<String>,
+    /// Whether clients must request the `mcp` subprotocol
+    strict_subprotocol: bool,
 }
 
+#[allow(clippy::result_large_err)] // tungstenite's handshake callback returns the full HTTP response as its error
 async fn handle_connection(
     stream: TcpStream,
     peer_addr: SocketAddr,
-    auth_token: String,
+    admission: Admission,
     notification_receiver: Option<NotificationReceiver>,
     worktree: Option<PathBuf>,
+    state: SharedState,
 ) -> Result<()> {
     info!("Handling connection from {}", peer_addr);
 
+    let mut control_token = None;
+    let mut read_only = false;
+    let mut auth_failed = false;
     let ws_stream = match accept_hdr_async(stream, |req: &Request, mut response: Response| {
-        // Check if client requested MCP protocol
-        if let Some(protocols) = req.headers().get("Sec-WebSocket-Protocol") {
-            if let Ok(protocols_str) = protocols.to_str() {
-                if protocols_str.contains("mcp") {
-                    // Add MCP protocol to response
-                    response
-                        .headers_mut()
-                        .insert("Sec-WebSocket-Protocol", "mcp".parse().unwrap());
-                    info!("MCP protocol negotiated for {}", peer_addr);
-                }
+        let presented = req
+            .headers()
+            .get(AUTH_HEADER)
+            .and_then(|token| token.to_str().ok())
+            .unwrap_or_default();
+        if req.uri().path() == CONTROL_PATH {
+            control_token = Some(presented.to_string());
+            return Ok(response);
+        }
+
+        // Sessions are only told apart by their token once read-only sessions exist
+        if let Some(read_only_token) = &admission.read_only_token {
+            if presented == read_only_token {
+                read_only = true;
+            } else if presented != admission.auth_token {
+                warn!("Rejecting {}: missing or unknown auth token", peer_addr);
+                auth_failed = true;
+                let mut rejection =
+                    ErrorResponse::new(Some("Invalid authorization token".to_string()));
+                *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+                return Err(rejection);
             }
         }
+
+        // Clients list the subprotocols they accept, possibly across several headers
+        let requested: Vec<&str> = req
+            .headers()
+            .get_all(SUBPROTOCOL_HEADER)
+            .iter()
+            .filter_map(|protocols| protocols.to_str().ok())
+            .flat_map(|protocols| protocols.split(','))
+            .map(str::trim)
+            .filter(|protocol| !protocol.is_empty())
+            .collect();
+
+        if requested.contains(&MCP_SUBPROTOCOL) {
+            response.headers_mut().insert(
+                SUBPROTOCOL_HEADER,
+                HeaderValue::from_static(MCP_SUBPROTOCOL),
+            );
+            info!(
+                "Negotiated subprotocol for {}: {}",
+                peer_addr, MCP_SUBPROTOCOL
+            );
+        } else if admission.strict_subprotocol {
+            warn!(
+                "Rejecting {}: requested subprotocols [{}], {} is required",
+                peer_addr,
+                requested.join(", "),
+                MCP_SUBPROTOCOL
+            );
+            let mut rejection = ErrorResponse::new(Some(format!(
+                "The {} WebSocket subprotocol is required",
+                MCP_SUBPROTOCOL
+            )));
+            *rejection.status_mut() = StatusCode::BAD_REQUEST;
+            return Err(rejection);
+        } else {
+            info!(
+                "Negotiated subprotocol for {}: none (requested [{}])",
+                peer_addr,
+                requested.join(", ")
+            );
+        }
         Ok(response)
     })
     .await
@@ -247,172 +719,442 @@ async fn handle_connection(
         }
         Err(e) => {
             error!("WebSocket handshake failed for {}: {}", peer_addr, e);
+            if auth_failed {
+                state.publish_status(StatusEvent::AuthFailed);
+            }
+            state
+                .connection_stats
+                .record_disconnect(DisconnectReason::HandshakeFailed);
             return Err(e.into());
         }
     };
 
-    handle_websocket_connection(ws_stream, peer_addr, auth_token, notification_receiver, worktree)
-        .await
+    if let Some(token) = control_token {
+        return handle_control_connection(
+            ws_stream,
+            peer_addr,
+            token == admission.auth_token,
+            state,
+        )
+        .await;
+    }
+
+    handle_websocket_connection(
+        ws_stream,
+        peer_addr,
+        read_only,
+        notification_receiver,
+        worktree,
+        state,
+    )
+    .await
+}
+
+/// Serve a one-shot control request, e.g. an at-mention from a slash command. Control
+/// connections are not MCP sessions: they neither receive notifications nor count as Claude
+async fn handle_control_connection(
+    mut ws_stream: WebSocketStream<TcpStream>,
+    peer_addr: SocketAddr,
+    authorized: bool,
+    state: SharedState,
+) -> Result<()> {
+    if !authorized {
+        warn!(
+            "Rejecting control connection from {}: bad auth token",
+            peer_addr
+        );
+        state.publish_status(StatusEvent::AuthFailed);
+        ws_stream.close(None).await?;
+        return Ok(());
+    }
+
+    while let Some(msg) = ws_stream.next().await {
+        if let Message::Text(text) = msg? {
+            let request: serde_json::Value = serde_json::from_str(&text)?;
+            let response = control::handle_control_request(&state, &request).await;
+            ws_stream.send(Message::Text(response.text)).await?;
+            if response.hand_over {
+                state.start_drain();
+            }
+            break;
+        }
+    }
+    ws_stream.close(None).await.ok();
+    Ok(())
 }
 
 async fn handle_websocket_connection(
     ws_stream: WebSocketStream<TcpStream>,
     peer_addr: SocketAddr,
-    _auth_token: String,
+    read_only: bool,
     mut notification_receiver: Option<NotificationReceiver>,
     worktree: Option<PathBuf>,
+    state: SharedState,
 ) -> Result<()> {
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+    let connected_at = Instant::now();
+    let mut dropped: u64 = 0;
 
     // Give MCPServer its own notification receiver so it can track selection state
     let mcp_receiver = notification_receiver.as_ref().map(|r| r.resubscribe());
-    let mcp_handler = MCPServer::with_notifications(mcp_receiver, worktree);
+    let mcp_handler = Arc::new(
+        MCPServer::with_notifications(mcp_receiver, worktree, state.clone()).read_only(read_only),
+    );
+    let session = state.mcp_session_started();
+    state.publish_status(StatusEvent::Connected);
+    let (finished_sender, mut finished) = mpsc::unbounded_channel();
+    let mut in_flight = InFlightRequests {
+        tasks: HashMap::new(),
+        next_task: 0,
+        writes: Arc::default(),
+        finished: finished_sender,
+    };
 
-    info!("WebSocket connection established with {}", peer_addr);
+    if read_only {
+        info!(
+            "Read-only WebSocket connection established with {}",
+            peer_addr
+        );
+    } else {
+        info!("WebSocket connection established with {}", peer_addr);
+    }
+
+    // Replay notifications raised while no MCP session was connected; a read-only session
+    // sees them without taking them from the owner's next session
+    let queued = if read_only {
+        state.queued_notifications()
+    } else {
+        state.take_queued_notifications()
+    };
+    let mut replay_failed = false;
+    for notification in queued {
+        debug!("Replaying queued notification: {}", notification.method);
+        mcp_handler.apply_notification(&notification).await;
+        if !state.forwards_notification(&notification.method) {
+            continue;
+        }
+        if let Err(e) = ws_sender
+            .send(Message::Text(serde_json::to_string(&notification)?))
+            .await
+        {
+            error!("Failed to replay notification to {}: {}", peer_addr, e);
+            replay_failed = true;
+            break;
+        }
+    }
 
     // Main message loop handling both WebSocket messages and IDE notifications
-    loop {
-        tokio::select! {
-            // Handle incoming WebSocket messages
-            msg = ws_receiver.next() => {
-                match msg {
-                    Some(msg) => {
-                        if let Err(e) = handle_websocket_message(msg, &mcp_handler, &mut ws_sender, peer_addr).await {
-                            error!("Error handling WebSocket message: {}", e);
-                            break;
+    let reason = if replay_failed {
+        DisconnectReason::WriteError
+    } else {
+        loop {
+            tokio::select! {
+                // Handle incoming WebSocket messages
+                msg = ws_receiver.next() => {
+                    match msg {
+                        Some(Ok(msg)) if msg.is_close() => {
+                            info!("Connection closed by {}", peer_addr);
+                            break DisconnectReason::ClientClose;
+                        }
+                        Some(Ok(msg)) => {
+                            if let Err(e) = handle_websocket_message(msg, &mcp_handler, &mut in_flight, &mut ws_sender, peer_addr).await {
+                                error!("Error handling WebSocket message: {}", e);
+                                break DisconnectReason::WriteError;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            error!("WebSocket error for {}: {}", peer_addr, e);
+                            break DisconnectReason::ReadError;
+                        }
+                        None => {
+                            info!("WebSocket connection with {} ended", peer_addr);
+                            break DisconnectReason::ClientClose;
                         }
                     }
-                    None => {
-                        info!("WebSocket connection with {} ended", peer_addr);
-                        break;
+                },
+                // Handle IDE notifications
+                notification = async {
+                    if let Some(ref mut receiver) = notification_receiver {
+                        receiver.recv().await
+                    } else {
+                        std::future::pending().await
                     }
-                }
-            },
-            // Handle IDE notifications
-            notification = async {
-                if let Some(ref mut receiver) = notification_receiver {
-                    receiver.recv().await
-                } else {
-                    std::future::pending().await
-                }
-            } => {
-                match notification {
-                    Ok(notification) => {
-                        debug!("Received IDE notification: {:?}", notification);
-
-                        // Forward the notification to the MCP client
-                        let notification_json = serde_json::to_string(&notification)?;
-                        if let Err(e) = ws_sender.send(Message::Text(notification_json)).await {
-                            error!("Failed to send IDE notification to {}: {}", peer_addr, e);
-                            break;
+                } => {
+                    match notification {
+                        Ok(notification) if !state.forwards_notification(&notification.method) => {
+                            debug!("Focus mode, not forwarding {}", notification.method);
+                        }
+                        Ok(notification) => {
+                            debug!("Received IDE notification: {:?}", notification);
+
+                            // Forward the notification to the MCP client
+                            let notification_json = serde_json::to_string(&notification)?;
+                            if let Err(e) = ws_sender.send(Message::Text(notification_json)).await {
+                                error!("Failed to send IDE notification to {}: {}", peer_addr, e);
+                                break DisconnectReason::WriteError;
+                            }
+                        }
+                        Err(RecvError::Lagged(count)) => {
+                            // The session fell behind; the missed notifications are gone
+                            // but later ones still arrive
+                            warn!("{} fell behind and missed {} notifications", peer_addr, count);
+                            dropped += count;
+                            state.connection_stats.record_dropped(count);
+                        }
+                        Err(RecvError::Closed) => {
+                            debug!("Notification channel closed");
+                            // Continue without notifications
+                            notification_receiver = None;
                         }
                     }
-                    Err(e) => {
-                        debug!("Notification channel error: {}", e);
-                        // Channel closed or lagged, continue without notifications
-                        notification_receiver = None;
+                }
+                // Answer tool calls that finished running, unless they were cancelled
+                Some((key, number, response)) = finished.recv() => {
+                    if in_flight.tasks.get(&key).is_none_or(|task| task.number != number) {
+                        continue;
+                    }
+                    in_flight.tasks.remove(&key);
+                    if let Err(e) = send_response(&mut ws_sender, &response, peer_addr).await {
+                        error!("Error sending MCP response: {}", e);
+                        break DisconnectReason::WriteError;
                     }
                 }
+                // Claude reconnects to the server that took over through its lock file
+                _ = state.drained() => {
+                    if state.teardown() == Some(Teardown::Shutdown) {
+                        info!("Closing session with {}, the server is shutting down", peer_addr);
+                        let frame = CloseFrame {
+                            code: CloseCode::Away,
+                            reason: "Server shutting down".into(),
+                        };
+                        let _ = ws_sender.send(Message::Close(Some(frame))).await;
+                        break DisconnectReason::Shutdown;
+                    }
+                    info!("Closing session with {} for the new server", peer_addr);
+                    let _ = ws_sender.send(Message::Close(None)).await;
+                    break DisconnectReason::Takeover;
+                }
             }
         }
-    }
+    };
 
+    for task in in_flight.tasks.into_values() {
+        task.cancel();
+    }
+    state.connection_stats.record_disconnect(reason);
+    info!(
+        "Connection from {} ended after {:?}: {} ({} notifications dropped)",
+        peer_addr,
+        connected_at.elapsed(),
+        reason.as_str(),
+        dropped
+    );
+    drop(session);
+    state.publish_status(StatusEvent::Disconnected);
+    state.notify_os(
+        OsNotificationEvent::JobFinished,
+        &state.localize(Text::SessionEnded, &[]),
+    );
     Ok(())
 }
 
+/// `tools/call` requests of a session still running, by JSON-RPC ID, so they can be
+/// cancelled. Finished calls send their response to the session loop.
+struct InFlightRequests {
+    tasks: HashMap<String, InFlightTask>,
+    /// Numbers tasks, so the response of a cancelled call isn't taken for that of a later
+    /// call reusing its ID
+    next_task: u64,
+    /// Held by the running call of a tool that changes something, so those run one at a
+    /// time and in the order they arrived, while observation tools run alongside them
+    writes: Arc<tokio::sync::Mutex<()>>,
+    finished: mpsc::UnboundedSender<(String, u64, MCPResponse)>,
+}
+
+struct InFlightTask {
+    number: u64,
+    handle: AbortHandle,
+    /// Set for a tool that changes something once it starts running
+    writing: Option<Arc<AtomicBool>>,
+}
+
+impl InFlightTask {
+    /// Stop the call, unless it is already changing something: it then runs to the end so
+    /// no file is left half-written, and its response is dropped
+    fn cancel(self) {
+        if self
+            .writing
+            .is_some_and(|writing| writing.load(Ordering::SeqCst))
+        {
+            debug!("Letting a started write finish, discarding its response");
+            return;
+        }
+        self.handle.abort();
+    }
+}
+
+/// JSON-RPC error code of a request cancelled before it finished
+const REQUEST_CANCELLED: i32 = -32800;
+
 async fn handle_websocket_message(
-    msg: Result<Message, tokio_tungstenite::tungstenite::Error>,
-    mcp_handler: &MCPServer,
-    ws_sender: &mut futures_util::stream::SplitSink<WebSocketStream<TcpStream>, Message>,
+    msg: Message,
+    mcp_handler: &Arc<MCPServer>,
+    in_flight: &mut InFlightRequests,
+    ws_sender: &mut WebSocketSender,
     peer_addr: SocketAddr,
 ) -> Result<()> {
-    match msg {
-        Ok(msg) => {
-            if msg.is_text() {
-                let text = msg.to_text().unwrap();
-                debug!("Received message from {}: {}", peer_addr, text);
-
-                // Try to parse as MCP request
-                match serde_json::from_str::<MCPRequest>(text) {
-                    Ok(mcp_request) => {
-                        info!("Processing MCP request: {}", mcp_request.method);
-
-                        // Handle notifications (requests without ID) separately
-                        if mcp_request.id.is_none()
-                            && mcp_request.method.starts_with("notifications/")
-                        {
-                            info!("Processing notification: {}", mcp_request.method);
-                            // Notifications don't get responses, just return
-                            return Ok(());
-                        }
+    if msg.is_text() {
+        let text = msg.to_text().unwrap();
+        debug!("Received message from {}: {}", peer_addr, text);
 
-                        match mcp_handler.handle_request(mcp_request).await {
-                            Ok(response) => {
-                                let response_json = serde_json::to_string(&response)?;
-                                debug!("Sending MCP response: {}", response_json);
+        // Try to parse as MCP request
+        match serde_json::from_str::<MCPRequest>(text) {
+            Ok(mcp_request) => {
+                info!("Processing MCP request: {}", mcp_request.method);
 
-                                if let Err(e) = ws_sender.send(Message::Text(response_json)).await {
-                                    error!("Failed to send MCP response to {}: {}", peer_addr, e);
-                                    return Err(e.into());
-                                }
-                            }
-                            Err(e) => {
-                                error!("Error handling MCP request: {}", e);
-                                let error_response = MCPResponse {
-                                    jsonrpc: "2.0".to_string(),
-                                    id: None,
-                                    result: None,
-                                    error: Some(crate::mcp::MCPError {
-                                        code: -32603,
-                                        message: "Internal error".to_string(),
-                                        data: Some(serde_json::json!({"details": e.to_string()})),
-                                    }),
-                                };
-
-                                let error_json = serde_json::to_string(&error_response)?;
-                                if let Err(e) = ws_sender.send(Message::Text(error_json)).await {
-                                    error!("Failed to send error response to {}: {}", peer_addr, e);
-                                    return Err(e.into());
-                                }
-                            }
-                        }
+                // `$/cancelRequest` is answered with a RequestCancelled error in place of the
+                // result; MCP's `notifications/cancelled` expects no response at all
+                let cancelled = match mcp_request.method.as_str() {
+                    "$/cancelRequest" => Some(("/id", true)),
+                    "notifications/cancelled" => Some(("/requestId", false)),
+                    _ => None,
+                };
+                if let Some((pointer, respond)) = cancelled {
+                    let Some(id) = mcp_request
+                        .params
+                        .as_ref()
+                        .and_then(|params| params.pointer(pointer))
+                    else {
+                        return Ok(());
+                    };
+                    let Some(task) = in_flight.tasks.remove(&id.to_string()) else {
+                        debug!("No running request {} to cancel", id);
+                        return Ok(());
+                    };
+                    info!("Cancelling request {}", id);
+                    task.cancel();
+                    if respond {
+                        let response = error_response(
+                            Some(id.clone()),
+                            REQUEST_CANCELLED,
+                            "Request cancelled",
+                        );
+                        send_response(ws_sender, &response, peer_addr).await?;
                     }
-                    Err(e) => {
-                        warn!("Failed to parse MCP request from {}: {}", peer_addr, e);
-                        debug!("Invalid message content: {}", text);
-
-                        // Send back a JSON-RPC error response
-                        let error_response = MCPResponse {
-                            jsonrpc: "2.0".to_string(),
-                            id: None,
-                            result: None,
-                            error: Some(crate::mcp::MCPError {
-                                code: -32700,
-                                message: "Parse error".to_string(),
-                                data: None,
-                            }),
-                        };
+                    return Ok(());
+                }
 
-                        let error_json = serde_json::to_string(&error_response)?;
-                        if let Err(e) = ws_sender.send(Message::Text(error_json)).await {
-                            error!(
-                                "Failed to send parse error response to {}: {}",
-                                peer_addr, e
-                            );
-                            return Err(e.into());
-                        }
+                // Handle notifications (requests without ID) separately
+                if mcp_request.id.is_none() && mcp_request.method.starts_with("notifications/") {
+                    info!("Processing notification: {}", mcp_request.method);
+                    // Notifications don't get responses, just return
+                    return Ok(());
+                }
+
+                // Tool calls can run for long, e.g. waiting on Zed or a hook, so they run in
+                // the background where they can be cancelled. Other requests are quick and
+                // keep their order.
+                if let (Some(id), "tools/call") = (&mcp_request.id, mcp_request.method.as_str()) {
+                    let key = id.to_string();
+                    let tool_name = mcp_request
+                        .params
+                        .as_ref()
+                        .and_then(|params| params.get("name"))
+                        .and_then(|name| name.as_str())
+                        .unwrap_or_default();
+                    let writing = (!is_observation_tool(tool_name))
+                        .then(|| (in_flight.writes.clone(), Arc::new(AtomicBool::new(false))));
+                    in_flight.next_task += 1;
+                    let number = in_flight.next_task;
+                    let handler = mcp_handler.clone();
+                    let finished = in_flight.finished.clone();
+                    let task_key = key.clone();
+                    let started = writing.as_ref().map(|(_, started)| started.clone());
+                    let task = tokio::spawn(async move {
+                        let _turn = match &writing {
+                            Some((writes, started)) => {
+                                let turn = writes.clone().lock_owned().await;
+                                started.store(true, Ordering::SeqCst);
+                                Some(turn)
+                            }
+                            None => None,
+                        };
+                        let response = process_request(&handler, mcp_request).await;
+                        let _ = finished.send((task_key, number, response));
+                    });
+                    let task = InFlightTask {
+                        number,
+                        handle: task.abort_handle(),
+                        writing: started,
+                    };
+                    // A client reusing the ID of a call still running gives up on that call
+                    if let Some(previous) = in_flight.tasks.insert(key.clone(), task) {
+                        warn!(
+                            "Request ID {} reused while its call was running, cancelling it",
+                            key
+                        );
+                        previous.cancel();
                     }
+                    return Ok(());
                 }
-            } else if msg.is_close() {
-                info!("Connection closed by {}", peer_addr);
-                return Ok(());
+
+                let response = process_request(mcp_handler, mcp_request).await;
+                send_response(ws_sender, &response, peer_addr).await?;
+            }
+            Err(e) => {
+                warn!("Failed to parse MCP request from {}: {}", peer_addr, e);
+                debug!("Invalid message content: {}", text);
+
+                // Send back a JSON-RPC error response
+                let response = error_response(None, -32700, "Parse error");
+                send_response(ws_sender, &response, peer_addr).await?;
             }
         }
+    }
+
+    Ok(())
+}
+
+type WebSocketSender = futures_util::stream::SplitSink<WebSocketStream<TcpStream>, Message>;
+
+/// Handle a request, turning failures into an internal error response
+async fn process_request(mcp_handler: &MCPServer, request: MCPRequest) -> MCPResponse {
+    match mcp_handler.handle_request(request).await {
+        Ok(response) => response,
         Err(e) => {
-            error!("WebSocket error for {}: {}", peer_addr, e);
-            return Err(e.into());
+            error!("Error handling MCP request: {}", e);
+            let mut response = error_response(None, -32603, "Internal error");
+            if let Some(error) = response.error.as_mut() {
+                error.data = Some(serde_json::json!({"details": e.to_string()}));
+            }
+            response
         }
     }
+}
+
+fn error_response(id: Option<serde_json::Value>, code: i32, message: &str) -> MCPResponse {
+    MCPResponse {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result: None,
+        error: Some(crate::mcp::MCPError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }),
+    }
+}
 
+async fn send_response(
+    ws_sender: &mut WebSocketSender,
+    response: &MCPResponse,
+    peer_addr: SocketAddr,
+) -> Result<()> {
+    let response_json = serde_json::to_string(response)?;
+    debug!("Sending MCP response: {}", response_json);
+    if let Err(e) = ws_sender.send(Message::Text(response_json)).await {
+        error!("Failed to send MCP response to {}: {}", peer_addr, e);
+        return Err(e.into());
+    }
     Ok(())
 }